@@ -0,0 +1,125 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nayud_batch::replication::{Outbox, OutboxRecord, OutboxTarget};
+
+const RECORDS: usize = 2000;
+const THREADS: usize = 16;
+
+fn temp_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_bench_{}_{}", tag, ts));
+    dir
+}
+
+fn record(i: usize) -> OutboxRecord {
+    OutboxRecord::new_simple(
+        format!("bench-{}", i),
+        format!("INSERT INTO batch.customers (id, name, email) VALUES ({}, 'customer {}', 'c{}@example.com')", i, i, i),
+        OutboxTarget::Both,
+    )
+}
+
+fn report(name: &str, started: Instant, outbox: &Outbox) {
+    let elapsed = started.elapsed();
+    println!(
+        "{:<34} {:>8.0} records/s  {:>5} fsyncs  {:>8.1?}",
+        name,
+        RECORDS as f64 / elapsed.as_secs_f64(),
+        outbox.commit_count(),
+        elapsed
+    );
+}
+
+fn frame(rec: &OutboxRecord) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(256);
+    payload.extend_from_slice(&(rec.idempotency_key.len() as u16).to_le_bytes());
+    payload.extend_from_slice(rec.idempotency_key.as_bytes());
+    payload.extend_from_slice(&(rec.statement.len() as u32).to_le_bytes());
+    payload.extend_from_slice(rec.statement.as_bytes());
+    let mut buf = Vec::with_capacity(payload.len() + 10);
+    buf.extend_from_slice(&0x4E415944u32.to_le_bytes());
+    buf.extend_from_slice(&1u16.to_le_bytes());
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&payload);
+    buf
+}
+
+fn per_record_baseline(name: &str) {
+    let dir = temp_dir("baseline");
+    std::fs::create_dir_all(&dir).expect("create dir");
+    let mut file = OpenOptions::new().create(true).append(true).open(dir.join("outbox.log")).expect("open log");
+    let started = Instant::now();
+    let mut end = 0;
+    for i in 0..RECORDS {
+        file.write_all(&frame(&record(i))).expect("write");
+        end = file.metadata().expect("metadata").len();
+        file.sync_data().expect("sync");
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{:<34} {:>8.0} records/s  {:>5} fsyncs  {:>8.1?}  ({} bytes)",
+        name,
+        RECORDS as f64 / elapsed.as_secs_f64(),
+        RECORDS,
+        elapsed,
+        end
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn sequential(name: &str) {
+    let dir = temp_dir("sequential");
+    let outbox = Outbox::open(&dir).expect("open outbox");
+    let started = Instant::now();
+    for i in 0..RECORDS {
+        outbox.append(record(i)).expect("append");
+    }
+    report(name, started, &outbox);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn concurrent(name: &str, window: Duration) {
+    let dir = temp_dir("concurrent");
+    let outbox = Arc::new(Outbox::open(&dir).expect("open outbox").with_group_commit_window(window));
+    let started = Instant::now();
+    let workers: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let outbox = outbox.clone();
+            std::thread::spawn(move || {
+                for i in (t..RECORDS).step_by(THREADS) {
+                    outbox.append(record(i)).expect("append");
+                }
+            })
+        })
+        .collect();
+    for w in workers {
+        w.join().expect("worker");
+    }
+    report(name, started, &outbox);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn batched(name: &str, batch: usize) {
+    let dir = temp_dir("batched");
+    let outbox = Outbox::open(&dir).expect("open outbox");
+    let started = Instant::now();
+    for chunk in (0..RECORDS).collect::<Vec<_>>().chunks(batch) {
+        outbox.append_batch(chunk.iter().map(|i| record(*i)).collect()).expect("append batch");
+    }
+    report(name, started, &outbox);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+fn main() {
+    println!("{} records, fsync on every commit", RECORDS);
+    per_record_baseline("baseline: write+metadata+sync_data");
+    sequential("1 writer, fsync per record");
+    concurrent(&format!("{} writers, group commit", THREADS), Duration::ZERO);
+    concurrent(&format!("{} writers, group commit 200us", THREADS), Duration::from_micros(200));
+    batched("append_batch(64)", 64);
+}
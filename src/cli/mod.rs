@@ -0,0 +1,810 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::{AppConfig, CliOverrides, Severity};
+use crate::db;
+use crate::errors::{AppError, AppResult};
+use crate::export::{run_export, ExportFormat, ExportManifest, ExportSource, ExportSpec};
+use crate::health::db_health;
+use crate::migrations;
+use crate::import::{run_import, ImportFormat, ImportMapping, ImportOptions, ImportReport};
+use crate::jobs::{JobRegistry, JobStatus};
+use crate::repair::{keys_from_report, read_keys, run_repair, RepairReport, RepairSpec, RepairStrategy};
+use crate::replication::{
+    load_primary, store_primary, Cluster, DefaultSyncCheck, Outbox, OutboxEntry, OutboxFilter, OutboxTarget, ReplicationManager, SyncCheck,
+};
+use crate::verify::{run_verify, VerifyReport, VerifySpec};
+
+const DEFAULT_DUMP_LIMIT: usize = 100;
+const DEFAULT_TAIL_LIMIT: usize = 10;
+const PROMOTE_REPLAY_BATCH: usize = 512;
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Serve,
+    PrintConfig,
+    CheckConfig,
+    EnsureKeyspaces,
+    Import(ImportArgs),
+    Export(ExportArgs),
+    Verify(VerifyArgs),
+    Repair(RepairArgs),
+    Migrate(MigrateArgs),
+    Outbox(OutboxCommand),
+    Failover(FailoverCommand),
+    Jobs(JobCommand),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> AppResult<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "human" | "text" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(AppError::config(format!("unknown output format '{}', expected human or json", other))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutboxCommand {
+    Stats,
+    Dump { from: u64, limit: usize, filter: OutboxFilter },
+    Tail { limit: usize },
+    Requeue { offset: u64 },
+    Truncate { all: bool },
+    Validate,
+    Seek { offset: Option<u64>, key: Option<String> },
+    Export { out: String, all: bool, allow_skips: bool },
+    Import { file: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailoverCommand {
+    Status,
+    Promote { to: Cluster, force: bool },
+}
+
+#[derive(Debug, Clone)]
+pub enum JobCommand {
+    Export(ExportArgs),
+    Verify(VerifyArgs),
+    Repair(RepairArgs),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ImportArgs {
+    pub file: String,
+    pub table: Option<String>,
+    pub mapping: Option<String>,
+    pub format: Option<ImportFormat>,
+    pub target: Option<OutboxTarget>,
+    pub concurrency: Option<usize>,
+    pub rejects: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ExportArgs {
+    pub table: Option<String>,
+    pub query: Option<String>,
+    pub format: Option<ExportFormat>,
+    pub out: Option<String>,
+    pub chunk_rows: Option<usize>,
+    pub page_size: Option<i32>,
+    pub compress: bool,
+    pub resume: bool,
+    pub cluster: Option<Cluster>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyArgs {
+    pub table: String,
+    pub ranges: Option<u32>,
+    pub page_size: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RepairArgs {
+    pub table: String,
+    pub keys: Option<String>,
+    pub strategy: Option<RepairStrategy>,
+    pub ranges: Option<u32>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MigrateArgs {
+    pub apply: bool,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invocation {
+    pub command: Command,
+    pub overrides: CliOverrides,
+    pub output: OutputFormat,
+}
+
+pub fn parse_invocation<I: IntoIterator<Item = String>>(args: I) -> AppResult<Invocation> {
+    let mut it = args.into_iter().peekable();
+    let mut overrides = CliOverrides::default();
+    let mut print_config = false;
+    let mut output = OutputFormat::default();
+    while let Some(flag) = it.next_if(|a| a.starts_with('-')) {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match flag.as_str() {
+            "--config" | "-c" => overrides.config_file = Some(value()?),
+            "--set" => overrides.push_assignment("--set", &value()?)?,
+            "--bind" => overrides.push("--bind", "server.bind_addr", value()?),
+            "--outbox-dir" => overrides.push("--outbox-dir", "replication.outbox_dir", value()?),
+            "--strict" => overrides.push("--strict", "strict", "true"),
+            "--print-config" => print_config = true,
+            "--output" => output = OutputFormat::parse(&value()?)?,
+            "--json" => output = OutputFormat::Json,
+            other => return Err(AppError::config(format!(
+                "unknown option '{}', expected --config, --set, --bind, --outbox-dir, --strict, --print-config, --output or --json",
+                other
+            ))),
+        }
+    }
+    let command = if print_config {
+        if let Some(extra) = it.next() {
+            return Err(AppError::config(format!("--print-config does not take a command, got '{}'", extra)));
+        }
+        Command::PrintConfig
+    } else {
+        parse_command(it)?
+    };
+    Ok(Invocation { command, overrides, output })
+}
+
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> AppResult<Command> {
+    parse_invocation(args).map(|inv| inv.command)
+}
+
+fn parse_command<I: Iterator<Item = String>>(mut it: I) -> AppResult<Command> {
+    match it.next().as_deref() {
+        None | Some("serve") => no_more_args("serve", it).map(|_| Command::Serve),
+        Some("check-config") => no_more_args("check-config", it).map(|_| Command::CheckConfig),
+        Some("ensure-keyspaces") => no_more_args("ensure-keyspaces", it).map(|_| Command::EnsureKeyspaces),
+        Some("import") => parse_import_args(it).map(Command::Import),
+        Some("export") => parse_export_args(it).map(Command::Export),
+        Some("verify") => parse_verify_args(it).map(Command::Verify),
+        Some("repair") => parse_repair_args(it).map(Command::Repair),
+        Some("migrate") => parse_migrate_args(it).map(Command::Migrate),
+        Some("outbox") => parse_outbox_args(it).map(Command::Outbox),
+        Some("failover") => parse_failover_args(it).map(Command::Failover),
+        Some("jobs") => parse_jobs_args(it).map(Command::Jobs),
+        Some(other) => Err(AppError::config(format!(
+            "unknown command '{}', expected serve, check-config, ensure-keyspaces, import, export, verify, repair, migrate, outbox, failover or jobs",
+            other
+        ))),
+    }
+}
+
+fn no_more_args<I: Iterator<Item = String>>(command: &str, mut it: I) -> AppResult<()> {
+    match it.next() {
+        Some(extra) => Err(AppError::config(format!("{} takes no arguments, got '{}'", command, extra))),
+        None => Ok(()),
+    }
+}
+
+fn number<T: FromStr>(what: &str, v: &str) -> AppResult<T> {
+    v.parse().map_err(|_| AppError::config(format!("invalid {} '{}'", what, v)))
+}
+
+fn parse_outbox_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<OutboxCommand> {
+    const ACTIONS: &str = "stats, dump, tail, requeue, truncate, validate, seek, export or import";
+    let action = it.next().ok_or_else(|| AppError::config(format!("outbox requires an action: {}", ACTIONS)))?;
+    let mut cmd = match action.as_str() {
+        "stats" => OutboxCommand::Stats,
+        "dump" => OutboxCommand::Dump { from: 0, limit: DEFAULT_DUMP_LIMIT, filter: OutboxFilter::default() },
+        "tail" => OutboxCommand::Tail { limit: DEFAULT_TAIL_LIMIT },
+        "requeue" => OutboxCommand::Requeue { offset: 0 },
+        "truncate" => OutboxCommand::Truncate { all: false },
+        "validate" => OutboxCommand::Validate,
+        "seek" => OutboxCommand::Seek { offset: None, key: None },
+        "export" => OutboxCommand::Export { out: String::new(), all: false, allow_skips: false },
+        "import" => OutboxCommand::Import { file: String::new() },
+        other => return Err(AppError::config(format!("unknown outbox action '{}', expected {}", other, ACTIONS))),
+    };
+    let mut confirmed = false;
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match (&mut cmd, flag.as_str()) {
+            (OutboxCommand::Dump { from, .. }, "--from") => *from = number("offset", &value()?)?,
+            (OutboxCommand::Dump { filter, .. }, "--key") => filter.key = Some(value()?),
+            (OutboxCommand::Dump { filter, .. }, "--target") => {
+                let v = value()?;
+                filter.target = Some(OutboxTarget::parse(&v).ok_or_else(|| AppError::config(format!("unknown target '{}'", v)))?);
+            }
+            (OutboxCommand::Dump { filter, .. }, "--since") => filter.since_ms = Some(number("timestamp", &value()?)?),
+            (OutboxCommand::Dump { filter, .. }, "--until") => filter.until_ms = Some(number("timestamp", &value()?)?),
+            (OutboxCommand::Dump { limit, .. } | OutboxCommand::Tail { limit }, "--limit" | "-n") => *limit = number("limit", &value()?)?,
+            (OutboxCommand::Requeue { offset }, "--offset") => *offset = number("offset", &value()?)?,
+            (OutboxCommand::Truncate { all }, "--all") => *all = true,
+            (OutboxCommand::Truncate { .. }, "--yes") => confirmed = true,
+            (OutboxCommand::Seek { offset, .. }, "--offset") => *offset = Some(number("offset", &value()?)?),
+            (OutboxCommand::Seek { key, .. }, "--key") => *key = Some(value()?),
+            (OutboxCommand::Export { out, .. }, "--out" | "-o") => *out = value()?,
+            (OutboxCommand::Export { all, .. }, "--all") => *all = true,
+            (OutboxCommand::Export { allow_skips, .. }, "--allow-skips") => *allow_skips = true,
+            (OutboxCommand::Import { file }, "--file" | "-f") => *file = value()?,
+            (_, other) => return Err(AppError::config(format!("unknown option '{}' for outbox {}", other, action))),
+        }
+    }
+    if cmd == (OutboxCommand::Truncate { all: true }) && !confirmed {
+        return Err(AppError::config("outbox truncate --all discards records that were never replayed; pass --yes to confirm"));
+    }
+    match &cmd {
+        OutboxCommand::Seek { offset, key } if offset.is_some() == key.is_some() => {
+            return Err(AppError::config("outbox seek requires exactly one of --offset or --key"));
+        }
+        OutboxCommand::Export { out, .. } if out.is_empty() => return Err(AppError::config("outbox export requires --out <file>")),
+        OutboxCommand::Import { file } if file.is_empty() => return Err(AppError::config("outbox import requires --file <file>")),
+        _ => {}
+    }
+    Ok(cmd)
+}
+
+fn parse_failover_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<FailoverCommand> {
+    match it.next().as_deref() {
+        Some("status") => no_more_args("failover status", it).map(|_| FailoverCommand::Status),
+        Some("promote") => {
+            let mut to = None;
+            let mut force = false;
+            for arg in it {
+                match arg.as_str() {
+                    "--force" => force = true,
+                    name if to.is_none() => {
+                        to = Some(Cluster::parse(name).ok_or_else(|| AppError::config(format!("unknown cluster '{}'", name)))?);
+                    }
+                    other => return Err(AppError::config(format!("unknown failover promote option '{}'", other))),
+                }
+            }
+            let to = to.ok_or_else(|| AppError::config("failover promote requires a cluster: active or passive"))?;
+            Ok(FailoverCommand::Promote { to, force })
+        }
+        Some(other) => Err(AppError::config(format!("unknown failover action '{}', expected status or promote", other))),
+        None => Err(AppError::config("failover requires an action: status or promote")),
+    }
+}
+
+fn parse_jobs_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<JobCommand> {
+    match it.next().as_deref() {
+        Some("run") => {}
+        Some(other) => return Err(AppError::config(format!("unknown jobs action '{}', expected run", other))),
+        None => return Err(AppError::config("jobs requires an action: run <name>")),
+    }
+    match it.next().as_deref() {
+        Some("export") => parse_export_args(it).map(JobCommand::Export),
+        Some("verify") => parse_verify_args(it).map(JobCommand::Verify),
+        Some("repair") => parse_repair_args(it).map(JobCommand::Repair),
+        Some(query) if !query.starts_with('-') => {
+            let args = std::iter::once("--query".to_string()).chain(std::iter::once(query.to_string())).chain(it);
+            parse_export_args(args).map(JobCommand::Export)
+        }
+        _ => Err(AppError::config("jobs run requires a job name: export, verify, repair or a named export query")),
+    }
+}
+
+fn parse_import_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<ImportArgs> {
+    let mut out = ImportArgs::default();
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match flag.as_str() {
+            "--file" | "-f" => out.file = value()?,
+            "--table" | "-t" => out.table = Some(value()?),
+            "--mapping" | "-m" => out.mapping = Some(value()?),
+            "--format" => out.format = Some(ImportFormat::parse(&value()?)?),
+            "--target" => {
+                let v = value()?;
+                out.target = Some(OutboxTarget::parse(&v).ok_or_else(|| AppError::config(format!("unknown target '{}'", v)))?);
+            }
+            "--concurrency" => {
+                let v = value()?;
+                out.concurrency = Some(v.parse().map_err(|_| AppError::config(format!("invalid concurrency '{}'", v)))?);
+            }
+            "--rejects" => out.rejects = Some(value()?),
+            other => return Err(AppError::config(format!("unknown import option '{}'", other))),
+        }
+    }
+    if out.file.is_empty() {
+        return Err(AppError::config("import requires --file <path> (use - for stdin)"));
+    }
+    if out.table.is_none() && out.mapping.is_none() {
+        return Err(AppError::config("import requires --table <keyspace.table> or --mapping <file>"));
+    }
+    Ok(out)
+}
+
+fn parse_export_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<ExportArgs> {
+    let mut out = ExportArgs { compress: true, ..ExportArgs::default() };
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match flag.as_str() {
+            "--table" | "-t" => out.table = Some(value()?),
+            "--query" | "-q" => out.query = Some(value()?),
+            "--format" => out.format = Some(ExportFormat::parse(&value()?)?),
+            "--out" | "-o" => out.out = Some(value()?),
+            "--chunk-rows" => {
+                let v = value()?;
+                out.chunk_rows = Some(v.parse().map_err(|_| AppError::config(format!("invalid chunk rows '{}'", v)))?);
+            }
+            "--page-size" => {
+                let v = value()?;
+                out.page_size = Some(v.parse().map_err(|_| AppError::config(format!("invalid page size '{}'", v)))?);
+            }
+            "--cluster" => {
+                out.cluster = match value()?.to_ascii_lowercase().as_str() {
+                    "active" => Some(Cluster::Active),
+                    "passive" => Some(Cluster::Passive),
+                    other => return Err(AppError::config(format!("unknown cluster '{}'", other))),
+                };
+            }
+            "--no-compress" => out.compress = false,
+            "--resume" => out.resume = true,
+            other => return Err(AppError::config(format!("unknown export option '{}'", other))),
+        }
+    }
+    if out.table.is_some() == out.query.is_some() {
+        return Err(AppError::config("export requires exactly one of --table <keyspace.table> or --query <name>"));
+    }
+    Ok(out)
+}
+
+fn parse_verify_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<VerifyArgs> {
+    let mut out = VerifyArgs::default();
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match flag.as_str() {
+            "--table" | "-t" => out.table = value()?,
+            "--ranges" => {
+                let v = value()?;
+                out.ranges = Some(v.parse().map_err(|_| AppError::config(format!("invalid ranges '{}'", v)))?);
+            }
+            "--page-size" => {
+                let v = value()?;
+                out.page_size = Some(v.parse().map_err(|_| AppError::config(format!("invalid page size '{}'", v)))?);
+            }
+            table if !table.starts_with('-') && out.table.is_empty() => out.table = table.to_string(),
+            other => return Err(AppError::config(format!("unknown verify option '{}'", other))),
+        }
+    }
+    if out.table.is_empty() {
+        return Err(AppError::config("verify requires a table: verify <keyspace.table> or --table <keyspace.table>"));
+    }
+    Ok(out)
+}
+
+fn parse_repair_args<I: Iterator<Item = String>>(mut it: I) -> AppResult<RepairArgs> {
+    let mut out = RepairArgs::default();
+    while let Some(flag) = it.next() {
+        let mut value = || it.next().ok_or_else(|| AppError::config(format!("missing value for {}", flag)));
+        match flag.as_str() {
+            "--table" | "-t" => out.table = value()?,
+            "--keys" | "-k" => out.keys = Some(value()?),
+            "--strategy" => out.strategy = Some(RepairStrategy::parse(&value()?)?),
+            "--ranges" => {
+                let v = value()?;
+                out.ranges = Some(v.parse().map_err(|_| AppError::config(format!("invalid ranges '{}'", v)))?);
+            }
+            "--dry-run" => out.dry_run = true,
+            other => return Err(AppError::config(format!("unknown repair option '{}'", other))),
+        }
+    }
+    if out.table.is_empty() {
+        return Err(AppError::config("repair requires --table <keyspace.table>"));
+    }
+    Ok(out)
+}
+
+fn parse_migrate_args<I: Iterator<Item = String>>(it: I) -> AppResult<MigrateArgs> {
+    let mut out = MigrateArgs::default();
+    let mut action_seen = false;
+    for arg in it {
+        match arg.as_str() {
+            "status" if !action_seen => action_seen = true,
+            "apply" if !action_seen => {
+                action_seen = true;
+                out.apply = true;
+            }
+            "--dry-run" => out.dry_run = true,
+            other => return Err(AppError::config(format!("unknown migrate option '{}', expected status or apply [--dry-run]", other))),
+        }
+    }
+    Ok(out)
+}
+
+pub async fn run_import_command(cfg: &AppConfig, args: &ImportArgs) -> AppResult<ImportReport> {
+    let mut mapping = match args.mapping.as_ref() {
+        Some(path) => ImportMapping::from_file(path)?,
+        None => ImportMapping::default(),
+    };
+    if let Some(t) = args.table.as_ref() {
+        match t.split_once('.') {
+            Some((ks, table)) => {
+                mapping.keyspace = Some(ks.to_string());
+                mapping.table = table.to_string();
+            }
+            None => mapping.table = t.clone(),
+        }
+    }
+    if mapping.table.is_empty() {
+        return Err(AppError::config("import mapping does not name a target table"));
+    }
+
+    let format = match args.format {
+        Some(f) => f,
+        None => ImportFormat::from_path(&args.file)
+            .ok_or_else(|| AppError::config("cannot infer import format from file name, pass --format csv|ndjson"))?,
+    };
+    let defaults = ImportOptions::default();
+    let opts = ImportOptions {
+        format,
+        target: args.target.unwrap_or(defaults.target),
+        concurrency: args.concurrency.unwrap_or(defaults.concurrency),
+        ..defaults
+    };
+
+    let reader: Box<dyn Read + Send> = if args.file == "-" {
+        Box::new(std::io::stdin())
+    } else {
+        let f = File::open(&args.file).map_err(|e| AppError::io(format!("failed to open {}", args.file), e))?;
+        Box::new(BufReader::new(f))
+    };
+
+    let clients = db::init_clients(cfg).await?;
+    let repl = ReplicationManager::from_config(cfg)?;
+
+    let report = run_import(reader, &mapping, &cfg.active.keyspace, &opts, &repl, &clients).await?;
+
+    if let Some(path) = args.rejects.as_ref() {
+        let f = File::create(path).map_err(|e| AppError::io(format!("failed to create {}", path), e))?;
+        report.write_rejections(f)?;
+    }
+
+    Ok(report)
+}
+
+pub fn export_spec(cfg: &AppConfig, args: &ExportArgs) -> AppResult<ExportSpec> {
+    let source = match (args.table.as_ref(), args.query.as_ref()) {
+        (Some(t), _) => match t.split_once('.') {
+            Some((ks, table)) => ExportSource::table(ks, table),
+            None => ExportSource::table(cfg.active.keyspace.clone(), t.clone()),
+        },
+        (None, Some(q)) => ExportSource::named_query(&cfg.export, q)?,
+        (None, None) => return Err(AppError::config("export requires --table or --query")),
+    };
+    let base = args.out.clone().unwrap_or_else(|| cfg.export.out_dir.clone());
+    let mut spec = ExportSpec::new(source, args.format.unwrap_or(ExportFormat::Csv), base)
+        .with_compress(args.compress)
+        .with_resume(args.resume)
+        .with_cluster(args.cluster);
+    if let Some(n) = args.chunk_rows { spec = spec.with_chunk_rows(n); }
+    if let Some(n) = args.page_size.or(cfg.driver.default_page_size) { spec = spec.with_page_size(n); }
+    Ok(spec)
+}
+
+pub async fn run_export_command(cfg: &AppConfig, args: &ExportArgs) -> AppResult<ExportManifest> {
+    let spec = export_spec(cfg, args)?;
+    let clients = db::init_clients(cfg).await?;
+    run_export(&spec, &clients).await
+}
+
+pub async fn run_verify_command(cfg: &AppConfig, args: &VerifyArgs) -> AppResult<VerifyReport> {
+    let mut spec = match args.table.split_once('.') {
+        Some((ks, table)) => VerifySpec::new(ks, table),
+        None => VerifySpec::new(cfg.active.keyspace.clone(), args.table.clone()),
+    };
+    if let Some(n) = args.ranges { spec = spec.with_ranges(n); }
+    if let Some(n) = args.page_size.or(cfg.driver.default_page_size) { spec = spec.with_page_size(n); }
+    let clients = db::init_clients(cfg).await?;
+    run_verify(&spec, &clients).await
+}
+
+pub async fn run_repair_command(cfg: &AppConfig, args: &RepairArgs) -> AppResult<RepairReport> {
+    let (keyspace, table) = match args.table.split_once('.') {
+        Some((ks, table)) => (ks.to_string(), table.to_string()),
+        None => (cfg.active.keyspace.clone(), args.table.clone()),
+    };
+    let clients = db::init_clients(cfg).await?;
+    let keys = match args.keys.as_deref() {
+        Some("-") => read_keys(std::io::stdin().lock())?,
+        Some(path) => {
+            let f = File::open(path).map_err(|e| AppError::io(format!("failed to open {}", path), e))?;
+            read_keys(BufReader::new(f))?
+        }
+        None => {
+            let mut spec = VerifySpec::new(keyspace.clone(), table.clone());
+            if let Some(n) = args.ranges { spec = spec.with_ranges(n); }
+            if let Some(n) = cfg.driver.default_page_size { spec = spec.with_page_size(n); }
+            keys_from_report(&run_verify(&spec, &clients).await?)
+        }
+    };
+    let spec = RepairSpec::new(keyspace, table, args.strategy.unwrap_or(RepairStrategy::WriteTime)).with_dry_run(args.dry_run);
+    let repl = ReplicationManager::from_config(cfg)?;
+    run_repair(&spec, &keys, &repl, &clients).await
+}
+
+pub async fn run_migrate_command(cfg: &AppConfig, args: &MigrateArgs) -> AppResult<serde_json::Value> {
+    let clients = db::init_clients(cfg).await?;
+    let value = if args.apply || args.dry_run {
+        if !args.dry_run { db::ensure_keyspaces(cfg, &clients).await?; }
+        serde_json::to_value(migrations::apply(cfg, &clients, args.dry_run).await?)
+    } else {
+        serde_json::to_value(migrations::status(cfg, &clients).await?)
+    };
+    value.map_err(|e| AppError::json("encode migration result", e))
+}
+
+fn open_outbox(cfg: &AppConfig, cmd: &OutboxCommand) -> AppResult<Outbox> {
+    match cmd {
+        OutboxCommand::Import { .. } => Outbox::open_with_config(cfg),
+        OutboxCommand::Requeue { .. } | OutboxCommand::Truncate { .. } | OutboxCommand::Seek { .. } => {
+            let dir = &cfg.replication.outbox_dir;
+            if !Path::new(dir).is_dir() {
+                return Err(AppError::not_found(format!("outbox directory {} does not exist", dir)));
+            }
+            Outbox::open_with_config(cfg)
+        }
+        _ => Outbox::open_read_only_with_config(cfg),
+    }
+}
+
+fn entries(records: Vec<(u64, u64, crate::replication::OutboxRecord)>) -> Vec<OutboxEntry> {
+    records.iter().map(|(start, end, rec)| OutboxEntry::new(*start, *end, rec)).collect()
+}
+
+pub fn run_outbox_command(cfg: &AppConfig, cmd: &OutboxCommand) -> AppResult<Value> {
+    let outbox = open_outbox(cfg, cmd)?;
+    match cmd {
+        OutboxCommand::Stats => to_json(outbox.stats()?),
+        OutboxCommand::Dump { from, limit, filter } => to_json(outbox.find(filter, *from, *limit)?),
+        OutboxCommand::Tail { limit } => to_json(entries(outbox.tail(*limit)?)),
+        OutboxCommand::Requeue { offset } => {
+            let previous = outbox.load_cursor()?;
+            outbox.requeue(*offset)?;
+            Ok(json!({ "previous_cursor": previous, "cursor": offset, "pending_records": outbox.pending_count()? }))
+        }
+        OutboxCommand::Truncate { all } => {
+            let removed = outbox.truncate(*all)?;
+            Ok(json!({ "removed_bytes": removed, "stats": outbox.stats()? }))
+        }
+        OutboxCommand::Validate => {
+            let scan = outbox.scan()?;
+            if !scan.is_healthy() {
+                return Err(AppError::validation(format!(
+                    "outbox {} is damaged: {}",
+                    cfg.replication.outbox_dir,
+                    scan.describe_problems()
+                )));
+            }
+            to_json(scan)
+        }
+        OutboxCommand::Seek { offset, key } => {
+            let previous = outbox.load_cursor()?;
+            let cursor = match (offset, key) {
+                (Some(offset), _) => {
+                    outbox.requeue(*offset)?;
+                    *offset
+                }
+                (None, Some(key)) => outbox.seek_to_key(key)?,
+                (None, None) => return Err(AppError::config("outbox seek requires --offset or --key")),
+            };
+            Ok(json!({ "previous_cursor": previous, "cursor": cursor, "pending_records": outbox.pending_count()? }))
+        }
+        OutboxCommand::Export { out, all, allow_skips } => {
+            let file = File::create(out).map_err(|e| AppError::io(format!("create {}", out), e))?;
+            let summary = outbox.export_to(BufWriter::new(file), *all)?;
+            if !allow_skips && summary.skipped_unknown_key + summary.skipped_corrupt_regions > 0 {
+                let _ = std::fs::remove_file(out);
+                return Err(AppError::validation(format!(
+                    "outbox export skipped {} record(s) encrypted with unknown keys and {} corrupt region(s); {} was removed, pass --allow-skips to export the readable records anyway",
+                    summary.skipped_unknown_key, summary.skipped_corrupt_regions, out
+                )));
+            }
+            Ok(json!({ "file": out, "exported": summary }))
+        }
+        OutboxCommand::Import { file } => {
+            let input = File::open(file).map_err(|e| AppError::io(format!("open {}", file), e))?;
+            let summary = outbox.import_from(BufReader::new(input))?;
+            Ok(json!({ "imported": summary, "stats": outbox.stats()? }))
+        }
+    }
+}
+
+pub async fn run_failover_command(cfg: &AppConfig, cmd: &FailoverCommand) -> AppResult<Value> {
+    let dir = &cfg.replication.outbox_dir;
+    let persisted = load_primary(dir)?;
+    let primary = persisted.unwrap_or(Cluster::Active);
+    let clients = db::init_clients(cfg).await?;
+    match cmd {
+        FailoverCommand::Status => {
+            let health = db_health(&clients).await.data;
+            let summary = |c: Option<&crate::health::ClusterHealth>| match c {
+                Some(h) => json!({ "state": h.state, "latency_ms": h.latency_ms, "release_version": h.release_version }),
+                None => Value::Null,
+            };
+            let outbox = if Path::new(dir).is_dir() { Some(Outbox::open_read_only(dir)?.stats()?) } else { None };
+            Ok(json!({
+                "primary": primary,
+                "primary_source": if persisted.is_some() { "persisted" } else { "default" },
+                "fail_threshold": cfg.replication.failover_fail_threshold,
+                "recover_threshold": cfg.replication.failover_recover_threshold,
+                "active": summary(health.as_ref().map(|h| &h.active)),
+                "passive": summary(health.as_ref().map(|h| &h.passive)),
+                "outbox": outbox,
+            }))
+        }
+        FailoverCommand::Promote { to, force } => {
+            let mut replayed = 0usize;
+            let mut outbox_locked = false;
+            if !force && *to != primary {
+                let check = DefaultSyncCheck::with_keyspaces(cfg.active.keyspace.clone(), cfg.passive.keyspace.clone());
+                if !check.ready_to_switch(&clients, primary, *to).await {
+                    return Err(AppError::unavailable(format!("{} cluster is not reachable; pass --force to promote anyway", to.label())));
+                }
+                let pending = match ReplicationManager::from_config(cfg) {
+                    Ok(repl) => {
+                        loop {
+                            let n = repl.replay_and_mark(PROMOTE_REPLAY_BATCH, &clients).await?;
+                            if n == 0 { break; }
+                            replayed += n;
+                        }
+                        repl.queue_len()
+                    }
+                    Err(AppError::Unavailable(_)) => {
+                        outbox_locked = true;
+                        Outbox::open_read_only(dir)?.pending_count()?
+                    }
+                    Err(e) => return Err(e),
+                };
+                if pending > 0 {
+                    return Err(AppError::unavailable(format!(
+                        "outbox still has {} record(s) that could not be replayed; pass --force to promote anyway",
+                        pending
+                    )));
+                }
+            }
+            store_primary(dir, *to)?;
+            Ok(json!({ "primary": to, "previous": primary, "replayed": replayed, "forced": force, "outbox_locked": outbox_locked }))
+        }
+    }
+}
+
+pub async fn run_ensure_keyspaces_command(cfg: &AppConfig) -> AppResult<Value> {
+    let clients = db::init_clients(cfg).await?;
+    db::ensure_keyspaces(cfg, &clients).await?;
+    Ok(json!({
+        "active": { "keyspace": cfg.active.keyspace, "connected": clients.is_connected(Cluster::Active) },
+        "passive": { "keyspace": cfg.passive.keyspace, "connected": clients.is_connected(Cluster::Passive) },
+    }))
+}
+
+pub async fn run_job_command(cfg: &AppConfig, cmd: &JobCommand) -> AppResult<Value> {
+    let registry = JobRegistry::new().with_artifact_dir(&cfg.jobs.artifact_dir).with_max_finished(cfg.jobs.max_finished);
+    let job = match cmd {
+        JobCommand::Export(args) => registry.run("export", None, run_export_command(cfg, args)).await,
+        JobCommand::Verify(args) => registry.run("verify", Some("verify-report.json"), run_verify_command(cfg, args)).await,
+        JobCommand::Repair(args) => registry.run("repair", Some("repair-report.json"), run_repair_command(cfg, args)).await,
+    };
+    if job.status == JobStatus::Failed {
+        return Err(AppError::other(format!(
+            "{} job {} failed: {}",
+            job.kind, job.id, job.error.as_deref().unwrap_or("unknown error")
+        )));
+    }
+    to_json(job)
+}
+
+fn to_json<T: Serialize>(value: T) -> AppResult<Value> {
+    serde_json::to_value(value).map_err(|e| AppError::json("encode command output", e))
+}
+
+pub async fn run_command(cfg: &AppConfig, command: &Command) -> AppResult<Option<Value>> {
+    let value = match command {
+        Command::Serve | Command::PrintConfig | Command::CheckConfig => return Ok(None),
+        Command::EnsureKeyspaces => run_ensure_keyspaces_command(cfg).await?,
+        Command::Import(args) => to_json(run_import_command(cfg, args).await?)?,
+        Command::Export(args) => to_json(run_export_command(cfg, args).await?)?,
+        Command::Verify(args) => to_json(run_verify_command(cfg, args).await?)?,
+        Command::Repair(args) => to_json(run_repair_command(cfg, args).await?)?,
+        Command::Migrate(args) => run_migrate_command(cfg, args).await?,
+        Command::Outbox(cmd) => run_outbox_command(cfg, cmd)?,
+        Command::Failover(cmd) => run_failover_command(cfg, cmd).await?,
+        Command::Jobs(cmd) => run_job_command(cfg, cmd).await?,
+    };
+    Ok(Some(value))
+}
+
+pub fn run_config_command(cfg: &AppConfig, command: &Command, output: OutputFormat) -> (String, AppResult<()>) {
+    let report = cfg.validate();
+    if let Command::PrintConfig = command {
+        let text = match output {
+            OutputFormat::Json => render(&json!({ "entries": cfg.effective(), "issues": report.issues }), output),
+            OutputFormat::Human => {
+                let mut text = cfg.render_effective();
+                for issue in &report.issues {
+                    text.push_str(&format!("# {:?}: {}\n", issue.severity, issue));
+                }
+                text
+            }
+        };
+        return (text, Ok(()));
+    }
+
+    let errors = report.errors().count();
+    let warnings = report.warnings().count();
+    let text = match output {
+        OutputFormat::Json => render(&json!({
+            "config_file": cfg.config_file,
+            "strict": cfg.strict,
+            "valid": errors == 0 && (!cfg.strict || warnings == 0),
+            "issues": report.issues,
+        }), output),
+        OutputFormat::Human => {
+            let mut text = format!("config file: {}\n", cfg.config_file.as_deref().unwrap_or("(none, defaults and environment only)"));
+            for issue in &report.issues {
+                let label = match issue.severity { Severity::Error => "error", Severity::Warning => "warning" };
+                text.push_str(&format!("{}: {}\n", label, issue));
+            }
+            text.push_str(&format!("{} error(s), {} warning(s)\n", errors, warnings));
+            text
+        }
+    };
+    (text, report.into_result(cfg.strict).map(|_| ()))
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Object(_) => "{}".to_string(),
+        Value::Array(items) => items.iter().map(scalar).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                flatten(&key, v, out);
+            }
+        }
+        Value::Array(items) if items.iter().any(|v| v.is_object() || v.is_array()) => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(&format!("{}[{}]", prefix, i), v, out);
+            }
+        }
+        other => out.push((prefix.to_string(), scalar(other))),
+    }
+}
+
+pub fn render(value: &Value, output: OutputFormat) -> String {
+    match output {
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(value).unwrap_or_default()),
+        OutputFormat::Human => {
+            let mut lines = Vec::new();
+            flatten("", value, &mut lines);
+            let width = lines.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+            lines
+                .into_iter()
+                .map(|(k, v)| if k.is_empty() { format!("{}\n", v) } else { format!("{:<width$}  {}\n", k, v, width = width) })
+                .collect()
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::config::{parse_bool, split_list, AppConfig, DbEndpoint, DriverConfig, ExecutionSettings, SecretResolver, TomlAppConfig};
+use crate::errors::{AppError, AppResult};
+use crate::utils::mask_secret;
+
+const DEFAULT_CONFIG_FILE: &str = "config/nayud-batch.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "layer", content = "origin", rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    File(String),
+    Env(String),
+    Cli(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(p) => write!(f, "file {}", p),
+            ConfigSource::Env(v) => write!(f, "env {}", v),
+            ConfigSource::Cli(flag) => write!(f, "cli {}", flag),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CliOverride {
+    pub flag: String,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CliOverrides {
+    pub config_file: Option<String>,
+    pub values: Vec<CliOverride>,
+}
+
+impl CliOverrides {
+    pub fn push(&mut self, flag: impl Into<String>, key: impl Into<String>, value: impl Into<String>) {
+        self.values.push(CliOverride { flag: flag.into(), key: key.into(), value: value.into() });
+    }
+
+    pub fn push_assignment(&mut self, flag: &str, assignment: &str) -> AppResult<()> {
+        match assignment.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                self.push(flag, key.trim(), value);
+                Ok(())
+            }
+            _ => Err(AppError::config(format!("{} expects key=value, got '{}'", flag, assignment))),
+        }
+    }
+}
+
+enum SetError {
+    UnknownKey,
+    Invalid(&'static str),
+}
+
+type SetResult = Result<(), SetError>;
+
+fn num<T: FromStr>(raw: &str, expected: &'static str) -> Result<T, SetError> {
+    raw.trim().parse().map_err(|_| SetError::Invalid(expected))
+}
+
+fn opt_num<T: FromStr>(raw: &str, expected: &'static str) -> Result<Option<T>, SetError> {
+    if raw.trim().is_empty() { Ok(None) } else { num(raw, expected).map(Some) }
+}
+
+fn boolean(raw: &str) -> Result<bool, SetError> { parse_bool(raw).map_err(|_| SetError::Invalid("boolean")) }
+
+fn opt_bool(raw: &str) -> Result<Option<bool>, SetError> {
+    if raw.trim().is_empty() { Ok(None) } else { boolean(raw).map(Some) }
+}
+
+fn opt_str(raw: &str) -> Option<String> {
+    if raw.trim().is_empty() { None } else { Some(raw.to_string()) }
+}
+
+fn set_endpoint(ep: &mut DbEndpoint, field: &str, raw: &str) -> SetResult {
+    match field {
+        "host" => ep.host = raw.to_string(),
+        "port" => ep.port = num(raw, "port")?,
+        "contact_points" => ep.contact_points = split_list(raw),
+        "keyspace" => ep.keyspace = raw.to_string(),
+        "datacenter" => ep.datacenter = raw.to_string(),
+        "rack" => ep.rack = raw.to_string(),
+        "username" => ep.username = raw.to_string(),
+        "password" => ep.password = raw.to_string(),
+        "username_file" => ep.username_file = opt_str(raw),
+        "password_file" => ep.password_file = opt_str(raw),
+        "use_tls" => ep.use_tls = boolean(raw)?,
+        "tls_ca_file" => ep.tls_ca_file = opt_str(raw),
+        "tls_cert_file" => ep.tls_cert_file = opt_str(raw),
+        "tls_key_file" => ep.tls_key_file = opt_str(raw),
+        "tls_key_password" => ep.tls_key_password = opt_str(raw),
+        "tls_min_version" => ep.tls_min_version = opt_str(raw),
+        "tls_ciphers" => ep.tls_ciphers = opt_str(raw),
+        "tls_verify_hostname" => ep.tls_verify_hostname = boolean(raw)?,
+        "tls_server_names" => ep.tls_server_names = split_list(raw),
+        "tls_insecure_skip_verify" => ep.tls_insecure_skip_verify = boolean(raw)?,
+        "replication_factor" => ep.replication_factor = opt_num(raw, "non-negative integer")?,
+        "durable_writes" => ep.durable_writes = opt_bool(raw)?,
+        _ => return Err(SetError::UnknownKey),
+    }
+    Ok(())
+}
+
+fn set_execution(ex: &mut ExecutionSettings, field: &str, raw: &str) -> SetResult {
+    match field {
+        "request_timeout_ms" => ex.request_timeout_ms = opt_num(raw, "non-negative integer")?,
+        "consistency" => ex.consistency = opt_str(raw),
+        "serial_consistency" => ex.serial_consistency = opt_str(raw),
+        "retry_policy" => ex.retry_policy = opt_str(raw),
+        "speculative_execution" => ex.speculative_execution = opt_str(raw),
+        "speculative_max_retries" => ex.speculative_max_retries = opt_num(raw, "non-negative integer")?,
+        "speculative_delay_ms" => ex.speculative_delay_ms = opt_num(raw, "non-negative integer")?,
+        "speculative_percentile" => ex.speculative_percentile = opt_num(raw, "number")?,
+        _ => return Err(SetError::UnknownKey),
+    }
+    Ok(())
+}
+
+fn set_driver(d: &mut DriverConfig, field: &str, raw: &str) -> SetResult {
+    if let Some(rest) = field.strip_prefix("active.") { return set_execution(&mut d.active, rest, raw); }
+    if let Some(rest) = field.strip_prefix("passive.") { return set_execution(&mut d.passive, rest, raw); }
+    if let Some(rest) = field.strip_prefix("profiles.") {
+        let (name, setting) = rest.split_once('.').ok_or(SetError::UnknownKey)?;
+        let mut profile = d.profiles.get(name).cloned().unwrap_or_default();
+        set_execution(&mut profile, setting, raw)?;
+        d.profiles.insert(name.to_string(), profile);
+        return Ok(());
+    }
+    match field {
+        "connection_timeout_ms" => d.connection_timeout_ms = opt_num(raw, "non-negative integer")?,
+        "reconnect_initial_ms" => d.reconnect_initial_ms = opt_num(raw, "non-negative integer")?,
+        "reconnect_max_ms" => d.reconnect_max_ms = opt_num(raw, "non-negative integer")?,
+        "tcp_keepalive_secs" => d.tcp_keepalive_secs = opt_num(raw, "non-negative integer")?,
+        "tcp_nodelay" => d.tcp_nodelay = opt_bool(raw)?,
+        "connections_per_shard" => d.connections_per_shard = opt_num(raw, "non-negative integer")?,
+        "connections_per_host" => d.connections_per_host = opt_num(raw, "non-negative integer")?,
+        "compression" => d.compression = opt_str(raw),
+        "default_page_size" => d.default_page_size = opt_num(raw, "integer")?,
+        "prepared_cache_size" => d.prepared_cache_size = opt_num(raw, "non-negative integer")?,
+        "prepared_statements" => {
+            d.prepared_statements = raw.split(';').map(str::trim).filter(|s| !s.is_empty()).map(String::from).collect()
+        }
+        "load_balancing" => d.load_balancing = opt_str(raw),
+        "token_aware" => d.token_aware = opt_bool(raw)?,
+        "permit_dc_failover" => d.permit_dc_failover = opt_bool(raw)?,
+        _ => return set_execution(&mut d.execution, field, raw),
+    }
+    Ok(())
+}
+
+fn set_value(cfg: &mut AppConfig, key: &str, raw: &str) -> SetResult {
+    let (section, field) = key.split_once('.').unwrap_or((key, ""));
+    match (section, field) {
+        ("active", f) => return set_endpoint(&mut cfg.active, f, raw),
+        ("passive", f) => return set_endpoint(&mut cfg.passive, f, raw),
+        ("driver", f) => return set_driver(&mut cfg.driver, f, raw),
+        ("server", "bind_addr") => cfg.server.bind_addr = raw.to_string(),
+        ("server", "shutdown_grace_ms") => cfg.server.shutdown_grace_ms = num(raw, "non-negative integer")?,
+        ("server", "log_level") => cfg.server.log_level = raw.to_string(),
+        ("server", "config_watch_ms") => cfg.server.config_watch_ms = num(raw, "non-negative integer")?,
+        ("server", "admin_token") => cfg.server.admin_token = opt_str(raw),
+        ("replication", "outbox_dir") => cfg.replication.outbox_dir = raw.to_string(),
+        ("replication", "drift_max_records") => cfg.replication.drift_max_records = num(raw, "non-negative integer")?,
+        ("replication", "drift_max_bytes") => cfg.replication.drift_max_bytes = num(raw, "non-negative integer")?,
+        ("replication", "failover_fail_threshold") => cfg.replication.failover_fail_threshold = num(raw, "non-negative integer")?,
+        ("replication", "failover_recover_threshold") => cfg.replication.failover_recover_threshold = num(raw, "non-negative integer")?,
+        ("replication", "outbox_compression") => cfg.replication.outbox_compression = raw.to_string(),
+        ("replication", "outbox_compression_min_bytes") => cfg.replication.outbox_compression_min_bytes = num(raw, "non-negative integer")?,
+        ("replication", "outbox_group_commit_us") => cfg.replication.outbox_group_commit_us = num(raw, "non-negative integer")?,
+        ("replication", "outbox_encryption_key_id") => cfg.replication.outbox_encryption_key_id = opt_num(raw, "key id between 1 and 65535")?,
+        ("replication", f) if f.starts_with("outbox_keys.") => {
+            cfg.replication.outbox_keys.insert(f["outbox_keys.".len()..].to_string(), raw.to_string());
+        }
+        ("replication", f) if f.starts_with("outbox_key_files.") => {
+            cfg.replication.outbox_key_files.insert(f["outbox_key_files.".len()..].to_string(), raw.to_string());
+        }
+        ("export", "out_dir") => cfg.export.out_dir = raw.to_string(),
+        ("export", f) if f.starts_with("queries.") => {
+            cfg.export.queries.insert(f["queries.".len()..].to_string(), raw.to_string());
+        }
+        ("jobs", "artifact_dir") => cfg.jobs.artifact_dir = raw.to_string(),
+        ("jobs", "max_finished") => cfg.jobs.max_finished = num(raw, "non-negative integer")?,
+        ("migrations", "dir") => cfg.migrations.dir = raw.to_string(),
+        ("migrations", "apply_on_startup") => cfg.migrations.apply_on_startup = boolean(raw)?,
+        ("secrets", "vault_addr") => cfg.secrets.vault_addr = opt_str(raw),
+        ("secrets", "vault_token") => cfg.secrets.vault_token = opt_str(raw),
+        ("secrets", "vault_token_file") => cfg.secrets.vault_token_file = opt_str(raw),
+        ("secrets", "vault_timeout_ms") => cfg.secrets.vault_timeout_ms = opt_num(raw, "non-negative integer")?,
+        ("strict", "") => cfg.strict = boolean(raw)?,
+        _ => return Err(SetError::UnknownKey),
+    }
+    Ok(())
+}
+
+fn env_candidates(key: &str) -> Vec<String> {
+    let upper = |s: &str| s.to_ascii_uppercase();
+    let (section, field) = key.split_once('.').unwrap_or((key, ""));
+    match section {
+        "active" => vec![format!("ACTIVE_DB_{}", upper(field)), format!("DB_{}", upper(field))],
+        "passive" => vec![format!("PASSIVE_DB_{}", upper(field)), format!("DB_{}", upper(field))],
+        "driver" => {
+            if field.starts_with("profiles.") {
+                Vec::new()
+            } else if let Some(rest) = field.strip_prefix("active.") {
+                vec![format!("ACTIVE_DB_{}", upper(rest))]
+            } else if let Some(rest) = field.strip_prefix("passive.") {
+                vec![format!("PASSIVE_DB_{}", upper(rest))]
+            } else {
+                vec![format!("DB_{}", upper(field))]
+            }
+        }
+        "server" => vec![format!("WEB_{}", upper(field))],
+        "replication" if !field.contains('.') => vec![format!("REPL_{}", upper(field))],
+        "export" if field == "out_dir" => vec!["EXPORT_OUT_DIR".to_string()],
+        "jobs" => vec![format!("JOBS_{}", upper(field))],
+        "migrations" => vec![format!("MIGRATIONS_{}", upper(field))],
+        "secrets" => vec![format!("SECRETS_{}", upper(field)), upper(field)],
+        "strict" => vec!["NAYUD_CONFIG_STRICT".to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn quoted(s: &str) -> String { format!("{:?}", s) }
+
+fn quoted_list(items: &[String]) -> String {
+    format!("[{}]", items.iter().map(|s| quoted(s)).collect::<Vec<_>>().join(", "))
+}
+
+fn shown<T: ToString>(v: &Option<T>) -> Option<String> { v.as_ref().map(ToString::to_string) }
+
+fn shown_str(v: &Option<String>) -> Option<String> { v.as_deref().map(quoted) }
+
+fn endpoint_entries(out: &mut Vec<(String, Option<String>)>, scope: &str, ep: &DbEndpoint) {
+    let mut push = |field: &str, v: Option<String>| out.push((format!("{}.{}", scope, field), v));
+    push("host", Some(quoted(&ep.host)));
+    push("port", Some(ep.port.to_string()));
+    push("contact_points", Some(quoted_list(&ep.contact_points)));
+    push("keyspace", Some(quoted(&ep.keyspace)));
+    push("datacenter", Some(quoted(&ep.datacenter)));
+    push("rack", Some(quoted(&ep.rack)));
+    push("username", Some(quoted(&ep.username)));
+    push("password", Some(quoted(&ep.password)));
+    push("username_file", shown_str(&ep.username_file));
+    push("password_file", shown_str(&ep.password_file));
+    push("use_tls", Some(ep.use_tls.to_string()));
+    push("tls_ca_file", shown_str(&ep.tls_ca_file));
+    push("tls_cert_file", shown_str(&ep.tls_cert_file));
+    push("tls_key_file", shown_str(&ep.tls_key_file));
+    push("tls_key_password", shown_str(&ep.tls_key_password));
+    push("tls_min_version", shown_str(&ep.tls_min_version));
+    push("tls_ciphers", shown_str(&ep.tls_ciphers));
+    push("tls_verify_hostname", Some(ep.tls_verify_hostname.to_string()));
+    push("tls_server_names", Some(quoted_list(&ep.tls_server_names)));
+    push("tls_insecure_skip_verify", Some(ep.tls_insecure_skip_verify.to_string()));
+    push("replication_factor", shown(&ep.replication_factor));
+    push("durable_writes", shown(&ep.durable_writes));
+}
+
+fn execution_entries(out: &mut Vec<(String, Option<String>)>, scope: &str, ex: &ExecutionSettings) {
+    let mut push = |field: &str, v: Option<String>| out.push((format!("{}.{}", scope, field), v));
+    push("request_timeout_ms", shown(&ex.request_timeout_ms));
+    push("consistency", shown_str(&ex.consistency));
+    push("serial_consistency", shown_str(&ex.serial_consistency));
+    push("retry_policy", shown_str(&ex.retry_policy));
+    push("speculative_execution", shown_str(&ex.speculative_execution));
+    push("speculative_max_retries", shown(&ex.speculative_max_retries));
+    push("speculative_delay_ms", shown(&ex.speculative_delay_ms));
+    push("speculative_percentile", shown(&ex.speculative_percentile));
+}
+
+fn driver_entries(out: &mut Vec<(String, Option<String>)>, d: &DriverConfig) {
+    execution_entries(out, "driver", &d.execution);
+    execution_entries(out, "driver.active", &d.active);
+    execution_entries(out, "driver.passive", &d.passive);
+    for (name, p) in &d.profiles {
+        execution_entries(out, &format!("driver.profiles.{}", name), p);
+    }
+    let mut push = |field: &str, v: Option<String>| out.push((format!("driver.{}", field), v));
+    push("connection_timeout_ms", shown(&d.connection_timeout_ms));
+    push("reconnect_initial_ms", shown(&d.reconnect_initial_ms));
+    push("reconnect_max_ms", shown(&d.reconnect_max_ms));
+    push("tcp_keepalive_secs", shown(&d.tcp_keepalive_secs));
+    push("tcp_nodelay", shown(&d.tcp_nodelay));
+    push("connections_per_shard", shown(&d.connections_per_shard));
+    push("connections_per_host", shown(&d.connections_per_host));
+    push("compression", shown_str(&d.compression));
+    push("default_page_size", shown(&d.default_page_size));
+    push("prepared_cache_size", shown(&d.prepared_cache_size));
+    push("prepared_statements", Some(quoted_list(&d.prepared_statements)));
+    push("load_balancing", shown_str(&d.load_balancing));
+    push("token_aware", shown(&d.token_aware));
+    push("permit_dc_failover", shown(&d.permit_dc_failover));
+}
+
+pub(crate) fn is_secret(key: &str) -> bool {
+    let field = key.rsplit('.').next().unwrap_or(key);
+    field == "username" || field.ends_with("password") || field.ends_with("token") || key.starts_with("replication.outbox_keys.")
+}
+
+fn flatten_toml(prefix: &str, value: &toml::Value, out: &mut Vec<String>) {
+    match value {
+        toml::Value::Table(t) => {
+            for (k, v) in t {
+                let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                flatten_toml(&key, v, out);
+            }
+        }
+        _ => out.push(prefix.to_string()),
+    }
+}
+
+fn interpolate_toml(prefix: &str, value: &mut toml::Value, resolver: &SecretResolver) -> AppResult<()> {
+    match value {
+        toml::Value::Table(t) => {
+            for (k, v) in t.iter_mut() {
+                let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                interpolate_toml(&key, v, resolver)?;
+            }
+        }
+        toml::Value::Array(items) => {
+            for v in items.iter_mut() {
+                interpolate_toml(prefix, v, resolver)?;
+            }
+        }
+        toml::Value::String(s) if !is_secret(prefix) && s.contains("${") => {
+            *s = resolver.interpolate(s).map_err(|e| e.with_context(prefix))?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: Option<String>,
+    pub source: ConfigSource,
+}
+
+impl AppConfig {
+    pub fn load(cli: &CliOverrides) -> Self {
+        let mut cfg = AppConfig::default();
+
+        let explicit = cli.config_file.clone().or_else(|| env::var("NAYUD_CONFIG_FILE").ok());
+        let path = match explicit {
+            Some(p) if !Path::new(&p).exists() => {
+                cfg.load_problems.push(format!("config file {} does not exist", p));
+                None
+            }
+            Some(p) => Some(p),
+            None => Path::new(DEFAULT_CONFIG_FILE).exists().then(|| DEFAULT_CONFIG_FILE.to_string()),
+        };
+        if let Some(p) = path {
+            match Self::from_file(&p) {
+                Ok(file_cfg) => cfg = file_cfg,
+                Err(e) => cfg.load_problems.push(e.to_message()),
+            }
+            cfg.config_file = Some(p);
+        }
+
+        cfg.apply_env();
+        cfg.apply_cli(cli);
+        cfg
+    }
+
+    pub fn from_env() -> Self {
+        let mut cfg = AppConfig::default();
+        cfg.apply_env();
+        cfg
+    }
+
+    pub fn from_file_or_env() -> Self { Self::load(&CliOverrides::default()) }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> AppResult<Self> {
+        let path = path.as_ref();
+        let s = fs::read_to_string(path).map_err(|e| AppError::io(format!("failed to read config file {}", path.display()), e))?;
+        let mut raw = toml::from_str::<toml::Value>(&s)
+            .map_err(|e| AppError::toml(format!("failed to parse config file {}", path.display()), e))?;
+        interpolate_toml("", &mut raw, &SecretResolver::local())
+            .map_err(|e| e.with_context(format!("config file {}", path.display())))?;
+        let tcfg: TomlAppConfig = raw.clone().try_into()
+            .map_err(|e| AppError::toml(format!("failed to parse config file {}", path.display()), e))?;
+        let mut keys = Vec::new();
+        flatten_toml("", &raw, &mut keys);
+
+        let mut cfg: AppConfig = tcfg.into();
+        let source = ConfigSource::File(path.display().to_string());
+        for key in keys {
+            cfg.provenance.insert(key, source.clone());
+        }
+        Ok(cfg)
+    }
+
+    fn apply_env(&mut self) {
+        for (key, _) in self.entries() {
+            for var in env_candidates(&key) {
+                let Ok(raw) = env::var(&var) else { continue };
+                match set_value(self, &key, &raw) {
+                    Ok(()) => {
+                        self.provenance.insert(key.clone(), ConfigSource::Env(var));
+                        break;
+                    }
+                    Err(SetError::Invalid(expected)) => {
+                        self.load_problems.push(format!("{}='{}' is not a valid {}", var, raw, expected));
+                    }
+                    Err(SetError::UnknownKey) => break,
+                }
+            }
+        }
+    }
+
+    fn apply_cli(&mut self, cli: &CliOverrides) {
+        for o in &cli.values {
+            match set_value(self, &o.key, &o.value) {
+                Ok(()) => {
+                    self.provenance.insert(o.key.clone(), ConfigSource::Cli(o.flag.clone()));
+                }
+                Err(SetError::Invalid(expected)) => {
+                    self.load_problems.push(format!("{} {}='{}' is not a valid {}", o.flag, o.key, o.value, expected));
+                }
+                Err(SetError::UnknownKey) => {
+                    self.load_problems.push(format!("{} names unknown config key '{}'", o.flag, o.key));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn entries(&self) -> Vec<(String, Option<String>)> {
+        let mut out = Vec::new();
+        endpoint_entries(&mut out, "active", &self.active);
+        endpoint_entries(&mut out, "passive", &self.passive);
+        driver_entries(&mut out, &self.driver);
+        out.push(("server.bind_addr".into(), Some(quoted(&self.server.bind_addr))));
+        out.push(("server.shutdown_grace_ms".into(), Some(self.server.shutdown_grace_ms.to_string())));
+        out.push(("server.log_level".into(), Some(quoted(&self.server.log_level))));
+        out.push(("server.config_watch_ms".into(), Some(self.server.config_watch_ms.to_string())));
+        out.push(("server.admin_token".into(), shown_str(&self.server.admin_token)));
+        out.push(("replication.outbox_dir".into(), Some(quoted(&self.replication.outbox_dir))));
+        out.push(("replication.drift_max_records".into(), Some(self.replication.drift_max_records.to_string())));
+        out.push(("replication.drift_max_bytes".into(), Some(self.replication.drift_max_bytes.to_string())));
+        out.push(("replication.failover_fail_threshold".into(), Some(self.replication.failover_fail_threshold.to_string())));
+        out.push(("replication.failover_recover_threshold".into(), Some(self.replication.failover_recover_threshold.to_string())));
+        out.push(("replication.outbox_compression".into(), Some(quoted(&self.replication.outbox_compression))));
+        out.push(("replication.outbox_compression_min_bytes".into(), Some(self.replication.outbox_compression_min_bytes.to_string())));
+        out.push(("replication.outbox_group_commit_us".into(), Some(self.replication.outbox_group_commit_us.to_string())));
+        out.push(("replication.outbox_encryption_key_id".into(), shown(&self.replication.outbox_encryption_key_id)));
+        for (id, key) in &self.replication.outbox_keys {
+            out.push((format!("replication.outbox_keys.{}", id), Some(quoted(key))));
+        }
+        for (id, path) in &self.replication.outbox_key_files {
+            out.push((format!("replication.outbox_key_files.{}", id), Some(quoted(path))));
+        }
+        out.push(("export.out_dir".into(), Some(quoted(&self.export.out_dir))));
+        for (name, q) in &self.export.queries {
+            out.push((format!("export.queries.{}", name), Some(quoted(q))));
+        }
+        out.push(("jobs.artifact_dir".into(), Some(quoted(&self.jobs.artifact_dir))));
+        out.push(("jobs.max_finished".into(), Some(self.jobs.max_finished.to_string())));
+        out.push(("migrations.dir".into(), Some(quoted(&self.migrations.dir))));
+        out.push(("migrations.apply_on_startup".into(), Some(self.migrations.apply_on_startup.to_string())));
+        out.push(("secrets.vault_addr".into(), shown_str(&self.secrets.vault_addr)));
+        out.push(("secrets.vault_token".into(), shown_str(&self.secrets.vault_token)));
+        out.push(("secrets.vault_token_file".into(), shown_str(&self.secrets.vault_token_file)));
+        out.push(("secrets.vault_timeout_ms".into(), shown(&self.secrets.vault_timeout_ms)));
+        out.push(("strict".into(), Some(self.strict.to_string())));
+        out
+    }
+
+    pub fn source_of(&self, key: &str) -> ConfigSource {
+        self.provenance.get(key).cloned().unwrap_or(ConfigSource::Default)
+    }
+
+    pub fn effective(&self) -> Vec<ConfigEntry> {
+        self.entries()
+            .into_iter()
+            .map(|(key, value)| {
+                let value = if is_secret(&key) && !value.as_deref().is_some_and(|v| v.contains("${")) {
+                    value.map(|v| quoted(&mask_secret(v.trim_matches('"'))))
+                } else {
+                    value
+                };
+                let source = self.source_of(&key);
+                ConfigEntry { key, value, source }
+            })
+            .collect()
+    }
+
+    pub fn render_effective(&self) -> String {
+        let entries = self.effective();
+        let lines: Vec<(String, String)> = entries
+            .into_iter()
+            .map(|e| match e.value {
+                Some(v) => (format!("{} = {}", e.key, v), e.source.to_string()),
+                None => (format!("# {} (unset)", e.key), e.source.to_string()),
+            })
+            .collect();
+        let width = lines.iter().map(|(l, _)| l.len()).max().unwrap_or(0);
+        let mut out = String::new();
+        for (line, source) in lines {
+            out.push_str(&format!("{:<width$}  # {}\n", line, source, width = width));
+        }
+        out
+    }
+}
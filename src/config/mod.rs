@@ -1,37 +1,127 @@
-use std::env;
-use std::fs;
-use std::path::Path;
-use log::warn;
+use std::collections::BTreeMap;
+use std::fmt;
 use serde::Deserialize;
 
-#[derive(Clone, Debug)]
+use crate::utils::mask_secret;
+
+pub mod layers;
+pub mod reload;
+pub mod secrets;
+pub mod validate;
+
+pub use layers::{CliOverride, CliOverrides, ConfigEntry, ConfigSource};
+pub use reload::{watched_files, ReloadReport, Reloader, SharedConfig};
+pub use secrets::{SecretProvider, SecretResolver};
+pub use validate::{ConfigIssue, Severity, ValidationReport};
+
+#[derive(Clone)]
 pub struct DbEndpoint {
     pub host: String,
     pub port: u16,
+    pub contact_points: Vec<String>,
     pub keyspace: String,
     pub datacenter: String,
     pub rack: String,
     pub username: String,
     pub password: String,
+    pub username_file: Option<String>,
+    pub password_file: Option<String>,
     pub use_tls: bool,
     pub tls_ca_file: Option<String>,
+    pub tls_cert_file: Option<String>,
+    pub tls_key_file: Option<String>,
+    pub tls_key_password: Option<String>,
+    pub tls_min_version: Option<String>,
+    pub tls_ciphers: Option<String>,
+    pub tls_verify_hostname: bool,
+    pub tls_server_names: Vec<String>,
     pub tls_insecure_skip_verify: bool,
     pub replication_factor: Option<u32>,
     pub durable_writes: Option<bool>,
 }
 
 #[derive(Clone, Debug, Default)]
-pub struct DriverConfig {
+pub struct ExecutionSettings {
     pub request_timeout_ms: Option<u64>,
+    pub consistency: Option<String>,
+    pub serial_consistency: Option<String>,
+    pub retry_policy: Option<String>,
+    pub speculative_execution: Option<String>,
+    pub speculative_max_retries: Option<usize>,
+    pub speculative_delay_ms: Option<u64>,
+    pub speculative_percentile: Option<f64>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DriverConfig {
+    pub execution: ExecutionSettings,
+    pub active: ExecutionSettings,
+    pub passive: ExecutionSettings,
+    pub profiles: BTreeMap<String, ExecutionSettings>,
     pub connection_timeout_ms: Option<u64>,
+    pub reconnect_initial_ms: Option<u64>,
+    pub reconnect_max_ms: Option<u64>,
     pub tcp_keepalive_secs: Option<u64>,
+    pub tcp_nodelay: Option<bool>,
+    pub connections_per_shard: Option<usize>,
+    pub connections_per_host: Option<usize>,
     pub compression: Option<String>,
     pub default_page_size: Option<i32>,
+    pub prepared_cache_size: Option<usize>,
+    pub prepared_statements: Vec<String>,
+    pub load_balancing: Option<String>,
+    pub token_aware: Option<bool>,
+    pub permit_dc_failover: Option<bool>,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Default)]
 pub struct ServerConfig {
     pub bind_addr: String,
+    pub shutdown_grace_ms: u64,
+    pub log_level: String,
+    pub config_watch_ms: u64,
+    pub admin_token: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplicationConfig {
+    pub outbox_dir: String,
+    pub drift_max_records: usize,
+    pub drift_max_bytes: u64,
+    pub failover_fail_threshold: u32,
+    pub failover_recover_threshold: u32,
+    pub outbox_compression: String,
+    pub outbox_compression_min_bytes: usize,
+    pub outbox_encryption_key_id: Option<u16>,
+    pub outbox_keys: BTreeMap<String, String>,
+    pub outbox_key_files: BTreeMap<String, String>,
+    pub outbox_group_commit_us: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ExportConfig {
+    pub out_dir: String,
+    pub queries: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct JobsConfig {
+    pub artifact_dir: String,
+    pub max_finished: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct MigrationsConfig {
+    pub dir: String,
+    pub apply_on_startup: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct SecretsConfig {
+    pub vault_addr: Option<String>,
+    pub vault_token: Option<String>,
+    pub vault_token_file: Option<String>,
+    pub vault_timeout_ms: Option<u64>,
 }
 
 #[derive(Clone, Debug)]
@@ -40,6 +130,15 @@ pub struct AppConfig {
     pub passive: DbEndpoint,
     pub driver: DriverConfig,
     pub server: ServerConfig,
+    pub replication: ReplicationConfig,
+    pub export: ExportConfig,
+    pub jobs: JobsConfig,
+    pub migrations: MigrationsConfig,
+    pub secrets: SecretsConfig,
+    pub strict: bool,
+    pub config_file: Option<String>,
+    pub load_problems: Vec<String>,
+    pub provenance: BTreeMap<String, ConfigSource>,
 }
 
 impl Default for DbEndpoint {
@@ -47,13 +146,23 @@ impl Default for DbEndpoint {
         Self {
             host: "127.0.0.1".into(),
             port: 9042,
+            contact_points: Vec::new(),
             keyspace: "batch".into(),
             datacenter: "asia-southeast2".into(),
             rack: "asia-southeast2-a".into(),
             username: "cassandra".into(),
             password: "cassandra".into(),
+            username_file: None,
+            password_file: None,
             use_tls: false,
             tls_ca_file: None,
+            tls_cert_file: None,
+            tls_key_file: None,
+            tls_key_password: None,
+            tls_min_version: None,
+            tls_ciphers: None,
+            tls_verify_hostname: false,
+            tls_server_names: Vec::new(),
             tls_insecure_skip_verify: false,
             replication_factor: Some(3),
             durable_writes: Some(true),
@@ -61,6 +170,36 @@ impl Default for DbEndpoint {
     }
 }
 
+impl Default for ReplicationConfig {
+    fn default() -> Self {
+        Self {
+            outbox_dir: "data/outbox".into(),
+            drift_max_records: 100,
+            drift_max_bytes: 1_000_000,
+            failover_fail_threshold: 3,
+            failover_recover_threshold: 5,
+            outbox_compression: "none".into(),
+            outbox_compression_min_bytes: 256,
+            outbox_encryption_key_id: None,
+            outbox_keys: BTreeMap::new(),
+            outbox_key_files: BTreeMap::new(),
+            outbox_group_commit_us: 0,
+        }
+    }
+}
+
+impl Default for ExportConfig {
+    fn default() -> Self { Self { out_dir: "data/export".into(), queries: BTreeMap::new() } }
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self { Self { artifact_dir: "data/jobs".into(), max_finished: 500 } }
+}
+
+impl Default for MigrationsConfig {
+    fn default() -> Self { Self { dir: "cql/migrations".into(), apply_on_startup: true } }
+}
+
 impl Default for AppConfig {
     fn default() -> Self {
         let active = DbEndpoint::default();
@@ -68,90 +207,112 @@ impl Default for AppConfig {
         passive.port = 9043;
         passive.rack = "asia-southeast2-b".into();
         let driver = DriverConfig::default();
-        let server = ServerConfig { bind_addr: "127.0.0.1:8080".into() };
-        Self { active, passive, driver, server }
+        let server = ServerConfig { bind_addr: "127.0.0.1:8080".into(), shutdown_grace_ms: 5000, log_level: "info".into(), config_watch_ms: 0, admin_token: None };
+        let replication = ReplicationConfig::default();
+        let export = ExportConfig::default();
+        let jobs = JobsConfig::default();
+        let migrations = MigrationsConfig::default();
+        Self { active, passive, driver, server, replication, export, jobs, migrations, secrets: SecretsConfig::default(), strict: false, config_file: None, load_problems: Vec::new(), provenance: BTreeMap::new() }
     }
 }
 
-impl AppConfig {
-    pub fn from_env() -> Self {
-        let defaults = AppConfig::default();
-        let active = DbEndpoint::from_env_with_defaults("ACTIVE_DB", Some("DB"), &defaults.active);
-        let passive_defaults = defaults.passive;
-        let passive = DbEndpoint::from_env_with_defaults("PASSIVE_DB", Some("DB"), &passive_defaults);
-        let driver = DriverConfig::from_env("DB");
-        let server = ServerConfig::from_env("WEB").unwrap_or_else(|| defaults.server.clone());
-        AppConfig { active, passive, driver, server }
+fn masked(value: &str) -> String {
+    if value.contains("${") { value.to_string() } else { mask_secret(value) }
+}
+
+impl fmt::Debug for DbEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DbEndpoint")
+            .field("host", &self.host)
+            .field("port", &self.port)
+            .field("contact_points", &self.contact_points)
+            .field("keyspace", &self.keyspace)
+            .field("datacenter", &self.datacenter)
+            .field("rack", &self.rack)
+            .field("username", &masked(&self.username))
+            .field("password", &masked(&self.password))
+            .field("username_file", &self.username_file)
+            .field("password_file", &self.password_file)
+            .field("use_tls", &self.use_tls)
+            .field("tls_ca_file", &self.tls_ca_file)
+            .field("tls_cert_file", &self.tls_cert_file)
+            .field("tls_key_file", &self.tls_key_file)
+            .field("tls_key_password", &self.tls_key_password.as_deref().map(masked))
+            .field("tls_min_version", &self.tls_min_version)
+            .field("tls_ciphers", &self.tls_ciphers)
+            .field("tls_verify_hostname", &self.tls_verify_hostname)
+            .field("tls_server_names", &self.tls_server_names)
+            .field("tls_insecure_skip_verify", &self.tls_insecure_skip_verify)
+            .field("replication_factor", &self.replication_factor)
+            .field("durable_writes", &self.durable_writes)
+            .finish()
     }
+}
 
-    pub fn from_file_or_env() -> Self {
-        let candidates: Vec<String> = env::var("NAYUD_CONFIG_FILE")
-            .ok()
-            .into_iter()
-            .chain(std::iter::once("config/nayud-batch.toml".to_string()))
-            .collect();
-
-        for p in candidates {
-            let path = Path::new(&p);
-            if path.exists() {
-                match fs::read_to_string(path) {
-                    Ok(s) => {
-                        match toml::from_str::<TomlAppConfig>(&s) {
-                            Ok(tcfg) => {
-                                let cfg: AppConfig = tcfg.into();
-                                return cfg;
-                            }
-                            Err(e) => {
-                                warn!(
-                                    "Failed to parse config file {}: {}. Falling back to env.",
-                                    p, e
-                                );
-                                break;
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        warn!(
-                            "Failed to read config file {}: {}. Falling back to env.",
-                            p, e
-                        );
-                        break;
-                    }
-                }
-            }
-        }
+impl fmt::Debug for ServerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ServerConfig")
+            .field("bind_addr", &self.bind_addr)
+            .field("shutdown_grace_ms", &self.shutdown_grace_ms)
+            .field("log_level", &self.log_level)
+            .field("config_watch_ms", &self.config_watch_ms)
+            .field("admin_token", &self.admin_token.as_deref().map(masked))
+            .finish()
+    }
+}
 
-        Self::from_env()
+impl fmt::Debug for SecretsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretsConfig")
+            .field("vault_addr", &self.vault_addr)
+            .field("vault_token", &self.vault_token.as_deref().map(masked))
+            .field("vault_token_file", &self.vault_token_file)
+            .field("vault_timeout_ms", &self.vault_timeout_ms)
+            .finish()
     }
 }
 
 impl DbEndpoint {
-    fn from_env_with_defaults(prefix: &str, global_prefix: Option<&str>, defaults: &DbEndpoint) -> DbEndpoint {
-        DbEndpoint {
-            host: read_env_string(prefix, global_prefix, "HOST", defaults.host.clone()),
-            port: read_env_u16(prefix, global_prefix, "PORT", defaults.port),
-            keyspace: read_env_string(prefix, global_prefix, "KEYSPACE", defaults.keyspace.clone()),
-            datacenter: read_env_string(prefix, global_prefix, "DATACENTER", defaults.datacenter.clone()),
-            rack: read_env_string(prefix, global_prefix, "RACK", defaults.rack.clone()),
-            username: read_env_string(prefix, global_prefix, "USERNAME", defaults.username.clone()),
-            password: read_env_string(prefix, global_prefix, "PASSWORD", defaults.password.clone()),
-            use_tls: read_env_bool(prefix, global_prefix, "USE_TLS", defaults.use_tls),
-            tls_ca_file: read_env_opt_string_scoped(prefix, global_prefix, "TLS_CA_FILE").or_else(|| defaults.tls_ca_file.clone()),
-            tls_insecure_skip_verify: read_env_bool(prefix, global_prefix, "TLS_INSECURE_SKIP_VERIFY", defaults.tls_insecure_skip_verify),
-            replication_factor: defaults.replication_factor.clone(),
-            durable_writes: defaults.durable_writes.clone(),
+    pub fn masked_credentials(&self) -> (String, String) {
+        let show = |file: &Option<String>, value: &str| match file {
+            Some(p) => format!("file:{}", p),
+            None => masked(value),
+        };
+        (show(&self.username_file, &self.username), show(&self.password_file, &self.password))
+    }
+
+    pub fn contact_points(&self) -> Vec<String> {
+        let raw: Vec<&str> = if self.contact_points.is_empty() {
+            vec![self.host.as_str()]
+        } else {
+            self.contact_points.iter().map(String::as_str).collect()
+        };
+        raw.into_iter().map(str::trim).filter(|p| !p.is_empty()).map(|p| with_default_port(p, self.port)).collect()
+    }
+}
+
+impl ExecutionSettings {
+    pub fn overlay(&self, over: &ExecutionSettings) -> ExecutionSettings {
+        ExecutionSettings {
+            request_timeout_ms: over.request_timeout_ms.or(self.request_timeout_ms),
+            consistency: over.consistency.clone().or_else(|| self.consistency.clone()),
+            serial_consistency: over.serial_consistency.clone().or_else(|| self.serial_consistency.clone()),
+            retry_policy: over.retry_policy.clone().or_else(|| self.retry_policy.clone()),
+            speculative_execution: over.speculative_execution.clone().or_else(|| self.speculative_execution.clone()),
+            speculative_max_retries: over.speculative_max_retries.or(self.speculative_max_retries),
+            speculative_delay_ms: over.speculative_delay_ms.or(self.speculative_delay_ms),
+            speculative_percentile: over.speculative_percentile.or(self.speculative_percentile),
         }
     }
 }
 
 impl DriverConfig {
-    pub fn from_env(global_prefix: &str) -> Self {
-        let request_timeout_ms = read_env_opt_u64(global_prefix, "REQUEST_TIMEOUT_MS");
-        let connection_timeout_ms = read_env_opt_u64(global_prefix, "CONNECTION_TIMEOUT_MS");
-        let tcp_keepalive_secs = read_env_opt_u64(global_prefix, "TCP_KEEPALIVE_SECS");
-        let compression = read_env_opt_string(global_prefix, "COMPRESSION");
-        let default_page_size = read_env_opt_i32(global_prefix, "DEFAULT_PAGE_SIZE");
-        Self { request_timeout_ms, connection_timeout_ms, tcp_keepalive_secs, compression, default_page_size }
+    pub fn execution_for(&self, which_active: bool) -> ExecutionSettings {
+        self.execution.overlay(if which_active { &self.active } else { &self.passive })
+    }
+
+    pub fn profile_for(&self, name: &str, which_active: bool) -> Option<ExecutionSettings> {
+        self.profiles.get(name).map(|p| self.execution_for(which_active).overlay(p))
     }
 }
 
@@ -160,13 +321,23 @@ impl DriverConfig {
 struct TomlDbEndpoint {
     host: String,
     port: u16,
+    contact_points: Vec<String>,
     keyspace: String,
     datacenter: String,
     rack: String,
     username: String,
     password: String,
+    username_file: Option<String>,
+    password_file: Option<String>,
     use_tls: bool,
     tls_ca_file: Option<String>,
+    tls_cert_file: Option<String>,
+    tls_key_file: Option<String>,
+    tls_key_password: Option<String>,
+    tls_min_version: Option<String>,
+    tls_ciphers: Option<String>,
+    tls_verify_hostname: bool,
+    tls_server_names: Vec<String>,
     tls_insecure_skip_verify: bool,
     replication_factor: Option<u32>,
     durable_writes: Option<bool>,
@@ -181,13 +352,23 @@ macro_rules! make_endpoint {
         $self_ {
             host: $src.host,
             port: $src.port,
+            contact_points: $src.contact_points,
             keyspace: $src.keyspace,
             datacenter: $src.datacenter,
             rack: $src.rack,
             username: $src.username,
             password: $src.password,
+            username_file: $src.username_file,
+            password_file: $src.password_file,
             use_tls: $src.use_tls,
             tls_ca_file: $src.tls_ca_file,
+            tls_cert_file: $src.tls_cert_file,
+            tls_key_file: $src.tls_key_file,
+            tls_key_password: $src.tls_key_password,
+            tls_min_version: $src.tls_min_version,
+            tls_ciphers: $src.tls_ciphers,
+            tls_verify_hostname: $src.tls_verify_hostname,
+            tls_server_names: $src.tls_server_names,
             tls_insecure_skip_verify: $src.tls_insecure_skip_verify,
             replication_factor: $src.replication_factor,
             durable_writes: $src.durable_writes,
@@ -209,22 +390,77 @@ impl From<TomlDbEndpoint> for DbEndpoint {
 
 #[derive(Clone, Debug, Deserialize, Default)]
 #[serde(default)]
-struct TomlDriverConfig {
+struct TomlExecutionSettings {
     request_timeout_ms: Option<u64>,
+    consistency: Option<String>,
+    serial_consistency: Option<String>,
+    retry_policy: Option<String>,
+    speculative_execution: Option<String>,
+    speculative_max_retries: Option<usize>,
+    speculative_delay_ms: Option<u64>,
+    speculative_percentile: Option<f64>,
+}
+
+impl From<TomlExecutionSettings> for ExecutionSettings {
+    fn from(t: TomlExecutionSettings) -> Self {
+        Self {
+            request_timeout_ms: t.request_timeout_ms,
+            consistency: t.consistency,
+            serial_consistency: t.serial_consistency,
+            retry_policy: t.retry_policy,
+            speculative_execution: t.speculative_execution,
+            speculative_max_retries: t.speculative_max_retries,
+            speculative_delay_ms: t.speculative_delay_ms,
+            speculative_percentile: t.speculative_percentile,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(default)]
+struct TomlDriverConfig {
+    #[serde(flatten)]
+    execution: TomlExecutionSettings,
+    active: TomlExecutionSettings,
+    passive: TomlExecutionSettings,
+    profiles: BTreeMap<String, TomlExecutionSettings>,
     connection_timeout_ms: Option<u64>,
+    reconnect_initial_ms: Option<u64>,
+    reconnect_max_ms: Option<u64>,
     tcp_keepalive_secs: Option<u64>,
+    tcp_nodelay: Option<bool>,
+    connections_per_shard: Option<usize>,
+    connections_per_host: Option<usize>,
     compression: Option<String>,
     default_page_size: Option<i32>,
+    prepared_cache_size: Option<usize>,
+    prepared_statements: Vec<String>,
+    load_balancing: Option<String>,
+    token_aware: Option<bool>,
+    permit_dc_failover: Option<bool>,
 }
 
 impl From<TomlDriverConfig> for DriverConfig {
     fn from(t: TomlDriverConfig) -> Self {
         Self {
-            request_timeout_ms: t.request_timeout_ms,
+            execution: t.execution.into(),
+            active: t.active.into(),
+            passive: t.passive.into(),
+            profiles: t.profiles.into_iter().map(|(k, v)| (k, v.into())).collect(),
             connection_timeout_ms: t.connection_timeout_ms,
+            reconnect_initial_ms: t.reconnect_initial_ms,
+            reconnect_max_ms: t.reconnect_max_ms,
             tcp_keepalive_secs: t.tcp_keepalive_secs,
+            tcp_nodelay: t.tcp_nodelay,
+            connections_per_shard: t.connections_per_shard,
+            connections_per_host: t.connections_per_host,
             compression: t.compression,
             default_page_size: t.default_page_size,
+            prepared_cache_size: t.prepared_cache_size,
+            prepared_statements: t.prepared_statements,
+            load_balancing: t.load_balancing,
+            token_aware: t.token_aware,
+            permit_dc_failover: t.permit_dc_failover,
         }
     }
 }
@@ -233,14 +469,162 @@ impl From<TomlDriverConfig> for DriverConfig {
 #[serde(default)]
 struct TomlServerConfig {
     bind_addr: String,
+    shutdown_grace_ms: u64,
+    log_level: String,
+    config_watch_ms: u64,
+    admin_token: Option<String>,
 }
 
 impl Default for TomlServerConfig {
-    fn default() -> Self { Self { bind_addr: "127.0.0.1:8080".into() } }
+    fn default() -> Self {
+        let d = AppConfig::default().server;
+        Self {
+            bind_addr: d.bind_addr,
+            shutdown_grace_ms: d.shutdown_grace_ms,
+            log_level: d.log_level,
+            config_watch_ms: d.config_watch_ms,
+            admin_token: d.admin_token,
+        }
+    }
 }
 
 impl From<TomlServerConfig> for ServerConfig {
-    fn from(t: TomlServerConfig) -> Self { ServerConfig { bind_addr: t.bind_addr } }
+    fn from(t: TomlServerConfig) -> Self {
+        ServerConfig {
+            bind_addr: t.bind_addr,
+            shutdown_grace_ms: t.shutdown_grace_ms,
+            log_level: t.log_level,
+            config_watch_ms: t.config_watch_ms,
+            admin_token: t.admin_token,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct TomlReplicationConfig {
+    outbox_dir: String,
+    drift_max_records: usize,
+    drift_max_bytes: u64,
+    failover_fail_threshold: u32,
+    failover_recover_threshold: u32,
+    outbox_compression: String,
+    outbox_compression_min_bytes: usize,
+    outbox_encryption_key_id: Option<u16>,
+    outbox_keys: BTreeMap<String, String>,
+    outbox_key_files: BTreeMap<String, String>,
+    outbox_group_commit_us: u64,
+}
+
+impl Default for TomlReplicationConfig {
+    fn default() -> Self {
+        let d = ReplicationConfig::default();
+        Self {
+            outbox_dir: d.outbox_dir,
+            drift_max_records: d.drift_max_records,
+            drift_max_bytes: d.drift_max_bytes,
+            failover_fail_threshold: d.failover_fail_threshold,
+            failover_recover_threshold: d.failover_recover_threshold,
+            outbox_compression: d.outbox_compression,
+            outbox_compression_min_bytes: d.outbox_compression_min_bytes,
+            outbox_encryption_key_id: d.outbox_encryption_key_id,
+            outbox_keys: d.outbox_keys,
+            outbox_key_files: d.outbox_key_files,
+            outbox_group_commit_us: d.outbox_group_commit_us,
+        }
+    }
+}
+
+impl From<TomlReplicationConfig> for ReplicationConfig {
+    fn from(t: TomlReplicationConfig) -> Self {
+        ReplicationConfig {
+            outbox_dir: t.outbox_dir,
+            drift_max_records: t.drift_max_records,
+            drift_max_bytes: t.drift_max_bytes,
+            failover_fail_threshold: t.failover_fail_threshold,
+            failover_recover_threshold: t.failover_recover_threshold,
+            outbox_compression: t.outbox_compression,
+            outbox_compression_min_bytes: t.outbox_compression_min_bytes,
+            outbox_encryption_key_id: t.outbox_encryption_key_id,
+            outbox_keys: t.outbox_keys,
+            outbox_key_files: t.outbox_key_files,
+            outbox_group_commit_us: t.outbox_group_commit_us,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct TomlExportConfig {
+    out_dir: String,
+    queries: BTreeMap<String, String>,
+}
+
+impl Default for TomlExportConfig {
+    fn default() -> Self {
+        let d = ExportConfig::default();
+        Self { out_dir: d.out_dir, queries: d.queries }
+    }
+}
+
+impl From<TomlExportConfig> for ExportConfig {
+    fn from(t: TomlExportConfig) -> Self { ExportConfig { out_dir: t.out_dir, queries: t.queries } }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct TomlJobsConfig {
+    artifact_dir: String,
+    max_finished: usize,
+}
+
+impl Default for TomlJobsConfig {
+    fn default() -> Self {
+        let d = JobsConfig::default();
+        Self { artifact_dir: d.artifact_dir, max_finished: d.max_finished }
+    }
+}
+
+impl From<TomlJobsConfig> for JobsConfig {
+    fn from(t: TomlJobsConfig) -> Self { JobsConfig { artifact_dir: t.artifact_dir, max_finished: t.max_finished } }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct TomlMigrationsConfig {
+    dir: String,
+    apply_on_startup: bool,
+}
+
+impl Default for TomlMigrationsConfig {
+    fn default() -> Self {
+        let d = MigrationsConfig::default();
+        Self { dir: d.dir, apply_on_startup: d.apply_on_startup }
+    }
+}
+
+impl From<TomlMigrationsConfig> for MigrationsConfig {
+    fn from(t: TomlMigrationsConfig) -> Self { MigrationsConfig { dir: t.dir, apply_on_startup: t.apply_on_startup } }
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+#[serde(default)]
+struct TomlSecretsConfig {
+    vault_addr: Option<String>,
+    vault_token: Option<String>,
+    vault_token_file: Option<String>,
+    vault_timeout_ms: Option<u64>,
+}
+
+impl From<TomlSecretsConfig> for SecretsConfig {
+    fn from(t: TomlSecretsConfig) -> Self {
+        SecretsConfig {
+            vault_addr: t.vault_addr,
+            vault_token: t.vault_token,
+            vault_token_file: t.vault_token_file,
+            vault_timeout_ms: t.vault_timeout_ms,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -250,20 +634,31 @@ struct TomlAppConfig {
     passive: TomlDbEndpoint,
     driver: TomlDriverConfig,
     server: TomlServerConfig,
+    replication: TomlReplicationConfig,
+    export: TomlExportConfig,
+    jobs: TomlJobsConfig,
+    migrations: TomlMigrationsConfig,
+    secrets: TomlSecretsConfig,
+    strict: bool,
 }
 
 impl Default for TomlAppConfig {
     fn default() -> Self {
         Self {
             active: TomlDbEndpoint::from(DbEndpoint::default()),
-            passive: TomlDbEndpoint::from({
-                let mut p = DbEndpoint::default();
-                p.port = 9043;
-                p.rack = "asia-southeast2-b".into();
-                p
+            passive: TomlDbEndpoint::from(DbEndpoint {
+                port: 9043,
+                rack: "asia-southeast2-b".into(),
+                ..DbEndpoint::default()
             }),
             driver: TomlDriverConfig::default(),
             server: TomlServerConfig::default(),
+            replication: TomlReplicationConfig::default(),
+            export: TomlExportConfig::default(),
+            jobs: TomlJobsConfig::default(),
+            migrations: TomlMigrationsConfig::default(),
+            secrets: TomlSecretsConfig::default(),
+            strict: false,
         }
     }
 }
@@ -275,53 +670,33 @@ impl From<TomlAppConfig> for AppConfig {
             passive: t.passive.into(),
             driver: t.driver.into(),
             server: t.server.into(),
+            replication: t.replication.into(),
+            export: t.export.into(),
+            jobs: t.jobs.into(),
+            migrations: t.migrations.into(),
+            secrets: t.secrets.into(),
+            strict: t.strict,
+            config_file: None,
+            load_problems: Vec::new(),
+            provenance: BTreeMap::new(),
         }
     }
 }
 
-fn read_env_string(prefix: &str, global_prefix: Option<&str>, name: &str, default: String) -> String {
-    let specific = format!("{}_{}", prefix, name);
-    if let Ok(val) = env::var(&specific) { return val; }
-    if let Some(gp) = global_prefix {
-        let global = format!("{}_{}", gp, name);
-        if let Ok(val) = env::var(&global) { return val; }
+fn with_default_port(point: &str, port: u16) -> String {
+    if point.starts_with('[') {
+        if point.contains("]:") { point.to_string() } else { format!("{}:{}", point, port) }
+    } else if point.parse::<std::net::Ipv6Addr>().is_ok() {
+        format!("[{}]:{}", point, port)
+    } else if point.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) {
+        point.to_string()
+    } else {
+        format!("{}:{}", point, port)
     }
-    default
 }
 
-impl AppConfig {
-}
-
-fn read_env_u16(prefix: &str, global_prefix: Option<&str>, name: &str, default: u16) -> u16 {
-    let specific = format!("{}_{}", prefix, name);
-    if let Ok(val) = env::var(&specific) {
-        if let Ok(port) = val.parse::<u16>() { return port; }
-    }
-    if let Some(gp) = global_prefix {
-        let global = format!("{}_{}", gp, name);
-        if let Ok(val) = env::var(&global) {
-            if let Ok(port) = val.parse::<u16>() { return port; }
-        }
-    }
-    default
-}
-
-fn read_env_bool(prefix: &str, global_prefix: Option<&str>, name: &str, default: bool) -> bool {
-    let specific = format!("{}_{}", prefix, name);
-    if let Ok(val) = env::var(&specific) {
-        if let Ok(b) = parse_bool(&val) { return b; }
-    }
-    if let Some(gp) = global_prefix {
-        let global = format!("{}_{}", gp, name);
-        if let Ok(val) = env::var(&global) {
-            if let Ok(b) = parse_bool(&val) { return b; }
-        }
-    }
-    default
-}
-
-fn read_env_opt_u64(global_prefix: &str, name: &str) -> Option<u64> {
-    env::var(format!("{}_{}", global_prefix, name)).ok().and_then(|v| v.parse::<u64>().ok())
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect()
 }
 
 fn parse_bool(s: &str) -> Result<bool, ()> {
@@ -331,27 +706,3 @@ fn parse_bool(s: &str) -> Result<bool, ()> {
         _ => Err(()),
     }
 }
-
-fn read_env_opt_i32(global_prefix: &str, name: &str) -> Option<i32> {
-    env::var(format!("{}_{}", global_prefix, name)).ok().and_then(|v| v.parse::<i32>().ok())
-}
-
-fn read_env_opt_string(global_prefix: &str, name: &str) -> Option<String> {
-    env::var(format!("{}_{}", global_prefix, name)).ok()
-}
-
-fn read_env_opt_string_scoped(prefix: &str, global_prefix: Option<&str>, name: &str) -> Option<String> {
-    let specific = format!("{}_{}", prefix, name);
-    if let Ok(val) = env::var(&specific) { return Some(val); }
-    if let Some(gp) = global_prefix {
-        let global = format!("{}_{}", gp, name);
-        if let Ok(val) = env::var(&global) { return Some(val); }
-    }
-    None
-}
-impl ServerConfig {
-    pub fn from_env(prefix: &str) -> Option<Self> {
-        let bind_addr = read_env_opt_string(prefix, "BIND_ADDR");
-        if let Some(ba) = bind_addr { Some(ServerConfig { bind_addr: ba }) } else { None }
-    }
-}
\ No newline at end of file
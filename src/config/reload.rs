@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arc_swap::ArcSwap;
+use log::{info, warn, LevelFilter};
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::config::{AppConfig, CliOverrides, SecretResolver};
+use crate::db::DbClients;
+use crate::errors::AppResult;
+use crate::replication::Cluster;
+
+const RESTART_ONLY: &[&str] = &[
+    "server.bind_addr",
+    "server.config_watch_ms",
+    "active.keyspace",
+    "active.replication_factor",
+    "active.durable_writes",
+    "passive.keyspace",
+    "passive.replication_factor",
+    "passive.durable_writes",
+    "replication.outbox_dir",
+    "replication.outbox_compression",
+    "replication.outbox_compression_min_bytes",
+    "replication.outbox_encryption_key_id",
+    "replication.outbox_group_commit_us",
+    "jobs.artifact_dir",
+    "jobs.max_finished",
+    "migrations.dir",
+    "migrations.apply_on_startup",
+    "driver.prepared_cache_size",
+    "driver.reconnect_initial_ms",
+    "driver.reconnect_max_ms",
+    "driver.prepared_statements",
+];
+
+const SESSION_ENDPOINT_FIELDS: &[&str] = &[
+    "host", "port", "contact_points", "username", "password", "username_file", "password_file", "use_tls",
+];
+
+const SESSION_DRIVER_FIELDS: &[&str] = &[
+    "driver.connection_timeout_ms",
+    "driver.tcp_keepalive_secs",
+    "driver.tcp_nodelay",
+    "driver.connections_per_shard",
+    "driver.connections_per_host",
+    "driver.compression",
+];
+
+fn is_restart_only(key: &str) -> bool {
+    RESTART_ONLY.contains(&key) || key.starts_with("replication.outbox_keys.") || key.starts_with("replication.outbox_key_files.")
+}
+
+fn is_session_key(key: &str) -> bool {
+    if SESSION_DRIVER_FIELDS.contains(&key) { return true; }
+    match key.split_once('.') {
+        Some(("active" | "passive", field)) => SESSION_ENDPOINT_FIELDS.contains(&field) || field.starts_with("tls_"),
+        _ => false,
+    }
+}
+
+fn is_profile_key(key: &str) -> bool {
+    matches!(key, "active.datacenter" | "active.rack" | "passive.datacenter" | "passive.rack")
+        || (key.starts_with("driver.") && !is_session_key(key) && !is_restart_only(key))
+}
+
+fn changed_keys(old: &AppConfig, new: &AppConfig) -> Vec<String> {
+    let before: BTreeMap<String, Option<String>> = old.entries().into_iter().collect();
+    let after: BTreeMap<String, Option<String>> = new.entries().into_iter().collect();
+    before
+        .keys()
+        .chain(after.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter(|k| before.get(*k) != after.get(*k))
+        .cloned()
+        .collect()
+}
+
+fn retain_static_settings(old: &AppConfig, new: &mut AppConfig) -> Vec<String> {
+    let mut kept: Vec<String> = changed_keys(old, new).into_iter().filter(|k| is_restart_only(k)).collect();
+
+    new.server.bind_addr = old.server.bind_addr.clone();
+    new.server.config_watch_ms = old.server.config_watch_ms;
+    for (o, n) in [(&old.active, &mut new.active), (&old.passive, &mut new.passive)] {
+        n.keyspace = o.keyspace.clone();
+        n.replication_factor = o.replication_factor;
+        n.durable_writes = o.durable_writes;
+    }
+    new.replication.outbox_dir = old.replication.outbox_dir.clone();
+    new.replication.outbox_compression = old.replication.outbox_compression.clone();
+    new.replication.outbox_compression_min_bytes = old.replication.outbox_compression_min_bytes;
+    new.replication.outbox_encryption_key_id = old.replication.outbox_encryption_key_id;
+    new.replication.outbox_keys = old.replication.outbox_keys.clone();
+    new.replication.outbox_key_files = old.replication.outbox_key_files.clone();
+    new.replication.outbox_group_commit_us = old.replication.outbox_group_commit_us;
+    new.jobs = old.jobs.clone();
+    new.migrations = old.migrations.clone();
+    new.driver.prepared_cache_size = old.driver.prepared_cache_size;
+    new.driver.reconnect_initial_ms = old.driver.reconnect_initial_ms;
+    new.driver.reconnect_max_ms = old.driver.reconnect_max_ms;
+    new.driver.prepared_statements = old.driver.prepared_statements.clone();
+
+    let names: BTreeSet<String> = old.driver.profiles.keys().chain(new.driver.profiles.keys()).cloned().collect();
+    for name in names {
+        match (old.driver.profiles.get(&name), new.driver.profiles.contains_key(&name)) {
+            (Some(settings), false) => {
+                new.driver.profiles.insert(name.clone(), settings.clone());
+            }
+            (None, true) => {
+                new.driver.profiles.remove(&name);
+            }
+            _ => continue,
+        }
+        kept.push(format!("driver.profiles.{}", name));
+    }
+    kept
+}
+
+#[derive(Debug)]
+pub struct SharedConfig {
+    inner: ArcSwap<AppConfig>,
+}
+
+impl SharedConfig {
+    pub fn new(cfg: AppConfig) -> Self { Self { inner: ArcSwap::from_pointee(cfg) } }
+
+    pub fn current(&self) -> Arc<AppConfig> { self.inner.load_full() }
+
+    pub fn store(&self, cfg: AppConfig) { self.inner.store(Arc::new(cfg)); }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReloadReport {
+    pub trigger: String,
+    pub applied: Vec<String>,
+    pub restart_required: Vec<String>,
+    pub failed: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ReloadReport {
+    pub fn is_noop(&self) -> bool { self.applied.is_empty() && self.restart_required.is_empty() && self.failed.is_empty() }
+}
+
+#[derive(Debug)]
+pub struct Reloader {
+    overrides: CliOverrides,
+    config: Arc<SharedConfig>,
+    clients: Arc<DbClients>,
+    log_level_pinned: bool,
+    lock: Mutex<()>,
+}
+
+impl Reloader {
+    pub fn new(overrides: CliOverrides, config: Arc<SharedConfig>, clients: Arc<DbClients>) -> Self {
+        Self { overrides, config, clients, log_level_pinned: false, lock: Mutex::new(()) }
+    }
+
+    pub fn with_log_level_pinned(mut self, pinned: bool) -> Self { self.log_level_pinned = pinned; self }
+
+    pub fn config(&self) -> &Arc<SharedConfig> { &self.config }
+
+    pub async fn reload(&self, trigger: &str) -> AppResult<ReloadReport> {
+        let _guard = self.lock.lock().await;
+        let old = self.config.current();
+        let mut new = AppConfig::load(&self.overrides);
+        let issues = new.validate().into_result(new.strict).map_err(|e| e.with_context("configuration reload rejected"))?;
+
+        let mut report = ReloadReport { trigger: trigger.to_string(), ..ReloadReport::default() };
+        report.warnings = issues.iter().map(ToString::to_string).collect();
+        report.restart_required = retain_static_settings(&old, &mut new);
+        let changed = changed_keys(&old, &new);
+
+        if changed.iter().any(|k| is_profile_key(k)) {
+            for (cluster, ep) in [(Cluster::Active, &new.active), (Cluster::Passive, &new.passive)] {
+                match self.clients.reconfigure_execution(cluster, ep, &new.driver) {
+                    Ok(unmapped) => {
+                        for name in unmapped {
+                            let key = format!("driver.profiles.{}", name);
+                            if !report.restart_required.contains(&key) { report.restart_required.push(key); }
+                        }
+                    }
+                    Err(e) => report.failed.push(format!("{} execution profiles: {}", cluster.label(), e.to_message())),
+                }
+            }
+        }
+
+        let secrets = Arc::new(SecretResolver::from_config(&new.secrets));
+        for (cluster, ep) in [(Cluster::Active, &new.active), (Cluster::Passive, &new.passive)] {
+            let Some(current) = self.clients.connector(cluster) else { continue };
+            let scope = if cluster == Cluster::Active { "active." } else { "passive." };
+            let mut seen = new.clone();
+            match cluster {
+                Cluster::Active => seen.active = current.endpoint.clone(),
+                Cluster::Passive => seen.passive = current.endpoint.clone(),
+            }
+            seen.driver = current.driver.clone();
+            let session_changed = changed_keys(&seen, &new)
+                .iter()
+                .any(|k| is_session_key(k) && (k.starts_with(scope) || k.starts_with("driver.")));
+
+            let next = match current.rebuild(ep, &new.driver, secrets.clone()).await {
+                Ok(next) => next,
+                Err(e) => {
+                    report.failed.push(format!("{} credentials: {}", cluster.label(), e.to_message()));
+                    continue;
+                }
+            };
+            if !session_changed && next.credentials() == current.credentials() { continue; }
+
+            if !self.clients.is_connected(cluster) {
+                self.clients.set_connector(cluster, next);
+                report.applied.push(format!("{} session settings (used on next reconnect)", cluster.label()));
+                continue;
+            }
+            match self.clients.rebuild_session(cluster, next).await {
+                Ok(()) => report.applied.push(format!("{} session rebuilt", cluster.label())),
+                Err(e) => report.failed.push(format!("{} session rebuild, keeping the old session: {}", cluster.label(), e.to_message())),
+            }
+        }
+
+        if changed.iter().any(|k| k == "server.log_level") {
+            if self.log_level_pinned {
+                report.warnings.push("server.log_level: ignored because RUST_LOG is set".to_string());
+            } else if let Ok(level) = new.server.log_level.parse::<LevelFilter>() {
+                log::set_max_level(level);
+            }
+        }
+
+        report.applied.extend(changed.into_iter().filter(|k| !report.restart_required.contains(k)));
+        self.config.store(new);
+
+        if report.is_noop() {
+            info!("Configuration reload ({}): no changes", trigger);
+        } else {
+            info!("Configuration reload ({}): applied {:?}", trigger, report.applied);
+        }
+        if !report.restart_required.is_empty() {
+            warn!("Configuration reload ({}): restart required for {:?}", trigger, report.restart_required);
+        }
+        for failure in &report.failed {
+            warn!("Configuration reload ({}): {}", trigger, failure);
+        }
+        Ok(report)
+    }
+
+    pub async fn watch_file(self: Arc<Self>, interval: Duration) {
+        let modified = |p: &str| std::fs::metadata(Path::new(p)).and_then(|m| m.modified()).ok();
+        let stamps = |cfg: &AppConfig| watched_files(cfg).into_iter().map(|p| { let m = modified(&p); (p, m) }).collect::<BTreeMap<_, _>>();
+        let mut last = stamps(&self.config.current());
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let now = stamps(&self.config.current());
+            let changed = now.iter().any(|(p, m)| m.is_some() && last.get(p) != Some(m));
+            last = now;
+            if !changed { continue; }
+            if let Err(e) = self.reload("file-watch").await {
+                warn!("{}", e.to_message());
+            }
+            last = stamps(&self.config.current());
+        }
+    }
+}
+
+pub fn watched_files(cfg: &AppConfig) -> Vec<String> {
+    let mut files = BTreeSet::new();
+    files.extend(cfg.config_file.clone());
+    files.extend(cfg.secrets.vault_token_file.clone());
+    for ep in [&cfg.active, &cfg.passive] {
+        files.extend(ep.username_file.clone());
+        files.extend(ep.password_file.clone());
+        for value in [&ep.username, &ep.password] {
+            let mut rest = value.as_str();
+            while let Some(i) = rest.find("${file:") {
+                let after = &rest[i + 7..];
+                let Some(end) = after.find('}') else { break };
+                files.insert(after[..end].trim().to_string());
+                rest = &after[end + 1..];
+            }
+        }
+    }
+    files.into_iter().collect()
+}
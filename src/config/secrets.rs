@@ -0,0 +1,305 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use openssl::ssl::{SslConnector, SslMethod};
+
+use crate::config::{DbEndpoint, SecretsConfig};
+use crate::errors::{AppError, AppResult};
+
+const DEFAULT_VAULT_TIMEOUT_MS: u64 = 5000;
+
+pub trait SecretProvider: Send + Sync {
+    fn scheme(&self) -> &str;
+    fn fetch(&self, reference: &str) -> AppResult<String>;
+}
+
+pub struct EnvProvider;
+
+impl SecretProvider for EnvProvider {
+    fn scheme(&self) -> &str { "env" }
+
+    fn fetch(&self, reference: &str) -> AppResult<String> {
+        env::var(reference).map_err(|_| AppError::config(format!("environment variable {} is not set", reference)))
+    }
+}
+
+pub struct FileProvider;
+
+impl SecretProvider for FileProvider {
+    fn scheme(&self) -> &str { "file" }
+
+    fn fetch(&self, reference: &str) -> AppResult<String> {
+        let s = fs::read_to_string(reference).map_err(|e| AppError::io(format!("failed to read secret file {}", reference), e))?;
+        Ok(s.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+pub struct VaultProvider {
+    addr: String,
+    token: Option<String>,
+    token_file: Option<String>,
+    timeout: Duration,
+}
+
+impl VaultProvider {
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self { addr: addr.into(), token: None, token_file: None, timeout: Duration::from_millis(DEFAULT_VAULT_TIMEOUT_MS) }
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_token_file(mut self, path: impl Into<String>) -> Self {
+        self.token_file = Some(path.into());
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn token(&self) -> AppResult<Option<String>> {
+        match &self.token_file {
+            Some(p) => FileProvider.fetch(p).map(Some),
+            None => Ok(self.token.clone().or_else(|| env::var("VAULT_TOKEN").ok())),
+        }
+    }
+}
+
+impl SecretProvider for VaultProvider {
+    fn scheme(&self) -> &str { "vault" }
+
+    fn fetch(&self, reference: &str) -> AppResult<String> {
+        let (path, field) = reference
+            .split_once('#')
+            .ok_or_else(|| AppError::config(format!("vault reference '{}' must look like <path>#<field>", reference)))?;
+        let url = format!("{}/v1/{}", self.addr.trim_end_matches('/'), path.trim_start_matches('/'));
+        let token = self.token()?;
+        let headers: Vec<(&str, &str)> = token.as_deref().map(|t| ("X-Vault-Token", t)).into_iter().collect();
+        let (status, body) = http_get(&url, &headers, self.timeout)?;
+        match status {
+            200 => {}
+            404 => return Err(AppError::config(format!("vault has no secret at '{}'", path))),
+            s if s >= 500 || s == 429 => return Err(AppError::unavailable(format!("vault returned HTTP {} for '{}'", s, path))),
+            s => return Err(AppError::config(format!("vault returned HTTP {} for '{}'", s, path))),
+        }
+        let doc: serde_json::Value = serde_json::from_str(&body).map_err(|e| AppError::json(format!("vault response for '{}'", path), e))?;
+        let data = &doc["data"];
+        let value = match data.get("data") {
+            Some(inner) if inner.get(field).is_some() => &inner[field],
+            _ => &data[field],
+        };
+        match value {
+            serde_json::Value::String(s) => Ok(s.clone()),
+            serde_json::Value::Null => Err(AppError::config(format!("vault secret '{}' has no field '{}'", path, field))),
+            other => Ok(other.to_string()),
+        }
+    }
+}
+
+fn http_get(url: &str, headers: &[(&str, &str)], timeout: Duration) -> AppResult<(u16, String)> {
+    let (tls, rest) = if let Some(r) = url.strip_prefix("https://") {
+        (true, r)
+    } else if let Some(r) = url.strip_prefix("http://") {
+        (false, r)
+    } else {
+        return Err(AppError::config(format!("unsupported secret provider URL '{}'", url)));
+    };
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let host = authority.rsplit_once(':').map_or(authority, |(h, _)| h).trim_matches(['[', ']']);
+    let target = if authority.rsplit_once(':').is_some_and(|(_, p)| p.parse::<u16>().is_ok()) {
+        authority.to_string()
+    } else {
+        format!("{}:{}", authority, if tls { 443 } else { 80 })
+    };
+
+    let ctx = |e: io::Error| AppError::io(format!("request to {}", authority), e);
+    let addr = target
+        .to_socket_addrs()
+        .map_err(ctx)?
+        .next()
+        .ok_or_else(|| AppError::config(format!("cannot resolve {}", authority)))?;
+    let stream = TcpStream::connect_timeout(&addr, timeout).map_err(ctx)?;
+    stream.set_read_timeout(Some(timeout)).map_err(ctx)?;
+    stream.set_write_timeout(Some(timeout)).map_err(ctx)?;
+
+    let mut request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n", path, authority);
+    for (k, v) in headers {
+        request.push_str(&format!("{}: {}\r\n", k, v));
+    }
+    request.push_str("\r\n");
+
+    let mut raw = Vec::new();
+    if tls {
+        let connector = SslConnector::builder(SslMethod::tls_client())
+            .map_err(|e| AppError::config(format!("tls setup for {}: {}", authority, e)))?
+            .build();
+        let mut s = connector
+            .connect(host, stream)
+            .map_err(|e| AppError::unavailable(format!("tls handshake with {}: {}", authority, e)))?;
+        s.write_all(request.as_bytes()).map_err(ctx)?;
+        s.read_to_end(&mut raw).map_err(ctx)?;
+    } else {
+        let mut s = stream;
+        s.write_all(request.as_bytes()).map_err(ctx)?;
+        s.read_to_end(&mut raw).map_err(ctx)?;
+    }
+    parse_http_response(&raw).ok_or_else(|| AppError::unavailable(format!("malformed HTTP response from {}", authority)))
+}
+
+fn parse_http_response(raw: &[u8]) -> Option<(u16, String)> {
+    let split = raw.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&raw[..split]);
+    let body = &raw[split + 4..];
+    let status = head.lines().next()?.split_whitespace().nth(1)?.parse().ok()?;
+    let chunked = head.lines().any(|l| {
+        l.split_once(':').is_some_and(|(k, v)| k.eq_ignore_ascii_case("transfer-encoding") && v.trim().eq_ignore_ascii_case("chunked"))
+    });
+    if !chunked {
+        return Some((status, String::from_utf8_lossy(body).into_owned()));
+    }
+    let mut out = Vec::new();
+    let mut rest = body;
+    loop {
+        let line_end = rest.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&rest[..line_end]).ok()?;
+        let size = usize::from_str_radix(size.split(';').next()?.trim(), 16).ok()?;
+        let after = &rest[line_end + 2..];
+        if size == 0 {
+            return Some((status, String::from_utf8_lossy(&out).into_owned()));
+        }
+        out.extend_from_slice(after.get(..size)?);
+        rest = after.get(size..)?.strip_prefix(b"\r\n")?;
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Ref { scheme: &'a str, reference: &'a str },
+}
+
+fn parse_template(value: &str) -> AppResult<Vec<Segment<'_>>> {
+    let mut out = Vec::new();
+    let mut rest = value;
+    while let Some(i) = rest.find("${") {
+        if rest[..i].ends_with('$') {
+            out.push(Segment::Text(&rest[..i - 1]));
+            out.push(Segment::Text("${"));
+            rest = &rest[i + 2..];
+            continue;
+        }
+        out.push(Segment::Text(&rest[..i]));
+        let after = &rest[i + 2..];
+        let end = after.find('}').ok_or_else(|| AppError::config("unterminated ${...} reference"))?;
+        let (scheme, reference) = after[..end]
+            .split_once(':')
+            .ok_or_else(|| AppError::config(format!("reference '${{{}}}' must look like ${{<provider>:<name>}}", &after[..end])))?;
+        out.push(Segment::Ref { scheme: scheme.trim(), reference: reference.trim() });
+        rest = &after[end + 1..];
+    }
+    out.push(Segment::Text(rest));
+    Ok(out)
+}
+
+pub fn references(value: &str) -> AppResult<Vec<(String, String)>> {
+    Ok(parse_template(value)?
+        .into_iter()
+        .filter_map(|s| match s {
+            Segment::Ref { scheme, reference } => Some((scheme.to_string(), reference.to_string())),
+            Segment::Text(_) => None,
+        })
+        .collect())
+}
+
+#[derive(Clone, Default)]
+pub struct SecretResolver {
+    providers: BTreeMap<String, Arc<dyn SecretProvider>>,
+}
+
+impl fmt::Debug for SecretResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretResolver").field("providers", &self.providers.keys().collect::<Vec<_>>()).finish()
+    }
+}
+
+impl SecretResolver {
+    pub fn local() -> Self {
+        Self::default().with_provider(Arc::new(EnvProvider)).with_provider(Arc::new(FileProvider))
+    }
+
+    pub fn from_config(cfg: &SecretsConfig) -> Self {
+        let mut resolver = Self::local();
+        if let Some(addr) = cfg.vault_addr.as_deref().filter(|a| !a.trim().is_empty()) {
+            let mut vault = VaultProvider::new(addr)
+                .with_timeout(Duration::from_millis(cfg.vault_timeout_ms.unwrap_or(DEFAULT_VAULT_TIMEOUT_MS)));
+            if let Some(t) = &cfg.vault_token { vault = vault.with_token(t.clone()); }
+            if let Some(p) = &cfg.vault_token_file { vault = vault.with_token_file(p.clone()); }
+            resolver = resolver.with_provider(Arc::new(vault));
+        }
+        resolver
+    }
+
+    pub fn with_provider(mut self, provider: Arc<dyn SecretProvider>) -> Self {
+        self.providers.insert(provider.scheme().to_string(), provider);
+        self
+    }
+
+    pub fn has_provider(&self, scheme: &str) -> bool { self.providers.contains_key(scheme) }
+
+    pub fn fetch(&self, scheme: &str, reference: &str) -> AppResult<String> {
+        let provider = self.providers.get(scheme).ok_or_else(|| {
+            AppError::config(format!(
+                "unknown secret provider '{}', expected one of: {}",
+                scheme,
+                self.providers.keys().cloned().collect::<Vec<_>>().join(", ")
+            ))
+        })?;
+        provider.fetch(reference).map_err(|e| e.with_context(format!("${{{}:{}}}", scheme, reference)))
+    }
+
+    pub fn interpolate(&self, value: &str) -> AppResult<String> {
+        let mut out = String::with_capacity(value.len());
+        for segment in parse_template(value)? {
+            match segment {
+                Segment::Text(t) => out.push_str(t),
+                Segment::Ref { scheme, reference } => out.push_str(&self.fetch(scheme, reference)?),
+            }
+        }
+        Ok(out)
+    }
+
+    fn secret(&self, field: &str, file: Option<&str>, value: &str) -> AppResult<String> {
+        match file {
+            Some(path) => self.fetch("file", path),
+            None => self.interpolate(value),
+        }
+        .map_err(|e| e.with_context(field))
+    }
+
+    pub fn resolve_endpoint(&self, ep: &DbEndpoint) -> AppResult<DbEndpoint> {
+        let mut out = ep.clone();
+        out.username = self.secret("username", ep.username_file.as_deref(), &ep.username)?;
+        out.password = self.secret("password", ep.password_file.as_deref(), &ep.password)?;
+        out.tls_key_password = ep.tls_key_password.as_deref().map(|v| self.secret("tls_key_password", None, v)).transpose()?;
+        out.username_file = None;
+        out.password_file = None;
+        Ok(out)
+    }
+
+    pub async fn resolve_endpoint_async(self: &Arc<Self>, ep: &DbEndpoint) -> AppResult<DbEndpoint> {
+        let resolver = Arc::clone(self);
+        let ep = ep.clone();
+        tokio::task::spawn_blocking(move || resolver.resolve_endpoint(&ep))
+            .await
+            .map_err(|e| AppError::other(format!("secret resolution task failed: {}", e)))?
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::secrets::references;
+use crate::config::{AppConfig, DbEndpoint, ReplicationConfig, SecretResolver};
+use crate::db::{parse_compression, policies, tls, unsupported_driver_settings, LoadBalancingSettings};
+use crate::errors::{AppError, AppResult};
+use crate::replication::{OutboxCodec, OutboxCompression};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<ConfigIssue>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, severity: Severity, field: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ConfigIssue { severity, field: field.into(), message: message.into() });
+    }
+
+    fn error(&mut self, field: impl Into<String>, message: impl Into<String>) { self.push(Severity::Error, field, message); }
+
+    fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) { self.push(Severity::Warning, field, message); }
+
+    pub fn errors(&self) -> impl Iterator<Item = &ConfigIssue> { self.issues.iter().filter(|i| i.severity == Severity::Error) }
+
+    pub fn warnings(&self) -> impl Iterator<Item = &ConfigIssue> { self.issues.iter().filter(|i| i.severity == Severity::Warning) }
+
+    pub fn has_errors(&self) -> bool { self.errors().next().is_some() }
+
+    pub fn is_clean(&self) -> bool { self.issues.is_empty() }
+
+    pub fn into_result(self, strict: bool) -> AppResult<Vec<ConfigIssue>> {
+        let fatal: Vec<&ConfigIssue> = self.issues.iter().filter(|i| strict || i.severity == Severity::Error).collect();
+        if fatal.is_empty() {
+            return Ok(self.issues);
+        }
+        let lines: Vec<String> = fatal.iter().map(|i| format!("  - {}", i)).collect();
+        let mode = if strict { " (strict mode treats warnings as errors)" } else { "" };
+        Err(AppError::config(format!(
+            "configuration has {} problem(s){}:\n{}",
+            fatal.len(), mode, lines.join("\n")
+        )))
+    }
+}
+
+fn valid_bind_addr(addr: &str) -> bool {
+    if addr.parse::<SocketAddr>().is_ok() { return true; }
+    match addr.rsplit_once(':') {
+        Some((host, port)) => !host.is_empty() && !host.contains(':') && port.parse::<u16>().is_ok(),
+        None => false,
+    }
+}
+
+fn check_file(report: &mut ValidationReport, field: String, path: Option<&str>) {
+    if let Some(p) = path.map(str::trim) {
+        if p.is_empty() {
+            report.error(field, "is set but empty");
+        } else if !Path::new(p).is_file() {
+            report.error(field, format!("file '{}' does not exist or is not a regular file", p));
+        }
+    }
+}
+
+fn check_secret(report: &mut ValidationReport, secrets: &SecretResolver, field: String, value: &str) {
+    let refs = match references(value) {
+        Ok(r) => r,
+        Err(e) => return report.error(field, e.to_message()),
+    };
+    for (scheme, reference) in refs {
+        match scheme.as_str() {
+            "file" if !Path::new(&reference).is_file() => {
+                report.error(field.clone(), format!("secret file '{}' does not exist", reference));
+            }
+            "env" if std::env::var(&reference).is_err() => {
+                report.error(field.clone(), format!("environment variable {} is not set", reference));
+            }
+            s if !secrets.has_provider(s) => {
+                let hint = if s == "vault" { "; set secrets.vault_addr" } else { "" };
+                report.error(field.clone(), format!("unknown secret provider '{}'{}", s, hint));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn check_outbox(report: &mut ValidationReport, secrets: &SecretResolver, repl: &ReplicationConfig) {
+    if OutboxCompression::parse(&repl.outbox_compression).is_none() {
+        report.error("replication.outbox_compression", format!("unknown codec '{}', expected none, lz4 or zstd", repl.outbox_compression));
+    }
+    let mut ids = BTreeSet::new();
+    let keys = repl.outbox_keys.iter().map(|(id, v)| ("outbox_keys", id, v));
+    let files = repl.outbox_key_files.iter().map(|(id, v)| ("outbox_key_files", id, v));
+    for (table, id, value) in keys.chain(files) {
+        let field = format!("replication.{}.{}", table, id);
+        match id.parse::<u16>() {
+            Ok(n) if n > 0 => {
+                if !ids.insert(n) { report.error(field.clone(), format!("key id {} is defined more than once", n)); }
+            }
+            _ => report.error(field.clone(), "key id must be an integer between 1 and 65535"),
+        }
+        if table == "outbox_key_files" {
+            check_file(report, field, Some(value));
+        } else if value.contains("${") {
+            check_secret(report, secrets, field, value);
+        } else if let Err(e) = OutboxCodec::parse_key(value.as_bytes()) {
+            report.error(field, e.to_message());
+        }
+    }
+    match repl.outbox_encryption_key_id {
+        Some(id) if !ids.contains(&id) => report.error(
+            "replication.outbox_encryption_key_id",
+            format!("key {} is not defined in replication.outbox_keys or replication.outbox_key_files", id),
+        ),
+        None if !ids.is_empty() => report.warning(
+            "replication.outbox_encryption_key_id",
+            "is unset, so new outbox records are written unencrypted; the configured keys are only used for reading",
+        ),
+        _ => {}
+    }
+}
+
+fn check_endpoint(report: &mut ValidationReport, secrets: &SecretResolver, scope: &str, ep: &DbEndpoint) {
+    if ep.contact_points().is_empty() {
+        report.error(format!("{}.host", scope), "no host or contact_points configured; set host or contact_points to at least one node");
+    } else if ep.contact_points.is_empty() && ep.host.trim().is_empty() {
+        report.error(format!("{}.host", scope), "must not be empty");
+    }
+    if ep.port == 0 {
+        report.error(format!("{}.port", scope), "must be between 1 and 65535");
+    }
+    if ep.keyspace.trim().is_empty() {
+        report.error(format!("{}.keyspace", scope), "must not be empty");
+    }
+    if ep.datacenter.trim().is_empty() {
+        report.warning(format!("{}.datacenter", scope), "is empty; datacenter-aware load balancing and NetworkTopologyStrategy need it");
+    }
+
+    check_file(report, format!("{}.username_file", scope), ep.username_file.as_deref());
+    check_file(report, format!("{}.password_file", scope), ep.password_file.as_deref());
+    if ep.username_file.is_none() { check_secret(report, secrets, format!("{}.username", scope), &ep.username); }
+    if ep.password_file.is_none() { check_secret(report, secrets, format!("{}.password", scope), &ep.password); }
+    if let Some(p) = ep.tls_key_password.as_deref() { check_secret(report, secrets, format!("{}.tls_key_password", scope), p); }
+
+    if ep.replication_factor == Some(0) {
+        report.error(format!("{}.replication_factor", scope), "must be at least 1");
+    }
+
+    if ep.use_tls {
+        check_file(report, format!("{}.tls_ca_file", scope), ep.tls_ca_file.as_deref());
+        check_file(report, format!("{}.tls_cert_file", scope), ep.tls_cert_file.as_deref());
+        check_file(report, format!("{}.tls_key_file", scope), ep.tls_key_file.as_deref());
+        if ep.tls_cert_file.is_some() != ep.tls_key_file.is_some() {
+            report.error(format!("{}.tls_cert_file", scope), "mutual TLS needs both tls_cert_file and tls_key_file");
+        }
+        if let Some(v) = ep.tls_min_version.as_deref()
+            && let Err(e) = tls::parse_tls_version(v)
+        {
+            report.error(format!("{}.tls_min_version", scope), e.to_message());
+        }
+        if ep.tls_insecure_skip_verify {
+            if ep.tls_verify_hostname {
+                report.error(format!("{}.tls_verify_hostname", scope), "cannot be combined with tls_insecure_skip_verify");
+            } else {
+                report.warning(format!("{}.tls_insecure_skip_verify", scope), "server certificates are not verified");
+            }
+        }
+    } else if ep.tls_ca_file.is_some() || ep.tls_cert_file.is_some() || ep.tls_key_file.is_some() {
+        report.warning(format!("{}.use_tls", scope), "TLS files are configured but use_tls is false, so they are ignored");
+    }
+}
+
+impl AppConfig {
+    pub fn validate(&self) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        for problem in &self.load_problems {
+            report.error("config", problem.clone());
+        }
+
+        let secrets = SecretResolver::from_config(&self.secrets);
+        check_endpoint(&mut report, &secrets, "active", &self.active);
+        check_endpoint(&mut report, &secrets, "passive", &self.passive);
+        if self.secrets.vault_token.is_some() && self.secrets.vault_token_file.is_some() {
+            report.warning("secrets.vault_token", "is ignored because secrets.vault_token_file is set");
+        }
+        check_file(&mut report, "secrets.vault_token_file".to_string(), self.secrets.vault_token_file.as_deref());
+
+        let active_points: BTreeSet<String> = self.active.contact_points().into_iter().map(|p| p.to_ascii_lowercase()).collect();
+        let passive_points: BTreeSet<String> = self.passive.contact_points().into_iter().map(|p| p.to_ascii_lowercase()).collect();
+        if !active_points.is_empty() && active_points == passive_points && self.active.keyspace == self.passive.keyspace {
+            report.error("passive", "Active and Passive point at the same contact points and keyspace; replication would write every change twice to one cluster");
+        } else if !active_points.is_disjoint(&passive_points) {
+            let shared: Vec<&String> = active_points.intersection(&passive_points).collect();
+            report.warning("passive.contact_points", format!("shares {:?} with active.contact_points", shared));
+        }
+
+        for (scope, ep) in [("active", &self.active), ("passive", &self.passive)] {
+            if let Err(e) = LoadBalancingSettings::from_config(ep, &self.driver) {
+                report.error(format!("driver.load_balancing ({})", scope), e.to_message());
+            }
+        }
+
+        if let Some(c) = self.driver.compression.as_deref()
+            && parse_compression(c).is_none()
+        {
+            report.error("driver.compression", format!("unknown value '{}', expected snappy, lz4 or none", c));
+        }
+        for w in unsupported_driver_settings(&self.driver) {
+            if !w.starts_with("driver.compression") { report.warning("driver", w); }
+        }
+
+        let mut scopes = vec![("driver.active".to_string(), self.driver.execution_for(true)), ("driver.passive".to_string(), self.driver.execution_for(false))];
+        for name in self.driver.profiles.keys() {
+            if let Some(settings) = self.driver.profile_for(name, true) {
+                scopes.push((format!("driver.profiles.{}", name), settings));
+            }
+        }
+        for (scope, settings) in scopes {
+            if let Err(e) = policies::check_settings(&settings) {
+                report.error(scope, e.to_message());
+            }
+        }
+        if let (Some(initial), Some(max)) = (self.driver.reconnect_initial_ms, self.driver.reconnect_max_ms)
+            && initial > max
+        {
+            report.warning("driver.reconnect_initial_ms", format!("{} is larger than reconnect_max_ms {}; the maximum is raised to match", initial, max));
+        }
+        if self.driver.prepared_cache_size == Some(0) {
+            report.warning("driver.prepared_cache_size", "0 is raised to 1; the cache cannot be disabled");
+        }
+        if self.jobs.max_finished == 0 {
+            report.warning("jobs.max_finished", "0 is raised to 1; the most recent finished job is always kept");
+        }
+
+        if !valid_bind_addr(self.server.bind_addr.trim()) {
+            report.error("server.bind_addr", format!("'{}' is not a valid host:port address", self.server.bind_addr));
+        }
+
+        for (field, value) in [
+            ("replication.outbox_dir", &self.replication.outbox_dir),
+            ("export.out_dir", &self.export.out_dir),
+            ("jobs.artifact_dir", &self.jobs.artifact_dir),
+            ("migrations.dir", &self.migrations.dir),
+        ] {
+            if value.trim().is_empty() { report.error(field, "must not be empty"); }
+        }
+        if self.server.log_level.parse::<log::LevelFilter>().is_err() {
+            report.error("server.log_level", format!("unknown level '{}', expected off, error, warn, info, debug or trace", self.server.log_level));
+        }
+        if self.replication.failover_fail_threshold == 0 {
+            report.error("replication.failover_fail_threshold", "must be at least 1");
+        }
+        if self.replication.failover_recover_threshold == 0 {
+            report.error("replication.failover_recover_threshold", "must be at least 1");
+        }
+        check_outbox(&mut report, &secrets, &self.replication);
+        if self.replication.outbox_group_commit_us > 100_000 {
+            report.warning("replication.outbox_group_commit_us", "a group commit window above 100ms delays every outbox write by that much");
+        }
+        if self.replication.drift_max_records == 0 || self.replication.drift_max_bytes == 0 {
+            report.warning("replication.drift_max_records", "a zero drift limit makes readiness fail whenever anything is queued");
+        }
+
+        report
+    }
+}
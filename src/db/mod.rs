@@ -1,60 +1,437 @@
-use openssl::ssl::{SslContextBuilder, SslMethod, SslVerifyMode};
-
-use scylla::client::execution_profile::ExecutionProfile;
+pub mod policies;
+pub mod prepared;
+pub mod reconnect;
+pub mod schema;
+pub mod tls;
+pub mod values;
+
+use arc_swap::{ArcSwap, ArcSwapOption};
+use log::{info, warn};
+use openssl::ssl::SslContext;
+
+use scylla::client::execution_profile::ExecutionProfileHandle;
 use scylla::client::session::Session;
 use scylla::client::session_builder::SessionBuilder;
+use scylla::client::PoolSize;
 use scylla::frame::Compression;
+use scylla::policies::load_balancing::{DefaultPolicy, LoadBalancingPolicy};
+use scylla::response::query_result::QueryResult;
+use scylla::serialize::row::SerializeRow;
 use scylla::statement::prepared::PreparedStatement;
 use scylla::statement::unprepared::Statement as UnpreparedStatement;
 use scylla::statement::Consistency;
 use scylla::value::Row;
 
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use tokio::sync::Mutex;
-
-use crate::config::{AppConfig, DbEndpoint, DriverConfig};
+use crate::config::{AppConfig, DbEndpoint, DriverConfig, SecretResolver};
+use crate::db::policies::{build_profile, parse_consistency, LatencyWindow};
+use crate::db::prepared::{CacheStats, StatementCache};
+use crate::db::tls::PinnedCertificates;
 use crate::errors::{AppError, AppResult};
+use crate::replication::Cluster;
+
+type PreparedCache = Arc<StatementCache<PreparedStatement>>;
+
+#[derive(Debug, Default)]
+pub struct ClusterExecution {
+    pub profiles: HashMap<String, ExecutionProfileHandle>,
+    pub default_consistency: Option<Consistency>,
+    pub page_size: Option<i32>,
+    pub latency: Arc<LatencyWindow>,
+}
+
+impl ClusterExecution {
+    pub fn from_config(ep: &DbEndpoint, drv: &DriverConfig, which_active: bool) -> AppResult<(ExecutionProfileHandle, Self)> {
+        let lb = LoadBalancingSettings::from_config(ep, drv)?.build();
+        let latency = Arc::new(LatencyWindow::new());
+        let settings = drv.execution_for(which_active);
+        let default_consistency = settings.consistency.as_deref().map(parse_consistency).transpose()?;
+        let default_handle = build_profile(&settings, lb.clone(), &latency)?.into_handle();
+        let mut profiles = HashMap::new();
+        for name in drv.profiles.keys() {
+            if let Some(p) = drv.profile_for(name, which_active) {
+                let handle = build_profile(&p, lb.clone(), &latency)
+                    .map_err(|e| e.with_context(format!("execution profile '{}'", name)))?
+                    .into_handle_with_label(name.clone());
+                profiles.insert(name.clone(), handle);
+            }
+        }
+        let page_size = drv.default_page_size.filter(|n| *n > 0);
+        Ok((default_handle, Self { profiles, default_consistency, page_size, latency }))
+    }
+
+    pub fn unmapped(&self, drv: &DriverConfig) -> Vec<String> {
+        drv.profiles.keys().filter(|name| !self.profiles.contains_key(*name)).cloned().collect()
+    }
+
+    pub fn remap(&self, default: Option<&ExecutionProfileHandle>, ep: &DbEndpoint, drv: &DriverConfig, which_active: bool) -> AppResult<Self> {
+        let lb = LoadBalancingSettings::from_config(ep, drv)?.build();
+        let settings = drv.execution_for(which_active);
+        let default_consistency = settings.consistency.as_deref().map(parse_consistency).transpose()?;
+        let default_profile = build_profile(&settings, lb.clone(), &self.latency)?;
+        let mut named = Vec::new();
+        for (name, handle) in &self.profiles {
+            if let Some(p) = drv.profile_for(name, which_active) {
+                let profile = build_profile(&p, lb.clone(), &self.latency)
+                    .map_err(|e| e.with_context(format!("execution profile '{}'", name)))?;
+                named.push((handle.clone(), profile));
+            }
+        }
+        if let Some(handle) = default {
+            handle.clone().map_to_another_profile(default_profile);
+        }
+        for (mut handle, profile) in named {
+            handle.map_to_another_profile(profile);
+        }
+        Ok(Self {
+            profiles: self.profiles.clone(),
+            default_consistency,
+            page_size: drv.default_page_size.filter(|n| *n > 0),
+            latency: self.latency.clone(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClusterConnector {
+    pub endpoint: DbEndpoint,
+    pub driver: DriverConfig,
+    profile: ExecutionProfileHandle,
+    tls: Option<SslContext>,
+    pins: Arc<PinnedCertificates>,
+    secrets: Arc<SecretResolver>,
+    credentials: u64,
+}
+
+pub fn credentials_fingerprint(ep: &DbEndpoint) -> u64 {
+    let mut h = DefaultHasher::new();
+    (&ep.username, &ep.password, &ep.tls_key_password).hash(&mut h);
+    h.finish()
+}
 
-type PreparedCache = Arc<Mutex<HashMap<String, Arc<PreparedStatement>>>>;
+impl ClusterConnector {
+    pub fn profile(&self) -> &ExecutionProfileHandle { &self.profile }
+
+    pub fn credentials(&self) -> u64 { self.credentials }
+
+    pub async fn rebuild(&self, endpoint: &DbEndpoint, driver: &DriverConfig, secrets: Arc<SecretResolver>) -> AppResult<Self> {
+        let resolved = secrets.resolve_endpoint_async(endpoint).await?;
+        let pins = Arc::new(PinnedCertificates::default());
+        let tls = tls::build_tls_context(&resolved, pins.clone())?;
+        Ok(Self {
+            endpoint: endpoint.clone(),
+            driver: driver.clone(),
+            profile: self.profile.clone(),
+            tls,
+            pins,
+            secrets,
+            credentials: credentials_fingerprint(&resolved),
+        })
+    }
+
+    pub async fn connect(&self) -> AppResult<Session> {
+        let endpoint = self.secrets.resolve_endpoint_async(&self.endpoint).await?;
+        if tls::verifies_node_names(&endpoint) {
+            self.pin_contact_points(&endpoint).await?;
+        }
+        let session = Box::pin(connect_once(&endpoint, &self.driver, self.profile.clone(), self.tls.clone())).await?;
+        if tls::verifies_node_names(&endpoint) {
+            self.pin_nodes(&endpoint, peer_nodes(&session), false).await;
+        }
+        Ok(session)
+    }
+
+    pub async fn pin_peers(&self, session: &Session) -> AppResult<()> {
+        let endpoint = self.secrets.resolve_endpoint_async(&self.endpoint).await?;
+        if tls::verifies_node_names(&endpoint) {
+            let recheck = self.pins.take_stale();
+            self.pin_nodes(&endpoint, peer_nodes(session), recheck).await;
+        }
+        Ok(())
+    }
+
+    async fn pin_contact_points(&self, ep: &DbEndpoint) -> AppResult<()> {
+        let mut nodes = Vec::new();
+        for point in ep.contact_points() {
+            let (resolved, _) = resolve_contact_points(std::slice::from_ref(&point)).await;
+            nodes.extend(resolved.into_iter().map(|addr| (addr, vec![tls::contact_host(&point)])));
+        }
+        match self.pin_nodes(ep, nodes, false).await {
+            Some(e) if self.pins.is_empty() => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    async fn pin_nodes(&self, ep: &DbEndpoint, nodes: Vec<(SocketAddr, Vec<String>)>, recheck: bool) -> Option<AppError> {
+        let timeout = Duration::from_millis(self.driver.connection_timeout_ms.unwrap_or(5000));
+        let mut last_err = None;
+        for (addr, aliases) in nodes {
+            if !recheck && self.pins.knows(&addr) { continue; }
+            let (ep, names) = (ep.clone(), tls::node_server_names(ep, addr.ip(), &aliases));
+            let checked = tokio::task::spawn_blocking(move || tls::verify_node(&ep, addr, names, timeout))
+                .await
+                .map_err(|e| AppError::other(format!("TLS check task failed: {}", e)))
+                .and_then(|r| r);
+            match checked {
+                Ok(fp) => {
+                    if self.pins.pin(addr, fp) { info!("Node {} presented a new certificate and was re-pinned", addr); }
+                }
+                Err(e) => {
+                    warn!("Node {} is not trusted: {}", addr, e.to_message());
+                    last_err = Some(e);
+                }
+            }
+        }
+        last_err
+    }
+}
+
+fn peer_nodes(session: &Session) -> Vec<(SocketAddr, Vec<String>)> {
+    session
+        .get_cluster_state()
+        .get_nodes_info()
+        .iter()
+        .map(|n| (SocketAddr::new(n.address.ip(), n.address.port()), Vec::new()))
+        .collect()
+}
 
 #[derive(Debug)]
 pub struct DbClients {
-    pub active: Option<Session>,
-    pub passive: Option<Session>,
+    active: ArcSwapOption<Session>,
+    passive: ArcSwapOption<Session>,
     active_cache: PreparedCache,
     passive_cache: PreparedCache,
+    active_exec: ArcSwap<ClusterExecution>,
+    passive_exec: ArcSwap<ClusterExecution>,
+    active_connector: ArcSwapOption<ClusterConnector>,
+    passive_connector: ArcSwapOption<ClusterConnector>,
+    primary: AtomicU8,
+    schema_versions: Mutex<[Option<uuid::Uuid>; 2]>,
 }
 
 impl Default for DbClients {
     fn default() -> Self {
         Self {
-            active: None,
-            passive: None,
-            active_cache: Arc::new(Mutex::new(HashMap::new())),
-            passive_cache: Arc::new(Mutex::new(HashMap::new())),
+            active: ArcSwapOption::empty(),
+            passive: ArcSwapOption::empty(),
+            active_cache: Arc::new(StatementCache::default()),
+            passive_cache: Arc::new(StatementCache::default()),
+            active_exec: ArcSwap::from_pointee(ClusterExecution::default()),
+            passive_exec: ArcSwap::from_pointee(ClusterExecution::default()),
+            active_connector: ArcSwapOption::empty(),
+            passive_connector: ArcSwapOption::empty(),
+            primary: AtomicU8::new(0),
+            schema_versions: Mutex::new([None, None]),
         }
     }
 }
 
 impl DbClients {
-    pub fn is_empty(&self) -> bool { self.active.is_none() && self.passive.is_none() }
+    pub fn is_empty(&self) -> bool { self.active.load().is_none() && self.passive.load().is_none() }
 
-    async fn get_or_prepare(&self, which_active: bool, cql: &str) -> Option<Arc<PreparedStatement>> {
-        let (sess_opt, cache) = if which_active { (&self.active, &self.active_cache) } else { (&self.passive, &self.passive_cache) };
-        let sess = match sess_opt.as_ref() { Some(s) => s, None => return None };
+    fn slot(&self, cluster: Cluster) -> (&ArcSwapOption<Session>, &PreparedCache) {
+        match cluster {
+            Cluster::Active => (&self.active, &self.active_cache),
+            Cluster::Passive => (&self.passive, &self.passive_cache),
+        }
+    }
+
+    pub fn session(&self, cluster: Cluster) -> Option<Arc<Session>> { self.slot(cluster).0.load_full() }
+
+    pub fn is_connected(&self, cluster: Cluster) -> bool { self.slot(cluster).0.load().is_some() }
+
+    pub fn primary(&self) -> Cluster {
+        match self.primary.load(Ordering::Acquire) {
+            0 => Cluster::Active,
+            _ => Cluster::Passive,
+        }
+    }
+
+    pub fn set_primary(&self, cluster: Cluster) {
+        self.primary.store(matches!(cluster, Cluster::Passive) as u8, Ordering::Release);
+    }
+
+    pub fn replace_session(&self, cluster: Cluster, session: Option<Session>) {
+        let (slot, cache) = self.slot(cluster);
+        slot.store(session.map(Arc::new));
+        cache.clear();
+    }
+
+    pub fn with_prepared_cache_capacity(mut self, capacity: usize) -> Self {
+        self.active_cache = Arc::new(StatementCache::new(capacity));
+        self.passive_cache = Arc::new(StatementCache::new(capacity));
+        self
+    }
+
+    pub fn prepared_stats(&self, cluster: Cluster) -> CacheStats { self.slot(cluster).1.stats() }
+
+    pub fn invalidate_prepared(&self, cluster: Cluster, cql: Option<&str>) {
+        let cache = self.slot(cluster).1;
+        match cql {
+            Some(cql) => { cache.invalidate(cql); }
+            None => { cache.clear(); }
+        }
+    }
+
+    pub fn note_statement(&self, cluster: Cluster, cql: &str) {
+        if prepared::is_schema_change(cql) { self.invalidate_prepared(cluster, None); }
+    }
+
+    pub async fn check_schema_versions(&self) {
+        for (i, cluster) in [Cluster::Active, Cluster::Passive].into_iter().enumerate() {
+            let Some(sess) = self.session(cluster) else { continue };
+            let Ok(Some(version)) = sess.check_schema_agreement().await else { continue };
+            let changed = {
+                let mut seen = self.schema_versions.lock().unwrap_or_else(|p| p.into_inner());
+                let changed = seen[i].is_some_and(|v| v != version);
+                seen[i] = Some(version);
+                changed
+            };
+            if changed {
+                info!("{} cluster schema changed to {}; clearing prepared statements", cluster.label(), version);
+                self.invalidate_prepared(cluster, None);
+            }
+        }
+    }
 
-        if let Some(ps) = cache.lock().await.get(cql).cloned() { return Some(ps); }
+    pub fn warm_statements(&self, cluster: Cluster) -> Vec<String> {
+        self.connector(cluster)
+            .map(|c| {
+                let ks = quote_ident(&c.endpoint.keyspace);
+                c.driver.prepared_statements.iter().map(|s| s.replace("{{KEYSPACE}}", &ks)).collect()
+            })
+            .unwrap_or_default()
+    }
 
-        match sess.prepare(cql).await {
-            Ok(ps) => {
-                let arc_ps = Arc::new(ps);
-                cache.lock().await.insert(cql.to_string(), arc_ps.clone());
-                Some(arc_ps)
+    pub async fn warm_prepared(&self, cluster: Cluster) -> Vec<(String, AppError)> {
+        let mut failures = Vec::new();
+        for cql in self.warm_statements(cluster) {
+            if let Err(e) = self.prepared(cluster, &cql).await {
+                failures.push((cql, e));
             }
-            Err(_) => None,
+        }
+        failures
+    }
+
+    pub fn with_session(self, cluster: Cluster, session: Option<Session>) -> Self {
+        self.slot(cluster).0.store(session.map(Arc::new));
+        self
+    }
+
+    pub fn with_connector(self, cluster: Cluster, connector: ClusterConnector) -> Self {
+        self.set_connector(cluster, connector);
+        self
+    }
+
+    fn connector_slot(&self, cluster: Cluster) -> &ArcSwapOption<ClusterConnector> {
+        match cluster {
+            Cluster::Active => &self.active_connector,
+            Cluster::Passive => &self.passive_connector,
+        }
+    }
+
+    pub fn set_connector(&self, cluster: Cluster, connector: ClusterConnector) {
+        self.connector_slot(cluster).store(Some(Arc::new(connector)));
+    }
+
+    pub fn connector(&self, cluster: Cluster) -> Option<Arc<ClusterConnector>> { self.connector_slot(cluster).load_full() }
+
+    pub async fn rebuild_session(&self, cluster: Cluster, connector: ClusterConnector) -> AppResult<()> {
+        let session = connector.connect().await?;
+        self.set_connector(cluster, connector);
+        self.replace_session(cluster, Some(session));
+        for (cql, e) in self.warm_prepared(cluster).await {
+            warn!("{} cluster: failed to prepare '{}': {}", cluster.label(), cql, e.to_message());
+        }
+        Ok(())
+    }
+
+    pub async fn pin_peer_certificates(&self) {
+        for cluster in [Cluster::Active, Cluster::Passive] {
+            if let (Some(connector), Some(session)) = (self.connector(cluster), self.session(cluster))
+                && let Err(e) = connector.pin_peers(&session).await
+            {
+                warn!("{} cluster: peer certificate check failed: {}", cluster.label(), e.to_message());
+            }
+        }
+    }
+
+    pub async fn try_reconnect(&self, cluster: Cluster) -> AppResult<bool> {
+        if self.is_connected(cluster) { return Ok(false); }
+        let connector = self
+            .connector(cluster)
+            .ok_or_else(|| AppError::db(format!("{} cluster has no connection settings to reconnect with", cluster.label())))?;
+        let session = connector.connect().await?;
+        self.replace_session(cluster, Some(session));
+        for (cql, e) in self.warm_prepared(cluster).await {
+            warn!("{} cluster: failed to prepare '{}': {}", cluster.label(), cql, e.to_message());
+        }
+        Ok(true)
+    }
+
+    pub fn with_execution(self, cluster: Cluster, exec: ClusterExecution) -> Self {
+        self.set_execution(cluster, exec);
+        self
+    }
+
+    fn execution_slot(&self, cluster: Cluster) -> &ArcSwap<ClusterExecution> {
+        match cluster {
+            Cluster::Active => &self.active_exec,
+            Cluster::Passive => &self.passive_exec,
+        }
+    }
+
+    pub fn set_execution(&self, cluster: Cluster, exec: ClusterExecution) { self.execution_slot(cluster).store(Arc::new(exec)); }
+
+    pub fn execution(&self, cluster: Cluster) -> Arc<ClusterExecution> { self.execution_slot(cluster).load_full() }
+
+    pub fn reconfigure_execution(&self, cluster: Cluster, ep: &DbEndpoint, drv: &DriverConfig) -> AppResult<Vec<String>> {
+        let connector = self.connector(cluster);
+        let current = self.execution(cluster);
+        let next = current.remap(connector.as_ref().map(|c| c.profile()), ep, drv, cluster == Cluster::Active)?;
+        self.set_execution(cluster, next);
+        Ok(current.unmapped(drv))
+    }
+
+    pub fn profile(&self, cluster: Cluster, name: &str) -> Option<ExecutionProfileHandle> {
+        self.execution(cluster).profiles.get(name).cloned()
+    }
+
+    pub fn default_consistency(&self, cluster: Cluster) -> Option<Consistency> { self.execution(cluster).default_consistency }
+
+    pub fn page_size(&self, cluster: Cluster) -> Option<i32> { self.execution(cluster).page_size }
+
+    pub fn record_latency(&self, cluster: Cluster, elapsed: Duration) { self.execution_slot(cluster).load().latency.record(elapsed) }
+
+    pub async fn prepared(&self, cluster: Cluster, cql: &str) -> AppResult<Arc<PreparedStatement>> {
+        let sess = self.session(cluster).ok_or_else(|| AppError::db(format!("{} database is unavailable", cluster.label())))?;
+        self.slot(cluster)
+            .1
+            .get_or_prepare(cql, || async {
+                let mut ps = sess.prepare(cql).await.map_err(|e| AppError::prepare(format!("{}: failed to prepare '{}'", cluster.label(), cql), e))?;
+                if let Some(n) = self.page_size(cluster) { ps.set_page_size(n); }
+                Ok(ps)
+            })
+            .await
+    }
+
+    pub async fn execute_prepared(&self, cluster: Cluster, cql: &str, values: impl SerializeRow) -> AppResult<QueryResult> {
+        let sess = self.session(cluster).ok_or_else(|| AppError::db(format!("{} database is unavailable", cluster.label())))?;
+        let ps = self.prepared(cluster, cql).await?;
+        match sess.execute_unpaged(&ps, &values).await {
+            Err(e) if prepared::is_unprepared(&e) => {
+                self.invalidate_prepared(cluster, Some(cql));
+                let ps = self.prepared(cluster, cql).await?;
+                sess.execute_unpaged(&ps, &values).await.map_err(|e| AppError::query(cluster.label(), e))
+            }
+            res => res.map_err(|e| AppError::query(cluster.label(), e)),
         }
     }
 
@@ -67,19 +444,17 @@ impl DbClients {
     }
 
     async fn ping_release_version(&self, which_active: bool) -> bool {
-        let sess_opt = if which_active { self.active.as_ref() } else { self.passive.as_ref() };
-        let Some(sess) = sess_opt else { return false };
+        let Some(sess) = self.session(if which_active { Cluster::Active } else { Cluster::Passive }) else { return false };
         let mut st = UnpreparedStatement::new("SELECT release_version FROM system.local");
         st.set_consistency(if which_active { Consistency::LocalQuorum } else { Consistency::One });
         st.set_is_idempotent(true);
         match sess.query_unpaged(st, &[]).await {
             Ok(qr) => {
-                if let Ok(rows_res) = qr.into_rows_result() {
-                    if let Ok(mut iter) = rows_res.rows::<Row>() {
-                        if let Some(item) = iter.next() {
-                            return item.is_ok();
-                        }
-                    }
+                if let Ok(rows_res) = qr.into_rows_result()
+                    && let Ok(mut iter) = rows_res.rows::<Row>()
+                    && let Some(item) = iter.next()
+                {
+                    return item.is_ok();
                 }
                 false
             }
@@ -96,8 +471,7 @@ impl DbClients {
     }
 
     async fn upsert_watermark(&self, which_active: bool, keyspace: &str, last_id: u64, now_ms: u64) -> bool {
-        let sess_opt = if which_active { self.active.as_ref() } else { self.passive.as_ref() };
-        let Some(sess) = sess_opt else { return false };
+        let Some(sess) = self.session(if which_active { Cluster::Active } else { Cluster::Passive }) else { return false };
         let qks = quote_ident(keyspace);
         let cql = format!(
             "INSERT INTO {}.repl_watermark (id, last_applied_log_id, heartbeat_ms) VALUES (1, {}, {})",
@@ -113,12 +487,49 @@ impl DbClients {
 const DEFAULT_RETRIES: usize = 3;
 
 pub async fn init_clients(cfg: &AppConfig) -> AppResult<DbClients> {
-    let active = connect_with_retries(&cfg.active, &cfg.driver, DEFAULT_RETRIES).await.ok();
-    let passive = connect_with_retries(&cfg.passive, &cfg.driver, DEFAULT_RETRIES).await.ok();
+    let (active_handle, active_exec) = ClusterExecution::from_config(&cfg.active, &cfg.driver, true)?;
+    let (passive_handle, passive_exec) = ClusterExecution::from_config(&cfg.passive, &cfg.driver, false)?;
+    let secrets = Arc::new(SecretResolver::from_config(&cfg.secrets));
+    let active_ep = secrets.resolve_endpoint_async(&cfg.active).await.map_err(|e| e.with_context("Active cluster"))?;
+    let passive_ep = secrets.resolve_endpoint_async(&cfg.passive).await.map_err(|e| e.with_context("Passive cluster"))?;
+    let (active_pins, passive_pins) = (Arc::new(PinnedCertificates::default()), Arc::new(PinnedCertificates::default()));
+    let active_tls = tls::build_tls_context(&active_ep, active_pins.clone()).map_err(|e| e.with_context("Active cluster"))?;
+    let passive_tls = tls::build_tls_context(&passive_ep, passive_pins.clone()).map_err(|e| e.with_context("Passive cluster"))?;
+    let active_conn = ClusterConnector {
+        endpoint: cfg.active.clone(),
+        driver: cfg.driver.clone(),
+        profile: active_handle,
+        tls: active_tls,
+        pins: active_pins,
+        secrets: secrets.clone(),
+        credentials: credentials_fingerprint(&active_ep),
+    };
+    let passive_conn = ClusterConnector {
+        endpoint: cfg.passive.clone(),
+        driver: cfg.driver.clone(),
+        profile: passive_handle,
+        tls: passive_tls,
+        pins: passive_pins,
+        secrets,
+        credentials: credentials_fingerprint(&passive_ep),
+    };
+    let backoff = reconnect::Backoff::from_config(&cfg.driver);
+    let (active, passive) = tokio::join!(
+        connect_with_retries(&active_conn, backoff.clone(), DEFAULT_RETRIES),
+        connect_with_retries(&passive_conn, backoff, DEFAULT_RETRIES)
+    );
+    let (active, passive) = (active.ok(), passive.ok());
     if active.is_none() && passive.is_none() {
         return Err(AppError::db("failed to connect to both Active and Passive clusters"));
     }
-    Ok(DbClients { active, passive, ..DbClients::default() })
+    Ok(DbClients::default()
+        .with_prepared_cache_capacity(cfg.driver.prepared_cache_size.unwrap_or(prepared::DEFAULT_CAPACITY))
+        .with_session(Cluster::Active, active)
+        .with_session(Cluster::Passive, passive)
+        .with_execution(Cluster::Active, active_exec)
+        .with_execution(Cluster::Passive, passive_exec)
+        .with_connector(Cluster::Active, active_conn)
+        .with_connector(Cluster::Passive, passive_conn))
 }
 
 pub async fn ensure_keyspaces(cfg: &AppConfig, clients: &DbClients) -> AppResult<()> {
@@ -143,8 +554,8 @@ async fn ensure_keyspace_for_cluster(
     clients: &DbClients,
     tmpl: &str,
 ) -> AppResult<()> {
-    let (sess_opt, label) = if which_active { (&clients.active, "Active") } else { (&clients.passive, "Passive") };
-    let sess = match sess_opt.as_ref() {
+    let (cluster, label) = if which_active { (Cluster::Active, "Active") } else { (Cluster::Passive, "Passive") };
+    let sess = match clients.session(cluster) {
         Some(s) => s,
         None => {
             return Err(AppError::db(format!(
@@ -155,31 +566,18 @@ async fn ensure_keyspace_for_cluster(
     };
 
     let cql_check = "SELECT keyspace_name FROM system_schema.keyspaces WHERE keyspace_name = ?";
-    if let Some(ps) = clients.get_or_prepare(which_active, cql_check).await {
-        match sess.execute_unpaged(&ps, (&ep.keyspace,)).await {
-            Ok(qr) => {
-                if let Ok(rows_res) = qr.into_rows_result() {
-                    if let Ok(mut iter) = rows_res.rows::<Row>() {
-                        if let Some(item) = iter.next() {
-                            if item.is_ok() {
-                                return Ok(());
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                return Err(AppError::db(format!(
-                    "{}: failed to check keyspace existence for '{}': {}",
-                    label, ep.keyspace, e
-                )));
+    match clients.execute_prepared(cluster, cql_check, (&ep.keyspace,)).await {
+        Ok(qr) => {
+            if let Ok(rows_res) = qr.into_rows_result()
+                && let Ok(mut iter) = rows_res.rows::<Row>()
+                && let Some(Ok(_)) = iter.next()
+            {
+                return Ok(());
             }
         }
-    } else {
-        return Err(AppError::db(format!(
-            "{}: failed to prepare keyspace existence check statement.",
-            label
-        )));
+        Err(e) => {
+            return Err(e.with_context(format!("{}: failed to check keyspace existence for '{}'", label, ep.keyspace)));
+        }
     }
 
     let rf = ep.replication_factor.unwrap_or(1);
@@ -195,18 +593,19 @@ async fn ensure_keyspace_for_cluster(
     st.set_is_idempotent(true);
 
     match sess.query_unpaged(st, &[]).await {
-        Ok(_) => Ok(()),
-        Err(e) => Err(AppError::db(format!(
-            "{}: failed to create keyspace '{}': {}",
-            label, ep.keyspace, e
-        ))),
+        Ok(_) => {
+            clients.invalidate_prepared(cluster, None);
+            Ok(())
+        }
+        Err(e) => Err(AppError::query(format!("{}: failed to create keyspace '{}'", label, ep.keyspace), e)),
     }
 }
 
-async fn connect_with_retries(ep: &DbEndpoint, drv: &DriverConfig, retries: usize) -> AppResult<Session> {
+async fn connect_with_retries(connector: &ClusterConnector, mut backoff: reconnect::Backoff, retries: usize) -> AppResult<Session> {
     let mut last_err: Option<AppError> = None;
-    for _ in 0..retries {
-        match connect_once(ep, drv).await {
+    for attempt in 0..retries {
+        if attempt > 0 { tokio::time::sleep(backoff.next_delay()).await; }
+        match connector.connect().await {
             Ok(sess) => return Ok(sess),
             Err(e) => { last_err = Some(e); }
         }
@@ -214,65 +613,196 @@ async fn connect_with_retries(ep: &DbEndpoint, drv: &DriverConfig, retries: usiz
     Err(last_err.unwrap_or_else(|| AppError::db("unknown connection error")))
 }
 
-async fn connect_once(ep: &DbEndpoint, drv: &DriverConfig) -> AppResult<Session> {
-    let addr = format!("{}:{}", ep.host, ep.port);
-    let mut builder = SessionBuilder::new().known_node(addr);
+pub async fn resolve_contact_points(points: &[String]) -> (Vec<SocketAddr>, Vec<String>) {
+    let mut resolved: Vec<SocketAddr> = Vec::new();
+    let mut unresolved = Vec::new();
+    for point in points {
+        match tokio::net::lookup_host(point.as_str()).await {
+            Ok(addrs) => {
+                let before = resolved.len();
+                for addr in addrs {
+                    if !resolved.contains(&addr) { resolved.push(addr); }
+                }
+                if resolved.len() == before { unresolved.push(point.clone()); }
+            }
+            Err(e) => {
+                log::warn!("Contact point {} did not resolve: {}", point, e);
+                unresolved.push(point.clone());
+            }
+        }
+    }
+    (resolved, unresolved)
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LbPreference {
+    DcRack,
+    Dc,
+    None,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LoadBalancingSettings {
+    pub preference: LbPreference,
+    pub datacenter: String,
+    pub rack: String,
+    pub token_aware: bool,
+    pub permit_dc_failover: bool,
+}
+
+impl LoadBalancingSettings {
+    pub fn from_config(ep: &DbEndpoint, drv: &DriverConfig) -> AppResult<Self> {
+        let preference = match drv.load_balancing.as_deref().map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+            None | Some("") => if ep.rack.is_empty() { LbPreference::Dc } else { LbPreference::DcRack },
+            Some("dc_rack_aware") | Some("rack_aware") => LbPreference::DcRack,
+            Some("dc_aware") => LbPreference::Dc,
+            Some("round_robin") | Some("none") => LbPreference::None,
+            Some(other) => {
+                return Err(AppError::config(format!(
+                    "unknown load_balancing '{}', expected dc_rack_aware, dc_aware or round_robin",
+                    other
+                )));
+            }
+        };
+        let preference = match preference {
+            LbPreference::DcRack if ep.rack.is_empty() => LbPreference::Dc,
+            p if p != LbPreference::None && ep.datacenter.is_empty() => LbPreference::None,
+            p => p,
+        };
+        Ok(Self {
+            preference,
+            datacenter: ep.datacenter.clone(),
+            rack: ep.rack.clone(),
+            token_aware: drv.token_aware.unwrap_or(true),
+            permit_dc_failover: drv.permit_dc_failover.unwrap_or(false),
+        })
+    }
+
+    pub fn build(&self) -> Arc<dyn LoadBalancingPolicy> {
+        let mut builder = DefaultPolicy::builder().token_aware(self.token_aware);
+        builder = match self.preference {
+            LbPreference::DcRack => builder.prefer_datacenter_and_rack(self.datacenter.clone(), self.rack.clone()),
+            LbPreference::Dc => builder.prefer_datacenter(self.datacenter.clone()),
+            LbPreference::None => builder,
+        };
+        if self.preference != LbPreference::None {
+            builder = builder.permit_dc_failover(self.permit_dc_failover);
+        }
+        builder.build()
+    }
 
+    pub fn describe(&self) -> String {
+        let locality = match self.preference {
+            LbPreference::DcRack => format!("dc_rack_aware dc={} rack={}", self.datacenter, self.rack),
+            LbPreference::Dc => format!("dc_aware dc={}", self.datacenter),
+            LbPreference::None => "round_robin".to_string(),
+        };
+        format!("{} token_aware={} permit_dc_failover={}", locality, self.token_aware, self.permit_dc_failover)
+    }
+}
+
+pub(crate) fn parse_compression(name: &str) -> Option<Option<Compression>> {
+    match name.trim().to_ascii_lowercase().as_str() {
+        "snappy" => Some(Some(Compression::Snappy)),
+        "lz4" => Some(Some(Compression::Lz4)),
+        "none" | "" => Some(None),
+        _ => None,
+    }
+}
+
+pub fn configure_session(mut builder: SessionBuilder, ep: &DbEndpoint, drv: &DriverConfig) -> SessionBuilder {
     if !ep.username.is_empty() || !ep.password.is_empty() {
         builder = builder.user(ep.username.clone(), ep.password.clone());
     }
 
-    if let Some(ms) = drv.connection_timeout_ms {
-        if ms > 0 {
-            builder = builder.connection_timeout(Duration::from_millis(ms));
-        }
+    if let Some(ms) = drv.connection_timeout_ms
+        && ms > 0
+    {
+        builder = builder.connection_timeout(Duration::from_millis(ms));
     }
 
-    if let Some(comp) = drv.compression.as_ref() {
-        match comp.to_ascii_lowercase().as_str() {
-            "snappy" => { builder = builder.compression(Some(Compression::Snappy)); }
-            "lz4" => { builder = builder.compression(Some(Compression::Lz4)); }
-            "none" | "" => { builder = builder.compression(None); }
-            _ => {}
-        }
+    if let Some(comp) = drv.compression.as_deref().and_then(parse_compression) {
+        builder = builder.compression(comp);
     }
 
-    if let Some(ms) = drv.request_timeout_ms {
-        let eph = if ms == 0 {
-            ExecutionProfile::builder().request_timeout(None).build().into_handle()
-        } else {
-            ExecutionProfile::builder()
-                .request_timeout(Some(Duration::from_millis(ms)))
-                .build()
-                .into_handle()
-        };
-        builder = builder.default_execution_profile_handle(eph);
+    if let Some(secs) = drv.tcp_keepalive_secs
+        && secs > 0
+    {
+        builder = builder.tcp_keepalive_interval(Duration::from_secs(secs));
     }
 
-    if ep.use_tls {
-        match SslContextBuilder::new(SslMethod::tls()) {
-            Ok(mut ctx_builder) => {
-                if ep.tls_insecure_skip_verify {
-                    ctx_builder.set_verify(SslVerifyMode::NONE);
-                } else {
-                    ctx_builder.set_verify(SslVerifyMode::PEER);
-                }
-                if let Some(ca_path) = ep.tls_ca_file.as_ref() {
-                    let _ = ctx_builder.set_ca_file(ca_path);
-                }
-                let ctx = ctx_builder.build();
-                builder = builder.tls_context(Some(ctx));
+    builder = builder.tcp_nodelay(drv.tcp_nodelay.unwrap_or(true));
+
+    let per_shard = drv.connections_per_shard.and_then(NonZeroUsize::new);
+    let per_host = drv.connections_per_host.and_then(NonZeroUsize::new);
+    if let Some(n) = per_shard {
+        builder = builder.pool_size(PoolSize::PerShard(n));
+    } else if let Some(n) = per_host {
+        builder = builder.pool_size(PoolSize::PerHost(n));
+    }
+
+    builder
+}
+
+pub fn unsupported_driver_settings(drv: &DriverConfig) -> Vec<String> {
+    let mut out = Vec::new();
+    if let Some(comp) = drv.compression.as_deref()
+        && parse_compression(comp).is_none()
+    {
+        out.push(format!("driver.compression '{}' is not supported (snappy, lz4 or none); compression stays at the driver default", comp));
+    }
+    if drv.tcp_keepalive_secs == Some(0) {
+        out.push("driver.tcp_keepalive_secs = 0 leaves TCP keepalive disabled".to_string());
+    }
+    if drv.connections_per_shard == Some(0) {
+        out.push("driver.connections_per_shard = 0 is ignored; it must be at least 1".to_string());
+    }
+    if drv.connections_per_host == Some(0) {
+        out.push("driver.connections_per_host = 0 is ignored; it must be at least 1".to_string());
+    }
+    if drv.connections_per_shard.is_some_and(|n| n > 0) && drv.connections_per_host.is_some() {
+        out.push("driver.connections_per_host is ignored because driver.connections_per_shard is set".to_string());
+    }
+    if let Some(n) = drv.default_page_size
+        && n <= 0
+    {
+        out.push(format!("driver.default_page_size {} is ignored; it must be positive", n));
+    }
+    let mut scopes = vec![("driver.active".to_string(), drv.execution_for(true)), ("driver.passive".to_string(), drv.execution_for(false))];
+    scopes.extend(drv.profiles.keys().filter_map(|name| Some((format!("driver.profiles.{}", name), drv.profile_for(name, true)?))));
+    for (scope, settings) in scopes {
+        let mode = settings.speculative_execution.as_deref().map(|m| m.trim().to_ascii_lowercase()).unwrap_or_default();
+        let tuned = settings.speculative_max_retries.is_some() || settings.speculative_delay_ms.is_some();
+        match mode.as_str() {
+            "" | "none" if tuned || settings.speculative_percentile.is_some() => {
+                out.push(format!("{}: speculative tuning has no effect while speculative_execution is none", scope));
             }
-            Err(e) => {
-                return Err(AppError::db(format!("failed to initialize TLS context: {}", e)));
+            "simple" if settings.speculative_percentile.is_some() => {
+                out.push(format!("{}: speculative_percentile only applies to speculative_execution = \"percentile\"", scope));
             }
+            _ => {}
         }
     }
+    out
+}
+
+async fn connect_once(ep: &DbEndpoint, drv: &DriverConfig, profile: ExecutionProfileHandle, tls: Option<SslContext>) -> AppResult<Session> {
+    let points = ep.contact_points();
+    if points.is_empty() {
+        return Err(AppError::db("no contact points configured"));
+    }
+    let (resolved, unresolved) = resolve_contact_points(&points).await;
+    let builder = SessionBuilder::new().known_nodes_addr(resolved).known_nodes(unresolved);
+    let mut builder = configure_session(builder, ep, drv).default_execution_profile_handle(profile);
+
+    if tls.is_some() {
+        builder = builder.tls_context(tls);
+    }
 
     let session = builder
         .build()
         .await
-        .map_err(|e| AppError::db(format!("connect error: {}", e)))?;
+        .map_err(|e| AppError::connect("connect error", e))?;
     Ok(session)
 }
 
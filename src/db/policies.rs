@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use scylla::client::execution_profile::ExecutionProfile;
+use scylla::policies::load_balancing::LoadBalancingPolicy;
+use scylla::policies::retry::{DefaultRetryPolicy, DowngradingConsistencyRetryPolicy, FallthroughRetryPolicy, RetryPolicy};
+use scylla::policies::speculative_execution::{Context, SimpleSpeculativeExecutionPolicy, SpeculativeExecutionPolicy};
+use scylla::statement::{Consistency, SerialConsistency};
+
+use crate::config::ExecutionSettings;
+use crate::errors::{AppError, AppResult};
+
+const LATENCY_WINDOW: usize = 1024;
+const MIN_LATENCY_SAMPLES: usize = 32;
+const DEFAULT_SPECULATIVE_RETRIES: usize = 2;
+const DEFAULT_SPECULATIVE_DELAY_MS: u64 = 100;
+const DEFAULT_SPECULATIVE_PERCENTILE: f64 = 99.0;
+
+pub fn parse_consistency(s: &str) -> AppResult<Consistency> {
+    match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+        "any" => Ok(Consistency::Any),
+        "one" => Ok(Consistency::One),
+        "two" => Ok(Consistency::Two),
+        "three" => Ok(Consistency::Three),
+        "quorum" => Ok(Consistency::Quorum),
+        "all" => Ok(Consistency::All),
+        "local_quorum" => Ok(Consistency::LocalQuorum),
+        "each_quorum" => Ok(Consistency::EachQuorum),
+        "local_one" => Ok(Consistency::LocalOne),
+        other => Err(AppError::config(format!("unknown consistency '{}'", other))),
+    }
+}
+
+pub fn parse_serial_consistency(s: &str) -> AppResult<Option<SerialConsistency>> {
+    match s.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+        "none" | "" => Ok(None),
+        "serial" => Ok(Some(SerialConsistency::Serial)),
+        "local_serial" => Ok(Some(SerialConsistency::LocalSerial)),
+        other => Err(AppError::config(format!("unknown serial consistency '{}'", other))),
+    }
+}
+
+pub fn retry_policy(name: &str) -> AppResult<Arc<dyn RetryPolicy>> {
+    match name.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+        "default" | "" => Ok(Arc::new(DefaultRetryPolicy::new())),
+        "fallthrough" | "none" => Ok(Arc::new(FallthroughRetryPolicy::new())),
+        "downgrading" | "downgrading_consistency" => Ok(Arc::new(DowngradingConsistencyRetryPolicy::new())),
+        other => Err(AppError::config(format!(
+            "unknown retry_policy '{}', expected default, fallthrough or downgrading",
+            other
+        ))),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct LatencyWindow {
+    samples: Mutex<VecDeque<Duration>>,
+}
+
+impl LatencyWindow {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn record(&self, elapsed: Duration) {
+        let mut samples = self.samples.lock().unwrap_or_else(|p| p.into_inner());
+        if samples.len() == LATENCY_WINDOW { samples.pop_front(); }
+        samples.push_back(elapsed);
+    }
+
+    pub fn len(&self) -> usize { self.samples.lock().unwrap_or_else(|p| p.into_inner()).len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn percentile(&self, pct: f64) -> Option<Duration> {
+        let mut sorted: Vec<Duration> = self.samples.lock().unwrap_or_else(|p| p.into_inner()).iter().copied().collect();
+        if sorted.len() < MIN_LATENCY_SAMPLES { return None; }
+        sorted.sort_unstable();
+        let rank = ((pct.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f64).round() as usize;
+        sorted.get(rank).copied()
+    }
+}
+
+#[derive(Debug)]
+pub struct PercentileSpeculativeExecution {
+    pub max_retry_count: usize,
+    pub percentile: f64,
+    pub fallback_interval: Duration,
+    pub window: Arc<LatencyWindow>,
+}
+
+impl SpeculativeExecutionPolicy for PercentileSpeculativeExecution {
+    fn max_retry_count(&self, _: &Context) -> usize { self.max_retry_count }
+
+    fn retry_interval(&self, _: &Context) -> Duration {
+        self.window.percentile(self.percentile).unwrap_or(self.fallback_interval)
+    }
+}
+
+pub fn speculative_policy(settings: &ExecutionSettings, window: &Arc<LatencyWindow>) -> AppResult<Option<Arc<dyn SpeculativeExecutionPolicy>>> {
+    let max_retry_count = settings.speculative_max_retries.unwrap_or(DEFAULT_SPECULATIVE_RETRIES);
+    let delay = Duration::from_millis(settings.speculative_delay_ms.unwrap_or(DEFAULT_SPECULATIVE_DELAY_MS));
+    match settings.speculative_execution.as_deref().map(|s| s.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("none") => Ok(None),
+        Some("simple") => Ok(Some(Arc::new(SimpleSpeculativeExecutionPolicy { max_retry_count, retry_interval: delay }))),
+        Some("percentile") => {
+            let percentile = settings.speculative_percentile.unwrap_or(DEFAULT_SPECULATIVE_PERCENTILE);
+            if !(0.0..=100.0).contains(&percentile) {
+                return Err(AppError::config(format!("speculative_percentile {} must be between 0 and 100", percentile)));
+            }
+            Ok(Some(Arc::new(PercentileSpeculativeExecution {
+                max_retry_count,
+                percentile,
+                fallback_interval: delay,
+                window: window.clone(),
+            })))
+        }
+        Some(other) => Err(AppError::config(format!(
+            "unknown speculative_execution '{}', expected none, simple or percentile",
+            other
+        ))),
+    }
+}
+
+pub fn check_settings(settings: &ExecutionSettings) -> AppResult<()> {
+    if let Some(c) = settings.consistency.as_deref() { parse_consistency(c)?; }
+    if let Some(c) = settings.serial_consistency.as_deref() { parse_serial_consistency(c)?; }
+    if let Some(r) = settings.retry_policy.as_deref() { retry_policy(r)?; }
+    speculative_policy(settings, &Arc::new(LatencyWindow::new()))?;
+    Ok(())
+}
+
+pub fn build_profile(
+    settings: &ExecutionSettings,
+    lb: Arc<dyn LoadBalancingPolicy>,
+    window: &Arc<LatencyWindow>,
+) -> AppResult<ExecutionProfile> {
+    let mut builder = ExecutionProfile::builder().load_balancing_policy(lb);
+    if let Some(ms) = settings.request_timeout_ms {
+        builder = builder.request_timeout(if ms == 0 { None } else { Some(Duration::from_millis(ms)) });
+    }
+    if let Some(c) = settings.consistency.as_deref() {
+        builder = builder.consistency(parse_consistency(c)?);
+    }
+    if let Some(c) = settings.serial_consistency.as_deref() {
+        builder = builder.serial_consistency(parse_serial_consistency(c)?);
+    }
+    if let Some(r) = settings.retry_policy.as_deref() {
+        builder = builder.retry_policy(retry_policy(r)?);
+    }
+    builder = builder.speculative_execution_policy(speculative_policy(settings, window)?);
+    Ok(builder.build())
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use tokio::sync::OnceCell;
+
+use scylla::errors::{DbError, ExecutionError, RequestAttemptError};
+
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq, Eq)]
+pub struct CacheStats {
+    pub capacity: usize,
+    pub size: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub prepares: u64,
+    pub evictions: u64,
+    pub invalidations: u64,
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    cell: Arc<OnceCell<Arc<V>>>,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+struct State<V> {
+    entries: HashMap<String, Entry<V>>,
+    clock: u64,
+}
+
+#[derive(Debug)]
+pub struct StatementCache<V> {
+    capacity: usize,
+    state: Mutex<State<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    prepares: AtomicU64,
+    evictions: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl<V> Default for StatementCache<V> {
+    fn default() -> Self { Self::new(DEFAULT_CAPACITY) }
+}
+
+impl<V> StatementCache<V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            state: Mutex::new(State { entries: HashMap::new(), clock: 0 }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            prepares: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State<V>> { self.state.lock().unwrap_or_else(|p| p.into_inner()) }
+
+    fn cell_for(&self, key: &str) -> Arc<OnceCell<Arc<V>>> {
+        let mut st = self.lock();
+        st.clock += 1;
+        let now = st.clock;
+        if let Some(e) = st.entries.get_mut(key) {
+            e.last_used = now;
+            let counter = if e.cell.initialized() { &self.hits } else { &self.misses };
+            counter.fetch_add(1, Ordering::Relaxed);
+            return e.cell.clone();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        if st.entries.len() >= self.capacity
+            && let Some(oldest) = st.entries.iter().min_by_key(|(_, e)| e.last_used).map(|(k, _)| k.clone())
+        {
+            st.entries.remove(&oldest);
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+        }
+        let cell = Arc::new(OnceCell::new());
+        st.entries.insert(key.to_string(), Entry { cell: cell.clone(), last_used: now });
+        cell
+    }
+
+    pub async fn get_or_prepare<F, Fut, E>(&self, key: &str, prepare: F) -> Result<Arc<V>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        let cell = self.cell_for(key);
+        cell.get_or_try_init(|| async {
+            self.prepares.fetch_add(1, Ordering::Relaxed);
+            prepare().await.map(Arc::new)
+        })
+        .await
+        .cloned()
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.lock().entries.get(key).is_some_and(|e| e.cell.initialized())
+    }
+
+    pub fn invalidate(&self, key: &str) -> bool {
+        let removed = self.lock().entries.remove(key).is_some();
+        if removed { self.invalidations.fetch_add(1, Ordering::Relaxed); }
+        removed
+    }
+
+    pub fn clear(&self) -> usize {
+        let mut st = self.lock();
+        let n = st.entries.len();
+        st.entries.clear();
+        self.invalidations.fetch_add(n as u64, Ordering::Relaxed);
+        n
+    }
+
+    pub fn len(&self) -> usize { self.lock().entries.len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            capacity: self.capacity,
+            size: self.len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            prepares: self.prepares.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            invalidations: self.invalidations.load(Ordering::Relaxed),
+        }
+    }
+}
+
+pub fn is_unprepared(err: &ExecutionError) -> bool {
+    matches!(err, ExecutionError::LastAttemptError(RequestAttemptError::DbError(DbError::Unprepared { .. }, _)))
+}
+
+pub fn is_schema_change(cql: &str) -> bool {
+    let head = cql.split_whitespace().next().unwrap_or("").to_ascii_uppercase();
+    matches!(head.as_str(), "CREATE" | "ALTER" | "DROP")
+}
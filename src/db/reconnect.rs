@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{info, warn};
+
+use crate::config::DriverConfig;
+use crate::db::DbClients;
+use crate::replication::Cluster;
+
+const DEFAULT_INITIAL_MS: u64 = 500;
+const DEFAULT_MAX_MS: u64 = 30_000;
+
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    current: Option<Duration>,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        let initial = initial.max(Duration::from_millis(1));
+        Self { initial, max: max.max(initial), current: None }
+    }
+
+    pub fn from_config(drv: &DriverConfig) -> Self {
+        Self::new(
+            Duration::from_millis(drv.reconnect_initial_ms.unwrap_or(DEFAULT_INITIAL_MS)),
+            Duration::from_millis(drv.reconnect_max_ms.unwrap_or(DEFAULT_MAX_MS)),
+        )
+    }
+
+    pub fn next_delay(&mut self) -> Duration {
+        let next = match self.current {
+            None => self.initial,
+            Some(d) => d.saturating_mul(2).min(self.max),
+        };
+        self.current = Some(next);
+        next
+    }
+
+    pub fn reset(&mut self) { self.current = None; }
+}
+
+pub async fn run_reconnect_loop(clients: Arc<DbClients>, backoff: Backoff) {
+    let mut state = [(Cluster::Active, backoff.clone(), Duration::ZERO), (Cluster::Passive, backoff.clone(), Duration::ZERO)];
+    loop {
+        let mut sleep_for = backoff.max;
+        for (cluster, backoff, wait) in state.iter_mut() {
+            if clients.is_connected(*cluster) {
+                backoff.reset();
+                *wait = Duration::ZERO;
+                continue;
+            }
+            if !wait.is_zero() {
+                sleep_for = sleep_for.min(*wait);
+                continue;
+            }
+            match clients.try_reconnect(*cluster).await {
+                Ok(_) => {
+                    info!("{} cluster session re-established", cluster.label());
+                    backoff.reset();
+                }
+                Err(e) => {
+                    *wait = backoff.next_delay();
+                    warn!("{} cluster reconnect failed, retrying in {:?}: {}", cluster.label(), wait, e.to_message());
+                    sleep_for = sleep_for.min(*wait);
+                }
+            }
+        }
+        tokio::time::sleep(sleep_for).await;
+        for (_, _, wait) in state.iter_mut() {
+            *wait = wait.saturating_sub(sleep_for);
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use scylla::client::session::Session;
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+
+use crate::db::DbClients;
+use crate::errors::{AppError, AppResult};
+use crate::replication::Cluster;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CqlType {
+    Ascii,
+    Text,
+    TinyInt,
+    SmallInt,
+    Int,
+    BigInt,
+    Varint,
+    Float,
+    Double,
+    Decimal,
+    Boolean,
+    Uuid,
+    TimeUuid,
+    Timestamp,
+    Date,
+    Time,
+    Blob,
+    Inet,
+    Unsupported(String),
+}
+
+impl CqlType {
+    pub fn parse(s: &str) -> Self {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "ascii" => CqlType::Ascii,
+            "text" | "varchar" => CqlType::Text,
+            "tinyint" => CqlType::TinyInt,
+            "smallint" => CqlType::SmallInt,
+            "int" => CqlType::Int,
+            "bigint" => CqlType::BigInt,
+            "varint" => CqlType::Varint,
+            "float" => CqlType::Float,
+            "double" => CqlType::Double,
+            "decimal" => CqlType::Decimal,
+            "boolean" => CqlType::Boolean,
+            "uuid" => CqlType::Uuid,
+            "timeuuid" => CqlType::TimeUuid,
+            "timestamp" => CqlType::Timestamp,
+            "date" => CqlType::Date,
+            "time" => CqlType::Time,
+            "blob" => CqlType::Blob,
+            "inet" => CqlType::Inet,
+            other => CqlType::Unsupported(other.to_string()),
+        }
+    }
+
+    pub fn is_supported(&self) -> bool { !matches!(self, CqlType::Unsupported(_)) }
+
+    pub fn render_literal(&self, raw: &str) -> Result<String, String> {
+        let v = raw.trim();
+        match self {
+            CqlType::Text => Ok(quote_literal(raw)),
+            CqlType::Ascii => {
+                if raw.is_ascii() { Ok(quote_literal(raw)) } else { Err(format!("'{}' is not ascii", raw)) }
+            }
+            CqlType::TinyInt => v.parse::<i8>().map(|n| n.to_string()).map_err(|e| format!("'{}' is not a tinyint: {}", v, e)),
+            CqlType::SmallInt => v.parse::<i16>().map(|n| n.to_string()).map_err(|e| format!("'{}' is not a smallint: {}", v, e)),
+            CqlType::Int => v.parse::<i32>().map(|n| n.to_string()).map_err(|e| format!("'{}' is not an int: {}", v, e)),
+            CqlType::BigInt => v.parse::<i64>().map(|n| n.to_string()).map_err(|e| format!("'{}' is not a bigint: {}", v, e)),
+            CqlType::Varint => {
+                let digits = v.strip_prefix(['-', '+']).unwrap_or(v);
+                if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    Ok(v.trim_start_matches('+').to_string())
+                } else {
+                    Err(format!("'{}' is not a varint", v))
+                }
+            }
+            CqlType::Float | CqlType::Double => {
+                let n = v.parse::<f64>().map_err(|e| format!("'{}' is not a number: {}", v, e))?;
+                if n.is_nan() {
+                    Ok("NaN".to_string())
+                } else if n.is_infinite() {
+                    Ok(if n > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() })
+                } else {
+                    Ok(v.trim_start_matches('+').to_string())
+                }
+            }
+            CqlType::Decimal => {
+                if is_decimal(v) { Ok(v.trim_start_matches('+').to_string()) } else { Err(format!("'{}' is not a decimal", v)) }
+            }
+            CqlType::Boolean => match v.to_ascii_lowercase().as_str() {
+                "true" | "1" | "yes" | "y" | "t" => Ok("true".to_string()),
+                "false" | "0" | "no" | "n" | "f" => Ok("false".to_string()),
+                _ => Err(format!("'{}' is not a boolean", v)),
+            },
+            CqlType::Uuid => uuid::Uuid::parse_str(v)
+                .map(|u| u.hyphenated().to_string())
+                .map_err(|e| format!("'{}' is not a uuid: {}", v, e)),
+            CqlType::TimeUuid => {
+                let u = uuid::Uuid::parse_str(v).map_err(|e| format!("'{}' is not a timeuuid: {}", v, e))?;
+                if u.get_version_num() == 1 { Ok(u.hyphenated().to_string()) } else { Err(format!("'{}' is not a version 1 uuid", v)) }
+            }
+            CqlType::Timestamp => {
+                if v.parse::<i64>().is_ok() {
+                    Ok(v.to_string())
+                } else if v.get(..10).is_some_and(is_date) {
+                    Ok(quote_literal(v))
+                } else {
+                    Err(format!("'{}' is not a timestamp (epoch millis or ISO-8601)", v))
+                }
+            }
+            CqlType::Date => {
+                if is_date(v) { Ok(quote_literal(v)) } else { Err(format!("'{}' is not a date (YYYY-MM-DD)", v)) }
+            }
+            CqlType::Time => {
+                if is_time(v) { Ok(quote_literal(v)) } else { Err(format!("'{}' is not a time (HH:MM:SS[.fff])", v)) }
+            }
+            CqlType::Blob => {
+                let hex = v.strip_prefix("0x").or_else(|| v.strip_prefix("0X")).unwrap_or(v);
+                if hex.len().is_multiple_of(2) && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    Ok(format!("0x{}", hex.to_ascii_lowercase()))
+                } else {
+                    Err(format!("'{}' is not a hex blob", v))
+                }
+            }
+            CqlType::Inet => v
+                .parse::<std::net::IpAddr>()
+                .map(|ip| quote_literal(&ip.to_string()))
+                .map_err(|e| format!("'{}' is not an inet address: {}", v, e)),
+            CqlType::Unsupported(t) => Err(format!("column type '{}' is not supported by import", t)),
+        }
+    }
+}
+
+pub fn quote_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn is_decimal(s: &str) -> bool {
+    let body = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(i) => (&body[..i], Some(&body[i + 1..])),
+        None => (body, None),
+    };
+    let (int_part, frac_part) = match mantissa.split_once('.') {
+        Some((i, f)) => (i, f),
+        None => (mantissa, ""),
+    };
+    let digits_ok = |p: &str| p.bytes().all(|b| b.is_ascii_digit());
+    if int_part.is_empty() && frac_part.is_empty() { return false; }
+    if !digits_ok(int_part) || !digits_ok(frac_part) { return false; }
+    match exponent {
+        Some(e) => {
+            let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+            !e.is_empty() && digits_ok(e)
+        }
+        None => true,
+    }
+}
+
+fn is_date(s: &str) -> bool {
+    let b = s.as_bytes();
+    if b.len() != 10 || b[4] != b'-' || b[7] != b'-' { return false; }
+    let (Ok(_), Ok(m), Ok(d)) = (s[..4].parse::<u32>(), s[5..7].parse::<u32>(), s[8..10].parse::<u32>()) else { return false };
+    (1..=12).contains(&m) && (1..=31).contains(&d)
+}
+
+fn is_time(s: &str) -> bool {
+    let (hms, frac) = match s.split_once('.') {
+        Some((h, f)) => (h, Some(f)),
+        None => (s, None),
+    };
+    let parts: Vec<&str> = hms.split(':').collect();
+    if parts.len() != 3 || parts.iter().any(|p| p.len() != 2) { return false; }
+    let (Ok(h), Ok(m), Ok(sec)) = (parts[0].parse::<u32>(), parts[1].parse::<u32>(), parts[2].parse::<u32>()) else { return false };
+    if h > 23 || m > 59 || sec > 59 { return false; }
+    match frac {
+        Some(f) => !f.is_empty() && f.len() <= 9 && f.bytes().all(|b| b.is_ascii_digit()),
+        None => true,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SchemaColumn {
+    pub name: String,
+    pub kind: String,
+    pub position: i32,
+    pub ty: CqlType,
+}
+
+impl SchemaColumn {
+    pub fn is_primary_key(&self) -> bool { self.kind == "partition_key" || self.kind == "clustering" }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableSchema {
+    pub keyspace: String,
+    pub table: String,
+    pub columns: Vec<SchemaColumn>,
+}
+
+impl TableSchema {
+    pub fn column(&self, name: &str) -> Option<&SchemaColumn> { self.columns.iter().find(|c| c.name == name) }
+
+    pub fn partition_key(&self) -> Vec<&SchemaColumn> { self.columns_of_kind("partition_key") }
+
+    pub fn clustering_key(&self) -> Vec<&SchemaColumn> { self.columns_of_kind("clustering") }
+
+    pub fn primary_key(&self) -> Vec<&SchemaColumn> {
+        let mut out = self.partition_key();
+        out.extend(self.clustering_key());
+        out
+    }
+
+    pub fn regular_columns(&self) -> Vec<&SchemaColumn> {
+        self.columns.iter().filter(|c| !c.is_primary_key()).collect()
+    }
+
+    fn columns_of_kind(&self, kind: &str) -> Vec<&SchemaColumn> {
+        let mut cols: Vec<&SchemaColumn> = self.columns.iter().filter(|c| c.kind == kind).collect();
+        cols.sort_by_key(|c| c.position);
+        cols
+    }
+}
+
+pub async fn load_table_schema(clients: &DbClients, keyspace: &str, table: &str) -> AppResult<TableSchema> {
+    let mut last_err: Option<AppError> = None;
+    for sess in [clients.session(Cluster::Active), clients.session(Cluster::Passive)].into_iter().flatten() {
+        match query_table_columns(&sess, keyspace, table).await {
+            Ok(columns) if columns.is_empty() => {
+                return Err(AppError::not_found(format!("table {}.{} does not exist or has no columns", keyspace, table)));
+            }
+            Ok(columns) => return Ok(TableSchema { keyspace: keyspace.to_string(), table: table.to_string(), columns }),
+            Err(e) => last_err = Some(e),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| AppError::db("no database session available to read table schema")))
+}
+
+async fn query_table_columns(sess: &Session, keyspace: &str, table: &str) -> AppResult<Vec<SchemaColumn>> {
+    let mut st = UnpreparedStatement::new(
+        "SELECT column_name, kind, position, type FROM system_schema.columns WHERE keyspace_name = ? AND table_name = ?",
+    );
+    st.set_consistency(Consistency::One);
+    st.set_is_idempotent(true);
+    let qr = sess
+        .query_unpaged(st, (keyspace, table))
+        .await
+        .map_err(|e| AppError::query(format!("failed to read schema for {}.{}", keyspace, table), e))?;
+    let rows_res = qr.into_rows_result().map_err(|e| AppError::decode("schema query returned no rows", e))?;
+    let iter = rows_res
+        .rows::<(String, String, i32, String)>()
+        .map_err(|e| AppError::decode("unexpected schema row type", e))?;
+    let mut columns = Vec::new();
+    for row in iter {
+        let (name, kind, position, ty) = row.map_err(|e| AppError::decode("failed to decode schema row", e))?;
+        columns.push(SchemaColumn { name, kind, position, ty: CqlType::parse(&ty) });
+    }
+    Ok(columns)
+}
@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::ssl::{Ssl, SslContext, SslContextBuilder, SslFiletype, SslMethod, SslVerifyMode, SslVersion};
+use openssl::x509::X509Ref;
+
+use crate::config::DbEndpoint;
+use crate::errors::{AppError, AppResult};
+
+pub fn parse_tls_version(s: &str) -> AppResult<SslVersion> {
+    match s.trim().to_ascii_lowercase().trim_start_matches("tls").trim_start_matches(['v', '_', '-']) {
+        "1.0" | "1_0" | "10" => Ok(SslVersion::TLS1),
+        "1.1" | "1_1" | "11" => Ok(SslVersion::TLS1_1),
+        "1.2" | "1_2" | "12" => Ok(SslVersion::TLS1_2),
+        "1.3" | "1_3" | "13" => Ok(SslVersion::TLS1_3),
+        _ => Err(AppError::config(format!("unsupported tls_min_version '{}', expected 1.0, 1.1, 1.2 or 1.3", s))),
+    }
+}
+
+fn host_of(point: &str) -> &str {
+    if let Some(rest) = point.strip_prefix('[') {
+        return rest.split(']').next().unwrap_or(rest);
+    }
+    match point.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => host,
+        _ => point,
+    }
+}
+
+pub fn contact_host(point: &str) -> String { host_of(point).to_ascii_lowercase() }
+
+pub fn node_server_names(ep: &DbEndpoint, ip: IpAddr, aliases: &[String]) -> Vec<String> {
+    let mut names = vec![ip.to_string()];
+    for name in aliases.iter().chain(&ep.tls_server_names) {
+        let name = name.trim().to_ascii_lowercase();
+        if !name.is_empty() && !names.contains(&name) { names.push(name); }
+    }
+    names
+}
+
+#[derive(Debug, Default)]
+pub struct PinnedCertificates {
+    nodes: RwLock<HashMap<SocketAddr, Vec<u8>>>,
+    stale: AtomicBool,
+}
+
+impl PinnedCertificates {
+    pub fn pin(&self, node: SocketAddr, fingerprint: Vec<u8>) -> bool {
+        let previous = self.nodes.write().unwrap_or_else(|p| p.into_inner()).insert(node, fingerprint.clone());
+        previous.is_some_and(|fp| fp != fingerprint)
+    }
+
+    pub fn knows(&self, node: &SocketAddr) -> bool { self.nodes.read().unwrap_or_else(|p| p.into_inner()).contains_key(node) }
+
+    pub fn pinned_for(&self, node: &SocketAddr) -> Option<Vec<u8>> {
+        self.nodes.read().unwrap_or_else(|p| p.into_inner()).get(node).cloned()
+    }
+
+    pub fn is_pinned_for(&self, node: &SocketAddr, fingerprint: &[u8]) -> bool {
+        self.nodes.read().unwrap_or_else(|p| p.into_inner()).get(node).is_some_and(|fp| fp == fingerprint)
+    }
+
+    pub fn is_pinned(&self, fingerprint: &[u8]) -> bool {
+        self.nodes.read().unwrap_or_else(|p| p.into_inner()).values().any(|fp| fp == fingerprint)
+    }
+
+    pub fn check(&self, fingerprint: &[u8]) -> bool {
+        let pinned = self.is_pinned(fingerprint);
+        if !pinned { self.stale.store(true, Ordering::Release); }
+        pinned
+    }
+
+    pub fn take_stale(&self) -> bool { self.stale.swap(false, Ordering::AcqRel) }
+
+    pub fn len(&self) -> usize { self.nodes.read().unwrap_or_else(|p| p.into_inner()).len() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+}
+
+pub fn fingerprint(cert: &X509Ref) -> Option<Vec<u8>> { cert.digest(MessageDigest::sha256()).ok().map(|d| d.to_vec()) }
+
+pub fn name_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_ascii_lowercase();
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => match host.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        },
+        None => pattern == host,
+    }
+}
+
+fn cert_matches(cert: &X509Ref, names: &[String]) -> bool {
+    let ips: Vec<IpAddr> = names.iter().filter_map(|n| n.parse().ok()).collect();
+    if let Some(sans) = cert.subject_alt_names() {
+        let mut has_dns = false;
+        for san in sans.iter() {
+            if let Some(dns) = san.dnsname() {
+                has_dns = true;
+                if names.iter().any(|n| n.parse::<IpAddr>().is_err() && name_matches(dns, n)) { return true; }
+            }
+            if let Some(raw) = san.ipaddress() {
+                let ip = match raw.len() {
+                    4 => <[u8; 4]>::try_from(raw).ok().map(IpAddr::from),
+                    16 => <[u8; 16]>::try_from(raw).ok().map(IpAddr::from),
+                    _ => None,
+                };
+                if ip.is_some_and(|ip| ips.contains(&ip)) { return true; }
+            }
+        }
+        if has_dns { return false; }
+    }
+    cert.subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .filter_map(|e| e.data().as_utf8().ok())
+        .any(|cn| names.iter().any(|n| name_matches(&cn, n)))
+}
+
+fn tls_err(what: &str, e: openssl::error::ErrorStack) -> AppError { AppError::config(format!("TLS {}: {}", what, e)) }
+
+fn split_ciphers(ciphers: &str) -> (String, String) {
+    let (suites, list): (Vec<&str>, Vec<&str>) = ciphers
+        .split(':')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .partition(|c| c.starts_with("TLS_"));
+    (list.join(":"), suites.join(":"))
+}
+
+fn base_builder(ep: &DbEndpoint) -> AppResult<SslContextBuilder> {
+    let mut ctx = SslContextBuilder::new(SslMethod::tls()).map_err(|e| tls_err("context", e))?;
+
+    if let Some(v) = ep.tls_min_version.as_deref() {
+        ctx.set_min_proto_version(Some(parse_tls_version(v)?)).map_err(|e| tls_err("min version", e))?;
+    }
+    if let Some(ciphers) = ep.tls_ciphers.as_deref().filter(|c| !c.trim().is_empty()) {
+        let (list, suites) = split_ciphers(ciphers);
+        if !list.is_empty() {
+            ctx.set_cipher_list(&list).map_err(|e| tls_err(&format!("cipher list '{}'", list), e))?;
+        }
+        if !suites.is_empty() {
+            ctx.set_ciphersuites(&suites).map_err(|e| tls_err(&format!("TLS 1.3 cipher suites '{}'", suites), e))?;
+        }
+    }
+
+    match ep.tls_ca_file.as_deref() {
+        Some(ca) => ctx.set_ca_file(ca).map_err(|e| tls_err(&format!("CA file '{}'", ca), e))?,
+        None => ctx.set_default_verify_paths().map_err(|e| tls_err("default CA paths", e))?,
+    }
+
+    match (ep.tls_cert_file.as_deref(), ep.tls_key_file.as_deref()) {
+        (Some(cert), Some(key)) => {
+            ctx.set_certificate_chain_file(cert).map_err(|e| tls_err(&format!("client certificate '{}'", cert), e))?;
+            match ep.tls_key_password.as_deref() {
+                Some(pw) => {
+                    let pem = fs::read(key).map_err(|e| AppError::config(format!("TLS client key '{}': {}", key, e)))?;
+                    let pkey = PKey::private_key_from_pem_passphrase(&pem, pw.as_bytes())
+                        .map_err(|e| tls_err(&format!("client key '{}'", key), e))?;
+                    ctx.set_private_key(&pkey).map_err(|e| tls_err(&format!("client key '{}'", key), e))?;
+                }
+                None => ctx.set_private_key_file(key, SslFiletype::PEM).map_err(|e| tls_err(&format!("client key '{}'", key), e))?,
+            }
+            ctx.check_private_key().map_err(|e| tls_err("client key does not match certificate", e))?;
+        }
+        (None, None) => {}
+        _ => return Err(AppError::config("TLS client authentication needs both tls_cert_file and tls_key_file")),
+    }
+    Ok(ctx)
+}
+
+pub fn verifies_node_names(ep: &DbEndpoint) -> bool { ep.use_tls && ep.tls_verify_hostname && !ep.tls_insecure_skip_verify }
+
+pub fn build_tls_context(ep: &DbEndpoint, pins: Arc<PinnedCertificates>) -> AppResult<Option<SslContext>> {
+    if !ep.use_tls { return Ok(None); }
+    let mut ctx = base_builder(ep)?;
+
+    if ep.tls_insecure_skip_verify {
+        if ep.tls_verify_hostname {
+            return Err(AppError::config("tls_verify_hostname cannot be combined with tls_insecure_skip_verify"));
+        }
+        ctx.set_verify(SslVerifyMode::NONE);
+    } else if ep.tls_verify_hostname {
+        ctx.set_verify_callback(SslVerifyMode::PEER, move |preverified, store| {
+            if !preverified || store.error_depth() != 0 { return preverified; }
+            store.current_cert().and_then(fingerprint).is_some_and(|fp| pins.check(&fp))
+        });
+    } else {
+        ctx.set_verify(SslVerifyMode::PEER);
+    }
+
+    Ok(Some(ctx.build()))
+}
+
+pub fn verify_node(ep: &DbEndpoint, addr: SocketAddr, names: Vec<String>, timeout: Duration) -> AppResult<Vec<u8>> {
+    let mut ctx = base_builder(ep)?;
+    let sni = names.iter().find(|n| n.parse::<IpAddr>().is_err() && !n.starts_with("*.")).cloned();
+    ctx.set_verify_callback(SslVerifyMode::PEER, move |preverified, store| {
+        if !preverified || store.error_depth() != 0 { return preverified; }
+        store.current_cert().is_some_and(|cert| cert_matches(cert, &names))
+    });
+    let ctx = ctx.build();
+
+    let tcp = TcpStream::connect_timeout(&addr, timeout).map_err(|e| AppError::io(format!("TLS check: connect to {}", addr), e))?;
+    tcp.set_read_timeout(Some(timeout)).map_err(|e| AppError::io("TLS check: socket timeout", e))?;
+    tcp.set_write_timeout(Some(timeout)).map_err(|e| AppError::io("TLS check: socket timeout", e))?;
+    let mut ssl = Ssl::new(&ctx).map_err(|e| tls_err("session", e))?;
+    if let Some(name) = sni.as_deref() {
+        ssl.set_hostname(name).map_err(|e| tls_err("server name", e))?;
+    }
+    let stream = ssl
+        .connect(tcp)
+        .map_err(|e| AppError::config(format!("TLS check: {} did not present a certificate valid for its address: {}", addr, e)))?;
+    stream
+        .ssl()
+        .peer_certificate()
+        .as_deref()
+        .and_then(fingerprint)
+        .ok_or_else(|| AppError::config(format!("TLS check: {} presented no certificate", addr)))
+}
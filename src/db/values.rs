@@ -0,0 +1,189 @@
+use scylla::value::{CqlDecimal, CqlValue, CqlVarint};
+
+use crate::db::schema::quote_literal;
+
+const UNIX_EPOCH_DAY_OFFSET: i64 = 1 << 31;
+
+pub fn to_text(v: &CqlValue) -> String {
+    match v {
+        CqlValue::Ascii(s) | CqlValue::Text(s) => s.clone(),
+        CqlValue::Boolean(b) => b.to_string(),
+        CqlValue::Blob(b) => format!("0x{}", hex(b)),
+        CqlValue::Counter(c) => c.0.to_string(),
+        CqlValue::Decimal(d) => decimal_to_string(d),
+        CqlValue::Date(d) => date_to_string(d.0),
+        CqlValue::Double(f) => f.to_string(),
+        CqlValue::Duration(d) => format!("{}mo{}d{}ns", d.months, d.days, d.nanoseconds),
+        CqlValue::Empty => String::new(),
+        CqlValue::Float(f) => f.to_string(),
+        CqlValue::Int(i) => i.to_string(),
+        CqlValue::BigInt(i) => i.to_string(),
+        CqlValue::SmallInt(i) => i.to_string(),
+        CqlValue::TinyInt(i) => i.to_string(),
+        CqlValue::Timestamp(t) => timestamp_to_string(t.0),
+        CqlValue::Time(t) => time_to_string(t.0),
+        CqlValue::Inet(ip) => ip.to_string(),
+        CqlValue::Uuid(u) => u.hyphenated().to_string(),
+        CqlValue::Timeuuid(u) => u.to_string(),
+        CqlValue::Varint(v) => varint_to_string(v),
+        other => to_json(other).to_string(),
+    }
+}
+
+pub fn to_json(v: &CqlValue) -> serde_json::Value {
+    use serde_json::Value as J;
+    match v {
+        CqlValue::Boolean(b) => J::Bool(*b),
+        CqlValue::Int(i) => J::from(*i),
+        CqlValue::BigInt(i) => J::from(*i),
+        CqlValue::SmallInt(i) => J::from(*i),
+        CqlValue::TinyInt(i) => J::from(*i),
+        CqlValue::Counter(c) => J::from(c.0),
+        CqlValue::Float(f) => serde_json::Number::from_f64(*f as f64).map(J::Number).unwrap_or(J::Null),
+        CqlValue::Double(f) => serde_json::Number::from_f64(*f).map(J::Number).unwrap_or(J::Null),
+        CqlValue::List(items) | CqlValue::Set(items) | CqlValue::Vector(items) => J::Array(items.iter().map(to_json).collect()),
+        CqlValue::Map(entries) => {
+            let all_text_keys = entries.iter().all(|(k, _)| matches!(k, CqlValue::Text(_) | CqlValue::Ascii(_)));
+            if all_text_keys {
+                J::Object(entries.iter().map(|(k, v)| (to_text(k), to_json(v))).collect())
+            } else {
+                J::Array(entries.iter().map(|(k, v)| J::Array(vec![to_json(k), to_json(v)])).collect())
+            }
+        }
+        CqlValue::Tuple(items) => J::Array(items.iter().map(|i| i.as_ref().map(to_json).unwrap_or(J::Null)).collect()),
+        CqlValue::UserDefinedType { fields, .. } => J::Object(
+            fields.iter().map(|(k, v)| (k.clone(), v.as_ref().map(to_json).unwrap_or(J::Null))).collect(),
+        ),
+        CqlValue::Empty => J::Null,
+        other => J::String(to_text(other)),
+    }
+}
+
+pub fn to_literal(v: &CqlValue) -> String {
+    let join = |items: &[CqlValue]| items.iter().map(to_literal).collect::<Vec<_>>().join(", ");
+    let opt = |v: &Option<CqlValue>| v.as_ref().map(to_literal).unwrap_or_else(|| "null".to_string());
+    match v {
+        CqlValue::Ascii(s) | CqlValue::Text(s) => quote_literal(s),
+        CqlValue::Float(f) => float_literal(*f as f64),
+        CqlValue::Double(f) => float_literal(*f),
+        CqlValue::Timestamp(t) => t.0.to_string(),
+        CqlValue::Date(_) | CqlValue::Time(_) | CqlValue::Inet(_) => quote_literal(&to_text(v)),
+        CqlValue::Empty => "null".to_string(),
+        CqlValue::List(items) | CqlValue::Vector(items) => format!("[{}]", join(items)),
+        CqlValue::Set(items) => format!("{{{}}}", join(items)),
+        CqlValue::Map(entries) => format!(
+            "{{{}}}",
+            entries.iter().map(|(k, v)| format!("{}: {}", to_literal(k), to_literal(v))).collect::<Vec<_>>().join(", ")
+        ),
+        CqlValue::Tuple(items) => format!("({})", items.iter().map(opt).collect::<Vec<_>>().join(", ")),
+        CqlValue::UserDefinedType { fields, .. } => format!(
+            "{{{}}}",
+            fields.iter().map(|(k, v)| format!("{}: {}", crate::db::quote_ident(k), opt(v))).collect::<Vec<_>>().join(", ")
+        ),
+        other => to_text(other),
+    }
+}
+
+fn float_literal(f: f64) -> String {
+    if f.is_nan() {
+        "NaN".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() }
+    } else {
+        f.to_string()
+    }
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+pub fn varint_to_string(v: &CqlVarint) -> String {
+    signed_be_to_decimal(v.as_signed_bytes_be_slice())
+}
+
+pub fn decimal_to_string(d: &CqlDecimal) -> String {
+    let (bytes, scale) = d.as_signed_be_bytes_slice_and_exponent();
+    let digits = signed_be_to_decimal(bytes);
+    if scale <= 0 {
+        if digits == "0" { return digits; }
+        return format!("{}{}", digits, "0".repeat(scale.unsigned_abs() as usize));
+    }
+    let (sign, magnitude) = match digits.strip_prefix('-') {
+        Some(m) => ("-", m.to_string()),
+        None => ("", digits),
+    };
+    let scale = scale as usize;
+    let padded = if magnitude.len() <= scale { format!("{}{}", "0".repeat(scale + 1 - magnitude.len()), magnitude) } else { magnitude };
+    let (int_part, frac_part) = padded.split_at(padded.len() - scale);
+    format!("{}{}.{}", sign, int_part, frac_part)
+}
+
+fn signed_be_to_decimal(bytes: &[u8]) -> String {
+    if bytes.is_empty() { return "0".to_string(); }
+    let negative = bytes[0] & 0x80 != 0;
+    let mut mag: Vec<u8> = if negative {
+        let mut inv: Vec<u8> = bytes.iter().map(|b| !b).collect();
+        for b in inv.iter_mut().rev() {
+            let (v, overflow) = b.overflowing_add(1);
+            *b = v;
+            if !overflow { break; }
+        }
+        inv
+    } else {
+        bytes.to_vec()
+    };
+
+    let mut digits = Vec::new();
+    while mag.iter().any(|&b| b != 0) {
+        let mut rem: u32 = 0;
+        for b in mag.iter_mut() {
+            let cur = (rem << 8) | *b as u32;
+            *b = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        digits.push(b'0' + rem as u8);
+    }
+    if digits.is_empty() { return "0".to_string(); }
+    if negative { digits.push(b'-'); }
+    digits.reverse();
+    String::from_utf8(digits).unwrap_or_default()
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+pub fn date_to_string(raw: u32) -> String {
+    let (y, m, d) = civil_from_days(raw as i64 - UNIX_EPOCH_DAY_OFFSET);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+pub fn timestamp_to_string(millis: i64) -> String {
+    let days = millis.div_euclid(86_400_000);
+    let ms_of_day = millis.rem_euclid(86_400_000);
+    let (y, m, d) = civil_from_days(days);
+    let secs = ms_of_day / 1000;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        y, m, d, secs / 3600, (secs / 60) % 60, secs % 60, ms_of_day % 1000
+    )
+}
+
+pub fn time_to_string(nanos: i64) -> String {
+    let secs = nanos / 1_000_000_000;
+    format!("{:02}:{:02}:{:02}.{:09}", secs / 3600, (secs / 60) % 60, secs % 60, nanos % 1_000_000_000)
+}
@@ -1,11 +1,35 @@
+use std::error::Error as StdError;
 use std::fmt;
+use std::io;
+
+use ntex::http::StatusCode;
+use scylla::errors::{DbError, ExecutionError, NewSessionError, PrepareError, RequestAttemptError, SchemaAgreementError};
+
+use crate::types::response::{
+    CODE_BAD_REQUEST, CODE_CONFIG, CODE_DB_ERROR, CODE_DB_UNAVAILABLE, CODE_FAILURE, CODE_FORBIDDEN, CODE_NOT_FOUND,
+    CODE_SERVICE_UNAVAILABLE, CODE_VALIDATION,
+};
+
+pub type BoxError = Box<dyn StdError + Send + Sync + 'static>;
 
 #[derive(Debug)]
 pub enum AppError {
     Config(String),
     Db(String),
     Web(String),
+    Validation(String),
+    NotFound(String),
+    Forbidden(String),
+    Unavailable(String),
     Other(String),
+    Io { context: String, source: io::Error },
+    Toml { context: String, source: Box<toml::de::Error> },
+    Json { context: String, source: serde_json::Error },
+    Query { context: String, source: Box<ExecutionError> },
+    Prepare { context: String, source: Box<PrepareError> },
+    Connect { context: String, source: Box<NewSessionError> },
+    SchemaAgreement { context: String, source: Box<SchemaAgreementError> },
+    Decode { context: String, source: BoxError },
 }
 
 impl fmt::Display for AppError {
@@ -14,22 +38,214 @@ impl fmt::Display for AppError {
             AppError::Config(m) => write!(f, "Config: {}", m),
             AppError::Db(m) => write!(f, "Db: {}", m),
             AppError::Web(m) => write!(f, "Web: {}", m),
+            AppError::Validation(m) => write!(f, "Validation: {}", m),
+            AppError::NotFound(m) => write!(f, "NotFound: {}", m),
+            AppError::Forbidden(m) => write!(f, "Forbidden: {}", m),
+            AppError::Unavailable(m) => write!(f, "Unavailable: {}", m),
             AppError::Other(m) => write!(f, "Other: {}", m),
+            AppError::Io { context, .. } => write!(f, "Io: {}", context),
+            AppError::Toml { context, .. } => write!(f, "Config: {}", context),
+            AppError::Json { context, .. } => write!(f, "Json: {}", context),
+            AppError::Query { context, .. }
+            | AppError::Prepare { context, .. }
+            | AppError::Connect { context, .. }
+            | AppError::SchemaAgreement { context, .. }
+            | AppError::Decode { context, .. } => write!(f, "Db: {}", context),
         }
     }
 }
 
-impl std::error::Error for AppError {}
+impl StdError for AppError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            AppError::Io { source, .. } => Some(source),
+            AppError::Toml { source, .. } => Some(source.as_ref()),
+            AppError::Json { source, .. } => Some(source),
+            AppError::Query { source, .. } => Some(source.as_ref()),
+            AppError::Prepare { source, .. } => Some(source.as_ref()),
+            AppError::Connect { source, .. } => Some(source.as_ref()),
+            AppError::SchemaAgreement { source, .. } => Some(source.as_ref()),
+            AppError::Decode { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
 
 pub type AppResult<T> = Result<T, AppError>;
 
 impl AppError {
-    pub fn to_message(&self) -> String { self.to_string() }
+    pub fn to_message(&self) -> String {
+        match self {
+            AppError::Toml { source, .. } => format!("{}: {}", self, source.message()),
+            _ => match StdError::source(self) {
+                Some(source) => format!("{}: {}", self, source),
+                None => self.to_string(),
+            },
+        }
+    }
 
     pub fn config(msg: impl Into<String>) -> Self { AppError::Config(msg.into()) }
     pub fn db(msg: impl Into<String>) -> Self { AppError::Db(msg.into()) }
     pub fn web(msg: impl Into<String>) -> Self { AppError::Web(msg.into()) }
+    pub fn validation(msg: impl Into<String>) -> Self { AppError::Validation(msg.into()) }
+    pub fn not_found(msg: impl Into<String>) -> Self { AppError::NotFound(msg.into()) }
+    pub fn forbidden(msg: impl Into<String>) -> Self { AppError::Forbidden(msg.into()) }
+    pub fn unavailable(msg: impl Into<String>) -> Self { AppError::Unavailable(msg.into()) }
     pub fn other(msg: impl Into<String>) -> Self { AppError::Other(msg.into()) }
+
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self { AppError::Io { context: context.into(), source } }
+    pub fn toml(context: impl Into<String>, source: toml::de::Error) -> Self { AppError::Toml { context: context.into(), source: Box::new(source) } }
+    pub fn json(context: impl Into<String>, source: serde_json::Error) -> Self { AppError::Json { context: context.into(), source } }
+    pub fn query(context: impl Into<String>, source: ExecutionError) -> Self { AppError::Query { context: context.into(), source: Box::new(source) } }
+    pub fn prepare(context: impl Into<String>, source: PrepareError) -> Self { AppError::Prepare { context: context.into(), source: Box::new(source) } }
+    pub fn connect(context: impl Into<String>, source: NewSessionError) -> Self { AppError::Connect { context: context.into(), source: Box::new(source) } }
+    pub fn schema_agreement(context: impl Into<String>, source: SchemaAgreementError) -> Self {
+        AppError::SchemaAgreement { context: context.into(), source: Box::new(source) }
+    }
+    pub fn decode(context: impl Into<String>, source: impl StdError + Send + Sync + 'static) -> Self {
+        AppError::Decode { context: context.into(), source: Box::new(source) }
+    }
+
+    pub fn with_context(self, ctx: impl fmt::Display) -> Self {
+        let prefix = |m: String| format!("{}: {}", ctx, m);
+        match self {
+            AppError::Config(m) => AppError::Config(prefix(m)),
+            AppError::Db(m) => AppError::Db(prefix(m)),
+            AppError::Web(m) => AppError::Web(prefix(m)),
+            AppError::Validation(m) => AppError::Validation(prefix(m)),
+            AppError::NotFound(m) => AppError::NotFound(prefix(m)),
+            AppError::Forbidden(m) => AppError::Forbidden(prefix(m)),
+            AppError::Unavailable(m) => AppError::Unavailable(prefix(m)),
+            AppError::Other(m) => AppError::Other(prefix(m)),
+            AppError::Io { context, source } => AppError::Io { context: prefix(context), source },
+            AppError::Toml { context, source } => AppError::Toml { context: prefix(context), source },
+            AppError::Json { context, source } => AppError::Json { context: prefix(context), source },
+            AppError::Query { context, source } => AppError::Query { context: prefix(context), source },
+            AppError::Prepare { context, source } => AppError::Prepare { context: prefix(context), source },
+            AppError::Connect { context, source } => AppError::Connect { context: prefix(context), source },
+            AppError::SchemaAgreement { context, source } => AppError::SchemaAgreement { context: prefix(context), source },
+            AppError::Decode { context, source } => AppError::Decode { context: prefix(context), source },
+        }
+    }
+
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            AppError::Config(_) => "CONFIG_INVALID",
+            AppError::Db(_) => "DB_ERROR",
+            AppError::Web(_) => "BAD_REQUEST",
+            AppError::Validation(_) => "VALIDATION_FAILED",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Forbidden(_) => "FORBIDDEN",
+            AppError::Unavailable(_) => "SERVICE_UNAVAILABLE",
+            AppError::Other(_) => "INTERNAL",
+            AppError::Io { .. } => "IO_ERROR",
+            AppError::Toml { .. } => "CONFIG_PARSE",
+            AppError::Json { .. } => "JSON_ERROR",
+            AppError::Query { .. } => "DB_QUERY_FAILED",
+            AppError::Prepare { .. } => "DB_PREPARE_FAILED",
+            AppError::Connect { .. } => "DB_CONNECT_FAILED",
+            AppError::SchemaAgreement { .. } => "DB_SCHEMA_AGREEMENT",
+            AppError::Decode { .. } => "DB_DECODE_FAILED",
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::Db(_) | AppError::Unavailable(_) | AppError::SchemaAgreement { .. } => true,
+            AppError::Io { source, .. } => io_retryable(source),
+            AppError::Query { source, .. } => execution_retryable(source),
+            AppError::Prepare { source, .. } => prepare_retryable(source),
+            AppError::Connect { source, .. } => connect_retryable(source),
+            _ => false,
+        }
+    }
+
+    pub fn is_db(&self) -> bool {
+        matches!(
+            self,
+            AppError::Db(_)
+                | AppError::Query { .. }
+                | AppError::Prepare { .. }
+                | AppError::Connect { .. }
+                | AppError::SchemaAgreement { .. }
+                | AppError::Decode { .. }
+        )
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Config(_) | AppError::Toml { .. } => CODE_CONFIG,
+            AppError::Web(_) => CODE_BAD_REQUEST,
+            AppError::Validation(_) => CODE_VALIDATION,
+            AppError::NotFound(_) => CODE_NOT_FOUND,
+            AppError::Forbidden(_) => CODE_FORBIDDEN,
+            AppError::Unavailable(_) => CODE_SERVICE_UNAVAILABLE,
+            e if e.is_db() => if e.is_retryable() { CODE_DB_UNAVAILABLE } else { CODE_DB_ERROR },
+            _ => CODE_FAILURE,
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode { crate::types::response::status_for_code(self.code()) }
+
+    pub fn source_chain(&self) -> Vec<String> {
+        let mut chain = Vec::new();
+        let mut cur = StdError::source(self);
+        while let Some(e) = cur {
+            chain.push(e.to_string());
+            cur = e.source();
+        }
+        chain
+    }
+}
+
+fn io_retryable(e: &io::Error) -> bool {
+    use io::ErrorKind::*;
+    matches!(e.kind(), Interrupted | TimedOut | WouldBlock | ConnectionRefused | ConnectionReset | ConnectionAborted | NotConnected | BrokenPipe)
+}
+
+fn attempt_retryable(e: &RequestAttemptError) -> bool {
+    match e {
+        RequestAttemptError::DbError(db, _) => matches!(
+            db,
+            DbError::Unavailable { .. }
+                | DbError::Overloaded
+                | DbError::IsBootstrapping
+                | DbError::ReadTimeout { .. }
+                | DbError::WriteTimeout { .. }
+                | DbError::TruncateError
+                | DbError::ServerError
+                | DbError::Unprepared { .. }
+                | DbError::RateLimitReached { .. }
+        ),
+        RequestAttemptError::SerializationError(_)
+        | RequestAttemptError::CqlRequestSerialization(_)
+        | RequestAttemptError::CqlResultParseError(_)
+        | RequestAttemptError::CqlErrorParseError(_)
+        | RequestAttemptError::BodyExtensionsParseError(_)
+        | RequestAttemptError::UnexpectedResponse(_) => false,
+        _ => true,
+    }
+}
+
+fn execution_retryable(e: &ExecutionError) -> bool {
+    match e {
+        ExecutionError::LastAttemptError(a) => attempt_retryable(a),
+        ExecutionError::PrepareError(p) => prepare_retryable(p),
+        ExecutionError::BadQuery(_) | ExecutionError::UseKeyspaceError(_) => false,
+        _ => true,
+    }
+}
+
+fn prepare_retryable(e: &PrepareError) -> bool {
+    match e {
+        PrepareError::ConnectionPoolError(_) => true,
+        PrepareError::AllAttemptsFailed { first_attempt } => attempt_retryable(first_attempt),
+        _ => false,
+    }
+}
+
+fn connect_retryable(e: &NewSessionError) -> bool {
+    !matches!(e, NewSessionError::EmptyKnownNodesList | NewSessionError::UseKeyspaceError(_))
 }
 
 impl From<&str> for AppError {
@@ -38,4 +254,4 @@ impl From<&str> for AppError {
 
 impl From<String> for AppError {
     fn from(value: String) -> Self { AppError::Other(value) }
-}
\ No newline at end of file
+}
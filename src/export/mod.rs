@@ -0,0 +1,557 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use parquet::basic::{Compression as ParquetCompression, ConvertedType, Repetition, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, DoubleType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::types::Type as SchemaType;
+
+use scylla::client::session::Session;
+use scylla::frame::response::result::{ColumnType, NativeType};
+use scylla::response::{PagingState, PagingStateResponse};
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+use scylla::value::{CqlValue, Row};
+
+use crate::config::ExportConfig;
+use crate::db::values::{hex, to_json, to_text};
+use crate::db::{quote_ident, DbClients};
+use crate::errors::{AppError, AppResult};
+use crate::replication::Cluster;
+use crate::utils::now_millis;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat { Csv, Ndjson, Parquet }
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> AppResult<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" | "json" => Ok(ExportFormat::Ndjson),
+            "parquet" => Ok(ExportFormat::Parquet),
+            other => Err(AppError::validation(format!("unknown export format '{}', expected csv, ndjson or parquet", other))),
+        }
+    }
+
+    fn extension(&self, compress: bool) -> &'static str {
+        match (self, compress) {
+            (ExportFormat::Csv, false) => "csv",
+            (ExportFormat::Csv, true) => "csv.gz",
+            (ExportFormat::Ndjson, false) => "ndjson",
+            (ExportFormat::Ndjson, true) => "ndjson.gz",
+            (ExportFormat::Parquet, _) => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExportSource {
+    Table { keyspace: String, table: String },
+    Query { name: String, cql: String },
+}
+
+impl ExportSource {
+    pub fn table(keyspace: impl Into<String>, table: impl Into<String>) -> Self {
+        ExportSource::Table { keyspace: keyspace.into(), table: table.into() }
+    }
+
+    pub fn named_query(cfg: &ExportConfig, name: &str) -> AppResult<Self> {
+        let cql = cfg
+            .queries
+            .get(name)
+            .ok_or_else(|| AppError::validation(format!("query '{}' is not in the export whitelist", name)))?;
+        if !cql.trim_start().to_ascii_lowercase().starts_with("select") {
+            return Err(AppError::validation(format!("export query '{}' must be a SELECT statement", name)));
+        }
+        Ok(ExportSource::Query { name: name.to_string(), cql: cql.clone() })
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            ExportSource::Table { keyspace, table } => format!("{}.{}", keyspace, table),
+            ExportSource::Query { name, .. } => format!("query-{}", name),
+        }
+    }
+
+    pub fn cql(&self) -> String {
+        match self {
+            ExportSource::Table { keyspace, table } => format!("SELECT * FROM {}.{}", quote_ident(keyspace), quote_ident(table)),
+            ExportSource::Query { cql, .. } => cql.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportSpec {
+    pub source: ExportSource,
+    pub format: ExportFormat,
+    pub out_dir: PathBuf,
+    pub chunk_rows: usize,
+    pub page_size: i32,
+    pub compress: bool,
+    pub resume: bool,
+    pub cluster: Option<Cluster>,
+}
+
+impl ExportSpec {
+    pub fn new<P: AsRef<Path>>(source: ExportSource, format: ExportFormat, base_dir: P) -> Self {
+        let dir_name: String = source
+            .label()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let out_dir = base_dir.as_ref().join(dir_name);
+        Self { source, format, out_dir, chunk_rows: 100_000, page_size: 5000, compress: true, resume: false, cluster: None }
+    }
+
+    pub fn with_resume(mut self, resume: bool) -> Self { self.resume = resume; self }
+
+    pub fn with_chunk_rows(mut self, rows: usize) -> Self { self.chunk_rows = rows.max(1); self }
+
+    pub fn with_page_size(mut self, size: i32) -> Self { self.page_size = size.max(1); self }
+
+    pub fn with_compress(mut self, compress: bool) -> Self { self.compress = compress; self }
+
+    pub fn with_cluster(mut self, cluster: Option<Cluster>) -> Self { self.cluster = cluster; self }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportChunk {
+    pub file: String,
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportManifest {
+    pub source: String,
+    pub cql: String,
+    pub format: ExportFormat,
+    pub compressed: bool,
+    pub cluster: String,
+    pub columns: Vec<String>,
+    pub chunks: Vec<ExportChunk>,
+    pub total_rows: u64,
+    pub complete: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paging_state: Option<String>,
+    pub started_ms: u64,
+    pub updated_ms: u64,
+}
+
+impl ExportManifest {
+    pub fn load<P: AsRef<Path>>(dir: P) -> AppResult<Option<Self>> {
+        let path = dir.as_ref().join(MANIFEST_FILE);
+        if !path.exists() { return Ok(None); }
+        let s = fs::read_to_string(&path).map_err(|e| AppError::io(format!("read {}", path.display()), e))?;
+        serde_json::from_str(&s)
+            .map(Some)
+            .map_err(|e| AppError::json(format!("parse {}", path.display()), e))
+    }
+
+    fn store(&self, dir: &Path) -> AppResult<()> {
+        let tmp = dir.join(format!("{}.tmp", MANIFEST_FILE));
+        let body = serde_json::to_vec_pretty(self).map_err(|e| AppError::json("encode manifest", e))?;
+        fs::write(&tmp, body).map_err(|e| AppError::io("write manifest", e))?;
+        fs::rename(&tmp, dir.join(MANIFEST_FILE)).map_err(|e| AppError::io("commit manifest", e))
+    }
+}
+
+pub async fn run_export(spec: &ExportSpec, clients: &DbClients) -> AppResult<ExportManifest> {
+    let (sess, cluster) = pick_session(clients, spec.cluster)?;
+    let cql = spec.source.cql();
+
+    let setup = spec.clone();
+    let manifest = blocking(move || prepare_manifest(&setup, cluster)).await?;
+    if manifest.complete { return Ok(manifest); }
+
+    let mut paging_state = match manifest.paging_state.as_deref() {
+        Some(h) => PagingState::new_from_raw_bytes(unhex(h)?),
+        None => PagingState::start(),
+    };
+
+    let mut st = UnpreparedStatement::new(cql.as_str());
+    st.set_page_size(spec.page_size);
+    st.set_consistency(if cluster == Cluster::Active { Consistency::LocalQuorum } else { Consistency::One });
+    st.set_is_idempotent(true);
+
+    let mut state = ChunkState { spec: spec.clone(), manifest, kinds: Vec::new(), writer: None, chunk_rows: 0 };
+
+    loop {
+        let (qr, paging) = sess
+            .query_single_page(st.clone(), &[], paging_state.clone())
+            .await
+            .map_err(|e| AppError::query("export query failed", e))?;
+        let rows_res = qr
+            .into_rows_result()
+            .map_err(|e| AppError::decode("export query did not return rows", e))?;
+
+        if state.kinds.is_empty() {
+            let specs = rows_res.column_specs();
+            state.manifest.columns = specs.iter().map(|c| c.name().to_string()).collect();
+            state.kinds = specs.iter().map(|c| ValueKind::of(c.typ())).collect();
+        }
+
+        let rows = rows_res
+            .rows::<Row>()
+            .map_err(|e| AppError::decode("export row type", e))?
+            .map(|row| row.map_err(|e| AppError::decode("export row decode", e)))
+            .collect::<AppResult<Vec<Row>>>()?;
+
+        let next = match paging {
+            PagingStateResponse::HasMorePages { state } => Some(state),
+            PagingStateResponse::NoMorePages => None,
+        };
+        let next_hex = next.as_ref().and_then(|s| s.as_bytes_slice().map(|b| hex(b)));
+        let last = next.is_none();
+
+        state = blocking(move || {
+            state.write_page(rows, next_hex, last)?;
+            Ok(state)
+        })
+        .await?;
+
+        match next {
+            Some(next) => paging_state = next,
+            None => break,
+        }
+    }
+
+    Ok(state.manifest)
+}
+
+async fn blocking<T: Send + 'static>(f: impl FnOnce() -> AppResult<T> + Send + 'static) -> AppResult<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::other(format!("export file task failed: {}", e)))?
+}
+
+fn prepare_manifest(spec: &ExportSpec, cluster: Cluster) -> AppResult<ExportManifest> {
+    fs::create_dir_all(&spec.out_dir)
+        .map_err(|e| AppError::io(format!("create export dir {}", spec.out_dir.display()), e))?;
+
+    let cql = spec.source.cql();
+    match ExportManifest::load(&spec.out_dir)? {
+        Some(m) if spec.resume => {
+            if m.cql != cql || m.format != spec.format || m.compressed != spec.compress {
+                return Err(AppError::config("cannot resume export: manifest was written for a different query or format"));
+            }
+            Ok(m)
+        }
+        prev => {
+            if let Some(old) = prev {
+                for chunk in &old.chunks {
+                    let _ = fs::remove_file(spec.out_dir.join(&chunk.file));
+                }
+            }
+            let now = now_millis() as u64;
+            Ok(ExportManifest {
+                source: spec.source.label(),
+                cql,
+                format: spec.format,
+                compressed: spec.compress,
+                cluster: format!("{:?}", cluster),
+                columns: Vec::new(),
+                chunks: Vec::new(),
+                total_rows: 0,
+                complete: false,
+                paging_state: None,
+                started_ms: now,
+                updated_ms: now,
+            })
+        }
+    }
+}
+
+struct ChunkState {
+    spec: ExportSpec,
+    manifest: ExportManifest,
+    kinds: Vec<ValueKind>,
+    writer: Option<Box<dyn ChunkWriter>>,
+    chunk_rows: u64,
+}
+
+impl ChunkState {
+    fn write_page(&mut self, rows: Vec<Row>, next: Option<String>, last: bool) -> AppResult<()> {
+        let spec = &self.spec;
+        for row in rows {
+            if self.writer.is_none() {
+                let path = spec.out_dir.join(chunk_file_name(self.manifest.chunks.len(), spec.format, spec.compress));
+                self.writer = Some(open_chunk(spec.format, &path, spec.compress, &self.manifest.columns, &self.kinds)?);
+            }
+            if let Some(w) = self.writer.as_mut() { w.write_row(&row.columns)?; }
+            self.chunk_rows += 1;
+        }
+
+        if self.chunk_rows >= spec.chunk_rows as u64 || last {
+            if let Some(w) = self.writer.take() {
+                let file = chunk_file_name(self.manifest.chunks.len(), spec.format, spec.compress);
+                w.finish()?;
+                let bytes = fs::metadata(spec.out_dir.join(&file)).map(|m| m.len()).unwrap_or(0);
+                self.manifest.chunks.push(ExportChunk { file, rows: self.chunk_rows, bytes });
+                self.manifest.total_rows += self.chunk_rows;
+                self.chunk_rows = 0;
+            }
+            self.manifest.paging_state = next;
+            self.manifest.complete = last;
+            self.manifest.updated_ms = now_millis() as u64;
+            self.manifest.store(&spec.out_dir)?;
+        }
+        Ok(())
+    }
+}
+
+fn pick_session(clients: &DbClients, preferred: Option<Cluster>) -> AppResult<(Arc<Session>, Cluster)> {
+    let first = preferred.unwrap_or_else(|| clients.primary());
+    for cl in [first, first.other()] {
+        if let Some(s) = clients.session(cl) {
+            return Ok((s, cl));
+        }
+    }
+    Err(AppError::db("no database session available for export"))
+}
+
+fn chunk_file_name(index: usize, format: ExportFormat, compress: bool) -> String {
+    format!("part-{:05}.{}", index, format.extension(compress))
+}
+
+fn unhex(s: &str) -> AppResult<Arc<[u8]>> {
+    if !s.len().is_multiple_of(2) { return Err(AppError::other("invalid paging state in manifest")); }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map(Arc::from)
+        .map_err(|_| AppError::other("invalid paging state in manifest"))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum ValueKind { I32, I64, Timestamp, F32, F64, Bool, Bytes, Text }
+
+impl ValueKind {
+    fn of(typ: &ColumnType<'_>) -> Self {
+        match typ {
+            ColumnType::Native(n) => match n {
+                NativeType::Int | NativeType::SmallInt | NativeType::TinyInt => ValueKind::I32,
+                NativeType::BigInt | NativeType::Counter => ValueKind::I64,
+                NativeType::Timestamp => ValueKind::Timestamp,
+                NativeType::Float => ValueKind::F32,
+                NativeType::Double => ValueKind::F64,
+                NativeType::Boolean => ValueKind::Bool,
+                NativeType::Blob => ValueKind::Bytes,
+                _ => ValueKind::Text,
+            },
+            _ => ValueKind::Text,
+        }
+    }
+}
+
+trait ChunkWriter: Send {
+    fn write_row(&mut self, row: &[Option<CqlValue>]) -> AppResult<()>;
+    fn finish(self: Box<Self>) -> AppResult<()>;
+}
+
+enum Sink {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Sink {
+    fn create(path: &Path, compress: bool) -> AppResult<Self> {
+        let f = File::create(path).map_err(|e| AppError::io(format!("create {}", path.display()), e))?;
+        let buf = BufWriter::new(f);
+        Ok(if compress { Sink::Gzip(GzEncoder::new(buf, flate2::Compression::default())) } else { Sink::Plain(buf) })
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        let mut buf = match self {
+            Sink::Plain(b) => b,
+            Sink::Gzip(g) => g.finish()?,
+        };
+        buf.flush()?;
+        buf.get_ref().sync_data()
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Sink::Plain(b) => b.write(data),
+            Sink::Gzip(g) => g.write(data),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Sink::Plain(b) => b.flush(),
+            Sink::Gzip(g) => g.flush(),
+        }
+    }
+}
+
+fn io_err(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> AppError { AppError::io("export write", std::io::Error::other(e)) }
+
+struct CsvChunk { writer: csv::Writer<Sink> }
+
+impl ChunkWriter for CsvChunk {
+    fn write_row(&mut self, row: &[Option<CqlValue>]) -> AppResult<()> {
+        self.writer
+            .write_record(row.iter().map(|v| v.as_ref().map(to_text).unwrap_or_default()))
+            .map_err(io_err)
+    }
+
+    fn finish(self: Box<Self>) -> AppResult<()> {
+        self.writer.into_inner().map_err(io_err)?.finish().map_err(io_err)
+    }
+}
+
+struct NdjsonChunk { sink: Sink, columns: Vec<String> }
+
+impl ChunkWriter for NdjsonChunk {
+    fn write_row(&mut self, row: &[Option<CqlValue>]) -> AppResult<()> {
+        let obj: serde_json::Map<String, serde_json::Value> = self
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(c, v)| (c.clone(), v.as_ref().map(to_json).unwrap_or(serde_json::Value::Null)))
+            .collect();
+        serde_json::to_writer(&mut self.sink, &obj).map_err(io_err)?;
+        self.sink.write_all(b"\n").map_err(io_err)
+    }
+
+    fn finish(self: Box<Self>) -> AppResult<()> { self.sink.finish().map_err(io_err) }
+}
+
+enum ParquetValues {
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+    Bool(Vec<bool>),
+    Bytes(Vec<ByteArray>),
+}
+
+struct ParquetColumn {
+    name: String,
+    kind: ValueKind,
+    values: ParquetValues,
+    def_levels: Vec<i16>,
+}
+
+impl ParquetColumn {
+    fn new(name: &str, kind: ValueKind) -> Self {
+        let values = match kind {
+            ValueKind::I32 => ParquetValues::I32(Vec::new()),
+            ValueKind::I64 | ValueKind::Timestamp => ParquetValues::I64(Vec::new()),
+            ValueKind::F32 => ParquetValues::F32(Vec::new()),
+            ValueKind::F64 => ParquetValues::F64(Vec::new()),
+            ValueKind::Bool => ParquetValues::Bool(Vec::new()),
+            ValueKind::Bytes | ValueKind::Text => ParquetValues::Bytes(Vec::new()),
+        };
+        Self { name: name.to_string(), kind, values, def_levels: Vec::new() }
+    }
+
+    fn push(&mut self, v: Option<&CqlValue>) {
+        let pushed = match (v, &mut self.values) {
+            (None, _) | (Some(CqlValue::Empty), _) => false,
+            (Some(CqlValue::Int(i)), ParquetValues::I32(out)) => { out.push(*i); true }
+            (Some(CqlValue::SmallInt(i)), ParquetValues::I32(out)) => { out.push(*i as i32); true }
+            (Some(CqlValue::TinyInt(i)), ParquetValues::I32(out)) => { out.push(*i as i32); true }
+            (Some(CqlValue::BigInt(i)), ParquetValues::I64(out)) => { out.push(*i); true }
+            (Some(CqlValue::Counter(c)), ParquetValues::I64(out)) => { out.push(c.0); true }
+            (Some(CqlValue::Timestamp(t)), ParquetValues::I64(out)) => { out.push(t.0); true }
+            (Some(CqlValue::Float(f)), ParquetValues::F32(out)) => { out.push(*f); true }
+            (Some(CqlValue::Double(f)), ParquetValues::F64(out)) => { out.push(*f); true }
+            (Some(CqlValue::Boolean(b)), ParquetValues::Bool(out)) => { out.push(*b); true }
+            (Some(CqlValue::Blob(b)), ParquetValues::Bytes(out)) => { out.push(ByteArray::from(b.clone())); true }
+            (Some(other), ParquetValues::Bytes(out)) => { out.push(ByteArray::from(to_text(other).into_bytes())); true }
+            _ => false,
+        };
+        self.def_levels.push(if pushed { 1 } else { 0 });
+    }
+
+    fn schema(&self) -> parquet::errors::Result<SchemaType> {
+        let (physical, converted) = match self.kind {
+            ValueKind::I32 => (PhysicalType::INT32, ConvertedType::NONE),
+            ValueKind::I64 => (PhysicalType::INT64, ConvertedType::NONE),
+            ValueKind::Timestamp => (PhysicalType::INT64, ConvertedType::TIMESTAMP_MILLIS),
+            ValueKind::F32 => (PhysicalType::FLOAT, ConvertedType::NONE),
+            ValueKind::F64 => (PhysicalType::DOUBLE, ConvertedType::NONE),
+            ValueKind::Bool => (PhysicalType::BOOLEAN, ConvertedType::NONE),
+            ValueKind::Bytes => (PhysicalType::BYTE_ARRAY, ConvertedType::NONE),
+            ValueKind::Text => (PhysicalType::BYTE_ARRAY, ConvertedType::UTF8),
+        };
+        SchemaType::primitive_type_builder(&self.name, physical)
+            .with_repetition(Repetition::OPTIONAL)
+            .with_converted_type(converted)
+            .build()
+    }
+}
+
+struct ParquetChunk { file: File, compress: bool, columns: Vec<ParquetColumn> }
+
+impl ChunkWriter for ParquetChunk {
+    fn write_row(&mut self, row: &[Option<CqlValue>]) -> AppResult<()> {
+        for (col, v) in self.columns.iter_mut().zip(row) {
+            col.push(v.as_ref());
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> AppResult<()> {
+        let ParquetChunk { file, compress, columns } = *self;
+        let fields = columns
+            .iter()
+            .map(|c| c.schema().map(Arc::new))
+            .collect::<parquet::errors::Result<Vec<_>>>()
+            .map_err(io_err)?;
+        let schema = SchemaType::group_type_builder("schema").with_fields(fields).build().map_err(io_err)?;
+        let props = WriterProperties::builder()
+            .set_compression(if compress { ParquetCompression::SNAPPY } else { ParquetCompression::UNCOMPRESSED })
+            .build();
+        let mut writer = SerializedFileWriter::new(file, Arc::new(schema), Arc::new(props)).map_err(io_err)?;
+        let mut rg = writer.next_row_group().map_err(io_err)?;
+        for col in &columns {
+            let Some(mut cw) = rg.next_column().map_err(io_err)? else { break };
+            let defs = Some(col.def_levels.as_slice());
+            match &col.values {
+                ParquetValues::I32(v) => cw.typed::<Int32Type>().write_batch(v, defs, None),
+                ParquetValues::I64(v) => cw.typed::<Int64Type>().write_batch(v, defs, None),
+                ParquetValues::F32(v) => cw.typed::<FloatType>().write_batch(v, defs, None),
+                ParquetValues::F64(v) => cw.typed::<DoubleType>().write_batch(v, defs, None),
+                ParquetValues::Bool(v) => cw.typed::<BoolType>().write_batch(v, defs, None),
+                ParquetValues::Bytes(v) => cw.typed::<ByteArrayType>().write_batch(v, defs, None),
+            }
+            .map_err(io_err)?;
+            cw.close().map_err(io_err)?;
+        }
+        rg.close().map_err(io_err)?;
+        let file = writer.into_inner().map_err(io_err)?;
+        file.sync_data().map_err(io_err)
+    }
+}
+
+fn open_chunk(format: ExportFormat, path: &Path, compress: bool, columns: &[String], kinds: &[ValueKind]) -> AppResult<Box<dyn ChunkWriter>> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(Sink::create(path, compress)?);
+            writer.write_record(columns).map_err(io_err)?;
+            Ok(Box::new(CsvChunk { writer }))
+        }
+        ExportFormat::Ndjson => Ok(Box::new(NdjsonChunk { sink: Sink::create(path, compress)?, columns: columns.to_vec() })),
+        ExportFormat::Parquet => {
+            let file = File::create(path).map_err(|e| AppError::io(format!("create {}", path.display()), e))?;
+            let columns = columns.iter().zip(kinds).map(|(n, k)| ParquetColumn::new(n, *k)).collect();
+            Ok(Box::new(ParquetChunk { file, compress, columns }))
+        }
+    }
+}
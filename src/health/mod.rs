@@ -1,26 +1,178 @@
+pub mod probes;
+
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use scylla::client::session::Session;
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+use scylla::value::{CqlValue, Row};
 use serde::Serialize;
 
 use crate::types::ApiResponse;
-use crate::types::response::{ApiMessage, CODE_FAILURE};
-use crate::db::DbClients;
+use crate::types::response::{ApiMessage, CODE_DB_UNAVAILABLE};
+use crate::db::values::to_text;
+use crate::db::{quote_ident, DbClients};
+use crate::db::prepared::CacheStats;
+use crate::replication::Cluster;
+use crate::utils::now_millis;
 
 #[derive(Debug, Serialize)]
 pub struct ServiceHealth { pub ok: bool }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Up,
+    Degraded,
+    #[default]
+    Down,
+}
+
+impl HealthState {
+    pub fn from_ok(ok: bool) -> Self { if ok { HealthState::Up } else { HealthState::Down } }
+
+    pub fn is_reachable(self) -> bool { self != HealthState::Down }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DcHealth { pub up: usize, pub down: usize }
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClusterHealth {
+    pub state: HealthState,
+    pub latency_ms: Option<u64>,
+    pub release_version: Option<String>,
+    pub known_nodes: usize,
+    pub connected_nodes: usize,
+    pub datacenters: BTreeMap<String, DcHealth>,
+    pub schema_agreement: Option<bool>,
+    pub keyspace_exists: Option<bool>,
+    pub watermark_log_id: Option<i64>,
+    pub heartbeat_age_ms: Option<u64>,
+    pub problems: Vec<String>,
+}
+
+impl ClusterHealth {
+    pub fn unreachable(reason: impl Into<String>) -> Self {
+        Self { state: HealthState::Down, problems: vec![reason.into()], ..Self::default() }
+    }
+
+    pub fn classify(&mut self) {
+        let down_nodes: usize = self.datacenters.values().map(|d| d.down).sum();
+        if down_nodes > 0 {
+            self.problems.push(format!("{} of {} known nodes are not connected", down_nodes, self.known_nodes));
+        }
+        if self.schema_agreement == Some(false) {
+            self.problems.push("schema versions disagree across nodes".to_string());
+        }
+        if self.keyspace_exists == Some(false) {
+            self.problems.push("application keyspace does not exist".to_string());
+        }
+        self.state = if self.problems.is_empty() { HealthState::Up } else { HealthState::Degraded };
+    }
+}
+
 #[derive(Debug, Serialize)]
-pub struct DbHealth { pub active_ok: bool, pub passive_ok: bool }
+pub struct DbHealth {
+    pub active_ok: bool,
+    pub passive_ok: bool,
+    pub active: ClusterHealth,
+    pub passive: ClusterHealth,
+}
+
+impl DbHealth {
+    pub fn from_clusters(active: ClusterHealth, passive: ClusterHealth) -> Self {
+        Self { active_ok: active.state.is_reachable(), passive_ok: passive.state.is_reachable(), active, passive }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PreparedCacheHealth { pub active: CacheStats, pub passive: CacheStats }
+
+pub fn prepared_cache_health(clients: &DbClients) -> ApiResponse<PreparedCacheHealth> {
+    let data = PreparedCacheHealth { active: clients.prepared_stats(Cluster::Active), passive: clients.prepared_stats(Cluster::Passive) };
+    ApiResponse::success_with("prepared statement cache", data)
+}
 
 pub fn service_health() -> ApiResponse<ServiceHealth> {
     ApiResponse::success_with("service healthy", ServiceHealth { ok: true })
 }
 
+async fn query_first_row(sess: &Session, cql: &str, consistency: Consistency) -> Result<Option<Row>, String> {
+    let mut st = UnpreparedStatement::new(cql);
+    st.set_consistency(consistency);
+    st.set_is_idempotent(true);
+    let qr = sess.query_unpaged(st, &[]).await.map_err(|e| e.to_string())?;
+    let rows_res = qr.into_rows_result().map_err(|e| e.to_string())?;
+    let mut rows = rows_res.rows::<Row>().map_err(|e| e.to_string())?;
+    rows.next().transpose().map_err(|e| e.to_string())
+}
+
+pub async fn probe_cluster(clients: &DbClients, cluster: Cluster) -> ClusterHealth {
+    let Some(sess) = clients.session(cluster) else {
+        return ClusterHealth::unreachable(format!("no session to the {} cluster", cluster.label()));
+    };
+    let consistency = if cluster == Cluster::Active { Consistency::LocalQuorum } else { Consistency::One };
+    let mut health = ClusterHealth::default();
+
+    let started = Instant::now();
+    match query_first_row(&sess, "SELECT release_version FROM system.local", consistency).await {
+        Ok(row) => {
+            health.latency_ms = Some(started.elapsed().as_millis() as u64);
+            health.release_version = row.and_then(|r| r.columns.into_iter().next().flatten()).map(|v| to_text(&v));
+        }
+        Err(e) => return ClusterHealth::unreachable(format!("health query failed: {}", e)),
+    }
+
+    let state = sess.get_cluster_state();
+    for node in state.get_nodes_info() {
+        let dc = health.datacenters.entry(node.datacenter.clone().unwrap_or_else(|| "unknown".to_string())).or_default();
+        if node.is_connected() { dc.up += 1; } else { dc.down += 1; }
+    }
+    health.known_nodes = state.get_nodes_info().len();
+    health.connected_nodes = health.datacenters.values().map(|d| d.up).sum();
+
+    match sess.check_schema_agreement().await {
+        Ok(v) => health.schema_agreement = Some(v.is_some()),
+        Err(e) => health.problems.push(format!("schema agreement check failed: {}", e)),
+    }
+
+    if let Some(conn) = clients.connector(cluster) {
+        let ks = &conn.endpoint.keyspace;
+        health.keyspace_exists = Some(state.get_keyspace(ks).is_some());
+        if health.keyspace_exists == Some(true) {
+            let cql = format!("SELECT last_applied_log_id, heartbeat_ms FROM {}.repl_watermark WHERE id = 1", quote_ident(ks));
+            match query_first_row(&sess, &cql, consistency).await {
+                Ok(Some(row)) => {
+                    let mut cols = row.columns.into_iter();
+                    if let Some(Some(CqlValue::BigInt(id))) = cols.next() { health.watermark_log_id = Some(id); }
+                    if let Some(Some(CqlValue::BigInt(hb))) = cols.next() {
+                        health.heartbeat_age_ms = Some((now_millis() as i64 - hb).max(0) as u64);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => health.problems.push(format!("watermark read failed: {}", e)),
+            }
+        }
+    }
+
+    health.classify();
+    health
+}
+
 pub async fn db_health(clients: &DbClients) -> ApiResponse<DbHealth> {
-    let (active_ok, passive_ok) = tokio::join!(
-        clients.ping_release_version_active(),
-        clients.ping_release_version_passive()
-    );
+    let (active, passive) = tokio::join!(probe_cluster(clients, Cluster::Active), probe_cluster(clients, Cluster::Passive));
+    let data = DbHealth::from_clusters(active, passive);
+    let (active_ok, passive_ok) = (data.active_ok, data.passive_ok);
 
-    let data = DbHealth { active_ok, passive_ok };
+    if active_ok && passive_ok && (data.active.state == HealthState::Degraded || data.passive.state == HealthState::Degraded) {
+        let mut notes = Vec::new();
+        for (label, h) in [("Active", &data.active), ("Passive", &data.passive)] {
+            if h.state == HealthState::Degraded { notes.push(format!("{}: {}", label, h.problems.join("; "))); }
+        }
+        return ApiResponse::success_with(format!("databases reachable but degraded ({})", notes.join(" | ")), data);
+    }
 
     match (active_ok, passive_ok) {
         (true, true) => ApiResponse::success_with("databases healthy", data),
@@ -28,19 +180,19 @@ pub async fn db_health(clients: &DbClients) -> ApiResponse<DbHealth> {
             let what = "Active database is unavailable or unhealthy".to_string();
             let why = "The application could not complete a basic health query against the Active cluster (SELECT release_version FROM system.local) or the request failed.".to_string();
             let how = "Ensure the Active database is running and reachable from this service. Verify host/port connectivity (firewall, security groups), credentials, and TLS settings if enabled. Check database logs for errors, then retry.".to_string();
-            ApiResponse { code: CODE_FAILURE, message: ApiMessage::Detail { what, why, how }, data: Some(data) }
+            ApiResponse { code: CODE_DB_UNAVAILABLE, message: ApiMessage::Detail { what, why, how }, data: Some(data), error: None }
         }
         (true, false) => {
             let what = "Passive database is unavailable or unhealthy".to_string();
             let why = "The application could not complete a basic health query against the Passive cluster (SELECT release_version FROM system.local) or the request failed.".to_string();
             let how = "Ensure the Passive database is running and reachable from this service. Verify host/port connectivity (firewall, security groups), credentials, and TLS settings if enabled. Check database logs for errors, then retry.".to_string();
-            ApiResponse { code: CODE_FAILURE, message: ApiMessage::Detail { what, why, how }, data: Some(data) }
+            ApiResponse { code: CODE_DB_UNAVAILABLE, message: ApiMessage::Detail { what, why, how }, data: Some(data), error: None }
         }
         (false, false) => {
             let what = "Both Active and Passive databases are unavailable".to_string();
             let why = "The service failed to complete a health query against either cluster. This often points to connectivity issues, incorrect credentials/TLS configuration, or the clusters being down.".to_string();
             let how = "1) Confirm both clusters are up and accepting connections. 2) From this host, verify DNS and that the database ports are reachable. 3) Validate credentials and TLS configuration (CA file, certificate validity, insecure skip verify). 4) Review database and network logs for errors. 5) Retry after addressing the root cause.".to_string();
-            ApiResponse { code: CODE_FAILURE, message: ApiMessage::Detail { what, why, how }, data: Some(data) }
+            ApiResponse { code: CODE_DB_UNAVAILABLE, message: ApiMessage::Detail { what, why, how }, data: Some(data), error: None }
         }
     }
 }
\ No newline at end of file
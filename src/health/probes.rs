@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serde::Serialize;
+
+use crate::config::ReplicationConfig;
+use crate::db::DbClients;
+use crate::health::probe_cluster;
+use crate::replication::ReplicationManager;
+use crate::types::ApiResponse;
+use crate::types::response::CODE_SERVICE_UNAVAILABLE;
+
+#[derive(Debug, Default)]
+pub struct ProbeState {
+    keyspaces_ok: AtomicBool,
+    migrations_ok: AtomicBool,
+    started: AtomicBool,
+    shutting_down: AtomicBool,
+}
+
+impl ProbeState {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn set_keyspaces_ok(&self, ok: bool) { self.keyspaces_ok.store(ok, Ordering::SeqCst); }
+
+    pub fn set_migrations_ok(&self, ok: bool) { self.migrations_ok.store(ok, Ordering::SeqCst); }
+
+    pub fn mark_started(&self) { self.started.store(true, Ordering::SeqCst); }
+
+    pub fn begin_shutdown(&self) { self.shutting_down.store(true, Ordering::SeqCst); }
+
+    pub fn is_shutting_down(&self) -> bool { self.shutting_down.load(Ordering::SeqCst) }
+
+    fn startup_checks(&self) -> Vec<ProbeCheck> {
+        vec![
+            ProbeCheck::from_flag("keyspaces", self.keyspaces_ok.load(Ordering::SeqCst), "application keyspaces have not been ensured"),
+            ProbeCheck::from_flag("migrations", self.migrations_ok.load(Ordering::SeqCst), "schema migrations are not applied on both clusters"),
+            ProbeCheck::from_flag("started", self.started.load(Ordering::SeqCst), "startup has not completed"),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ProbeCheck {
+    pub fn pass(name: &'static str) -> Self { Self { name, ok: true, detail: None } }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>) -> Self { Self { name, ok: false, detail: Some(detail.into()) } }
+
+    fn from_flag(name: &'static str, ok: bool, detail: &str) -> Self {
+        if ok { Self::pass(name) } else { Self::fail(name, detail) }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbeReport {
+    pub ok: bool,
+    pub checks: Vec<ProbeCheck>,
+}
+
+impl ProbeReport {
+    pub fn from_checks(checks: Vec<ProbeCheck>) -> Self {
+        Self { ok: checks.iter().all(|c| c.ok), checks }
+    }
+
+    pub fn into_response(self, probe: &str) -> ApiResponse<ProbeReport> {
+        if self.ok {
+            return ApiResponse::success_with(format!("{} probe passed", probe), self);
+        }
+        let failed: Vec<String> = self.checks.iter()
+            .filter(|c| !c.ok)
+            .map(|c| format!("{}: {}", c.name, c.detail.as_deref().unwrap_or("failed")))
+            .collect();
+        let mut resp = ApiResponse::failure_detail(
+            format!("{} probe failed", probe),
+            failed.join("; "),
+            "Inspect the failing checks listed in data.checks, resolve the underlying condition, and the probe will pass on its next run.",
+        ).with_code(CODE_SERVICE_UNAVAILABLE);
+        resp.data = Some(self);
+        resp
+    }
+}
+
+pub fn liveness() -> ProbeReport {
+    ProbeReport::from_checks(vec![ProbeCheck::pass("process")])
+}
+
+pub fn startup(probes: &ProbeState) -> ProbeReport {
+    ProbeReport::from_checks(probes.startup_checks())
+}
+
+pub fn outbox_checks(repl: &ReplicationManager, cfg: &ReplicationConfig) -> Vec<ProbeCheck> {
+    let writable = match repl.check_outbox_writable() {
+        Ok(()) => ProbeCheck::pass("outbox_writable"),
+        Err(e) => ProbeCheck::fail("outbox_writable", e.to_message()),
+    };
+    let drift = match repl.drift_status(cfg.drift_max_records, cfg.drift_max_bytes) {
+        Ok(Some(d)) if d.healthy => ProbeCheck::pass("outbox_drift"),
+        Ok(Some(d)) => ProbeCheck::fail("outbox_drift", format!(
+            "{} records / {} bytes pending exceed the limit of {} records / {} bytes",
+            d.pending_records, d.pending_bytes, cfg.drift_max_records, cfg.drift_max_bytes
+        )),
+        Ok(None) => ProbeCheck::fail("outbox_drift", "outbox is not open"),
+        Err(e) => ProbeCheck::fail("outbox_drift", e.to_message()),
+    };
+    vec![writable, drift]
+}
+
+pub async fn readiness(
+    probes: &ProbeState,
+    clients: &DbClients,
+    replication: &ReplicationManager,
+    cfg: &ReplicationConfig,
+) -> ProbeReport {
+    let mut checks = vec![ProbeCheck::from_flag("accepting_traffic", !probes.is_shutting_down(), "service is shutting down")];
+    checks.extend(probes.startup_checks());
+
+    let primary = clients.primary();
+    let health = probe_cluster(clients, primary).await;
+    checks.push(if health.state.is_reachable() {
+        ProbeCheck::pass("primary_reachable")
+    } else {
+        ProbeCheck::fail("primary_reachable", format!("{} cluster: {}", primary.label(), health.problems.join("; ")))
+    });
+
+    checks.extend(outbox_checks(replication, cfg));
+
+    ProbeReport::from_checks(checks)
+}
@@ -0,0 +1,470 @@
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use scylla::statement::Consistency;
+
+pub use crate::db::schema::{load_table_schema, CqlType, SchemaColumn, TableSchema};
+use crate::db::{quote_ident, DbClients};
+use crate::errors::{AppError, AppResult};
+use crate::replication::{OutboxRecord, OutboxTarget, ReplicationManager};
+
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 512;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImportFormat { Csv, Ndjson }
+
+impl ImportFormat {
+    pub fn parse(s: &str) -> AppResult<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "csv" => Ok(ImportFormat::Csv),
+            "ndjson" | "jsonl" | "json" => Ok(ImportFormat::Ndjson),
+            other => Err(AppError::validation(format!("unknown import format '{}', expected csv or ndjson", other))),
+        }
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let ext = path.as_ref().extension()?.to_str()?;
+        Self::parse(ext).ok()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct ColumnMapping {
+    pub source: String,
+    pub column: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct ImportMapping {
+    pub keyspace: Option<String>,
+    pub table: String,
+    pub columns: Vec<ColumnMapping>,
+}
+
+impl ImportMapping {
+    pub fn identity(keyspace: Option<String>, table: impl Into<String>) -> Self {
+        Self { keyspace, table: table.into(), columns: Vec::new() }
+    }
+
+    pub fn from_toml_str(s: &str) -> AppResult<Self> {
+        toml::from_str::<ImportMapping>(s).map_err(|e| AppError::toml("invalid import mapping", e))
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> AppResult<Self> {
+        let p = path.as_ref();
+        let s = std::fs::read_to_string(p)
+            .map_err(|e| AppError::io(format!("failed to read import mapping {}", p.display()), e))?;
+        Self::from_toml_str(&s)
+    }
+
+    pub fn parse_pairs(keyspace: Option<String>, table: impl Into<String>, pairs: &str) -> AppResult<Self> {
+        let mut columns = Vec::new();
+        for pair in pairs.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let Some((source, column)) = pair.split_once(':') else {
+                return Err(AppError::validation(format!("invalid mapping entry '{}', expected source:column", pair)));
+            };
+            columns.push(ColumnMapping { source: source.trim().to_string(), column: column.trim().to_string() });
+        }
+        Ok(Self { keyspace, table: table.into(), columns })
+    }
+
+    pub fn is_identity(&self) -> bool { self.columns.is_empty() }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub format: ImportFormat,
+    pub target: OutboxTarget,
+    pub consistency: Option<Consistency>,
+    pub concurrency: usize,
+    pub delimiter: u8,
+    pub max_rejections: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            format: ImportFormat::Csv,
+            target: OutboxTarget::Both,
+            consistency: None,
+            concurrency: 16,
+            delimiter: b',',
+            max_rejections: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PlannedColumn {
+    source: String,
+    column: String,
+    ty: CqlType,
+    key: bool,
+}
+
+#[derive(Debug, Clone)]
+struct ImportPlan {
+    keyspace: String,
+    table: String,
+    columns: Vec<PlannedColumn>,
+}
+
+impl ImportPlan {
+    fn build(mapping: &ImportMapping, schema: &TableSchema) -> AppResult<Self> {
+        let pairs: Vec<ColumnMapping> = if mapping.is_identity() {
+            schema.columns.iter().map(|c| ColumnMapping { source: c.name.clone(), column: c.name.clone() }).collect()
+        } else {
+            mapping.columns.clone()
+        };
+
+        let mut problems = Vec::new();
+        let mut seen = HashSet::new();
+        let mut columns = Vec::with_capacity(pairs.len());
+        for m in pairs {
+            if !seen.insert(m.column.clone()) {
+                problems.push(format!("column '{}' is mapped more than once", m.column));
+                continue;
+            }
+            match schema.column(&m.column) {
+                Some(sc) if sc.ty.is_supported() => {
+                    columns.push(PlannedColumn { source: m.source, column: m.column, ty: sc.ty.clone(), key: sc.is_primary_key() });
+                }
+                Some(sc) if mapping.is_identity() && !sc.is_primary_key() => {}
+                Some(sc) => problems.push(format!("column '{}' has unsupported type {:?}", sc.name, sc.ty)),
+                None => problems.push(format!("column '{}' does not exist in {}.{}", m.column, schema.keyspace, schema.table)),
+            }
+        }
+
+        let plan = ImportPlan { keyspace: schema.keyspace.clone(), table: schema.table.clone(), columns };
+        problems.extend(plan.missing_keys(schema));
+        if problems.is_empty() { Ok(plan) } else { Err(AppError::config(format!("invalid import mapping: {}", problems.join("; ")))) }
+    }
+
+    fn missing_keys(&self, schema: &TableSchema) -> Vec<String> {
+        schema
+            .columns
+            .iter()
+            .filter(|c| c.is_primary_key() && !self.columns.iter().any(|p| p.column == c.name))
+            .map(|c| format!("primary key column '{}' is not mapped", c.name))
+            .collect()
+    }
+
+    fn restrict_to_headers(&mut self, headers: &csv::StringRecord, identity: bool, schema: &TableSchema) -> AppResult<Vec<usize>> {
+        let find = |src: &str| headers.iter().position(|h| h.trim() == src);
+        if identity {
+            self.columns.retain(|c| find(&c.source).is_some());
+            let missing = self.missing_keys(schema);
+            if !missing.is_empty() {
+                return Err(AppError::config(format!("invalid import input: {}", missing.join("; "))));
+            }
+        }
+        let mut indices = Vec::with_capacity(self.columns.len());
+        let mut missing = Vec::new();
+        for c in &self.columns {
+            match find(&c.source) {
+                Some(i) => indices.push(i),
+                None => missing.push(c.source.clone()),
+            }
+        }
+        if missing.is_empty() {
+            Ok(indices)
+        } else {
+            Err(AppError::config(format!("input is missing mapped source columns: {}", missing.join(", "))))
+        }
+    }
+
+    fn render_insert(&self, values: &[Option<String>]) -> Result<(String, String), String> {
+        let mut names = Vec::with_capacity(self.columns.len());
+        let mut literals = Vec::with_capacity(self.columns.len());
+        let mut key_parts = Vec::new();
+        for (col, val) in self.columns.iter().zip(values) {
+            let val = match val {
+                Some(v) if v.is_empty() && col.ty != CqlType::Text && col.ty != CqlType::Ascii => None,
+                other => other.as_deref(),
+            };
+            let Some(raw) = val else {
+                if col.key { return Err(format!("missing value for primary key column '{}'", col.column)); }
+                continue;
+            };
+            let lit = col.ty.render_literal(raw).map_err(|e| format!("column '{}': {}", col.column, e))?;
+            if col.key { key_parts.push(lit.clone()); }
+            names.push(quote_ident(&col.column));
+            literals.push(lit);
+        }
+        let cql = format!(
+            "INSERT INTO {}.{} ({}) VALUES ({})",
+            quote_ident(&self.keyspace),
+            quote_ident(&self.table),
+            names.join(", "),
+            literals.join(", ")
+        );
+        let mut key = format!("import:{}.{}:{}", self.keyspace, self.table, key_parts.join("|"));
+        if key.len() > MAX_IDEMPOTENCY_KEY_LEN {
+            let mut cut = MAX_IDEMPOTENCY_KEY_LEN;
+            while !key.is_char_boundary(cut) { cut -= 1; }
+            key.truncate(cut);
+        }
+        Ok((key, cql))
+    }
+}
+
+enum RecordReader<R: Read> {
+    Csv { reader: csv::Reader<R>, indices: Vec<usize>, record: csv::StringRecord },
+    Ndjson { lines: std::io::Lines<BufReader<R>>, line: u64, sources: Vec<String> },
+}
+
+struct SourceRecord {
+    line: u64,
+    raw: String,
+    values: Result<Vec<Option<String>>, String>,
+}
+
+impl<R: Read> RecordReader<R> {
+    fn next_record(&mut self) -> AppResult<Option<SourceRecord>> {
+        match self {
+            RecordReader::Csv { reader, indices, record } => match reader.read_record(record) {
+                Ok(false) => Ok(None),
+                Ok(true) => {
+                    let line = record.position().map(|p| p.line()).unwrap_or(0);
+                    let raw = record.iter().collect::<Vec<_>>().join(",");
+                    let values = indices.iter().map(|&i| record.get(i).map(str::to_string)).collect();
+                    Ok(Some(SourceRecord { line, raw, values: Ok(values) }))
+                }
+                Err(e) if e.is_io_error() => Err(AppError::io("import read error", e.into())),
+                Err(e) => {
+                    let line = e.position().map(|p| p.line()).unwrap_or(0);
+                    Ok(Some(SourceRecord { line, raw: String::new(), values: Err(format!("malformed csv record: {}", e)) }))
+                }
+            },
+            RecordReader::Ndjson { lines, line, sources } => loop {
+                let Some(next) = lines.next() else { return Ok(None) };
+                *line += 1;
+                let raw = next.map_err(|e| AppError::io(format!("import read error at line {}", line), e))?;
+                if raw.trim().is_empty() { continue; }
+                let values = parse_ndjson_line(&raw, sources);
+                return Ok(Some(SourceRecord { line: *line, raw, values }));
+            },
+        }
+    }
+}
+
+fn parse_ndjson_line(raw: &str, sources: &[String]) -> Result<Vec<Option<String>>, String> {
+    let value: serde_json::Value = serde_json::from_str(raw).map_err(|e| format!("malformed json: {}", e))?;
+    let serde_json::Value::Object(obj) = value else { return Err("expected a json object per line".to_string()) };
+    Ok(sources
+        .iter()
+        .map(|src| match obj.get(src) {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(s)) => Some(s.clone()),
+            Some(other) => Some(other.to_string()),
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRejection {
+    pub line: u64,
+    pub reason: String,
+    pub raw: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub keyspace: String,
+    pub table: String,
+    pub rows_read: u64,
+    pub rows_written: u64,
+    pub rows_queued: u64,
+    pub rows_rejected: u64,
+    pub rejections: Vec<ImportRejection>,
+    pub rejections_truncated: bool,
+}
+
+impl ImportReport {
+    fn reject(&mut self, max: usize, line: u64, reason: impl Into<String>, raw: impl Into<String>) {
+        self.rows_rejected += 1;
+        if self.rejections.len() < max {
+            self.rejections.push(ImportRejection { line, reason: reason.into(), raw: raw.into() });
+        } else {
+            self.rejections_truncated = true;
+        }
+    }
+
+    pub fn write_rejections<W: std::io::Write>(&self, mut out: W) -> AppResult<()> {
+        for r in &self.rejections {
+            let line = serde_json::to_string(r).map_err(|e| AppError::json("encode rejection", e))?;
+            writeln!(out, "{}", line).map_err(|e| AppError::io("write rejection", e))?;
+        }
+        Ok(())
+    }
+}
+
+struct PendingWrite {
+    line: u64,
+    raw: String,
+    key: String,
+    cql: String,
+}
+
+pub struct StreamReader {
+    rx: mpsc::Receiver<std::io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl StreamReader {
+    pub fn channel(capacity: usize) -> (mpsc::Sender<std::io::Result<Vec<u8>>>, Self) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        (tx, Self { rx, chunk: Vec::new(), pos: 0 })
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(Ok(chunk)) => {
+                    self.chunk = chunk;
+                    self.pos = 0;
+                }
+                Some(Err(e)) => return Err(e),
+                None => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.chunk.len() - self.pos);
+        out[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+enum ParsedRow {
+    Write(PendingWrite),
+    Reject { line: u64, reason: String, raw: String },
+}
+
+pub async fn run_import<R: Read + Send + 'static>(
+    reader: R,
+    mapping: &ImportMapping,
+    default_keyspace: &str,
+    opts: &ImportOptions,
+    repl: &ReplicationManager,
+    clients: &DbClients,
+) -> AppResult<ImportReport> {
+    let keyspace = mapping.keyspace.clone().unwrap_or_else(|| default_keyspace.to_string());
+    let schema = load_table_schema(clients, &keyspace, &mapping.table).await?;
+    run_import_with_schema(reader, mapping, &schema, opts, repl, clients).await
+}
+
+pub async fn run_import_with_schema<R: Read + Send + 'static>(
+    reader: R,
+    mapping: &ImportMapping,
+    schema: &TableSchema,
+    opts: &ImportOptions,
+    repl: &ReplicationManager,
+    clients: &DbClients,
+) -> AppResult<ImportReport> {
+    let plan = ImportPlan::build(mapping, schema)?;
+    let mut report = ImportReport { keyspace: plan.keyspace.clone(), table: plan.table.clone(), ..ImportReport::default() };
+    let concurrency = opts.concurrency.max(1);
+
+    let (tx, mut rx) = mpsc::channel(concurrency * 2);
+    let (mapping, schema, parse_opts) = (mapping.clone(), schema.clone(), opts.clone());
+    let parser = tokio::task::spawn_blocking(move || {
+        if let Err(e) = parse_rows(reader, plan, &mapping, &schema, &parse_opts, &tx) {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+
+    let mut window: Vec<PendingWrite> = Vec::with_capacity(concurrency);
+    while let Some(row) = rx.recv().await {
+        report.rows_read += 1;
+        match row? {
+            ParsedRow::Write(w) => window.push(w),
+            ParsedRow::Reject { line, reason, raw } => report.reject(opts.max_rejections, line, reason, raw),
+        }
+        if window.len() >= concurrency {
+            flush_window(&mut window, opts, repl, clients, &mut report).await;
+        }
+    }
+    flush_window(&mut window, opts, repl, clients, &mut report).await;
+    parser.await.map_err(|e| AppError::other(format!("import reader stopped: {}", e)))?;
+
+    Ok(report)
+}
+
+fn parse_rows<R: Read>(
+    reader: R,
+    mut plan: ImportPlan,
+    mapping: &ImportMapping,
+    schema: &TableSchema,
+    opts: &ImportOptions,
+    tx: &mpsc::Sender<AppResult<ParsedRow>>,
+) -> AppResult<()> {
+    let mut source = match opts.format {
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .delimiter(opts.delimiter)
+                .has_headers(true)
+                .flexible(true)
+                .from_reader(reader);
+            let headers = reader
+                .headers()
+                .map_err(|e| if e.is_io_error() { AppError::io("failed to read csv header", e.into()) } else { AppError::validation(format!("failed to read csv header: {}", e)) })?
+                .clone();
+            let indices = plan.restrict_to_headers(&headers, mapping.is_identity(), schema)?;
+            RecordReader::Csv { reader, indices, record: csv::StringRecord::new() }
+        }
+        ImportFormat::Ndjson => {
+            let sources = plan.columns.iter().map(|c| c.source.clone()).collect();
+            RecordReader::Ndjson { lines: BufReader::new(reader).lines(), line: 0, sources }
+        }
+    };
+
+    while let Some(rec) = source.next_record()? {
+        let row = match rec.values.and_then(|values| plan.render_insert(&values)) {
+            Ok((key, cql)) => ParsedRow::Write(PendingWrite { line: rec.line, raw: rec.raw, key, cql }),
+            Err(reason) => ParsedRow::Reject { line: rec.line, reason, raw: rec.raw },
+        };
+        if tx.blocking_send(Ok(row)).is_err() { break; }
+    }
+    Ok(())
+}
+
+async fn flush_window(
+    window: &mut Vec<PendingWrite>,
+    opts: &ImportOptions,
+    repl: &ReplicationManager,
+    clients: &DbClients,
+    report: &mut ImportReport,
+) {
+    if window.is_empty() { return; }
+    let outcomes = join_all(window.iter().map(|w| repl.apply_simple(&w.cql, opts.target, opts.consistency, clients))).await;
+    for (w, outcome) in window.drain(..).zip(outcomes) {
+        if let Some((_, e)) = outcome.rejected.first() {
+            report.reject(opts.max_rejections, w.line, format!("write rejected: {}", e.to_message()), w.raw);
+            continue;
+        }
+        if outcome.failed.is_empty() {
+            report.rows_written += 1;
+            continue;
+        }
+        let mut enqueue_err = None;
+        for target in outcome.failed {
+            if let Err(e) = repl.enqueue(OutboxRecord::new_simple(w.key.clone(), w.cql.clone(), target)).await {
+                enqueue_err = Some(e);
+                break;
+            }
+        }
+        match enqueue_err {
+            None => report.rows_queued += 1,
+            Some(e) => report.reject(opts.max_rejections, w.line, format!("write failed and outbox enqueue failed: {}", e.to_message()), w.raw),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use core::future::Future;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::errors::{AppError, AppResult};
+use crate::utils::now_millis;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: String,
+    pub status: JobStatus,
+    pub created_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobRegistry {
+    inner: Arc<Mutex<HashMap<String, JobInfo>>>,
+    artifact_dir: PathBuf,
+    max_finished: usize,
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self { inner: Arc::new(Mutex::new(HashMap::new())), artifact_dir: PathBuf::from("data/jobs"), max_finished: 500 }
+    }
+}
+
+impl JobRegistry {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn with_artifact_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.artifact_dir = dir.as_ref().to_path_buf();
+        self
+    }
+
+    pub fn with_max_finished(mut self, max: usize) -> Self {
+        self.max_finished = max.max(1);
+        self
+    }
+
+    pub fn create(&self, kind: impl Into<String>) -> JobInfo {
+        let info = JobInfo {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind: kind.into(),
+            status: JobStatus::Pending,
+            created_ms: now_millis() as u64,
+            started_ms: None,
+            finished_ms: None,
+            error: None,
+            result: None,
+            artifact: None,
+        };
+        self.lock().insert(info.id.clone(), info.clone());
+        info
+    }
+
+    pub fn mark_running(&self, id: &str) {
+        if let Some(job) = self.lock().get_mut(id) {
+            job.status = JobStatus::Running;
+            job.started_ms = Some(now_millis() as u64);
+        }
+    }
+
+    pub fn complete(&self, id: &str, outcome: AppResult<serde_json::Value>) {
+        if let Some(job) = self.lock().get_mut(id) {
+            job.finished_ms = Some(now_millis() as u64);
+            match outcome {
+                Ok(v) => {
+                    job.status = JobStatus::Succeeded;
+                    job.result = Some(v);
+                }
+                Err(e) => {
+                    job.status = JobStatus::Failed;
+                    job.error = Some(e.to_message());
+                }
+            }
+        }
+        self.evict_finished();
+    }
+
+    fn evict_finished(&self) {
+        let evicted: Vec<String> = {
+            let mut jobs = self.lock();
+            let mut finished: Vec<(u64, String)> = jobs
+                .values()
+                .filter_map(|j| j.finished_ms.map(|t| (t, j.id.clone())))
+                .collect();
+            if finished.len() <= self.max_finished { return; }
+            finished.sort();
+            let excess = finished.len() - self.max_finished;
+            finished
+                .into_iter()
+                .take(excess)
+                .filter_map(|(_, id)| jobs.remove(&id).map(|_| id))
+                .collect()
+        };
+        for id in evicted {
+            let dir = self.artifact_dir.join(&id);
+            if dir.exists() {
+                let _ = std::fs::remove_dir_all(&dir);
+            }
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobInfo> { self.lock().get(id).cloned() }
+
+    pub fn artifact_path(&self, id: &str) -> Option<PathBuf> {
+        self.lock().get(id).and_then(|j| j.artifact.clone()).map(PathBuf::from)
+    }
+
+    pub fn list(&self) -> Vec<JobInfo> {
+        let mut jobs: Vec<JobInfo> = self.lock().values().cloned().collect();
+        jobs.sort_by_key(|j| j.created_ms);
+        jobs
+    }
+
+    pub fn spawn<F, T>(&self, kind: impl Into<String>, fut: F) -> JobInfo
+    where
+        F: Future<Output = AppResult<T>> + 'static,
+        T: Serialize,
+    {
+        let info = self.create(kind);
+        let registry = self.clone();
+        let id = info.id.clone();
+        ntex::rt::spawn(async move { registry.execute(&id, None, fut).await });
+        info
+    }
+
+    pub fn spawn_with_artifact<F, T>(&self, kind: impl Into<String>, file_name: &'static str, fut: F) -> JobInfo
+    where
+        F: Future<Output = AppResult<T>> + 'static,
+        T: Serialize,
+    {
+        let info = self.create(kind);
+        let registry = self.clone();
+        let id = info.id.clone();
+        ntex::rt::spawn(async move { registry.execute(&id, Some(file_name), fut).await });
+        info
+    }
+
+    pub async fn run<F, T>(&self, kind: impl Into<String>, file_name: Option<&str>, fut: F) -> JobInfo
+    where
+        F: Future<Output = AppResult<T>>,
+        T: Serialize,
+    {
+        let info = self.create(kind);
+        self.execute(&info.id, file_name, fut).await;
+        self.get(&info.id).unwrap_or(info)
+    }
+
+    async fn execute<F, T>(&self, id: &str, file_name: Option<&str>, fut: F)
+    where
+        F: Future<Output = AppResult<T>>,
+        T: Serialize,
+    {
+        self.mark_running(id);
+        let outcome = match (fut.await, file_name) {
+            (Ok(v), Some(name)) => self.store_artifact(id, name, &v),
+            (Ok(v), None) => serde_json::to_value(v).map_err(|e| AppError::json("encode job result", e)),
+            (Err(e), _) => Err(e),
+        };
+        self.complete(id, outcome);
+    }
+
+    fn store_artifact<T: Serialize>(&self, id: &str, file_name: &str, value: &T) -> AppResult<serde_json::Value> {
+        let dir = self.artifact_dir.join(id);
+        std::fs::create_dir_all(&dir).map_err(|e| AppError::io(format!("create artifact dir {}", dir.display()), e))?;
+        let path = dir.join(file_name);
+        let body = serde_json::to_vec_pretty(value).map_err(|e| AppError::json("encode artifact", e))?;
+        std::fs::write(&path, body).map_err(|e| AppError::io(format!("write artifact {}", path.display()), e))?;
+        let path_str = path.display().to_string();
+        if let Some(job) = self.lock().get_mut(id) {
+            job.artifact = Some(path_str.clone());
+        }
+        Ok(serde_json::json!({ "artifact": path_str }))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, JobInfo>> {
+        self.inner.lock().unwrap_or_else(|p| p.into_inner())
+    }
+}
@@ -1,9 +1,16 @@
+pub mod cli;
 pub mod config;
 pub mod db;
+pub mod export;
 pub mod replication;
+pub mod repair;
 pub mod health;
+pub mod import;
+pub mod jobs;
+pub mod migrations;
 pub mod errors;
 pub mod types;
 pub mod middleware;
 pub mod utils;
+pub mod verify;
 pub mod web;
\ No newline at end of file
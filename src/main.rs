@@ -2,15 +2,7 @@ use log::{info, warn};
 
 use ntex::rt::System;
 
-mod config;
-mod db;
-mod replication;
-mod health;
-mod errors;
-mod types;
-mod middleware;
-mod utils;
-mod web;
+use nayud_batch::{cli, config, db, health, jobs, migrations, replication, types, web};
 
 #[cfg(unix)]
 use tokio::signal::unix::{signal as unix_signal, SignalKind};
@@ -19,29 +11,84 @@ use std::time::Duration;
 
 #[ntex::main]
 async fn main() -> std::io::Result<()> {
-    let _ = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).try_init();
+    let log_level_pinned = std::env::var_os("RUST_LOG").is_some();
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::default());
+    if !log_level_pinned {
+        logger.filter_level(log::LevelFilter::Trace);
+    }
+    if logger.try_init().is_ok() && !log_level_pinned {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+
+    let invocation = match cli::parse_invocation(std::env::args().skip(1)) {
+        Ok(i) => i,
+        Err(e) => {
+            warn!("{}", e.to_message());
+            return Err(std::io::Error::other(e.to_message()));
+        }
+    };
 
     info!("nayud-batch: initializing configuration");
-    let cfg = config::AppConfig::from_file_or_env();
+    let cfg = config::AppConfig::load(&invocation.overrides);
+    let command = invocation.command;
+    if let cli::Command::PrintConfig | cli::Command::CheckConfig = command {
+        let (text, outcome) = cli::run_config_command(&cfg, &command, invocation.output);
+        print!("{}", text);
+        return outcome.map_err(|e| std::io::Error::other(e.to_message()));
+    }
+    match cfg.validate().into_result(cfg.strict) {
+        Ok(issues) => {
+            for issue in issues {
+                warn!("Config warning: {}", issue);
+            }
+        }
+        Err(e) => {
+            warn!("{}", e.to_message());
+            return Err(std::io::Error::other(e.to_message()));
+        }
+    }
+    if !log_level_pinned && let Ok(level) = cfg.server.log_level.parse::<log::LevelFilter>() {
+        log::set_max_level(level);
+    }
+
+    if !matches!(command, cli::Command::Serve) {
+        return match cli::run_command(&cfg, &command).await {
+            Ok(out) => {
+                if let Some(value) = out {
+                    print!("{}", cli::render(&value, invocation.output));
+                }
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Command failed: {}", e.to_message());
+                Err(std::io::Error::other(e.to_message()))
+            }
+        };
+    }
 
-    let masked_user = utils::mask_secret(&cfg.active.username);
-    let masked_pass = utils::mask_secret(&cfg.active.password);
+    let (masked_user, masked_pass) = cfg.active.masked_credentials();
 
     info!(
-        "Active DB: {}:{} keyspace={} dc={} rack={} user={} pass={}",
-        cfg.active.host, cfg.active.port, cfg.active.keyspace, cfg.active.datacenter, cfg.active.rack,
+        "Active DB: {} keyspace={} dc={} rack={} user={} pass={}",
+        cfg.active.contact_points().join(","), cfg.active.keyspace, cfg.active.datacenter, cfg.active.rack,
         masked_user, masked_pass
     );
 
-    let masked_user_p = utils::mask_secret(&cfg.passive.username);
-    let masked_pass_p = utils::mask_secret(&cfg.passive.password);
+    let (masked_user_p, masked_pass_p) = cfg.passive.masked_credentials();
 
     info!(
-        "Passive DB: {}:{} keyspace={} dc={} rack={} user={} pass={}",
-        cfg.passive.host, cfg.passive.port, cfg.passive.keyspace, cfg.passive.datacenter, cfg.passive.rack,
+        "Passive DB: {} keyspace={} dc={} rack={} user={} pass={}",
+        cfg.passive.contact_points().join(","), cfg.passive.keyspace, cfg.passive.datacenter, cfg.passive.rack,
         masked_user_p, masked_pass_p
     );
 
+    for (label, ep) in [("Active", &cfg.active), ("Passive", &cfg.passive)] {
+        match db::LoadBalancingSettings::from_config(ep, &cfg.driver) {
+            Ok(lb) => info!("{} load balancing: {}", label, lb.describe()),
+            Err(e) => warn!("{} load balancing: {}", label, e.to_message()),
+        }
+    }
+
     let clients = match db::init_clients(&cfg).await {
         Ok(c) => c,
         Err(e) => {
@@ -51,10 +98,21 @@ async fn main() -> std::io::Result<()> {
                 _ => e.to_message(),
             };
             warn!("Database init error: {}", msg);
-            return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+            return Err(std::io::Error::other(msg));
         }
     };
 
+    match replication::load_primary(&cfg.replication.outbox_dir) {
+        Ok(Some(primary)) => {
+            info!("Resuming with {} as the primary cluster", primary.label());
+            clients.set_primary(primary);
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Ignoring persisted failover state: {}", e.to_message()),
+    }
+
+    let probes = Arc::new(health::probes::ProbeState::new());
+
     if let Err(e) = db::ensure_keyspaces(&cfg, &clients).await {
         let resp = types::ApiResponse::<()>::from_error(&e);
         let msg = match resp.message {
@@ -62,48 +120,184 @@ async fn main() -> std::io::Result<()> {
             _ => e.to_message(),
         };
         warn!("Keyspace ensure error: {}", msg);
-        return Err(std::io::Error::new(std::io::ErrorKind::Other, msg));
+        return Err(std::io::Error::other(msg));
     }
+    probes.set_keyspaces_ok(true);
+
+    let migration_status = if cfg.migrations.apply_on_startup {
+        migrations::apply(&cfg, &clients, false).await.map(|run| run.status)
+    } else {
+        migrations::status(&cfg, &clients).await
+    };
+    match migration_status.and_then(|st| st.ensure_in_agreement().map(|_| st)) {
+        Ok(st) => {
+            info!(
+                "Schema migrations: Active={:?} Passive={:?}",
+                st.active.current_version, st.passive.current_version
+            );
+            probes.set_migrations_ok(true);
+        }
+        Err(e) => {
+            warn!("Refusing to serve: {}", e.to_message());
+            return Err(std::io::Error::other(e.to_message()));
+        }
+    }
+
+    for cluster in [replication::Cluster::Active, replication::Cluster::Passive] {
+        if !clients.is_connected(cluster) { continue; }
+        for (cql, e) in clients.warm_prepared(cluster).await {
+            warn!("{} prepared statement warm-up failed for '{}': {}", cluster.label(), cql, e.to_message());
+        }
+    }
+
+    let repl = match replication::ReplicationManager::from_config(&cfg) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!("Outbox unavailable at {}: {}", cfg.replication.outbox_dir, e.to_message());
+            replication::ReplicationManager::new()
+        }
+    };
 
     let clients_arc = Arc::new(clients);
-    let cfg_arc = Arc::new(cfg.clone());
+    let shared_cfg = Arc::new(config::SharedConfig::new(cfg.clone()));
+    let reloader = Arc::new(
+        config::Reloader::new(invocation.overrides.clone(), shared_cfg.clone(), clients_arc.clone())
+            .with_log_level_pinned(log_level_pinned),
+    );
+
+    ntex::rt::spawn(db::reconnect::run_reconnect_loop(clients_arc.clone(), db::reconnect::Backoff::from_config(&cfg.driver)));
+
+    {
+        let pin_clients = clients_arc.clone();
+        ntex::rt::spawn(async move {
+            let mut ticker = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                ticker.tick().await;
+                pin_clients.pin_peer_certificates().await;
+            }
+        });
+    }
 
     {
         let bg_clients = clients_arc.clone();
-        let bg_cfg = cfg_arc.clone();
+        let bg_cfg = shared_cfg.clone();
+        let bg_probes = probes.clone();
+        let mut migrated = [replication::Cluster::Active, replication::Cluster::Passive].map(|c| bg_clients.is_connected(c));
         ntex::rt::spawn(async move {
             let mut ticker = tokio::time::interval(Duration::from_secs(60));
             loop {
                 ticker.tick().await;
-                if let Err(e) = db::ensure_keyspaces(&*bg_cfg, &*bg_clients).await {
+                let cfg = bg_cfg.current();
+                bg_clients.check_schema_versions().await;
+                let res = db::ensure_keyspaces(&cfg, &bg_clients).await;
+                bg_probes.set_keyspaces_ok(res.is_ok());
+                if let Err(e) = res {
                     let resp = types::ApiResponse::<()>::from_error(&e);
                     let msg = match resp.message {
                         types::response::ApiMessage::Detail { what, why, how } => format!("{} | {} | {}", what, why, how),
                         _ => e.to_message(),
                     };
                     warn!("Periodic keyspace ensure error: {}", msg);
+                    continue;
+                }
+                if !cfg.migrations.apply_on_startup { continue; }
+                for (cluster, done) in [replication::Cluster::Active, replication::Cluster::Passive].into_iter().zip(migrated.iter_mut()) {
+                    if *done || !bg_clients.is_connected(cluster) { continue; }
+                    match migrations::apply_to(&cfg, &bg_clients, cluster, false).await {
+                        Ok(run) => {
+                            info!("{} cluster recovered; applied {} pending migration(s)", cluster.label(), run.migrations.len());
+                            *done = true;
+                        }
+                        Err(e) => {
+                            warn!("{} migrations after reconnect failed: {}", cluster.label(), e.to_message());
+                            bg_probes.set_migrations_ok(false);
+                        }
+                    }
+                }
+                if migrated.iter().all(|d| *d) {
+                    let agreed = migrations::status(&cfg, &bg_clients).await.and_then(|st| st.ensure_in_agreement());
+                    bg_probes.set_migrations_ok(agreed.is_ok());
+                }
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    {
+        let reloader = reloader.clone();
+        ntex::rt::spawn(async move {
+            if let Ok(mut hup) = unix_signal(SignalKind::hangup()) {
+                while hup.recv().await.is_some() {
+                    info!("SIGHUP received, reloading configuration");
+                    if let Err(e) = reloader.reload("sighup").await {
+                        warn!("{}", e.to_message());
+                    }
                 }
             }
         });
     }
 
-    ntex::rt::spawn(async move {
-        if tokio::signal::ctrl_c().await.is_ok() {
-            info!("Shutdown signal received (Ctrl+C). Stopping system gracefully...");
-            System::current().stop();
+    if cfg.server.config_watch_ms > 0 {
+        let files = config::watched_files(&cfg);
+        if files.is_empty() {
+            warn!("server.config_watch_ms is set but no config or secret file was loaded; nothing to watch");
+        } else {
+            info!("Watching {} for changes every {} ms", files.join(", "), cfg.server.config_watch_ms);
+            ntex::rt::spawn(reloader.clone().watch_file(Duration::from_millis(cfg.server.config_watch_ms)));
         }
-    });
+    }
+
+    probes.mark_started();
+
+    {
+        let probes = probes.clone();
+        let shared_cfg = shared_cfg.clone();
+        ntex::rt::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                let grace = Duration::from_millis(shared_cfg.current().server.shutdown_grace_ms);
+                info!("Shutdown signal received (Ctrl+C). Reporting unready for {:?}, then stopping...", grace);
+                probes.begin_shutdown();
+                tokio::time::sleep(grace).await;
+                System::current().stop();
+            }
+        });
+    }
 
     #[cfg(unix)]
-    ntex::rt::spawn(async move {
-        if let Ok(mut term) = unix_signal(SignalKind::terminate()) {
-            term.recv().await;
-            info!("Shutdown signal received (SIGTERM). Stopping system gracefully...");
-            System::current().stop();
-        }
-    });
+    {
+        let probes = probes.clone();
+        let shared_cfg = shared_cfg.clone();
+        ntex::rt::spawn(async move {
+            if let Ok(mut term) = unix_signal(SignalKind::terminate()) {
+                term.recv().await;
+                let grace = Duration::from_millis(shared_cfg.current().server.shutdown_grace_ms);
+                info!("Shutdown signal received (SIGTERM). Reporting unready for {:?}, then stopping...", grace);
+                probes.begin_shutdown();
+                tokio::time::sleep(grace).await;
+                System::current().stop();
+            }
+        });
+    }
 
     let bind_addr = cfg.server.bind_addr.clone();
     info!("Starting HTTP server on {bind_addr}");
-    web::start_server(clients_arc, &bind_addr).await
+    let state = web::AppState {
+        db_clients: clients_arc,
+        replication: Arc::new(repl),
+        config: shared_cfg,
+        jobs: jobs::JobRegistry::new().with_artifact_dir(&cfg.jobs.artifact_dir).with_max_finished(cfg.jobs.max_finished),
+        probes,
+        reloader,
+    };
+
+    {
+        let clients = state.db_clients.clone();
+        let mut worker = replication::SyncWorker::new()
+            .with_replication((*state.replication).clone())
+            .with_failover(replication::FailoverManager::new_with_config(&cfg))
+            .with_shared_config(state.config.clone());
+        ntex::rt::spawn(async move { worker.run_loop(&clients).await });
+    }
+
+    web::start_server(state, &bind_addr).await
 }
\ No newline at end of file
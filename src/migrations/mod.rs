@@ -0,0 +1,347 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use scylla::client::session::Session;
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+
+use crate::config::AppConfig;
+use crate::db::values::hex;
+use crate::db::{quote_ident, DbClients};
+use crate::errors::{AppError, AppResult};
+use crate::replication::Cluster;
+use crate::utils::now_millis;
+
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub body: String,
+}
+
+impl Migration {
+    pub fn new(version: u32, name: impl Into<String>, body: impl Into<String>) -> Self {
+        let body = body.into();
+        Self { version, name: name.into(), checksum: checksum(&body), body }
+    }
+
+    pub fn statements(&self, keyspace: &str) -> Vec<String> {
+        split_statements(&self.body.replace("{{KEYSPACE}}", &quote_ident(keyspace)))
+    }
+}
+
+pub fn checksum(body: &str) -> String { hex(&openssl::sha::sha256(body.as_bytes())) }
+
+pub fn parse_file_name(file_name: &str) -> Option<(u32, String)> {
+    let stem = file_name.strip_suffix(".cql")?;
+    let (num, name) = stem.split_once('_').unwrap_or((stem, ""));
+    if num.is_empty() || !num.bytes().all(|b| b.is_ascii_digit()) { return None; }
+    Some((num.parse().ok()?, name.to_string()))
+}
+
+pub fn load_migrations<P: AsRef<Path>>(dir: P) -> AppResult<Vec<Migration>> {
+    let dir = dir.as_ref();
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::config(format!(
+                "migrations dir {} does not exist; set migrations.dir to an absolute path or run from the install directory",
+                dir.display()
+            )));
+        }
+        Err(e) => return Err(AppError::io(format!("failed to read migrations dir {}", dir.display()), e)),
+    };
+    let mut out: Vec<Migration> = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| AppError::io(format!("failed to list {}", dir.display()), e))?;
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        if !file_name.ends_with(".cql") { continue; }
+        let (version, name) = parse_file_name(&file_name)
+            .ok_or_else(|| AppError::config(format!("migration file '{}' must be named NNNN_description.cql", file_name)))?;
+        let body = std::fs::read_to_string(entry.path())
+            .map_err(|e| AppError::io(format!("failed to read migration {}", file_name), e))?;
+        out.push(Migration::new(version, name, body));
+    }
+    out.sort_by_key(|m| m.version);
+    if let Some(dup) = out.windows(2).find(|w| w[0].version == w[1].version) {
+        return Err(AppError::config(format!("duplicate migration version {}", dup[0].version)));
+    }
+    Ok(out)
+}
+
+pub fn split_statements(body: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut chars = body.chars().peekable();
+    let mut in_quote = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_quote = !in_quote;
+                cur.push(c);
+            }
+            '-' | '/' if !in_quote && chars.peek() == Some(&c) => {
+                for n in chars.by_ref() {
+                    if n == '\n' { break; }
+                }
+                cur.push('\n');
+            }
+            ';' if !in_quote => {
+                let stmt = cur.trim();
+                if !stmt.is_empty() { out.push(stmt.to_string()); }
+                cur.clear();
+            }
+            _ => cur.push(c),
+        }
+    }
+    let stmt = cur.trim();
+    if !stmt.is_empty() { out.push(stmt.to_string()); }
+    out
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingMigration {
+    pub version: u32,
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ClusterMigrationStatus {
+    pub cluster: String,
+    pub keyspace: String,
+    pub reachable: bool,
+    pub current_version: Option<u32>,
+    pub applied: Vec<AppliedMigration>,
+    pub pending: Vec<PendingMigration>,
+    pub checksum_mismatches: Vec<u32>,
+    pub unknown: Vec<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ClusterMigrationStatus {
+    pub fn evaluate(cluster: &str, keyspace: &str, migrations: &[Migration], applied: Vec<AppliedMigration>) -> Self {
+        let mut status = ClusterMigrationStatus {
+            cluster: cluster.to_string(),
+            keyspace: keyspace.to_string(),
+            reachable: true,
+            current_version: applied.iter().map(|a| a.version).max(),
+            ..ClusterMigrationStatus::default()
+        };
+        for a in &applied {
+            match migrations.iter().find(|m| m.version == a.version) {
+                Some(m) if m.checksum != a.checksum => status.checksum_mismatches.push(a.version),
+                Some(_) => {}
+                None => status.unknown.push(a.version),
+            }
+        }
+        for m in migrations {
+            if !applied.iter().any(|a| a.version == m.version) {
+                status.pending.push(PendingMigration { version: m.version, name: m.name.clone(), statements: Vec::new() });
+            }
+        }
+        status.applied = applied;
+        status
+    }
+
+    fn unreachable(cluster: &str, keyspace: &str, error: String) -> Self {
+        ClusterMigrationStatus { cluster: cluster.into(), keyspace: keyspace.into(), error: Some(error), ..ClusterMigrationStatus::default() }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub active: ClusterMigrationStatus,
+    pub passive: ClusterMigrationStatus,
+    pub in_agreement: bool,
+}
+
+impl MigrationStatus {
+    pub fn new(active: ClusterMigrationStatus, passive: ClusterMigrationStatus) -> Self {
+        let clean = |s: &ClusterMigrationStatus| s.checksum_mismatches.is_empty();
+        let versions_match = !(active.reachable && passive.reachable) || active.current_version == passive.current_version;
+        let in_agreement = versions_match && clean(&active) && clean(&passive);
+        Self { active, passive, in_agreement }
+    }
+
+    pub fn ensure_in_agreement(&self) -> AppResult<()> {
+        if self.in_agreement { return Ok(()); }
+        let mut problems = Vec::new();
+        for s in [&self.active, &self.passive] {
+            if !s.checksum_mismatches.is_empty() {
+                problems.push(format!("{}: checksum mismatch for applied versions {:?}", s.cluster, s.checksum_mismatches));
+            }
+        }
+        if self.active.current_version != self.passive.current_version {
+            problems.push(format!(
+                "schema versions differ (Active={:?}, Passive={:?})",
+                self.active.current_version, self.passive.current_version
+            ));
+        }
+        Err(AppError::db(format!("schema migrations are not in agreement: {}", problems.join("; "))))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterRun {
+    pub cluster: String,
+    pub migrations: Vec<PendingMigration>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationRun {
+    pub dry_run: bool,
+    pub applied: Vec<ClusterRun>,
+    pub status: MigrationStatus,
+}
+
+fn keyspace_for(cfg: &AppConfig, cluster: Cluster) -> &str {
+    match cluster {
+        Cluster::Active => &cfg.active.keyspace,
+        Cluster::Passive => &cfg.passive.keyspace,
+    }
+}
+
+async fn exec(sess: &Session, cql: &str) -> AppResult<()> {
+    let mut st = UnpreparedStatement::new(cql);
+    st.set_consistency(Consistency::Quorum);
+    sess.query_unpaged(st, &[]).await.map_err(|e| AppError::query(format!("'{}' failed", cql), e))?;
+    sess.await_schema_agreement().await.map_err(|e| AppError::schema_agreement(format!("schema agreement not reached after '{}'", cql), e))?;
+    Ok(())
+}
+
+async fn read_applied(sess: &Session, keyspace: &str) -> AppResult<Vec<AppliedMigration>> {
+    let mut st = UnpreparedStatement::new(
+        "SELECT table_name FROM system_schema.tables WHERE keyspace_name = ? AND table_name = 'schema_migrations'",
+    );
+    st.set_consistency(Consistency::One);
+    let exists = sess
+        .query_unpaged(st, (keyspace,))
+        .await
+        .map_err(|e| AppError::query("failed to look up schema_migrations", e))?
+        .into_rows_result()
+        .map(|r| r.rows_num() > 0)
+        .unwrap_or(false);
+    if !exists { return Ok(Vec::new()); }
+
+    let cql = format!("SELECT version, name, checksum, applied_ms FROM {}.schema_migrations", quote_ident(keyspace));
+    let mut st = UnpreparedStatement::new(cql);
+    st.set_consistency(Consistency::Quorum);
+    let qr = sess.query_unpaged(st, &[]).await.map_err(|e| AppError::query("failed to read schema_migrations", e))?;
+    let rows_res = qr.into_rows_result().map_err(|e| AppError::decode("schema_migrations returned no rows", e))?;
+    let iter = rows_res
+        .rows::<(i32, Option<String>, Option<String>, Option<i64>)>()
+        .map_err(|e| AppError::decode("unexpected schema_migrations row type", e))?;
+    let mut out = Vec::new();
+    for row in iter {
+        let (version, name, checksum, applied_ms) = row.map_err(|e| AppError::decode("failed to decode schema_migrations row", e))?;
+        out.push(AppliedMigration {
+            version: version as u32,
+            name: name.unwrap_or_default(),
+            checksum: checksum.unwrap_or_default(),
+            applied_ms: applied_ms.unwrap_or_default(),
+        });
+    }
+    out.sort_by_key(|a| a.version);
+    Ok(out)
+}
+
+async fn cluster_status(cfg: &AppConfig, clients: &DbClients, cluster: Cluster, migrations: &[Migration]) -> ClusterMigrationStatus {
+    let (label, keyspace) = (cluster.label(), keyspace_for(cfg, cluster));
+    let Some(sess) = clients.session(cluster) else {
+        return ClusterMigrationStatus::unreachable(label, keyspace, format!("{} database is unavailable", label));
+    };
+    match read_applied(&sess, keyspace).await {
+        Ok(applied) => ClusterMigrationStatus::evaluate(label, keyspace, migrations, applied),
+        Err(e) => ClusterMigrationStatus::unreachable(label, keyspace, e.to_message()),
+    }
+}
+
+pub async fn status(cfg: &AppConfig, clients: &DbClients) -> AppResult<MigrationStatus> {
+    let migrations = load_migrations(&cfg.migrations.dir)?;
+    let (active, passive) = tokio::join!(
+        cluster_status(cfg, clients, Cluster::Active, &migrations),
+        cluster_status(cfg, clients, Cluster::Passive, &migrations),
+    );
+    Ok(MigrationStatus::new(active, passive))
+}
+
+async fn apply_cluster(
+    cfg: &AppConfig,
+    clients: &DbClients,
+    cluster: Cluster,
+    migrations: &[Migration],
+    dry_run: bool,
+) -> AppResult<Vec<PendingMigration>> {
+    let (label, keyspace) = (cluster.label(), keyspace_for(cfg, cluster));
+    let sess = clients.session(cluster).ok_or_else(|| AppError::db(format!("{} database is unavailable for migrations", label)))?;
+    let status = ClusterMigrationStatus::evaluate(label, keyspace, migrations, read_applied(&sess, keyspace).await?);
+    if !status.checksum_mismatches.is_empty() {
+        return Err(AppError::db(format!(
+            "{}: applied migrations {:?} were modified after being applied",
+            label, status.checksum_mismatches
+        )));
+    }
+
+    let mut done = Vec::new();
+    let pending = migrations.iter().filter(|m| status.pending.iter().any(|p| p.version == m.version));
+    if !dry_run && !status.pending.is_empty() {
+        exec(
+            &sess,
+            &format!(
+                "CREATE TABLE IF NOT EXISTS {}.schema_migrations (version int PRIMARY KEY, name text, checksum text, applied_ms bigint)",
+                quote_ident(keyspace)
+            ),
+        )
+        .await?;
+    }
+    for m in pending {
+        let statements = m.statements(keyspace);
+        if !dry_run {
+            for cql in &statements {
+                exec(&sess, cql).await.map_err(|e| e.with_context(format!("{}: migration {} failed", label, m.version)))?;
+                clients.note_statement(cluster, cql);
+            }
+            let mut st = UnpreparedStatement::new(format!(
+                "INSERT INTO {}.schema_migrations (version, name, checksum, applied_ms) VALUES (?, ?, ?, ?)",
+                quote_ident(keyspace)
+            ));
+            st.set_consistency(Consistency::Quorum);
+            sess.query_unpaged(st, (m.version as i32, &m.name, &m.checksum, now_millis() as i64))
+                .await
+                .map_err(|e| AppError::query(format!("{}: failed to record migration {}", label, m.version), e))?;
+        }
+        done.push(PendingMigration { version: m.version, name: m.name.clone(), statements });
+    }
+    Ok(done)
+}
+
+pub async fn apply_to(cfg: &AppConfig, clients: &DbClients, cluster: Cluster, dry_run: bool) -> AppResult<ClusterRun> {
+    let migrations = load_migrations(&cfg.migrations.dir)?;
+    let done = apply_cluster(cfg, clients, cluster, &migrations, dry_run).await?;
+    Ok(ClusterRun { cluster: cluster.label().to_string(), migrations: done })
+}
+
+pub async fn apply(cfg: &AppConfig, clients: &DbClients, dry_run: bool) -> AppResult<MigrationRun> {
+    let migrations = load_migrations(&cfg.migrations.dir)?;
+    let mut applied = Vec::new();
+    for cluster in [Cluster::Active, Cluster::Passive] {
+        if clients.session(cluster).is_none() { continue; }
+        let done = apply_cluster(cfg, clients, cluster, &migrations, dry_run).await?;
+        applied.push(ClusterRun { cluster: cluster.label().to_string(), migrations: done });
+    }
+    let status = status(cfg, clients).await?;
+    Ok(MigrationRun { dry_run, applied, status })
+}
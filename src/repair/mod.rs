@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use serde::Serialize;
+
+use scylla::client::session::Session;
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+use scylla::value::{CqlValue, Row};
+
+use crate::db::schema::{load_table_schema, CqlType, SchemaColumn, TableSchema};
+use crate::db::values::{to_literal, to_text};
+use crate::db::{quote_ident, DbClients};
+use crate::errors::{AppError, AppResult};
+use crate::replication::{Cluster, OutboxRecord, OutboxTarget, ReplicationManager};
+use crate::utils::now_millis;
+use crate::verify::VerifyReport;
+
+pub type PrimaryKey = serde_json::Map<String, serde_json::Value>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RepairStrategy {
+    WriteTime,
+    SourceOfTruth(Cluster),
+}
+
+impl RepairStrategy {
+    pub fn parse(s: &str) -> AppResult<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "writetime" | "write_time" | "timestamp" => Ok(RepairStrategy::WriteTime),
+            "active" => Ok(RepairStrategy::SourceOfTruth(Cluster::Active)),
+            "passive" => Ok(RepairStrategy::SourceOfTruth(Cluster::Passive)),
+            other => Err(AppError::validation(format!("unknown repair strategy '{}', expected writetime, active or passive", other))),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairStrategy::WriteTime => "writetime",
+            RepairStrategy::SourceOfTruth(Cluster::Active) => "active",
+            RepairStrategy::SourceOfTruth(Cluster::Passive) => "passive",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RepairSpec {
+    pub keyspace: String,
+    pub table: String,
+    pub strategy: RepairStrategy,
+    pub dry_run: bool,
+    pub consistency: Consistency,
+    pub max_entries: usize,
+}
+
+impl RepairSpec {
+    pub fn new(keyspace: impl Into<String>, table: impl Into<String>, strategy: RepairStrategy) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            strategy,
+            dry_run: false,
+            consistency: Consistency::LocalQuorum,
+            max_entries: 10_000,
+        }
+    }
+
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self { self.dry_run = dry_run; self }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairAction {
+    InSync,
+    Upsert,
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairEntry {
+    pub primary_key: PrimaryKey,
+    pub action: RepairAction,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub winner: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<i64>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub statements: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairFailure {
+    pub primary_key: PrimaryKey,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RepairReport {
+    pub keyspace: String,
+    pub table: String,
+    pub strategy: String,
+    pub dry_run: bool,
+    pub keys_examined: u64,
+    pub rows_in_sync: u64,
+    pub rows_repaired: u64,
+    pub rows_queued: u64,
+    pub entries: Vec<RepairEntry>,
+    pub entries_truncated: bool,
+    pub failures: Vec<RepairFailure>,
+}
+
+pub fn keys_from_report(report: &VerifyReport) -> Vec<PrimaryKey> {
+    report.missing.iter().chain(&report.extra).chain(&report.differing).map(|d| d.primary_key.clone()).collect()
+}
+
+pub fn read_keys<R: Read>(mut reader: R) -> AppResult<Vec<PrimaryKey>> {
+    let mut body = String::new();
+    reader.read_to_string(&mut body).map_err(|e| AppError::io("failed to read repair keys", e))?;
+    parse_keys(&body)
+}
+
+pub fn parse_keys(body: &str) -> AppResult<Vec<PrimaryKey>> {
+    if let Ok(v) = serde_json::from_str::<serde_json::Value>(body) {
+        return match v {
+            serde_json::Value::Object(ref o) if o.contains_key("missing") || o.contains_key("differing") => {
+                let report: VerifyReport = serde_json::from_value(v)
+                    .map_err(|e| AppError::validation(format!("invalid verify report: {}", e)))?;
+                Ok(keys_from_report(&report))
+            }
+            serde_json::Value::Object(o) => Ok(vec![o]),
+            serde_json::Value::Array(items) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| match item {
+                    serde_json::Value::Object(o) => Ok(o),
+                    _ => Err(AppError::validation(format!("repair key {} is not a JSON object", i + 1))),
+                })
+                .collect(),
+            _ => Err(AppError::validation("repair keys must be a verify report, a JSON array or NDJSON objects")),
+        };
+    }
+    let mut keys = Vec::new();
+    for (i, line) in body.lines().enumerate() {
+        if line.trim().is_empty() { continue; }
+        match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(serde_json::Value::Object(o)) => keys.push(o),
+            Ok(_) => return Err(AppError::validation(format!("line {}: repair key is not a JSON object", i + 1))),
+            Err(e) => return Err(AppError::validation(format!("line {}: invalid JSON: {}", i + 1, e))),
+        }
+    }
+    Ok(keys)
+}
+
+fn json_to_raw(v: &serde_json::Value) -> Option<String> {
+    match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        serde_json::Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn has_writetime(ty: &CqlType) -> bool {
+    match ty {
+        CqlType::Unsupported(s) => s.starts_with("frozen<") || s.starts_with("tuple<"),
+        _ => true,
+    }
+}
+
+struct RowPlan<'a> {
+    table: String,
+    keys: Vec<&'a SchemaColumn>,
+    regulars: Vec<&'a SchemaColumn>,
+    timed: Vec<bool>,
+    select_cols: String,
+}
+
+impl<'a> RowPlan<'a> {
+    fn build(schema: &'a TableSchema) -> AppResult<Self> {
+        let keys = schema.primary_key();
+        if keys.is_empty() {
+            return Err(AppError::validation(format!("table {}.{} has no primary key", schema.keyspace, schema.table)));
+        }
+        if schema.columns.iter().any(|c| matches!(&c.ty, CqlType::Unsupported(t) if t == "counter")) {
+            return Err(AppError::validation(format!("table {}.{} is a counter table and cannot be repaired", schema.keyspace, schema.table)));
+        }
+        let regulars = schema.regular_columns();
+        let timed: Vec<bool> = regulars.iter().map(|c| has_writetime(&c.ty)).collect();
+        let mut cols: Vec<String> = regulars.iter().map(|c| quote_ident(&c.name)).collect();
+        cols.extend(regulars.iter().zip(&timed).filter(|(_, t)| **t).map(|(c, _)| format!("WRITETIME({})", quote_ident(&c.name))));
+        if cols.is_empty() {
+            cols.push(quote_ident(&keys[0].name));
+        }
+        Ok(Self {
+            table: format!("{}.{}", quote_ident(&schema.keyspace), quote_ident(&schema.table)),
+            keys,
+            regulars,
+            timed,
+            select_cols: cols.join(", "),
+        })
+    }
+
+    fn where_clause(&self, pk: &PrimaryKey) -> Result<String, String> {
+        let mut parts = Vec::with_capacity(self.keys.len());
+        for col in &self.keys {
+            let raw = pk.get(&col.name).and_then(json_to_raw).ok_or_else(|| format!("primary key column '{}' is missing", col.name))?;
+            let lit = col.ty.render_literal(&raw).map_err(|e| format!("column '{}': {}", col.name, e))?;
+            parts.push(format!("{} = {}", quote_ident(&col.name), lit));
+        }
+        Ok(parts.join(" AND "))
+    }
+
+    fn decode(&self, row: Row) -> FetchedRow {
+        let mut cols = row.columns.into_iter();
+        let values: Vec<Option<CqlValue>> = if self.regulars.is_empty() {
+            Vec::new()
+        } else {
+            cols.by_ref().take(self.regulars.len()).collect()
+        };
+        let mut stamps = cols.map(|v| match v {
+            Some(CqlValue::BigInt(ts)) => Some(ts),
+            _ => None,
+        });
+        let writetimes = self.timed.iter().map(|t| if *t { stamps.next().flatten() } else { None }).collect();
+        FetchedRow { values, writetimes }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedRow {
+    pub values: Vec<Option<CqlValue>>,
+    pub writetimes: Vec<Option<i64>>,
+}
+
+impl FetchedRow {
+    fn max_timestamp(&self) -> Option<i64> { self.writetimes.iter().flatten().copied().max() }
+
+    fn same_values(&self, other: &FetchedRow) -> bool {
+        self.values.len() == other.values.len()
+            && self.values.iter().zip(&other.values).all(|(a, b)| match (a, b) {
+                (None, None) => true,
+                (Some(a), Some(b)) => to_text(a) == to_text(b),
+                _ => false,
+            })
+    }
+}
+
+async fn fetch_row(sess: Option<&Session>, plan: &RowPlan<'_>, where_clause: &str, cl: Consistency) -> AppResult<Option<FetchedRow>> {
+    let sess = sess.ok_or_else(|| AppError::db("cluster session is unavailable"))?;
+    let cql = format!("SELECT {} FROM {} WHERE {}", plan.select_cols, plan.table, where_clause);
+    let mut st = UnpreparedStatement::new(cql);
+    st.set_consistency(cl);
+    st.set_is_idempotent(true);
+    let qr = sess.query_unpaged(st, &[]).await.map_err(|e| AppError::query("repair read failed", e))?;
+    let rows_res = qr.into_rows_result().map_err(|e| AppError::decode("repair read returned no rows", e))?;
+    let mut rows = rows_res.rows::<Row>().map_err(|e| AppError::decode("repair read row type", e))?;
+    match rows.next() {
+        Some(row) => Ok(Some(plan.decode(row.map_err(|e| AppError::decode("repair read row decode", e))?))),
+        None => Ok(None),
+    }
+}
+
+fn pick_winner(strategy: RepairStrategy, active: &Option<FetchedRow>, passive: &Option<FetchedRow>) -> Option<Cluster> {
+    match (active, passive) {
+        (None, None) => None,
+        (Some(a), Some(p)) if a.same_values(p) => None,
+        _ => match strategy {
+            RepairStrategy::SourceOfTruth(c) => Some(c),
+            RepairStrategy::WriteTime => match (active, passive) {
+                (Some(_), None) => Some(Cluster::Active),
+                (None, Some(_)) => Some(Cluster::Passive),
+                (Some(a), Some(p)) if p.max_timestamp() > a.max_timestamp() => Some(Cluster::Passive),
+                _ => Some(Cluster::Active),
+            },
+        },
+    }
+}
+
+fn upsert_statements(plan: &RowPlan<'_>, pk: &PrimaryKey, where_clause: &str, winner: &FetchedRow, loser: Option<&FetchedRow>, stamp: Option<i64>) -> Result<Vec<String>, String> {
+    let row_ts = stamp.or(winner.max_timestamp());
+    let key_cols: Vec<String> = plan.keys.iter().map(|c| quote_ident(&c.name)).collect();
+    let mut key_lits = Vec::with_capacity(plan.keys.len());
+    for col in &plan.keys {
+        let raw = pk.get(&col.name).and_then(json_to_raw).ok_or_else(|| format!("primary key column '{}' is missing", col.name))?;
+        key_lits.push(col.ty.render_literal(&raw)?);
+    }
+
+    let mut groups: BTreeMap<Option<i64>, Vec<(String, String)>> = BTreeMap::new();
+    let mut cleared = Vec::new();
+    for (i, col) in plan.regulars.iter().enumerate() {
+        match &winner.values[i] {
+            Some(v) => groups.entry(stamp.or(winner.writetimes[i]).or(row_ts)).or_default().push((quote_ident(&col.name), to_literal(v))),
+            None if loser.is_some_and(|l| l.values[i].is_some()) => cleared.push(quote_ident(&col.name)),
+            None => {}
+        }
+    }
+    if groups.is_empty() {
+        groups.insert(row_ts, Vec::new());
+    }
+
+    let mut out = Vec::with_capacity(groups.len() + 1);
+    for (ts, cols) in groups {
+        let mut names = key_cols.clone();
+        let mut lits = key_lits.clone();
+        for (n, l) in cols {
+            names.push(n);
+            lits.push(l);
+        }
+        let using = ts.map(|t| format!(" USING TIMESTAMP {}", t)).unwrap_or_default();
+        out.push(format!("INSERT INTO {} ({}) VALUES ({}){}", plan.table, names.join(", "), lits.join(", "), using));
+    }
+    if !cleared.is_empty() {
+        let using = row_ts.map(|t| format!(" USING TIMESTAMP {}", t)).unwrap_or_default();
+        out.push(format!("DELETE {} FROM {}{} WHERE {}", cleared.join(", "), plan.table, using, where_clause));
+    }
+    Ok(out)
+}
+
+fn other(cluster: Cluster) -> Cluster {
+    match cluster {
+        Cluster::Active => Cluster::Passive,
+        Cluster::Passive => Cluster::Active,
+    }
+}
+
+fn target_of(cluster: Cluster) -> OutboxTarget {
+    match cluster {
+        Cluster::Active => OutboxTarget::Active,
+        Cluster::Passive => OutboxTarget::Passive,
+    }
+}
+
+fn cluster_name(cluster: Cluster) -> &'static str {
+    match cluster {
+        Cluster::Active => "active",
+        Cluster::Passive => "passive",
+    }
+}
+
+fn fresh_timestamp(now_us: i64, rows: [&Option<FetchedRow>; 2]) -> i64 {
+    rows.into_iter().flatten().filter_map(|r| r.max_timestamp()).map(|t| t + 1).fold(now_us, i64::max)
+}
+
+fn plan_key(plan: &RowPlan<'_>, strategy: RepairStrategy, pk: &PrimaryKey, where_clause: &str, active: Option<FetchedRow>, passive: Option<FetchedRow>, now_us: i64) -> Result<(Option<Cluster>, RepairEntry), String> {
+    let Some(winner) = pick_winner(strategy, &active, &passive) else {
+        let entry = RepairEntry { primary_key: pk.clone(), action: RepairAction::InSync, winner: None, timestamp: None, statements: Vec::new() };
+        return Ok((None, entry));
+    };
+    let stamp = match strategy {
+        RepairStrategy::SourceOfTruth(_) => Some(fresh_timestamp(now_us, [&active, &passive])),
+        RepairStrategy::WriteTime => None,
+    };
+    let (win_row, lose_row) = match winner {
+        Cluster::Active => (active, passive),
+        Cluster::Passive => (passive, active),
+    };
+    let (action, timestamp, statements) = match win_row {
+        Some(w) => {
+            let ts = stamp.or(w.max_timestamp());
+            (RepairAction::Upsert, ts, upsert_statements(plan, pk, where_clause, &w, lose_row.as_ref(), stamp)?)
+        }
+        None => {
+            let using = stamp.map(|t| format!(" USING TIMESTAMP {}", t)).unwrap_or_default();
+            (RepairAction::Delete, stamp, vec![format!("DELETE FROM {}{} WHERE {}", plan.table, using, where_clause)])
+        }
+    };
+    Ok((Some(winner), RepairEntry { primary_key: pk.clone(), action, winner: Some(cluster_name(winner)), timestamp, statements }))
+}
+
+pub fn plan_repair(schema: &TableSchema, strategy: RepairStrategy, pk: &PrimaryKey, active: Option<FetchedRow>, passive: Option<FetchedRow>, now_us: i64) -> AppResult<RepairEntry> {
+    let plan = RowPlan::build(schema)?;
+    let where_clause = plan.where_clause(pk).map_err(AppError::validation)?;
+    plan_key(&plan, strategy, pk, &where_clause, active, passive, now_us)
+        .map(|(_, entry)| entry)
+        .map_err(AppError::validation)
+}
+
+pub async fn run_repair(spec: &RepairSpec, keys: &[PrimaryKey], repl: &ReplicationManager, clients: &DbClients) -> AppResult<RepairReport> {
+    let schema = load_table_schema(clients, &spec.keyspace, &spec.table).await?;
+    run_repair_with_schema(spec, &schema, keys, repl, clients).await
+}
+
+pub async fn run_repair_with_schema(
+    spec: &RepairSpec,
+    schema: &TableSchema,
+    keys: &[PrimaryKey],
+    repl: &ReplicationManager,
+    clients: &DbClients,
+) -> AppResult<RepairReport> {
+    let plan = RowPlan::build(schema)?;
+    let mut report = RepairReport {
+        keyspace: spec.keyspace.clone(),
+        table: spec.table.clone(),
+        strategy: spec.strategy.as_str().to_string(),
+        dry_run: spec.dry_run,
+        ..RepairReport::default()
+    };
+
+    for pk in keys {
+        report.keys_examined += 1;
+        let where_clause = match plan.where_clause(pk) {
+            Ok(w) => w,
+            Err(reason) => {
+                report.failures.push(RepairFailure { primary_key: pk.clone(), reason });
+                continue;
+            }
+        };
+        let (active_sess, passive_sess) = (clients.session(Cluster::Active), clients.session(Cluster::Passive));
+        let (a, p) = tokio::join!(
+            fetch_row(active_sess.as_deref(), &plan, &where_clause, spec.consistency),
+            fetch_row(passive_sess.as_deref(), &plan, &where_clause, spec.consistency)
+        );
+        let (a, p) = match (a, p) {
+            (Ok(a), Ok(p)) => (a, p),
+            (Err(e), _) | (_, Err(e)) => {
+                report.failures.push(RepairFailure { primary_key: pk.clone(), reason: e.to_message() });
+                continue;
+            }
+        };
+        let now_us = now_millis() as i64 * 1000;
+        let (winner, entry) = match plan_key(&plan, spec.strategy, pk, &where_clause, a, p, now_us) {
+            Ok(planned) => planned,
+            Err(reason) => {
+                report.failures.push(RepairFailure { primary_key: pk.clone(), reason });
+                continue;
+            }
+        };
+
+        let Some(winner) = winner else {
+            report.rows_in_sync += 1;
+            continue;
+        };
+        if !spec.dry_run {
+            let loser = target_of(other(winner));
+            let key = format!("repair:{}.{}:{}", spec.keyspace, spec.table, serde_json::Value::Object(pk.clone()));
+            let mut queued = false;
+            let mut failure = None;
+            for cql in &entry.statements {
+                let outcome = repl.apply_simple(cql, loser, Some(spec.consistency), clients).await;
+                if let Some((_, e)) = outcome.rejected.into_iter().next() {
+                    failure = Some(format!("repair write rejected: {}", e.to_message()));
+                    break;
+                }
+                for failed in outcome.failed {
+                    if let Err(e) = repl.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), failed)).await {
+                        failure = Some(format!("could not queue repair write: {}", e.to_message()));
+                        break;
+                    }
+                    queued = true;
+                }
+                if failure.is_some() { break; }
+            }
+            if let Some(reason) = failure {
+                report.failures.push(RepairFailure { primary_key: pk.clone(), reason });
+                continue;
+            }
+            if queued { report.rows_queued += 1; } else { report.rows_repaired += 1; }
+        }
+        if report.entries.len() < spec.max_entries {
+            report.entries.push(entry);
+        } else {
+            report.entries_truncated = true;
+        }
+    }
+
+    Ok(report)
+}
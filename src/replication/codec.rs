@@ -0,0 +1,283 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Read;
+
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use super::{OutboxRecord, HEADER_LEN, OB_MAGIC, OB_VERSION};
+use crate::config::{ReplicationConfig, SecretResolver};
+use crate::errors::{AppError, AppResult};
+
+pub(super) const OB_VERSION_FLAGGED: u16 = 2;
+pub(super) const FLAGGED_HEADER_LEN: usize = HEADER_LEN + 1 + 2;
+
+const FLAG_COMPRESSION_MASK: u8 = 0x03;
+const FLAG_ENCRYPTED: u8 = 0x80;
+const KNOWN_FLAGS: u8 = FLAG_COMPRESSION_MASK | FLAG_ENCRYPTED;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+const MAX_RECORD_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OutboxCompression {
+    #[default]
+    None,
+    Lz4,
+    Zstd,
+}
+
+impl OutboxCompression {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "none" | "" => Some(OutboxCompression::None),
+            "lz4" => Some(OutboxCompression::Lz4),
+            "zstd" => Some(OutboxCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    fn flag(self) -> u8 {
+        match self {
+            OutboxCompression::None => 0,
+            OutboxCompression::Lz4 => 1,
+            OutboxCompression::Zstd => 2,
+        }
+    }
+
+    fn from_flags(flags: u8) -> Option<Self> {
+        match flags & FLAG_COMPRESSION_MASK {
+            0 => Some(OutboxCompression::None),
+            1 => Some(OutboxCompression::Lz4),
+            2 => Some(OutboxCompression::Zstd),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> AppResult<Vec<u8>> {
+        match self {
+            OutboxCompression::None => Ok(data.to_vec()),
+            OutboxCompression::Lz4 => Ok(lz4_flex::compress_prepend_size(data)),
+            OutboxCompression::Zstd => zstd::bulk::compress(data, 0).map_err(|e| AppError::io("outbox compress", e)),
+        }
+    }
+
+    fn decompress(self, data: &[u8]) -> Option<Cow<'_, [u8]>> {
+        match self {
+            OutboxCompression::None => Some(Cow::Borrowed(data)),
+            OutboxCompression::Lz4 => {
+                let size = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+                if size > MAX_RECORD_BYTES { return None; }
+                lz4_flex::decompress_size_prepended(data).ok().map(Cow::Owned)
+            }
+            OutboxCompression::Zstd => zstd::bulk::decompress(data, MAX_RECORD_BYTES).ok().map(Cow::Owned),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct FrameHeader {
+    pub(super) flags: u8,
+    pub(super) key_id: u16,
+    pub(super) payload_len: usize,
+    pub(super) header_len: usize,
+}
+
+impl FrameHeader {
+    pub(super) fn frame_len(&self) -> u64 { (self.header_len + self.payload_len) as u64 }
+}
+
+pub(super) fn parse_header(buf: &[u8]) -> Result<FrameHeader, &'static str> {
+    if buf.len() < HEADER_LEN { return Err("truncated header"); }
+    let magic = u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]);
+    let version = u16::from_le_bytes([buf[4], buf[5]]);
+    let payload_len = u32::from_le_bytes([buf[6], buf[7], buf[8], buf[9]]) as usize;
+    if magic != OB_MAGIC { return Err("bad magic"); }
+    match version {
+        OB_VERSION => Ok(FrameHeader { flags: 0, key_id: 0, payload_len, header_len: HEADER_LEN }),
+        OB_VERSION_FLAGGED => {
+            if buf.len() < FLAGGED_HEADER_LEN { return Err("truncated header"); }
+            let flags = buf[HEADER_LEN];
+            if flags & !KNOWN_FLAGS != 0 || OutboxCompression::from_flags(flags).is_none() {
+                return Err("unsupported frame flags");
+            }
+            let key_id = u16::from_le_bytes([buf[HEADER_LEN + 1], buf[HEADER_LEN + 2]]);
+            Ok(FrameHeader { flags, key_id, payload_len, header_len: FLAGGED_HEADER_LEN })
+        }
+        _ => Err("unsupported frame version"),
+    }
+}
+
+pub(super) fn read_header<R: Read>(r: &mut R) -> Result<(FrameHeader, [u8; FLAGGED_HEADER_LEN]), &'static str> {
+    let mut buf = [0u8; FLAGGED_HEADER_LEN];
+    r.read_exact(&mut buf[..HEADER_LEN]).map_err(|_| "truncated header")?;
+    if u16::from_le_bytes([buf[4], buf[5]]) == OB_VERSION_FLAGGED {
+        r.read_exact(&mut buf[HEADER_LEN..]).map_err(|_| "truncated header")?;
+    }
+    Ok((parse_header(&buf)?, buf))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum FrameError {
+    Corrupt(&'static str),
+    MissingKey(u16),
+}
+
+impl FrameError {
+    pub(super) fn reason(self) -> &'static str {
+        match self {
+            FrameError::Corrupt(reason) => reason,
+            FrameError::MissingKey(_) => "unknown encryption key",
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct OutboxCodec {
+    compression: OutboxCompression,
+    min_bytes: usize,
+    keys: BTreeMap<u16, [u8; KEY_LEN]>,
+    write_key: Option<u16>,
+}
+
+impl fmt::Debug for OutboxCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboxCodec")
+            .field("compression", &self.compression)
+            .field("min_bytes", &self.min_bytes)
+            .field("key_ids", &self.keys.keys().collect::<Vec<_>>())
+            .field("write_key", &self.write_key)
+            .finish()
+    }
+}
+
+impl OutboxCodec {
+    pub fn plain() -> Self { Self::default() }
+
+    pub fn with_compression(mut self, compression: OutboxCompression, min_bytes: usize) -> Self {
+        self.compression = compression;
+        self.min_bytes = min_bytes;
+        self
+    }
+
+    pub fn with_key(mut self, id: u16, key: [u8; KEY_LEN]) -> Self {
+        self.keys.insert(id, key);
+        self
+    }
+
+    pub fn with_write_key(mut self, id: Option<u16>) -> AppResult<Self> {
+        if let Some(id) = id && !self.keys.contains_key(&id) {
+            return Err(AppError::config(format!("outbox encryption key {} is not configured", id)));
+        }
+        self.write_key = id;
+        Ok(self)
+    }
+
+    pub fn compression(&self) -> OutboxCompression { self.compression }
+
+    pub fn write_key(&self) -> Option<u16> { self.write_key }
+
+    pub fn parse_key(raw: &[u8]) -> AppResult<[u8; KEY_LEN]> {
+        if let Ok(key) = <[u8; KEY_LEN]>::try_from(raw) {
+            return Ok(key);
+        }
+        let text = std::str::from_utf8(raw).map(str::trim).unwrap_or_default();
+        let bytes: Option<Vec<u8>> = (text.is_ascii() && text.len() == KEY_LEN * 2)
+            .then(|| (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect())
+            .flatten();
+        bytes
+            .and_then(|b| b.try_into().ok())
+            .ok_or_else(|| AppError::config(format!("outbox key must be {} raw bytes or {} hex characters", KEY_LEN, KEY_LEN * 2)))
+    }
+
+    pub fn from_config(cfg: &ReplicationConfig, secrets: &SecretResolver) -> AppResult<Self> {
+        let compression = OutboxCompression::parse(&cfg.outbox_compression).ok_or_else(|| {
+            AppError::config(format!("unknown replication.outbox_compression '{}', expected none, lz4 or zstd", cfg.outbox_compression))
+        })?;
+        let mut codec = Self::plain().with_compression(compression, cfg.outbox_compression_min_bytes);
+        let key_id = |id: &str, field: &str| {
+            id.parse::<u16>()
+                .ok()
+                .filter(|n| *n > 0)
+                .ok_or_else(|| AppError::config(format!("{}: key id '{}' must be an integer between 1 and 65535", field, id)))
+        };
+        for (id, value) in &cfg.outbox_keys {
+            let field = format!("replication.outbox_keys.{}", id);
+            let raw = secrets.interpolate(value).map_err(|e| e.with_context(field.clone()))?;
+            let key = Self::parse_key(raw.as_bytes()).map_err(|e| e.with_context(field.clone()))?;
+            codec = codec.with_key(key_id(id, &field)?, key);
+        }
+        for (id, path) in &cfg.outbox_key_files {
+            let field = format!("replication.outbox_key_files.{}", id);
+            let raw = std::fs::read(path).map_err(|e| AppError::io(format!("{}: failed to read {}", field, path), e))?;
+            let key = Self::parse_key(&raw).map_err(|e| e.with_context(field.clone()))?;
+            codec = codec.with_key(key_id(id, &field)?, key);
+        }
+        codec.with_write_key(cfg.outbox_encryption_key_id)
+    }
+
+    pub(super) fn encode_frame(&self, rec: &OutboxRecord) -> AppResult<Vec<u8>> {
+        let plain = rec.encode();
+        let mut compression = OutboxCompression::None;
+        let mut payload = plain;
+        if self.compression != OutboxCompression::None && payload.len() >= self.min_bytes {
+            let packed = self.compression.compress(&payload)?;
+            if packed.len() < payload.len() {
+                payload = packed;
+                compression = self.compression;
+            }
+        }
+
+        let flags = compression.flag() | if self.write_key.is_some() { FLAG_ENCRYPTED } else { 0 };
+        if flags == 0 {
+            let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+            frame.extend_from_slice(&OB_MAGIC.to_le_bytes());
+            frame.extend_from_slice(&OB_VERSION.to_le_bytes());
+            frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            frame.extend_from_slice(&payload);
+            return Ok(frame);
+        }
+
+        let key_id = self.write_key.unwrap_or(0);
+        let stored_len = if self.write_key.is_some() { NONCE_LEN + payload.len() + TAG_LEN } else { payload.len() };
+        let mut frame = Vec::with_capacity(FLAGGED_HEADER_LEN + stored_len);
+        frame.extend_from_slice(&OB_MAGIC.to_le_bytes());
+        frame.extend_from_slice(&OB_VERSION_FLAGGED.to_le_bytes());
+        frame.extend_from_slice(&(stored_len as u32).to_le_bytes());
+        frame.push(flags);
+        frame.extend_from_slice(&key_id.to_le_bytes());
+
+        match self.write_key.and_then(|id| self.keys.get(&id)) {
+            Some(key) => {
+                let mut nonce = [0u8; NONCE_LEN];
+                openssl::rand::rand_bytes(&mut nonce).map_err(|e| AppError::other(format!("outbox nonce generation failed: {}", e)))?;
+                let mut tag = [0u8; TAG_LEN];
+                let sealed = encrypt_aead(Cipher::aes_256_gcm(), key, Some(&nonce), &frame, &payload, &mut tag)
+                    .map_err(|e| AppError::other(format!("outbox encryption failed: {}", e)))?;
+                frame.extend_from_slice(&nonce);
+                frame.extend_from_slice(&sealed);
+                frame.extend_from_slice(&tag);
+            }
+            None => frame.extend_from_slice(&payload),
+        }
+        Ok(frame)
+    }
+
+    pub(super) fn decode_payload(&self, header: &[u8], info: &FrameHeader, payload: &[u8]) -> Result<OutboxRecord, FrameError> {
+        let opened;
+        let mut data = payload;
+        if info.flags & FLAG_ENCRYPTED != 0 {
+            let key = self.keys.get(&info.key_id).ok_or(FrameError::MissingKey(info.key_id))?;
+            if data.len() < NONCE_LEN + TAG_LEN { return Err(FrameError::Corrupt("truncated ciphertext")); }
+            let (nonce, rest) = data.split_at(NONCE_LEN);
+            let (sealed, tag) = rest.split_at(rest.len() - TAG_LEN);
+            opened = decrypt_aead(Cipher::aes_256_gcm(), key, Some(nonce), header, sealed, tag)
+                .map_err(|_| FrameError::Corrupt("decryption failed"))?;
+            data = &opened;
+        }
+        let compression = OutboxCompression::from_flags(info.flags).ok_or(FrameError::Corrupt("unsupported frame flags"))?;
+        let plain = compression.decompress(data).ok_or(FrameError::Corrupt("decompression failed"))?;
+        OutboxRecord::decode(&plain).ok_or(FrameError::Corrupt("undecodable payload"))
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Seek, SeekFrom, Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::codec::{read_header, FrameError};
+use super::{Outbox, OutboxCodec, OutboxRecord, OutboxTarget, OB_MAGIC};
+use crate::errors::{AppError, AppResult};
+
+const MAX_REPORTED_REGIONS: usize = 10;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParamView {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    pub bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    pub hex: String,
+}
+
+impl ParamView {
+    fn new(marker: Option<String>, raw: &[u8]) -> Self {
+        let text = std::str::from_utf8(raw)
+            .ok()
+            .filter(|s| !s.chars().any(|c| c.is_control() && !c.is_whitespace()))
+            .map(str::to_string);
+        Self { marker, bytes: raw.len(), text, hex: to_hex(raw) }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Word(String),
+    Punct(String),
+    Marker(Option<String>),
+    Literal,
+}
+
+fn tokenize(cql: &str) -> Vec<Token> {
+    let chars: Vec<char> = cql.chars().collect();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            let mut j = i + 1;
+            while j < chars.len() {
+                if chars[j] == c && chars.get(j + 1) == Some(&c) {
+                    j += 2;
+                } else if chars[j] == c {
+                    break;
+                } else {
+                    j += 1;
+                }
+            }
+            out.push(if c == '"' { Token::Word(chars[i + 1..j.min(chars.len())].iter().collect()) } else { Token::Literal });
+            i = j + 1;
+        } else if c == '?' {
+            out.push(Token::Marker(None));
+            i += 1;
+        } else if c == ':' && chars.get(i + 1).is_some_and(|n| n.is_alphanumeric() || *n == '_') {
+            let end = (i + 1..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_')).unwrap_or(chars.len());
+            out.push(Token::Marker(Some(chars[i + 1..end].iter().collect())));
+            i = end;
+        } else if c.is_alphanumeric() || c == '_' {
+            let end = (i..chars.len()).find(|&j| !(chars[j].is_alphanumeric() || chars[j] == '_' || chars[j] == '.')).unwrap_or(chars.len());
+            let word: String = chars[i..end].iter().collect();
+            out.push(if c.is_ascii_digit() { Token::Literal } else { Token::Word(word) });
+            i = end;
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            let op = if matches!(two.as_str(), "<=" | ">=" | "!=") { two } else { c.to_string() };
+            i += op.chars().count();
+            out.push(Token::Punct(op));
+        }
+    }
+    out
+}
+
+fn is_word(token: &Token, keyword: &str) -> bool { matches!(token, Token::Word(w) if w.eq_ignore_ascii_case(keyword)) }
+
+fn marker_name(tokens: &[Token], at: usize) -> Option<String> {
+    let in_list = |t: &Token| matches!(t, Token::Marker(_) | Token::Literal) || matches!(t, Token::Punct(p) if p == "(" || p == ",");
+    let mut i = at;
+    while i > 0 && in_list(&tokens[i - 1]) { i -= 1; }
+    let mut prev = i.checked_sub(1)?;
+    for clause in ["TTL", "TIMESTAMP", "LIMIT"] {
+        if is_word(&tokens[prev], clause) { return Some(format!("[{}]", clause.to_ascii_lowercase())); }
+    }
+    if is_word(&tokens[prev], "KEY") && prev > 0 && is_word(&tokens[prev - 1], "CONTAINS") { prev -= 1; }
+    if !(is_operator(&tokens[prev]) || is_word(&tokens[prev], "IN") || is_word(&tokens[prev], "CONTAINS")) { return None; }
+    match tokens.get(prev.checked_sub(1)?)? {
+        Token::Word(w) => Some(w.clone()),
+        _ => None,
+    }
+}
+
+fn is_operator(token: &Token) -> bool {
+    matches!(token, Token::Punct(p) if matches!(p.as_str(), "=" | "<" | ">" | "<=" | ">=" | "!=" | "+" | "-"))
+}
+
+pub fn bind_markers(cql: &str) -> Vec<Option<String>> {
+    let tokens = tokenize(cql);
+    let mut columns = Vec::new();
+    if tokens.first().is_some_and(|t| is_word(t, "INSERT"))
+        && let Some(open) = tokens.iter().position(|t| matches!(t, Token::Punct(p) if p == "("))
+    {
+        for token in &tokens[open + 1..] {
+            match token {
+                Token::Word(w) => columns.push(w.clone()),
+                Token::Punct(p) if p == ")" => break,
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let mut values: Option<(usize, usize)> = None;
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct(p) if p == "(" => {
+                if let Some((depth, _)) = values.as_mut() {
+                    *depth += 1;
+                } else if i > 0 && is_word(&tokens[i - 1], "VALUES") {
+                    values = Some((1, 0));
+                }
+            }
+            Token::Punct(p) if p == ")" => {
+                if let Some((depth, _)) = values.as_mut() {
+                    *depth -= 1;
+                    if *depth == 0 { values = None; }
+                }
+            }
+            Token::Punct(p) if p == "," => {
+                if let Some((1, pos)) = values.as_mut() { *pos += 1; }
+            }
+            Token::Marker(name) => {
+                let name = match (name, values) {
+                    (Some(n), _) => Some(n.clone()),
+                    (None, Some((_, pos))) => columns.get(pos).cloned(),
+                    (None, None) => marker_name(&tokens, i),
+                };
+                out.push(name);
+            }
+            _ => {}
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxEntry {
+    pub offset: u64,
+    pub next_offset: u64,
+    pub key: String,
+    pub target: OutboxTarget,
+    pub created_ms: u64,
+    pub statement: String,
+    pub params: Vec<ParamView>,
+}
+
+impl OutboxEntry {
+    pub fn new(offset: u64, next_offset: u64, rec: &OutboxRecord) -> Self {
+        let mut markers = bind_markers(&rec.statement).into_iter();
+        Self {
+            offset,
+            next_offset,
+            key: rec.idempotency_key.clone(),
+            target: rec.target,
+            created_ms: rec.created_ms,
+            statement: rec.statement.clone(),
+            params: rec.params.iter().map(|p| ParamView::new(markers.next().flatten(), p)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutboxFilter {
+    pub key: Option<String>,
+    pub target: Option<OutboxTarget>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+}
+
+impl OutboxFilter {
+    pub fn matches(&self, rec: &OutboxRecord) -> bool {
+        self.key.as_deref().is_none_or(|k| rec.idempotency_key == k)
+            && self.target.is_none_or(|t| rec.target == t)
+            && self.since_ms.is_none_or(|ms| rec.created_ms >= ms)
+            && self.until_ms.is_none_or(|ms| rec.created_ms < ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CorruptRegion {
+    pub start: u64,
+    pub end: u64,
+    pub reason: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LockedRecord {
+    pub start: u64,
+    pub end: u64,
+    pub key_id: u16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxScan {
+    pub file_len: u64,
+    pub cursor: u64,
+    pub cursor_on_boundary: bool,
+    pub records: usize,
+    pub pending_records: usize,
+    pub corrupt: Vec<CorruptRegion>,
+    pub unknown_key: Vec<LockedRecord>,
+    #[serde(skip)]
+    pub entries: Vec<(u64, u64, OutboxRecord)>,
+}
+
+impl OutboxScan {
+    pub fn is_healthy(&self) -> bool { self.corrupt.is_empty() && self.unknown_key.is_empty() && self.cursor_on_boundary }
+
+    pub fn describe_problems(&self) -> String {
+        let mut parts: Vec<String> = self
+            .corrupt
+            .iter()
+            .take(MAX_REPORTED_REGIONS)
+            .map(|r| format!("bytes {}..{} ({})", r.start, r.end, r.reason))
+            .collect();
+        if self.corrupt.len() > MAX_REPORTED_REGIONS {
+            parts.push(format!("and {} more", self.corrupt.len() - MAX_REPORTED_REGIONS));
+        }
+        let key_ids: BTreeSet<u16> = self.unknown_key.iter().map(|r| r.key_id).collect();
+        for id in key_ids {
+            let count = self.unknown_key.iter().filter(|r| r.key_id == id).count();
+            parts.push(format!("{} record(s) encrypted with unknown key {}", count, id));
+        }
+        if !self.cursor_on_boundary {
+            parts.push(format!("cursor {} is not at a record boundary", self.cursor));
+        }
+        parts.join("; ")
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedRecord {
+    pub key: String,
+    pub target: OutboxTarget,
+    pub created_ms: u64,
+    pub statement: String,
+    #[serde(default)]
+    pub params: Vec<String>,
+    #[serde(default)]
+    pub replayed: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TransferSummary {
+    pub records: usize,
+    pub replayed: usize,
+    pub skipped_corrupt_regions: usize,
+    pub skipped_unknown_key: usize,
+    pub cursor: u64,
+}
+
+fn to_hex(raw: &[u8]) -> String { raw.iter().map(|b| format!("{:02x}", b)).collect() }
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) { return None; }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+struct Frames<'a, R> {
+    codec: &'a OutboxCodec,
+    reader: R,
+    at: u64,
+    pos: u64,
+    len: u64,
+}
+
+impl<'a, R: BufRead + Seek> Frames<'a, R> {
+    fn new(codec: &'a OutboxCodec, mut reader: R) -> io::Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        Ok(Self { codec, reader, at: 0, pos: 0, len })
+    }
+
+    fn frame_at(&mut self, pos: u64) -> Result<(u64, Result<OutboxRecord, u16>), &'static str> {
+        if pos != self.at {
+            self.reader.seek(SeekFrom::Start(pos)).map_err(|_| "unreadable frame")?;
+        }
+        self.at = u64::MAX;
+        let (info, header) = read_header(&mut self.reader)?;
+        let next = pos + info.frame_len();
+        if next > self.len { return Err("truncated payload"); }
+        let mut payload = vec![0u8; info.payload_len];
+        self.reader.read_exact(&mut payload).map_err(|_| "truncated payload")?;
+        self.at = next;
+        match self.codec.decode_payload(&header[..info.header_len], &info, &payload) {
+            Ok(rec) => Ok((next, Ok(rec))),
+            Err(FrameError::MissingKey(id)) => Ok((next, Err(id))),
+            Err(e) => Err(e.reason()),
+        }
+    }
+
+    fn find_magic(&mut self, from: u64) -> Option<u64> {
+        self.reader.seek(SeekFrom::Start(from)).ok()?;
+        self.at = from;
+        let mut window = 0u32;
+        while self.at < self.len {
+            let start = self.at;
+            let buf = self.reader.fill_buf().ok()?;
+            if buf.is_empty() { break; }
+            let n = buf.len().min((self.len - start) as usize);
+            let hit = buf[..n].iter().enumerate().position(|(k, b)| {
+                window = (window >> 8) | (u32::from(*b) << 24);
+                start + k as u64 + 1 - from >= 4 && window == OB_MAGIC
+            });
+            let used = hit.map_or(n, |k| k + 1);
+            self.reader.consume(used);
+            self.at += used as u64;
+            if hit.is_some() { return Some(self.at - 4); }
+        }
+        None
+    }
+
+    fn resume_after(&mut self, pos: u64) -> u64 {
+        let mut from = pos + 1;
+        while let Some(found) = self.find_magic(from) {
+            if self.frame_at(found).is_ok() { return found; }
+            from = found + 1;
+        }
+        self.len
+    }
+}
+
+enum Frame {
+    Record(u64, u64, OutboxRecord),
+    Locked(LockedRecord),
+    Corrupt(CorruptRegion),
+}
+
+impl Frame {
+    fn record(self) -> Option<(u64, u64, OutboxRecord)> {
+        match self {
+            Frame::Record(start, end, rec) => Some((start, end, rec)),
+            _ => None,
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Iterator for Frames<'_, R> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.len { return None; }
+        let pos = self.pos;
+        match self.frame_at(pos) {
+            Ok((next, decoded)) => {
+                self.pos = next;
+                Some(match decoded {
+                    Ok(rec) => Frame::Record(pos, next, rec),
+                    Err(key_id) => Frame::Locked(LockedRecord { start: pos, end: next, key_id }),
+                })
+            }
+            Err(reason) => {
+                self.pos = self.resume_after(pos);
+                Some(Frame::Corrupt(CorruptRegion { start: pos, end: self.pos, reason }))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ByteScan {
+    pub records: Vec<(u64, u64, OutboxRecord)>,
+    pub unknown_key: Vec<LockedRecord>,
+    pub corrupt: Vec<CorruptRegion>,
+}
+
+fn collect<R: BufRead + Seek>(frames: Frames<'_, R>) -> ByteScan {
+    let mut out = ByteScan::default();
+    for frame in frames {
+        match frame {
+            Frame::Record(start, end, rec) => out.records.push((start, end, rec)),
+            Frame::Locked(locked) => out.unknown_key.push(locked),
+            Frame::Corrupt(region) => out.corrupt.push(region),
+        }
+    }
+    out
+}
+
+pub fn scan_bytes(codec: &OutboxCodec, data: &[u8]) -> ByteScan {
+    Frames::new(codec, Cursor::new(data)).map(collect).unwrap_or_default()
+}
+
+impl Outbox {
+    fn frames(&self) -> AppResult<Frames<'_, BufReader<File>>> {
+        let file = File::open(&self.files.log_path).map_err(|e| AppError::io("outbox read", e))?;
+        Frames::new(&self.codec, BufReader::new(file)).map_err(|e| AppError::io("outbox read", e))
+    }
+
+    pub fn scan(&self) -> AppResult<OutboxScan> {
+        let cursor = self.load_cursor()?;
+        let frames = self.frames()?;
+        let file_len = frames.len;
+        let ByteScan { records: entries, unknown_key, corrupt } = collect(frames);
+        let cursor_on_boundary = cursor == file_len
+            || entries.iter().any(|(start, _, _)| *start == cursor)
+            || unknown_key.iter().any(|r| r.start == cursor);
+        Ok(OutboxScan {
+            file_len,
+            cursor,
+            cursor_on_boundary,
+            records: entries.len(),
+            pending_records: entries.iter().filter(|(start, _, _)| *start >= cursor).count(),
+            corrupt,
+            unknown_key,
+            entries,
+        })
+    }
+
+    pub fn find(&self, filter: &OutboxFilter, from: u64, limit: usize) -> AppResult<Vec<OutboxEntry>> {
+        let mut out = Vec::new();
+        if limit == 0 { return Ok(out); }
+        for (start, end, rec) in self.frames()?.filter_map(Frame::record) {
+            if start < from || !filter.matches(&rec) { continue; }
+            out.push(OutboxEntry::new(start, end, &rec));
+            if out.len() >= limit { break; }
+        }
+        Ok(out)
+    }
+
+    pub fn seek_to_key(&self, key: &str) -> AppResult<u64> {
+        let (offset, _, _) = self
+            .frames()?
+            .filter_map(Frame::record)
+            .find(|(_, _, rec)| rec.idempotency_key == key)
+            .ok_or_else(|| AppError::not_found(format!("no outbox record with key '{}'", key)))?;
+        self.store_cursor(offset)?;
+        Ok(offset)
+    }
+
+    pub fn export_to<W: Write>(&self, mut out: W, include_replayed: bool) -> AppResult<TransferSummary> {
+        let cursor = self.load_cursor()?;
+        let mut summary = TransferSummary::default();
+        for frame in self.frames()? {
+            let (start, rec) = match frame {
+                Frame::Record(start, _, rec) => (start, rec),
+                Frame::Locked(_) => {
+                    summary.skipped_unknown_key += 1;
+                    continue;
+                }
+                Frame::Corrupt(_) => {
+                    summary.skipped_corrupt_regions += 1;
+                    continue;
+                }
+            };
+            let replayed = start < cursor;
+            if replayed && !include_replayed { continue; }
+            let line = ExportedRecord {
+                key: rec.idempotency_key,
+                target: rec.target,
+                created_ms: rec.created_ms,
+                statement: rec.statement,
+                params: rec.params.iter().map(|p| to_hex(p)).collect(),
+                replayed,
+            };
+            serde_json::to_writer(&mut out, &line).map_err(|e| AppError::json("encode outbox export", e))?;
+            out.write_all(b"\n").map_err(|e| AppError::io("write outbox export", e))?;
+            summary.records += 1;
+            if replayed { summary.replayed += 1; }
+        }
+        out.flush().map_err(|e| AppError::io("write outbox export", e))?;
+        Ok(summary)
+    }
+
+    pub fn import_from<R: BufRead>(&self, input: R) -> AppResult<TransferSummary> {
+        if self.end_offset()? != 0 {
+            return Err(AppError::validation(format!("outbox {} is not empty; import only into an empty outbox", self.files.dir.display())));
+        }
+        let mut records = Vec::new();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.map_err(|e| AppError::io("read outbox import", e))?;
+            if line.trim().is_empty() { continue; }
+            let rec: ExportedRecord = serde_json::from_str(&line).map_err(|e| AppError::json(format!("outbox import line {}", i + 1), e))?;
+            let params = rec
+                .params
+                .iter()
+                .map(|p| from_hex(p).ok_or_else(|| AppError::validation(format!("outbox import line {}: param is not valid hex", i + 1))))
+                .collect::<AppResult<Vec<_>>>()?;
+            if rec.replayed && records.last().is_some_and(|(_, replayed)| !replayed) {
+                return Err(AppError::validation(format!("outbox import line {}: replayed record follows a pending one", i + 1)));
+            }
+            let record = OutboxRecord { idempotency_key: rec.key, statement: rec.statement, params, target: rec.target, created_ms: rec.created_ms };
+            records.push((record, rec.replayed));
+        }
+
+        let replayed = records.iter().take_while(|(_, replayed)| *replayed).count();
+        let ends = self.append_batch(records.into_iter().map(|(record, _)| record).collect())?;
+        let mut summary = TransferSummary { records: ends.len(), replayed, ..TransferSummary::default() };
+        if replayed > 0 {
+            summary.cursor = ends[replayed - 1];
+        }
+        self.store_cursor(summary.cursor)?;
+        Ok(summary)
+    }
+}
@@ -1,27 +1,86 @@
 use core::future::Future;
 
-use scylla::client::session::Session;
 use scylla::statement::Consistency;
 use scylla::statement::unprepared::Statement as UnpreparedStatement;
+use log::{info, warn};
 use scylla::value::Row;
+use serde::{Deserialize, Serialize};
 
 use std::time::{Instant, SystemTime, UNIX_EPOCH, Duration};
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write, Seek, SeekFrom};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::path::{Path, PathBuf};
 
-use crate::config::AppConfig;
+use crate::config::{AppConfig, SecretResolver, SharedConfig};
 use crate::db::DbClients;
 use crate::errors::{AppError, AppResult};
-use crate::health::{db_health, DbHealth};
+use crate::health::{db_health, ClusterHealth, DbHealth, HealthState};
 use crate::types::ApiResponse;
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+mod codec;
+pub mod inspect;
+mod writer;
+
+pub use codec::{OutboxCodec, OutboxCompression};
+
+use codec::{read_header, FrameError};
+use writer::GroupWriter;
+pub use inspect::{CorruptRegion, ExportedRecord, LockedRecord, OutboxEntry, OutboxFilter, OutboxScan, ParamView, TransferSummary};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Cluster {
     Active,
     Passive,
 }
 
+impl Cluster {
+    pub fn label(self) -> &'static str {
+        match self {
+            Cluster::Active => "Active",
+            Cluster::Passive => "Passive",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "active" => Some(Cluster::Active),
+            "passive" => Some(Cluster::Passive),
+            _ => None,
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Cluster::Active => Cluster::Passive,
+            Cluster::Passive => Cluster::Active,
+        }
+    }
+}
+
+const PRIMARY_FILE: &str = "failover.primary";
+
+pub fn load_primary<P: AsRef<Path>>(dir: P) -> AppResult<Option<Cluster>> {
+    let path = dir.as_ref().join(PRIMARY_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(s) => Cluster::parse(&s)
+            .map(Some)
+            .ok_or_else(|| AppError::config(format!("{} names unknown cluster '{}'", path.display(), s.trim()))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(AppError::io(format!("failed to read {}", path.display()), e)),
+    }
+}
+
+pub fn store_primary<P: AsRef<Path>>(dir: P, cluster: Cluster) -> AppResult<()> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir).map_err(|e| AppError::io("failover state dir", e))?;
+    let path = dir.join(PRIMARY_FILE);
+    let tmp = dir.join(format!("{}.tmp", PRIMARY_FILE));
+    std::fs::write(&tmp, cluster.label().to_ascii_lowercase()).map_err(|e| AppError::io(format!("failed to write {}", tmp.display()), e))?;
+    std::fs::rename(&tmp, &path).map_err(|e| AppError::io(format!("failed to replace {}", path.display()), e))
+}
+
 #[allow(async_fn_in_trait)]
 pub trait SyncCheck: Send + Sync {
     async fn ready_to_switch(
@@ -32,9 +91,21 @@ pub trait SyncCheck: Send + Sync {
     ) -> bool;
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutboxTarget { Active, Passive, Both }
 
+impl OutboxTarget {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "active" => Some(OutboxTarget::Active),
+            "passive" => Some(OutboxTarget::Passive),
+            "both" => Some(OutboxTarget::Both),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OutboxRecord {
     pub idempotency_key: String,
@@ -106,117 +177,351 @@ impl OutboxRecord {
     }
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxStats {
+    pub dir: String,
+    pub cursor: u64,
+    pub end: u64,
+    pub records: usize,
+    pub pending_records: usize,
+    pub pending_bytes: u64,
+}
+
 const OB_MAGIC: u32 = 0x4E415944;
 const OB_VERSION: u16 = 1;
 const HEADER_LEN: usize = 4 + 2 + 4;
 
 #[derive(Debug)]
-pub struct Outbox {
+struct OutboxFiles {
     dir: PathBuf,
     log_path: PathBuf,
     cursor_path: PathBuf,
-    file: File,
-    fsync: bool,
+    writer: Option<GroupWriter>,
+    cursor: Mutex<()>,
+    _lock: Option<File>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    files: Arc<OutboxFiles>,
+    codec: Arc<OutboxCodec>,
+}
+
+fn sync_dir(dir: &Path) -> AppResult<()> {
+    File::open(dir).and_then(|d| d.sync_all()).map_err(|e| AppError::io("outbox sync dir", e))
+}
+
+fn lock_outbox_dir(dir: &Path) -> AppResult<File> {
+    let mut file = OpenOptions::new().create(true).truncate(false).write(true).open(dir.join("outbox.lock"))
+        .map_err(|e| AppError::io("outbox open lock", e))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            return Err(AppError::unavailable(format!("outbox directory {} is in use by another process", dir.display())));
+        }
+        Err(TryLockError::Error(e)) => return Err(AppError::io("outbox lock", e)),
+    }
+    file.set_len(0).map_err(|e| AppError::io("outbox write lock", e))?;
+    file.write_all(format!("{}\n", std::process::id()).as_bytes()).map_err(|e| AppError::io("outbox write lock", e))?;
+    Ok(file)
 }
 
 impl Outbox {
     pub fn open<P: AsRef<Path>>(dir: P) -> AppResult<Self> {
         let dir_path = dir.as_ref().to_path_buf();
-        std::fs::create_dir_all(&dir_path).map_err(|e| AppError::other(format!("outbox create dir: {}", e)))?;
+        std::fs::create_dir_all(&dir_path).map_err(|e| AppError::io("outbox create dir", e))?;
+        let lock = lock_outbox_dir(&dir_path)?;
         let log_path = dir_path.join("outbox.log");
         let cursor_path = dir_path.join("outbox.cursor");
-        let file = OpenOptions::new().create(true).append(true).open(&log_path)
-            .map_err(|e| AppError::other(format!("outbox open log: {}", e)))?;
         if !cursor_path.exists() {
             std::fs::write(&cursor_path, 0u64.to_le_bytes())
-                .map_err(|e| AppError::other(format!("outbox init cursor: {}", e)))?;
+                .map_err(|e| AppError::io("outbox init cursor", e))?;
         }
-        Ok(Outbox { dir: dir_path, log_path, cursor_path, file, fsync: true })
+        Self::from_files(OutboxFiles {
+            dir: dir_path.clone(),
+            log_path: log_path.clone(),
+            cursor_path: cursor_path.clone(),
+            writer: None,
+            cursor: Mutex::new(()),
+            _lock: None,
+        })
+        .recover_truncate()?;
+        let writer = GroupWriter::open(&log_path)?;
+        Ok(Self::from_files(OutboxFiles { dir: dir_path, log_path, cursor_path, writer: Some(writer), cursor: Mutex::new(()), _lock: Some(lock) }))
     }
 
-    pub fn with_fsync(mut self, fsync: bool) -> Self { self.fsync = fsync; self }
+    pub fn open_read_only<P: AsRef<Path>>(dir: P) -> AppResult<Self> {
+        let dir_path = dir.as_ref().to_path_buf();
+        if !dir_path.is_dir() {
+            return Err(AppError::not_found(format!("outbox directory {} does not exist", dir_path.display())));
+        }
+        let log_path = dir_path.join("outbox.log");
+        let cursor_path = dir_path.join("outbox.cursor");
+        Ok(Self::from_files(OutboxFiles { dir: dir_path, log_path, cursor_path, writer: None, cursor: Mutex::new(()), _lock: None }))
+    }
+
+    fn from_files(files: OutboxFiles) -> Self {
+        Outbox { files: Arc::new(files), codec: Arc::new(OutboxCodec::plain()) }
+    }
+
+    pub fn open_with_config(cfg: &AppConfig) -> AppResult<Self> {
+        let codec = OutboxCodec::from_config(&cfg.replication, &SecretResolver::from_config(&cfg.secrets))?;
+        Ok(Self::open(&cfg.replication.outbox_dir)?
+            .with_codec(codec)
+            .with_group_commit_window(Duration::from_micros(cfg.replication.outbox_group_commit_us)))
+    }
+
+    pub fn open_read_only_with_config(cfg: &AppConfig) -> AppResult<Self> {
+        let codec = OutboxCodec::from_config(&cfg.replication, &SecretResolver::from_config(&cfg.secrets))?;
+        Ok(Self::open_read_only(&cfg.replication.outbox_dir)?.with_codec(codec))
+    }
+
+    pub fn with_fsync(self, fsync: bool) -> Self {
+        if let Some(w) = &self.files.writer { w.set_fsync(fsync); }
+        self
+    }
+
+    pub fn with_group_commit_window(self, window: Duration) -> Self {
+        if let Some(w) = &self.files.writer { w.set_window(window); }
+        self
+    }
+
+    pub fn with_codec(mut self, codec: OutboxCodec) -> Self { self.codec = Arc::new(codec); self }
+
+    pub fn codec(&self) -> &OutboxCodec { &self.codec }
 
-    pub fn append(&mut self, mut rec: OutboxRecord) -> AppResult<u64> {
-        if rec.created_ms == 0 {
-            rec.created_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+    pub fn dir(&self) -> &Path { &self.files.dir }
+
+    pub fn is_read_only(&self) -> bool { self.files.writer.is_none() }
+
+    fn writer(&self) -> AppResult<&GroupWriter> {
+        self.files.writer.as_ref()
+            .ok_or_else(|| AppError::validation(format!("outbox {} was opened read-only", self.files.dir.display())))
+    }
+
+    fn cursor_guard(&self) -> MutexGuard<'_, ()> { self.files.cursor.lock().unwrap_or_else(|p| p.into_inner()) }
+
+    pub fn check_writable(&self) -> AppResult<()> {
+        self.writer()?;
+        OpenOptions::new().append(true).open(&self.files.log_path)
+            .map_err(|e| AppError::io("outbox log not writable", e))?;
+        OpenOptions::new().write(true).open(&self.files.cursor_path)
+            .map_err(|e| AppError::io("outbox cursor not writable", e))?;
+        Ok(())
+    }
+
+    pub fn append(&self, rec: OutboxRecord) -> AppResult<u64> {
+        let ends = self.append_batch(vec![rec])?;
+        Ok(ends[0])
+    }
+
+    pub fn append_batch(&self, recs: Vec<OutboxRecord>) -> AppResult<Vec<u64>> {
+        let writer = self.writer()?;
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let frames = recs
+            .into_iter()
+            .map(|mut rec| {
+                if rec.created_ms == 0 { rec.created_ms = now_ms; }
+                self.codec.encode_frame(&rec)
+            })
+            .collect::<AppResult<Vec<_>>>()?;
+        writer.append(&frames)
+    }
+
+    pub async fn append_async(&self, rec: OutboxRecord) -> AppResult<u64> {
+        let ends = self.append_batch_async(vec![rec]).await?;
+        Ok(ends[0])
+    }
+
+    pub async fn append_batch_async(&self, recs: Vec<OutboxRecord>) -> AppResult<Vec<u64>> {
+        self.writer()?;
+        let outbox = self.clone();
+        tokio::task::spawn_blocking(move || outbox.append_batch(recs))
+            .await
+            .map_err(|e| AppError::other(format!("outbox append task failed: {}", e)))?
+    }
+
+    pub fn durable_offset(&self) -> u64 {
+        match &self.files.writer {
+            Some(w) => w.durable(),
+            None => self.end_offset().unwrap_or(0),
         }
-        let payload = rec.encode();
-        let payload_len = payload.len() as u32;
-        let header_len = HEADER_LEN as u64;
-        let mut end_offset = std::fs::metadata(&self.log_path).map(|m| m.len()).unwrap_or(0);
-        self.file.write_all(&OB_MAGIC.to_le_bytes())
-            .and_then(|_| self.file.write_all(&OB_VERSION.to_le_bytes()))
-            .and_then(|_| self.file.write_all(&payload_len.to_le_bytes()))
-            .and_then(|_| self.file.write_all(&payload))
-            .map_err(|e| AppError::other(format!("outbox append: {}", e)))?;
-        if self.fsync {
-            self.file.sync_data().ok();
+    }
+
+    pub fn commit_count(&self) -> u64 { self.files.writer.as_ref().map_or(0, |w| w.commits()) }
+
+    fn read_cursor_state(&self) -> AppResult<(u64, Option<u64>)> {
+        let bytes = std::fs::read(&self.files.cursor_path).map_err(|e| AppError::io("cursor read", e))?;
+        let word = |at: usize| bytes.get(at..at + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()));
+        let offset = word(0).ok_or_else(|| AppError::io("cursor read", std::io::ErrorKind::UnexpectedEof.into()))?;
+        Ok((offset, word(8)))
+    }
+
+    fn read_cursor_file(&self) -> AppResult<u64> {
+        match self.read_cursor_state()? {
+            (offset, Some(rebase)) if !self.truncate_tmp_path().exists() => Ok(offset.saturating_sub(rebase)),
+            (offset, _) => Ok(offset),
+        }
+    }
+
+    fn recover_truncate(&self) -> AppResult<()> {
+        let tmp_path = self.truncate_tmp_path();
+        match self.read_cursor_state()? {
+            (offset, Some(rebase)) => {
+                if tmp_path.exists() {
+                    std::fs::rename(&tmp_path, &self.files.log_path).map_err(|e| AppError::io("outbox truncate rename", e))?;
+                    sync_dir(&self.files.dir)?;
+                }
+                self.write_cursor_state(offset.saturating_sub(rebase), None)
+            }
+            (_, None) if tmp_path.exists() => {
+                std::fs::remove_file(&tmp_path).map_err(|e| AppError::io("outbox remove truncate temp", e))
+            }
+            _ => Ok(()),
         }
-        end_offset += header_len + payload_len as u64;
-        Ok(end_offset)
     }
 
+    fn truncate_tmp_path(&self) -> PathBuf { self.files.dir.join("outbox.log.tmp") }
+
     pub fn load_cursor(&self) -> AppResult<u64> {
-        let mut buf = [0u8; 8];
-        let mut f = File::open(&self.cursor_path).map_err(|e| AppError::other(format!("cursor open: {}", e)))?;
-        f.read_exact(&mut buf).map_err(|e| AppError::other(format!("cursor read: {}", e)))?;
-        Ok(u64::from_le_bytes(buf))
+        let _guard = self.cursor_guard();
+        self.read_cursor_file()
     }
 
     pub fn end_offset(&self) -> AppResult<u64> {
-        std::fs::metadata(&self.log_path)
-            .map(|m| m.len())
-            .map_err(|e| AppError::other(format!("outbox metadata: {}", e)))
+        match &self.files.writer {
+            Some(w) => Ok(w.end()),
+            None => match std::fs::metadata(&self.files.log_path) {
+                Ok(meta) => Ok(meta.len()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+                Err(e) => Err(AppError::io("outbox metadata", e)),
+            },
+        }
     }
 
     pub fn current_cursor(&self) -> AppResult<u64> { self.load_cursor() }
 
     pub fn store_cursor(&self, offset: u64) -> AppResult<()> {
-        std::fs::write(&self.cursor_path, offset.to_le_bytes()).map_err(|e| AppError::other(format!("cursor write: {}", e)))
+        self.writer()?;
+        let _guard = self.cursor_guard();
+        self.write_cursor_file(offset)
+    }
+
+    fn write_cursor_file(&self, offset: u64) -> AppResult<()> { self.write_cursor_state(offset, None) }
+
+    fn write_cursor_state(&self, offset: u64, rebase: Option<u64>) -> AppResult<()> {
+        let mut bytes = offset.to_le_bytes().to_vec();
+        if let Some(rebase) = rebase { bytes.extend_from_slice(&rebase.to_le_bytes()); }
+        let tmp_path = self.files.dir.join("outbox.cursor.tmp");
+        {
+            let mut tmp = File::create(&tmp_path).map_err(|e| AppError::io("cursor write", e))?;
+            tmp.write_all(&bytes).map_err(|e| AppError::io("cursor write", e))?;
+            tmp.sync_data().map_err(|e| AppError::io("cursor sync", e))?;
+        }
+        std::fs::rename(&tmp_path, &self.files.cursor_path).map_err(|e| AppError::io("cursor rename", e))
     }
 
     pub fn read_from(&self, mut offset: u64, max: usize) -> AppResult<Vec<(u64, u64, OutboxRecord)>> {
-        let mut f = OpenOptions::new().read(true).open(&self.log_path)
-            .map_err(|e| AppError::other(format!("outbox read open: {}", e)))?;
+        let mut f = OpenOptions::new().read(true).open(&self.files.log_path)
+            .map_err(|e| AppError::io("outbox read open", e))?;
+        let len = f.metadata().map_err(|e| AppError::io("outbox metadata", e))?.len();
         f.seek(SeekFrom::Start(offset)).ok();
         let mut out = Vec::new();
         for _ in 0..max {
-            let mut hbuf = [0u8; HEADER_LEN];
-            match f.read_exact(&mut hbuf) {
-                Ok(()) => {}
-                Err(e) => {
-                    let _ = e; break;
+            let Ok((info, hbuf)) = read_header(&mut f) else { break };
+            if offset + info.frame_len() > len { break; }
+            let mut payload = vec![0u8; info.payload_len];
+            if f.read_exact(&mut payload).is_err() { break; }
+            let rec = match self.codec.decode_payload(&hbuf[..info.header_len], &info, &payload) {
+                Ok(r) => r,
+                Err(FrameError::MissingKey(id)) => {
+                    return Err(AppError::config(format!(
+                        "outbox record at offset {} is encrypted with key {} which is not configured",
+                        offset, id
+                    )));
                 }
-            }
-            let magic = u32::from_le_bytes([hbuf[0], hbuf[1], hbuf[2], hbuf[3]]);
-            let version = u16::from_le_bytes([hbuf[4], hbuf[5]]);
-            let plen = u32::from_le_bytes([hbuf[6], hbuf[7], hbuf[8], hbuf[9]]) as usize;
-            if magic != OB_MAGIC || version != OB_VERSION { break; }
-            let mut payload = vec![0u8; plen];
-            if let Err(_) = f.read_exact(&mut payload) { break; }
-            let rec = match OutboxRecord::decode(&payload) { Some(r) => r, None => break };
+                Err(FrameError::Corrupt(_)) => break,
+            };
             let start = offset;
-            offset = offset + HEADER_LEN as u64 + plen as u64;
+            offset += info.frame_len();
             out.push((start, offset, rec));
         }
         Ok(out)
     }
 
+    fn record_offsets(&self) -> AppResult<(Vec<u64>, u64)> {
+        let mut f = OpenOptions::new().read(true).open(&self.files.log_path)
+            .map_err(|e| AppError::io("outbox read open", e))?;
+        let mut offsets = Vec::new();
+        let mut offset = 0u64;
+        while let Ok((info, _)) = read_header(&mut f) {
+            if f.seek(SeekFrom::Current(info.payload_len as i64)).is_err() { break; }
+            offsets.push(offset);
+            offset += info.frame_len();
+        }
+        Ok((offsets, offset))
+    }
+
+    pub fn stats(&self) -> AppResult<OutboxStats> {
+        let cursor = self.load_cursor()?;
+        let end = self.end_offset()?;
+        let (offsets, _) = self.record_offsets()?;
+        Ok(OutboxStats {
+            dir: self.files.dir.display().to_string(),
+            cursor,
+            end,
+            records: offsets.len(),
+            pending_records: offsets.iter().filter(|o| **o >= cursor).count(),
+            pending_bytes: end.saturating_sub(cursor),
+        })
+    }
+
+    pub fn tail(&self, n: usize) -> AppResult<Vec<(u64, u64, OutboxRecord)>> {
+        let (offsets, _) = self.record_offsets()?;
+        match offsets.get(offsets.len().saturating_sub(n)) {
+            Some(&start) => self.read_from(start, n),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn requeue(&self, offset: u64) -> AppResult<()> {
+        let (offsets, valid_end) = self.record_offsets()?;
+        if offset != valid_end && offsets.binary_search(&offset).is_err() {
+            return Err(AppError::validation(format!("offset {} is not the start of an outbox record", offset)));
+        }
+        self.store_cursor(offset)
+    }
+
+    pub fn truncate(&self, drop_pending: bool) -> AppResult<u64> {
+        let writer = self.writer()?;
+        let _guard = self.cursor_guard();
+        writer.rewrite(|end| {
+            let cursor = self.read_cursor_file()?;
+            let keep_from = if drop_pending { end } else { cursor.min(end) };
+            let tmp_path = self.truncate_tmp_path();
+            {
+                let mut src = File::open(&self.files.log_path).map_err(|e| AppError::io("outbox read open", e))?;
+                src.seek(SeekFrom::Start(keep_from)).map_err(|e| AppError::io("outbox seek", e))?;
+                let mut tmp = File::create(&tmp_path).map_err(|e| AppError::io("outbox truncate temp", e))?;
+                std::io::copy(&mut src.take(end - keep_from), &mut tmp).map_err(|e| AppError::io("outbox truncate copy", e))?;
+                tmp.sync_all().map_err(|e| AppError::io("outbox truncate sync", e))?;
+            }
+            self.write_cursor_state(cursor, Some(keep_from))?;
+            std::fs::rename(&tmp_path, &self.files.log_path).map_err(|e| AppError::io("outbox truncate rename", e))?;
+            sync_dir(&self.files.dir)?;
+            self.write_cursor_file(cursor.saturating_sub(keep_from))?;
+            Ok(keep_from)
+        })
+    }
+
     pub fn pending_count(&self) -> AppResult<usize> {
-        let mut f = OpenOptions::new().read(true).open(&self.log_path)
-            .map_err(|e| AppError::other(format!("outbox read open: {}", e)))?;
-        let mut offset = self.load_cursor()?;
+        let mut f = OpenOptions::new().read(true).open(&self.files.log_path)
+            .map_err(|e| AppError::io("outbox read open", e))?;
+        let offset = self.load_cursor()?;
         f.seek(SeekFrom::Start(offset)).ok();
         let mut count = 0usize;
-        loop {
-            let mut hbuf = [0u8; HEADER_LEN];
-            if f.read_exact(&mut hbuf).is_err() { break; }
-            let magic = u32::from_le_bytes([hbuf[0], hbuf[1], hbuf[2], hbuf[3]]);
-            let version = u16::from_le_bytes([hbuf[4], hbuf[5]]);
-            let plen = u32::from_le_bytes([hbuf[6], hbuf[7], hbuf[8], hbuf[9]]) as usize;
-            if magic != OB_MAGIC || version != OB_VERSION { break; }
-            if f.seek(SeekFrom::Current(plen as i64)).is_err() { break; }
-            offset += HEADER_LEN as u64 + plen as u64;
+        while let Ok((info, _)) = read_header(&mut f) {
+            if f.seek(SeekFrom::Current(info.payload_len as i64)).is_err() { break; }
             count += 1;
         }
         Ok(count)
@@ -254,11 +559,16 @@ struct FailoverState {
     primary: Cluster,
     last_active_ok: bool,
     last_passive_ok: bool,
+    last_active_state: HealthState,
+    last_passive_state: HealthState,
     consecutive_active_fail: u32,
     consecutive_active_success: u32,
     consecutive_passive_success: u32,
     pending: Option<Cluster>,
     last_switch: Option<Instant>,
+    fail_threshold: u32,
+    recover_threshold: u32,
+    promoted: bool,
 }
 
 impl Default for FailoverState {
@@ -267,22 +577,27 @@ impl Default for FailoverState {
             primary: Cluster::Active,
             last_active_ok: false,
             last_passive_ok: false,
+            last_active_state: HealthState::Down,
+            last_passive_state: HealthState::Down,
             consecutive_active_fail: 0,
             consecutive_active_success: 0,
             consecutive_passive_success: 0,
             pending: None,
             last_switch: None,
+            fail_threshold: 3,
+            recover_threshold: 5,
+            promoted: false,
         }
     }
 }
 
 impl FailoverState {
-    const FAIL_THRESHOLD: u32 = 3;
-    const RECOVER_THRESHOLD: u32 = 5;
-
-    fn update_with(&mut self, active_ok: bool, passive_ok: bool) {
+    fn update_with(&mut self, active: HealthState, passive: HealthState) {
+        let (active_ok, passive_ok) = (active.is_reachable(), passive.is_reachable());
         self.last_active_ok = active_ok;
         self.last_passive_ok = passive_ok;
+        self.last_active_state = active;
+        self.last_passive_state = passive;
 
         if active_ok {
             self.consecutive_active_success = self.consecutive_active_success.saturating_add(1);
@@ -300,14 +615,14 @@ impl FailoverState {
 
         self.pending = match self.primary {
             Cluster::Active => {
-                if !active_ok && self.consecutive_active_fail >= Self::FAIL_THRESHOLD && passive_ok {
+                if !active_ok && self.consecutive_active_fail >= self.fail_threshold && passive_ok {
                     Some(Cluster::Passive)
                 } else {
                     None
                 }
             }
             Cluster::Passive => {
-                if active_ok && self.consecutive_active_success >= Self::RECOVER_THRESHOLD {
+                if !self.promoted && active_ok && self.consecutive_active_success >= self.recover_threshold {
                     Some(Cluster::Active)
                 } else {
                     None
@@ -323,58 +638,106 @@ impl FailoverState {
         self.consecutive_active_success = 0;
         self.consecutive_passive_success = 0;
         self.pending = None;
+        self.promoted = false;
     }
 
     fn pending(&self) -> Option<Cluster> { self.pending }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct FailoverManager {
     state: FailoverState,
     sync: DefaultSyncCheck,
     force_ready: bool,
-}
-
-impl Default for FailoverManager {
-    fn default() -> Self {
-        Self { state: FailoverState::default(), sync: DefaultSyncCheck::default(), force_ready: false }
-    }
+    state_dir: Option<PathBuf>,
+    persisted: Option<Cluster>,
 }
 
 impl FailoverManager {
     pub fn new() -> Self { Self::default() }
 
     pub fn new_with_config(cfg: &AppConfig) -> Self {
-        let mut checker = DefaultSyncCheck::default();
-        checker.active_keyspace = Some(cfg.active.keyspace.clone());
-        checker.passive_keyspace = Some(cfg.passive.keyspace.clone());
-        Self { state: FailoverState::default(), sync: checker, force_ready: false }
+        let checker = DefaultSyncCheck::with_keyspaces(cfg.active.keyspace.clone(), cfg.passive.keyspace.clone());
+        let state_dir = Some(PathBuf::from(&cfg.replication.outbox_dir));
+        let mut mgr = Self { state: FailoverState::default(), sync: checker, force_ready: false, state_dir, persisted: None }
+            .with_thresholds(cfg.replication.failover_fail_threshold, cfg.replication.failover_recover_threshold);
+        match load_primary(&cfg.replication.outbox_dir) {
+            Ok(Some(primary)) => {
+                mgr.state.primary = primary;
+                mgr.persisted = Some(primary);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Ignoring persisted failover state: {}", e.to_message()),
+        }
+        mgr
     }
 
     pub fn with_force_ready(mut self, v: bool) -> Self { self.force_ready = v; self }
 
+    pub fn with_thresholds(mut self, fail: u32, recover: u32) -> Self {
+        self.set_thresholds(fail, recover);
+        self
+    }
+
+    pub fn set_thresholds(&mut self, fail: u32, recover: u32) {
+        self.state.fail_threshold = fail.max(1);
+        self.state.recover_threshold = recover.max(1);
+    }
+
+    pub fn thresholds(&self) -> (u32, u32) { (self.state.fail_threshold, self.state.recover_threshold) }
+
     pub fn current_primary(&self) -> Cluster { self.state.primary }
 
     pub fn last_switch(&self) -> Option<Instant> { self.state.last_switch }
 
     pub fn last_status(&self) -> (bool, bool) { (self.state.last_active_ok, self.state.last_passive_ok) }
 
+    pub fn last_health(&self) -> (HealthState, HealthState) { (self.state.last_active_state, self.state.last_passive_state) }
+
     async fn maybe_switch(&mut self, clients: &DbClients) {
         if let Some(to) = self.state.pending() {
             let from = self.state.primary;
             if self.force_ready || self.sync.ready_to_switch(clients, from, to).await {
                 self.state.commit_switch(to);
+                clients.set_primary(to);
+                if let Some(dir) = &self.state_dir {
+                    match store_primary(dir, to) {
+                        Ok(()) => self.persisted = Some(to),
+                        Err(e) => warn!("Failed to persist failover to {}: {}", to.label(), e.to_message()),
+                    }
+                }
             }
         }
     }
 
+    fn adopt_persisted(&mut self, clients: &DbClients) {
+        let Some(dir) = &self.state_dir else { return };
+        let primary = match load_primary(dir) {
+            Ok(Some(p)) => p,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Ignoring persisted failover state: {}", e.to_message());
+                return;
+            }
+        };
+        if self.persisted == Some(primary) { return; }
+        self.persisted = Some(primary);
+        if primary != self.state.primary {
+            info!("Adopting {} as the primary cluster from {}", primary.label(), dir.join(PRIMARY_FILE).display());
+            self.state.commit_switch(primary);
+            self.state.promoted = true;
+            clients.set_primary(primary);
+        }
+    }
+
     pub async fn tick(&mut self, clients: &DbClients) -> ApiResponse<DbHealth> {
+        self.adopt_persisted(clients);
         let resp = db_health(clients).await;
-        let (a_ok, p_ok) = match &resp.data {
-            Some(d) => (d.active_ok, d.passive_ok),
-            None => (false, false),
+        let (a, p) = match &resp.data {
+            Some(d) => (d.active.state, d.passive.state),
+            None => (HealthState::Down, HealthState::Down),
         };
-        self.state.update_with(a_ok, p_ok);
+        self.state.update_with(a, p);
 
         self.maybe_switch(clients).await;
 
@@ -382,26 +745,41 @@ impl FailoverManager {
     }
 
     pub async fn tick_with_status(&mut self, clients: &DbClients, a_ok: bool, p_ok: bool) -> ApiResponse<DbHealth> {
-        let resp = ApiResponse::success_with("databases healthy", DbHealth { active_ok: a_ok, passive_ok: p_ok });
-        self.state.update_with(a_ok, p_ok);
+        self.tick_with_health(clients, HealthState::from_ok(a_ok), HealthState::from_ok(p_ok)).await
+    }
+
+    pub async fn tick_with_health(&mut self, clients: &DbClients, active: HealthState, passive: HealthState) -> ApiResponse<DbHealth> {
+        let health = ClusterHealth::default();
+        let data = DbHealth::from_clusters(ClusterHealth { state: active, ..health.clone() }, ClusterHealth { state: passive, ..health });
+        let resp = ApiResponse::success_with("databases healthy", data);
+        self.adopt_persisted(clients);
+        self.state.update_with(active, passive);
         self.maybe_switch(clients).await;
         resp
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ReplicationManager {
     outbox: Option<Outbox>,
     active_keyspace: Option<String>,
     passive_keyspace: Option<String>,
+    replay: Arc<tokio::sync::Mutex<()>>,
 }
 
 impl ReplicationManager {
-    pub fn new() -> Self { Self { outbox: None, active_keyspace: None, passive_keyspace: None } }
+    pub fn new() -> Self { Self::default() }
 
     pub fn with_outbox_dir<P: AsRef<Path>>(dir: P) -> AppResult<Self> {
-        let ob = Outbox::open(dir)?;
-        Ok(Self { outbox: Some(ob), active_keyspace: None, passive_keyspace: None })
+        Ok(Self::with_outbox(Outbox::open(dir)?))
+    }
+
+    pub fn with_outbox(outbox: Outbox) -> Self {
+        Self { outbox: Some(outbox), ..Self::default() }
+    }
+
+    pub fn from_config(cfg: &AppConfig) -> AppResult<Self> {
+        Ok(Self::with_outbox(Outbox::open_with_config(cfg)?).with_keyspaces(cfg.active.keyspace.clone(), cfg.passive.keyspace.clone()))
     }
 
     pub fn with_keyspaces(mut self, active: impl Into<String>, passive: impl Into<String>) -> Self {
@@ -412,6 +790,13 @@ impl ReplicationManager {
 
     pub fn has_outbox(&self) -> bool { self.outbox.is_some() }
 
+    pub fn check_outbox_writable(&self) -> AppResult<()> {
+        match &self.outbox {
+            Some(ob) => ob.check_writable(),
+            None => Err(AppError::other("outbox is not open")),
+        }
+    }
+
     pub fn queue_len(&self) -> usize {
         match &self.outbox {
             Some(ob) => ob.pending_count().unwrap_or(0),
@@ -419,9 +804,9 @@ impl ReplicationManager {
         }
     }
 
-    pub fn enqueue(&mut self, rec: OutboxRecord) -> AppResult<u64> {
-        match &mut self.outbox {
-            Some(ob) => ob.append(rec),
+    pub async fn enqueue(&self, rec: OutboxRecord) -> AppResult<u64> {
+        match &self.outbox {
+            Some(ob) => ob.append_async(rec).await,
             None => Err(AppError::other("outbox not configured")),
         }
     }
@@ -434,22 +819,12 @@ impl ReplicationManager {
     }
 
     async fn write_watermark_for(&self, which_active: bool, clients: &DbClients, keyspace_opt: &Option<String>, last_id: u64) -> bool {
-        let sess_opt = if which_active { clients.active.as_ref() } else { clients.passive.as_ref() };
-        if let (Some(sess), Some(ks)) = (sess_opt, keyspace_opt.as_ref()) {
-            let create = format!(
-                "CREATE TABLE IF NOT EXISTS {}.repl_watermark (id tinyint PRIMARY KEY, last_applied_log_id bigint, heartbeat_ms bigint)",
-                crate::db::quote_ident(ks)
-            );
-            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
-            let c1 = Self::exec_unpaged_session(Some(sess), &create, Consistency::One).await;
-            let c2 = if which_active {
-                clients.upsert_watermark_active(ks, last_id, now_ms).await
-            } else {
-                clients.upsert_watermark_passive(ks, last_id, now_ms).await
-            };
-            c1 && c2
+        let Some(ks) = keyspace_opt.as_ref() else { return false };
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        if which_active {
+            clients.upsert_watermark_active(ks, last_id, now_ms).await
         } else {
-            false
+            clients.upsert_watermark_passive(ks, last_id, now_ms).await
         }
     }
 
@@ -474,17 +849,17 @@ impl ReplicationManager {
         }
     }
 
-    pub async fn replay_with<F, Fut>(&mut self, max: usize, mut apply: F) -> AppResult<usize>
+    pub async fn replay_with<F, Fut>(&self, max: usize, mut apply: F) -> AppResult<usize>
     where
         F: FnMut(OutboxRecord) -> Fut,
         Fut: Future<Output = bool>,
     {
-        let Some(ob) = self.outbox.as_mut() else { return Ok(0) };
+        let Some(ob) = self.outbox.as_ref() else { return Ok(0) };
+        let _replay = self.replay.lock().await;
         let cursor = ob.load_cursor()?;
         let batch = ob.read_from(cursor, max)?;
         let mut processed = 0usize;
-        for (start, end, rec) in batch {
-            let _ = start;
+        for (_, end, rec) in batch {
             if apply(rec).await {
                 ob.store_cursor(end)?;
                 processed += 1;
@@ -495,24 +870,25 @@ impl ReplicationManager {
         Ok(processed)
     }
 
-    pub async fn replay_and_mark(&mut self, max: usize, clients: &DbClients) -> AppResult<usize> {
+    pub async fn replay_and_mark(&self, max: usize, clients: &DbClients) -> AppResult<usize> {
         let mut processed = 0usize;
         let mut marks: Vec<(Cluster, u64)> = Vec::new();
         {
-            let Some(ob) = self.outbox.as_mut() else { return Ok(0) };
+            let Some(ob) = self.outbox.as_ref() else { return Ok(0) };
+            let _replay = self.replay.lock().await;
             let cursor = ob.load_cursor()?;
             let batch = ob.read_from(cursor, max)?;
             for (_start, end, rec) in batch {
                 let applied_ok = match rec.target {
                     OutboxTarget::Active => {
-                        Self::exec_unpaged_session(clients.active.as_ref(), rec.statement.as_str(), Consistency::LocalQuorum).await
+                        Self::exec_unpaged_session(clients, Cluster::Active, rec.statement.as_str(), None, None).await.is_ok()
                     }
                     OutboxTarget::Passive => {
-                        Self::exec_unpaged_session(clients.passive.as_ref(), rec.statement.as_str(), Consistency::One).await
+                        Self::exec_unpaged_session(clients, Cluster::Passive, rec.statement.as_str(), None, None).await.is_ok()
                     }
                     OutboxTarget::Both => {
-                        let a = Self::exec_unpaged_session(clients.active.as_ref(), rec.statement.as_str(), Consistency::LocalQuorum).await;
-                        let b = Self::exec_unpaged_session(clients.passive.as_ref(), rec.statement.as_str(), Consistency::One).await;
+                        let a = Self::exec_unpaged_session(clients, Cluster::Active, rec.statement.as_str(), None, None).await.is_ok();
+                        let b = Self::exec_unpaged_session(clients, Cluster::Passive, rec.statement.as_str(), None, None).await.is_ok();
                         a && b
                     }
                 };
@@ -541,93 +917,161 @@ impl ReplicationManager {
         Ok(processed)
     }
 
+    pub fn tick(&self) {}
 
-    pub fn tick(&mut self) {}
+    fn fallback_consistency(cluster: Cluster) -> Consistency {
+        match cluster {
+            Cluster::Active => Consistency::LocalQuorum,
+            Cluster::Passive => Consistency::One,
+        }
+    }
 
-    fn build_statement(cql: &str, consistency: Consistency) -> UnpreparedStatement {
+    fn build_statement(
+        clients: &DbClients,
+        cluster: Cluster,
+        cql: &str,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+    ) -> UnpreparedStatement {
         let mut st = UnpreparedStatement::new(cql);
-        st.set_consistency(consistency);
+        let handle = profile.and_then(|name| clients.profile(cluster, name));
+        match consistency {
+            Some(cl) => st.set_consistency(cl),
+            None if handle.is_none() && clients.default_consistency(cluster).is_none() => {
+                st.set_consistency(Self::fallback_consistency(cluster))
+            }
+            None => {}
+        }
+        if handle.is_some() {
+            st.set_execution_profile_handle(handle);
+        }
         st.set_is_idempotent(true);
         st
     }
 
-    async fn exec_unpaged_session(sess_opt: Option<&Session>, cql: &str, consistency: Consistency) -> bool {
-        if let Some(sess) = sess_opt {
-            let st = Self::build_statement(cql, consistency);
-            sess.query_unpaged(st, &[]).await.is_ok()
-        } else {
-            false
-        }
-    }
-
-    async fn try_read_rows(sess_opt: Option<&Session>, cql: &str, consistency: Consistency) -> Option<Vec<Row>> {
-        if let Some(sess) = sess_opt {
-            let st = Self::build_statement(cql, consistency);
-            if let Ok(qr) = sess.query_unpaged(st, &[]).await {
-                if let Ok(rows_res) = qr.into_rows_result() {
-                    if let Ok(mut iter) = rows_res.rows::<Row>() {
-                        let mut rows_out = Vec::new();
-                        while let Some(item) = iter.next() {
-                            match item {
-                                Ok(row) => rows_out.push(row),
-                                Err(_) => { break; }
-                            }
-                        }
-                        return Some(rows_out);
-                    }
+    fn check_profile(clients: &DbClients, profile: Option<&str>) -> AppResult<()> {
+        match profile {
+            Some(name) if clients.profile(Cluster::Active, name).is_none() && clients.profile(Cluster::Passive, name).is_none() => {
+                Err(AppError::config(format!("unknown execution profile '{}'", name)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    async fn exec_unpaged_session(
+        clients: &DbClients,
+        cluster: Cluster,
+        cql: &str,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+    ) -> AppResult<()> {
+        let Some(sess) = clients.session(cluster) else {
+            return Err(AppError::unavailable(format!("{} cluster is not connected", cluster.label())));
+        };
+        let st = Self::build_statement(clients, cluster, cql, consistency, profile);
+        let started = Instant::now();
+        sess.query_unpaged(st, &[]).await.map_err(|e| AppError::query(format!("{} write failed", cluster.label()), e))?;
+        clients.record_latency(cluster, started.elapsed());
+        clients.note_statement(cluster, cql);
+        Ok(())
+    }
+
+    async fn try_read_rows(
+        clients: &DbClients,
+        cluster: Cluster,
+        cql: &str,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+    ) -> Option<Vec<Row>> {
+        let sess = clients.session(cluster)?;
+        let st = Self::build_statement(clients, cluster, cql, consistency, profile);
+        let started = Instant::now();
+        if let Ok(qr) = sess.query_unpaged(st, &[]).await
+            && let Ok(rows_res) = qr.into_rows_result()
+            && let Ok(iter) = rows_res.rows::<Row>()
+        {
+            clients.record_latency(cluster, started.elapsed());
+            let mut rows_out = Vec::new();
+            for item in iter {
+                match item {
+                    Ok(row) => rows_out.push(row),
+                    Err(_) => { break; }
                 }
             }
+            return Some(rows_out);
         }
         None
     }
 
+    pub async fn apply_simple(
+        &self,
+        cql: &str,
+        target: OutboxTarget,
+        consistency: Option<Consistency>,
+        clients: &DbClients,
+    ) -> WriteOutcome {
+        self.apply_simple_with_profile(cql, target, consistency, None, clients).await
+    }
+
+    pub async fn apply_simple_with_profile(
+        &self,
+        cql: &str,
+        target: OutboxTarget,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+        clients: &DbClients,
+    ) -> WriteOutcome {
+        let mut outcome = WriteOutcome::default();
+
+        let clusters: &[(Cluster, OutboxTarget)] = match target {
+            OutboxTarget::Active => &[(Cluster::Active, OutboxTarget::Active)],
+            OutboxTarget::Passive => &[(Cluster::Passive, OutboxTarget::Passive)],
+            OutboxTarget::Both => &[(Cluster::Active, OutboxTarget::Active), (Cluster::Passive, OutboxTarget::Passive)],
+        };
+        for &(cluster, single) in clusters {
+            match Self::exec_unpaged_session(clients, cluster, cql, consistency, profile).await {
+                Ok(()) => outcome.any_ok = true,
+                Err(e) if e.is_retryable() => outcome.failed.push(single),
+                Err(e) => outcome.rejected.push((single, e)),
+            }
+        }
+
+        outcome
+    }
+
     pub async fn write_simple(
-        &mut self,
+        &self,
         idempotency_key: impl Into<String>,
         cql: impl Into<String>,
         target: OutboxTarget,
         consistency: Option<Consistency>,
         clients: &DbClients,
     ) -> AppResult<bool> {
+        self.write_simple_with_profile(idempotency_key, cql, target, consistency, None, clients).await
+    }
+
+    pub async fn write_simple_with_profile(
+        &self,
+        idempotency_key: impl Into<String>,
+        cql: impl Into<String>,
+        target: OutboxTarget,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+        clients: &DbClients,
+    ) -> AppResult<bool> {
+        Self::check_profile(clients, profile)?;
         let key = idempotency_key.into();
         let cql = cql.into();
-        let mut any_ok = false;
-
-        match target {
-            OutboxTarget::Active => {
-                let cl = consistency.unwrap_or(Consistency::LocalQuorum);
-                let ok = Self::exec_unpaged_session(clients.active.as_ref(), &cql, cl).await;
-                if !ok {
-                    let _ = self.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), OutboxTarget::Active));
-                }
-                any_ok |= ok;
-            }
-            OutboxTarget::Passive => {
-                let cl = consistency.unwrap_or(Consistency::One);
-                let ok = Self::exec_unpaged_session(clients.passive.as_ref(), &cql, cl).await;
-                if !ok {
-                    let _ = self.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), OutboxTarget::Passive));
-                }
-                any_ok |= ok;
-            }
-            OutboxTarget::Both => {
-                let cl_a = consistency.unwrap_or(Consistency::LocalQuorum);
-                let ok_a = Self::exec_unpaged_session(clients.active.as_ref(), &cql, cl_a).await;
-                if !ok_a {
-                    let _ = self.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), OutboxTarget::Active));
-                }
-                any_ok |= ok_a;
 
-                let cl_p = consistency.unwrap_or(Consistency::One);
-                let ok_p = Self::exec_unpaged_session(clients.passive.as_ref(), &cql, cl_p).await;
-                if !ok_p {
-                    let _ = self.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), OutboxTarget::Passive));
-                }
-                any_ok |= ok_p;
-            }
+        let outcome = self.apply_simple_with_profile(&cql, target, consistency, profile, clients).await;
+        for failed in outcome.failed {
+            let _ = self.enqueue(OutboxRecord::new_simple(key.clone(), cql.clone(), failed)).await;
+        }
+        if !outcome.any_ok && let Some((_, e)) = outcome.rejected.into_iter().next() {
+            return Err(e);
         }
 
-        Ok(any_ok)
+        Ok(outcome.any_ok)
     }
 
     pub async fn read_simple(
@@ -636,26 +1080,40 @@ impl ReplicationManager {
         consistency: Option<Consistency>,
         clients: &DbClients,
     ) -> AppResult<Option<(Cluster, Vec<Row>)>> {
-        let cql = cql.into();
+        self.read_simple_with_profile(cql, consistency, None, clients).await
+    }
 
-        let cl_a = consistency.unwrap_or(Consistency::LocalQuorum);
-        if let Some(rows_out) = Self::try_read_rows(clients.active.as_ref(), &cql, cl_a).await {
-            return Ok(Some((Cluster::Active, rows_out)));
-        }
+    pub async fn read_simple_with_profile(
+        &self,
+        cql: impl Into<String>,
+        consistency: Option<Consistency>,
+        profile: Option<&str>,
+        clients: &DbClients,
+    ) -> AppResult<Option<(Cluster, Vec<Row>)>> {
+        Self::check_profile(clients, profile)?;
+        let cql = cql.into();
 
-        let cl_p = consistency.unwrap_or(Consistency::One);
-        if let Some(rows_out) = Self::try_read_rows(clients.passive.as_ref(), &cql, cl_p).await {
-            return Ok(Some((Cluster::Passive, rows_out)));
+        for cluster in [Cluster::Active, Cluster::Passive] {
+            if let Some(rows_out) = Self::try_read_rows(clients, cluster, &cql, consistency, profile).await {
+                return Ok(Some((cluster, rows_out)));
+            }
         }
 
         Ok(None)
     }
 
-    pub async fn replay_simple(&mut self, max: usize, clients: &DbClients) -> AppResult<usize> {
+    pub async fn replay_simple(&self, max: usize, clients: &DbClients) -> AppResult<usize> {
         self.replay_and_mark(max, clients).await
     }
 }
 
+#[derive(Debug, Default)]
+pub struct WriteOutcome {
+    pub any_ok: bool,
+    pub failed: Vec<OutboxTarget>,
+    pub rejected: Vec<(OutboxTarget, AppError)>,
+}
+
 #[derive(Debug)]
 pub struct DriftStatus {
     pub pending_records: usize,
@@ -674,6 +1132,11 @@ pub struct SyncWorker {
     drift_rec_threshold: usize,
     drift_bytes_threshold: u64,
     last_drift: Option<DriftStatus>,
+    config: Option<Arc<SharedConfig>>,
+}
+
+impl Default for SyncWorker {
+    fn default() -> Self { Self::new() }
 }
 
 impl SyncWorker {
@@ -686,6 +1149,7 @@ impl SyncWorker {
             drift_rec_threshold: 100,
             drift_bytes_threshold: 1_000_000,
             last_drift: None,
+            config: None,
         }
     }
 
@@ -698,6 +1162,16 @@ impl SyncWorker {
         Ok(self)
     }
 
+    pub fn with_replication(mut self, repl: ReplicationManager) -> Self { self.repl = repl; self }
+
+    pub fn with_failover(mut self, failover: FailoverManager) -> Self { self.failover = failover; self }
+
+    pub fn with_shared_config(mut self, config: Arc<SharedConfig>) -> Self {
+        self.apply_config(&config.current());
+        self.config = Some(config);
+        self
+    }
+
     pub fn with_drift_thresholds(mut self, rec_threshold: usize, bytes_threshold: u64) -> Self {
         self.drift_rec_threshold = rec_threshold;
         self.drift_bytes_threshold = bytes_threshold;
@@ -709,11 +1183,24 @@ impl SyncWorker {
         self
     }
 
+    pub fn apply_config(&mut self, cfg: &AppConfig) {
+        self.drift_rec_threshold = cfg.replication.drift_max_records;
+        self.drift_bytes_threshold = cfg.replication.drift_max_bytes;
+        self.failover.set_thresholds(cfg.replication.failover_fail_threshold, cfg.replication.failover_recover_threshold);
+    }
+
+    pub fn failover(&self) -> &FailoverManager { &self.failover }
+
+    pub fn drift_thresholds(&self) -> (usize, u64) { (self.drift_rec_threshold, self.drift_bytes_threshold) }
+
     pub fn queue_len(&self) -> usize { self.repl.queue_len() }
 
     pub fn has_outbox(&self) -> bool { self.repl.has_outbox() }
 
     pub async fn run_once(&mut self, clients: &DbClients) -> AppResult<(ApiResponse<DbHealth>, usize)> {
+        if let Some(config) = self.config.clone() {
+            self.apply_config(&config.current());
+        }
         let health = self.failover.tick(clients).await;
         let mut processed = 0usize;
         if self.repl.has_outbox() && self.max_replay_per_tick > 0 {
@@ -723,16 +1210,16 @@ impl SyncWorker {
             }
         }
 
-        if self.repl.has_outbox() {
-            if let Ok(Some(cur)) = self.repl.current_cursor() {
-                let _ = self.repl.write_watermark_cluster(Cluster::Passive, cur, clients).await;
-            }
+        if self.repl.has_outbox()
+            && let Ok(Some(cur)) = self.repl.current_cursor()
+        {
+            let _ = self.repl.write_watermark_cluster(Cluster::Passive, cur, clients).await;
         }
 
         if let Some(ds) = self.repl.drift_status(self.drift_rec_threshold, self.drift_bytes_threshold)? {
             let unhealthy = !ds.healthy;
             if unhealthy {
-                log::warn!(
+                warn!(
                     "drift warning: pending_records={} pending_bytes={} cursor={} end={}",
                     ds.pending_records, ds.pending_bytes, ds.cursor, ds.end
                 );
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use crate::errors::{AppError, AppResult};
+
+const WRITE_BUFFER_BYTES: usize = 256 * 1024;
+
+#[derive(Debug)]
+struct Pending {
+    buf: BufWriter<File>,
+    file: Arc<File>,
+    end: u64,
+    flushed: u64,
+    epoch: u64,
+    stale: bool,
+}
+
+#[derive(Debug)]
+struct SyncState {
+    durable: u64,
+    leader: bool,
+    epoch: u64,
+    discarded: BTreeMap<u64, (u64, String)>,
+    inflight: BTreeMap<u64, usize>,
+}
+
+#[derive(Debug)]
+pub(super) struct GroupWriter {
+    path: PathBuf,
+    pending: Mutex<Pending>,
+    sync: Mutex<SyncState>,
+    synced: Condvar,
+    durable_hint: AtomicU64,
+    commits: AtomicU64,
+    fsync: AtomicBool,
+    window_us: AtomicU64,
+}
+
+fn open_log(path: &Path) -> AppResult<(BufWriter<File>, Arc<File>, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| AppError::io("outbox open log", e))?;
+    let len = file.metadata().map_err(|e| AppError::io("outbox metadata", e))?.len();
+    let sync_file = file.try_clone().map_err(|e| AppError::io("outbox open log", e))?;
+    Ok((BufWriter::with_capacity(WRITE_BUFFER_BYTES, file), Arc::new(sync_file), len))
+}
+
+impl GroupWriter {
+    pub(super) fn open(path: &Path) -> AppResult<Self> {
+        let (buf, file, len) = open_log(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            pending: Mutex::new(Pending { buf, file, end: len, flushed: len, epoch: 0, stale: false }),
+            sync: Mutex::new(SyncState { durable: len, leader: false, epoch: 0, discarded: BTreeMap::new(), inflight: BTreeMap::new() }),
+            synced: Condvar::new(),
+            durable_hint: AtomicU64::new(len),
+            commits: AtomicU64::new(0),
+            fsync: AtomicBool::new(true),
+            window_us: AtomicU64::new(0),
+        })
+    }
+
+    pub(super) fn set_fsync(&self, fsync: bool) { self.fsync.store(fsync, Ordering::Relaxed) }
+
+    pub(super) fn set_window(&self, window: Duration) {
+        self.window_us.store(window.as_micros().min(u64::MAX as u128) as u64, Ordering::Relaxed)
+    }
+
+    fn pending(&self) -> MutexGuard<'_, Pending> { self.pending.lock().unwrap_or_else(|p| p.into_inner()) }
+
+    fn state(&self) -> MutexGuard<'_, SyncState> { self.sync.lock().unwrap_or_else(|p| p.into_inner()) }
+
+    pub(super) fn end(&self) -> u64 { self.pending().end }
+
+    pub(super) fn durable(&self) -> u64 { self.durable_hint.load(Ordering::Acquire) }
+
+    pub(super) fn commits(&self) -> u64 { self.commits.load(Ordering::Relaxed) }
+
+    pub(super) fn append(&self, frames: &[Vec<u8>]) -> AppResult<Vec<u64>> {
+        let (epoch, ends) = {
+            let mut p = self.pending();
+            self.reopen_if_stale(&mut p)?;
+            let mut ends = Vec::with_capacity(frames.len());
+            for frame in frames {
+                if let Err(e) = p.buf.write_all(frame) {
+                    return Err(self.discard(&mut p, AppError::io("outbox append", e)));
+                }
+                p.end += frame.len() as u64;
+                ends.push(p.end);
+            }
+            if !ends.is_empty() {
+                *self.state().inflight.entry(p.epoch).or_default() += 1;
+            }
+            (p.epoch, ends)
+        };
+        if let Some(&last) = ends.last() {
+            let committed = self.commit(epoch, last);
+            self.release(epoch);
+            committed?;
+        }
+        Ok(ends)
+    }
+
+    fn lead(&self, mut epoch: Option<u64>, offset: u64) -> AppResult<bool> {
+        let mut state = self.state();
+        loop {
+            if let Some(e) = epoch {
+                if state.epoch != e {
+                    match state.discarded.get(&e) {
+                        Some((kept, cause)) if offset > *kept => {
+                            return Err(AppError::other(format!("outbox write was discarded after an I/O error: {}", cause)));
+                        }
+                        Some(_) => {
+                            epoch = Some(e + 1);
+                            continue;
+                        }
+                        None => return Ok(false),
+                    }
+                }
+                if state.durable >= offset {
+                    return Ok(false);
+                }
+            }
+            if !state.leader {
+                state.leader = true;
+                return Ok(true);
+            }
+            state = self.synced.wait(state).unwrap_or_else(|p| p.into_inner());
+        }
+    }
+
+    fn step_down(&self, epoch: u64, durable: Option<u64>) {
+        let mut state = self.state();
+        state.leader = false;
+        if let Some(durable) = durable {
+            if epoch > state.epoch {
+                state.epoch = epoch;
+                state.durable = durable;
+            } else if epoch == state.epoch {
+                state.durable = state.durable.max(durable);
+            }
+            self.durable_hint.store(state.durable, Ordering::Release);
+        }
+        self.synced.notify_all();
+    }
+
+    fn release(&self, epoch: u64) {
+        let mut state = self.state();
+        if let Some(n) = state.inflight.get_mut(&epoch) {
+            *n -= 1;
+            if *n == 0 { state.inflight.remove(&epoch); }
+        }
+        let floor = state.inflight.keys().next().copied().unwrap_or(state.epoch);
+        state.discarded = state.discarded.split_off(&floor);
+    }
+
+    fn commit(&self, epoch: u64, offset: u64) -> AppResult<()> {
+        if !self.lead(Some(epoch), offset)? {
+            return Ok(());
+        }
+        match self.flush_and_sync() {
+            Ok((epoch, target)) => {
+                self.commits.fetch_add(1, Ordering::Relaxed);
+                self.step_down(epoch, Some(target));
+                Ok(())
+            }
+            Err(e) => {
+                self.step_down(epoch, None);
+                Err(e)
+            }
+        }
+    }
+
+    fn flush_and_sync(&self) -> AppResult<(u64, u64)> {
+        let fsync = self.fsync.load(Ordering::Relaxed);
+        let window = self.window_us.load(Ordering::Relaxed);
+        if fsync && window > 0 {
+            std::thread::sleep(Duration::from_micros(window));
+        }
+        let (epoch, target, file) = {
+            let mut p = self.pending();
+            self.flush_locked(&mut p)?;
+            (p.epoch, p.end, p.file.clone())
+        };
+        if fsync {
+            file.sync_data().map_err(|e| AppError::io("outbox fsync", e))?;
+        }
+        Ok((epoch, target))
+    }
+
+    fn flush_locked(&self, p: &mut Pending) -> AppResult<()> {
+        self.reopen_if_stale(p)?;
+        if let Err(e) = p.buf.flush() {
+            return Err(self.discard(p, AppError::io("outbox flush", e)));
+        }
+        p.flushed = p.end;
+        Ok(())
+    }
+
+    pub(super) fn rewrite<F>(&self, rewrite: F) -> AppResult<u64>
+    where
+        F: FnOnce(u64) -> AppResult<u64>,
+    {
+        self.lead(None, 0)?;
+        let mut p = self.pending();
+        let synced = self.flush_locked(&mut p).and_then(|()| {
+            if self.fsync.load(Ordering::Relaxed) {
+                p.file.sync_data().map_err(|e| AppError::io("outbox fsync", e))?;
+            }
+            Ok(p.end)
+        });
+        let synced = match synced {
+            Ok(end) => end,
+            Err(e) => {
+                let epoch = p.epoch;
+                drop(p);
+                self.step_down(epoch, None);
+                return Err(e);
+            }
+        };
+
+        let outcome = rewrite(synced).and_then(|kept| {
+            let (buf, file, len) = open_log(&self.path)?;
+            *p = Pending { buf, file, end: len, flushed: len, epoch: p.epoch + 1, stale: false };
+            Ok(kept)
+        });
+        let (epoch, durable) = (p.epoch, if outcome.is_ok() { p.end } else { synced });
+        self.step_down(epoch, Some(durable));
+        drop(p);
+        outcome
+    }
+
+    fn reopen_if_stale(&self, p: &mut Pending) -> AppResult<()> {
+        if !p.stale { return Ok(()); }
+        let (fresh, file, _) = open_log(&self.path)?;
+        let _ = std::mem::replace(&mut p.buf, fresh).into_parts();
+        p.file = file;
+        p.stale = false;
+        Ok(())
+    }
+
+    fn discard(&self, p: &mut Pending, err: AppError) -> AppError {
+        let _ = p.file.set_len(p.flushed);
+        p.end = p.flushed;
+        p.epoch += 1;
+        p.stale = true;
+        let reopened = self.reopen_if_stale(p);
+        let mut state = self.state();
+        state.discarded.insert(p.epoch - 1, (p.flushed, err.to_message()));
+        state.epoch = p.epoch;
+        state.durable = state.durable.min(p.flushed);
+        self.durable_hint.store(state.durable, Ordering::Release);
+        self.synced.notify_all();
+        match reopened {
+            Ok(()) => err,
+            Err(e) => e.with_context(err.to_message()),
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use ntex::http::StatusCode;
 use serde::Serialize;
 
 use crate::errors::AppError;
@@ -16,17 +17,84 @@ fn map_app_error_to_detail(err: &AppError) -> (String, String, String) {
         ),
         AppError::Web(msg) => (
             format!("Request error: {}", msg),
-            "Your request could not be completed due to a server-side issue.".to_string(),
-            "Please retry in a moment. If it keeps happening, contact support and include the time of the error and what you tried to do.".to_string(),
+            "The request was malformed or could not be understood by the service.".to_string(),
+            "Check the request path, parameters and body against the API documentation, then send it again.".to_string(),
+        ),
+        AppError::Validation(msg) => (
+            format!("Invalid request: {}", msg),
+            "One of the values supplied with the request is missing or not allowed.".to_string(),
+            "Correct the value named in the message and send the request again.".to_string(),
+        ),
+        AppError::NotFound(msg) => (
+            format!("Not found: {}", msg),
+            "The requested resource does not exist or is no longer available.".to_string(),
+            "Check the identifier you used. Jobs and their artifacts are only kept while the service is running.".to_string(),
+        ),
+        AppError::Forbidden(msg) => (
+            format!("Forbidden: {}", msg),
+            "The request is not allowed from this client or without valid credentials.".to_string(),
+            "Send the request from an allowed address or with the required token, then try again.".to_string(),
+        ),
+        AppError::Unavailable(msg) => (
+            format!("Service unavailable: {}", msg),
+            "A dependency the service needs is temporarily unavailable.".to_string(),
+            "Please retry shortly. If the problem persists, check the health-check endpoints for the failing component.".to_string(),
         ),
         AppError::Other(msg) => (
             format!("Unexpected error: {}", msg),
             "An unexpected problem occurred.".to_string(),
             "Please try again. If the issue persists, contact support with a short description of the action you took and this error message.".to_string(),
         ),
+        AppError::Io { context, .. } => (
+            format!("File system error: {}: {}", context, err.source_chain().join(": ")),
+            "The service could not read or write a file it needs.".to_string(),
+            "Check that the path exists, that the disk is not full, and that the service user has permission to access it. Then try again.".to_string(),
+        ),
+        AppError::Toml { context, source } => (
+            format!("Configuration error: {}: {}", context, source.message()),
+            format!("The configuration could not be parsed. {}", source.to_string().trim()),
+            "Fix the TOML syntax or value at the reported location and restart the service.".to_string(),
+        ),
+        AppError::Json { context, source } => (
+            format!("Encoding error: {}: {}", context, source),
+            "The service could not encode or decode a JSON document.".to_string(),
+            "If the document was supplied by you, check that it is valid JSON with the expected fields. Otherwise contact support with this error message.".to_string(),
+        ),
+        AppError::Query { context, .. } | AppError::Prepare { context, .. } | AppError::SchemaAgreement { context, .. } => {
+            let what = format!("Database error: {}: {}", context, err.source_chain().join(": "));
+            if err.is_retryable() {
+                (
+                    what,
+                    "The database did not complete the request in time, or the replicas or connections it needed were unavailable.".to_string(),
+                    "This is usually temporary. Retry the request; if it keeps failing, check cluster health and node availability.".to_string(),
+                )
+            } else {
+                (
+                    what,
+                    "The database rejected the request.".to_string(),
+                    "Retrying will not help. Check the statement, the table schema and the credentials' permissions named in the message.".to_string(),
+                )
+            }
+        }
+        AppError::Connect { context, .. } => (
+            format!("Database connection error: {}: {}", context, err.source_chain().join(": ")),
+            "The service could not open a session to the database cluster.".to_string(),
+            "Please ensure the database is running and reachable. Check the contact points, port, credentials and TLS settings, then try again.".to_string(),
+        ),
+        AppError::Decode { context, .. } => (
+            format!("Database error: {}: {}", context, err.source_chain().join(": ")),
+            "The database returned data in a shape the service did not expect.".to_string(),
+            "Check that the table schema matches what the service expects and that all schema migrations were applied.".to_string(),
+        ),
     }
 }
 
+#[derive(Clone, Debug, Serialize, PartialEq, Eq)]
+pub struct ErrorInfo {
+    pub code: &'static str,
+    pub retryable: bool,
+}
+
 #[derive(Clone, Debug, Serialize, PartialEq)]
 #[serde(untagged)]
 pub enum ApiMessage {
@@ -40,17 +108,39 @@ pub struct ApiResponse<T> {
     pub message: ApiMessage,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorInfo>,
 }
 
 pub const CODE_SUCCESS: &str = "00";
+pub const CODE_VALIDATION: &str = "10";
+pub const CODE_BAD_REQUEST: &str = "11";
+pub const CODE_FORBIDDEN: &str = "13";
+pub const CODE_NOT_FOUND: &str = "14";
+pub const CODE_CONFIG: &str = "20";
+pub const CODE_DB_UNAVAILABLE: &str = "30";
+pub const CODE_SERVICE_UNAVAILABLE: &str = "31";
+pub const CODE_DB_ERROR: &str = "32";
 pub const CODE_FAILURE: &str = "99";
 
+pub fn status_for_code(code: &str) -> StatusCode {
+    match code {
+        CODE_SUCCESS => StatusCode::OK,
+        CODE_VALIDATION | CODE_BAD_REQUEST => StatusCode::BAD_REQUEST,
+        CODE_FORBIDDEN => StatusCode::FORBIDDEN,
+        CODE_NOT_FOUND => StatusCode::NOT_FOUND,
+        CODE_DB_UNAVAILABLE | CODE_SERVICE_UNAVAILABLE => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
 impl<T> ApiResponse<T> {
     pub fn ok(message: impl Into<String>, data: Option<T>) -> Self {
         Self {
             code: CODE_SUCCESS,
             message: ApiMessage::Text(message.into()),
             data,
+            error: None,
         }
     }
 
@@ -70,6 +160,7 @@ impl<T> ApiResponse<T> {
             code: CODE_FAILURE,
             message: ApiMessage::Detail { what: what.into(), why: why.into(), how: how.into() },
             data: None,
+            error: None,
         }
     }
 
@@ -77,13 +168,24 @@ impl<T> ApiResponse<T> {
         self.code == CODE_SUCCESS
     }
 
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = code;
+        self
+    }
+
+    pub fn status(&self) -> StatusCode { status_for_code(self.code) }
+
+    pub fn from_app_error(err: &AppError) -> Self {
+        let (what, why, how) = map_app_error_to_detail(err);
+        let mut resp = Self::failure_detail(what, why, how).with_code(err.code());
+        resp.error = Some(ErrorInfo { code: err.error_code(), retryable: err.is_retryable() });
+        resp
+    }
+
     pub fn from_result(res: crate::errors::AppResult<T>, success_message: impl Into<String>) -> Self {
         match res {
             Ok(v) => Self::success_with(success_message, v),
-            Err(e) => {
-                let (what, why, how) = map_app_error_to_detail(&e);
-                Self::failure_detail(what, why, how)
-            }
+            Err(e) => Self::from_app_error(&e),
         }
     }
 
@@ -94,7 +196,7 @@ impl<T> ApiResponse<T> {
     ) -> Self {
         match opt {
             Some(v) => Self::success_with(some_message, v),
-            None => Self::failure(none_message),
+            None => Self::failure(none_message).with_code(CODE_NOT_FOUND),
         }
     }
 }
@@ -104,8 +206,5 @@ impl ApiResponse<()> {
         ApiResponse::ok(message, None)
     }
 
-    pub fn from_error(err: &AppError) -> Self {
-        let (what, why, how) = map_app_error_to_detail(err);
-        Self::failure_detail(what, why, how)
-    }
+    pub fn from_error(err: &AppError) -> Self { Self::from_app_error(err) }
 }
\ No newline at end of file
@@ -0,0 +1,337 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use scylla::client::session::Session;
+use scylla::response::{PagingState, PagingStateResponse};
+use scylla::statement::Consistency;
+use scylla::statement::unprepared::Statement as UnpreparedStatement;
+use scylla::value::{CqlValue, Row};
+
+use crate::db::schema::{load_table_schema, TableSchema};
+use crate::db::values::{to_json, to_text};
+use crate::db::{quote_ident, DbClients};
+use crate::errors::{AppError, AppResult};
+use crate::replication::Cluster;
+use crate::utils::now_millis;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+pub struct TokenRange {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl TokenRange {
+    pub fn full_ring() -> Self { Self { start: i64::MIN, end: i64::MAX } }
+
+    pub fn split(&self, parts: u32) -> Vec<TokenRange> {
+        let parts = parts.max(1) as i128;
+        let lo = self.start as i128;
+        let width = self.end as i128 - lo + 1;
+        let parts = parts.min(width);
+        (0..parts)
+            .map(|i| {
+                let start = lo + width * i / parts;
+                let end = lo + width * (i + 1) / parts - 1;
+                TokenRange { start: start as i64, end: end as i64 }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifySpec {
+    pub keyspace: String,
+    pub table: String,
+    pub ranges: u32,
+    pub split_factor: u32,
+    pub leaf_rows: u64,
+    pub max_depth: u32,
+    pub page_size: i32,
+    pub max_diffs: usize,
+    pub consistency: Consistency,
+}
+
+impl VerifySpec {
+    pub fn new(keyspace: impl Into<String>, table: impl Into<String>) -> Self {
+        Self {
+            keyspace: keyspace.into(),
+            table: table.into(),
+            ranges: 256,
+            split_factor: 16,
+            leaf_rows: 1000,
+            max_depth: 4,
+            page_size: 5000,
+            max_diffs: 10_000,
+            consistency: Consistency::LocalQuorum,
+        }
+    }
+
+    pub fn with_ranges(mut self, ranges: u32) -> Self { self.ranges = ranges.max(1); self }
+
+    pub fn with_page_size(mut self, size: i32) -> Self { self.page_size = size.max(1); self }
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize)]
+pub struct RangeDigest {
+    pub rows: u64,
+    pub hash: u64,
+}
+
+impl RangeDigest {
+    pub fn new() -> Self { Self { rows: 0, hash: FNV_OFFSET } }
+
+    pub fn absorb(&mut self, row_hash: u64) {
+        self.rows += 1;
+        self.hash = fnv_extend(self.hash ^ self.rows, &row_hash.to_le_bytes());
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RowDiff {
+    pub token: i64,
+    pub primary_key: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct VerifyReport {
+    pub keyspace: String,
+    pub table: String,
+    pub ranges_total: u64,
+    pub ranges_mismatched: u64,
+    pub leaf_ranges_compared: u64,
+    pub rows_active: u64,
+    pub rows_passive: u64,
+    pub missing: Vec<RowDiff>,
+    pub extra: Vec<RowDiff>,
+    pub differing: Vec<RowDiff>,
+    pub truncated: bool,
+    pub started_ms: u64,
+    pub finished_ms: u64,
+}
+
+impl VerifyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.ranges_mismatched == 0 && self.missing.is_empty() && self.extra.is_empty() && self.differing.is_empty()
+    }
+
+    pub fn diff_count(&self) -> usize { self.missing.len() + self.extra.len() + self.differing.len() }
+}
+
+struct RangeQuery {
+    cql: String,
+    key_names: Vec<String>,
+}
+
+impl RangeQuery {
+    fn build(schema: &TableSchema) -> AppResult<Self> {
+        let partition: Vec<String> = schema.partition_key().iter().map(|c| quote_ident(&c.name)).collect();
+        if partition.is_empty() {
+            return Err(AppError::validation(format!("table {}.{} has no partition key", schema.keyspace, schema.table)));
+        }
+        let key_names: Vec<String> = schema.primary_key().iter().map(|c| c.name.clone()).collect();
+        let mut select: Vec<String> = key_names.iter().map(|n| quote_ident(n)).collect();
+        select.extend(schema.regular_columns().iter().map(|c| quote_ident(&c.name)));
+        let token = format!("token({})", partition.join(", "));
+        let cql = format!(
+            "SELECT {}, {} FROM {}.{} WHERE {} >= ? AND {} <= ?",
+            token,
+            select.join(", "),
+            quote_ident(&schema.keyspace),
+            quote_ident(&schema.table),
+            token,
+            token
+        );
+        Ok(Self { cql, key_names })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScannedRow {
+    pub token: i64,
+    pub key: String,
+    pub primary_key: serde_json::Map<String, serde_json::Value>,
+    pub hash: u64,
+}
+
+impl ScannedRow {
+    pub fn new(token: i64, values: &[Option<CqlValue>], key_names: &[String]) -> Self {
+        let mut primary_key = serde_json::Map::new();
+        for (name, v) in key_names.iter().zip(values) {
+            primary_key.insert(name.clone(), v.as_ref().map(to_json).unwrap_or(serde_json::Value::Null));
+        }
+        let key = serde_json::Value::Object(primary_key.clone()).to_string();
+        Self { token, key, primary_key, hash: row_hash(values) }
+    }
+}
+
+#[allow(async_fn_in_trait)]
+pub trait RangeSource {
+    async fn digest(&self, range: TokenRange) -> AppResult<RangeDigest>;
+
+    async fn rows(&self, range: TokenRange) -> AppResult<BTreeMap<String, ScannedRow>>;
+}
+
+fn fnv_extend(mut h: u64, bytes: &[u8]) -> u64 {
+    for b in bytes {
+        h ^= *b as u64;
+        h = h.wrapping_mul(FNV_PRIME);
+    }
+    h
+}
+
+pub fn row_hash(values: &[Option<CqlValue>]) -> u64 {
+    let mut hash = FNV_OFFSET;
+    for v in values {
+        match v {
+            Some(v) => {
+                let text = to_text(v);
+                hash = fnv_extend(hash, &[1]);
+                hash = fnv_extend(hash, &(text.len() as u64).to_le_bytes());
+                hash = fnv_extend(hash, text.as_bytes());
+            }
+            None => hash = fnv_extend(hash, &[0]),
+        }
+    }
+    hash
+}
+
+fn scan_row(row: Row, key_names: &[String]) -> AppResult<ScannedRow> {
+    let mut cols = row.columns.into_iter();
+    let token = match cols.next().flatten() {
+        Some(CqlValue::BigInt(t)) => t,
+        _ => return Err(AppError::db("range scan returned a row without a token")),
+    };
+    let values: Vec<Option<CqlValue>> = cols.collect();
+    Ok(ScannedRow::new(token, &values, key_names))
+}
+
+async fn scan_range<F>(sess: &Session, q: &RangeQuery, range: TokenRange, spec: &VerifySpec, mut each: F) -> AppResult<()>
+where
+    F: FnMut(ScannedRow),
+{
+    let mut st = UnpreparedStatement::new(q.cql.as_str());
+    st.set_page_size(spec.page_size);
+    st.set_consistency(spec.consistency);
+    st.set_is_idempotent(true);
+    let mut paging_state = PagingState::start();
+    loop {
+        let (qr, paging) = sess
+            .query_single_page(st.clone(), (range.start, range.end), paging_state)
+            .await
+            .map_err(|e| AppError::query("range scan failed", e))?;
+        let rows_res = qr.into_rows_result().map_err(|e| AppError::decode("range scan returned no rows", e))?;
+        let rows = rows_res.rows::<Row>().map_err(|e| AppError::decode("range scan row type", e))?;
+        for row in rows {
+            let row = row.map_err(|e| AppError::decode("range scan row decode", e))?;
+            each(scan_row(row, &q.key_names)?);
+        }
+        match paging {
+            PagingStateResponse::HasMorePages { state } => paging_state = state,
+            PagingStateResponse::NoMorePages => return Ok(()),
+        }
+    }
+}
+
+struct SessionSource<'a> {
+    sess: &'a Session,
+    q: &'a RangeQuery,
+    spec: &'a VerifySpec,
+}
+
+impl RangeSource for SessionSource<'_> {
+    async fn digest(&self, range: TokenRange) -> AppResult<RangeDigest> {
+        let mut digest = RangeDigest::new();
+        scan_range(self.sess, self.q, range, self.spec, |r| digest.absorb(r.hash)).await?;
+        Ok(digest)
+    }
+
+    async fn rows(&self, range: TokenRange) -> AppResult<BTreeMap<String, ScannedRow>> {
+        let mut rows = BTreeMap::new();
+        scan_range(self.sess, self.q, range, self.spec, |r| {
+            rows.insert(r.key.clone(), r);
+        })
+        .await?;
+        Ok(rows)
+    }
+}
+
+fn sessions(clients: &DbClients) -> AppResult<(Arc<Session>, Arc<Session>)> {
+    let active = clients.session(Cluster::Active).ok_or_else(|| AppError::db("Active cluster is unavailable for verification"))?;
+    let passive = clients.session(Cluster::Passive).ok_or_else(|| AppError::db("Passive cluster is unavailable for verification"))?;
+    Ok((active, passive))
+}
+
+pub async fn run_verify(spec: &VerifySpec, clients: &DbClients) -> AppResult<VerifyReport> {
+    let schema = load_table_schema(clients, &spec.keyspace, &spec.table).await?;
+    run_verify_with_schema(spec, &schema, clients).await
+}
+
+pub async fn run_verify_with_schema(spec: &VerifySpec, schema: &TableSchema, clients: &DbClients) -> AppResult<VerifyReport> {
+    let (active, passive) = sessions(clients)?;
+    let q = RangeQuery::build(schema)?;
+
+    let mut report = VerifyReport {
+        keyspace: spec.keyspace.clone(),
+        table: spec.table.clone(),
+        started_ms: now_millis() as u64,
+        ..VerifyReport::default()
+    };
+    compare_sources(
+        spec,
+        &SessionSource { sess: &active, q: &q, spec },
+        &SessionSource { sess: &passive, q: &q, spec },
+        &mut report,
+    )
+    .await?;
+    report.finished_ms = now_millis() as u64;
+    Ok(report)
+}
+
+pub async fn compare_sources<A: RangeSource, P: RangeSource>(spec: &VerifySpec, active: &A, passive: &P, report: &mut VerifyReport) -> AppResult<()> {
+    let mut queue: Vec<(TokenRange, u32)> = TokenRange::full_ring().split(spec.ranges).into_iter().rev().map(|r| (r, 0)).collect();
+    report.ranges_total = queue.len() as u64;
+
+    while let Some((range, depth)) = queue.pop() {
+        let (da, dp) = tokio::join!(active.digest(range), passive.digest(range));
+        let (da, dp) = (da?, dp?);
+        if depth == 0 {
+            report.rows_active += da.rows;
+            report.rows_passive += dp.rows;
+        }
+        if da == dp { continue; }
+        if depth == 0 { report.ranges_mismatched += 1; }
+
+        let largest = da.rows.max(dp.rows);
+        if largest > spec.leaf_rows && depth < spec.max_depth && range.start < range.end {
+            queue.extend(range.split(spec.split_factor).into_iter().rev().map(|r| (r, depth + 1)));
+            continue;
+        }
+
+        report.leaf_ranges_compared += 1;
+        let (ra, rp) = tokio::join!(active.rows(range), passive.rows(range));
+        let (ra, mut rp) = (ra?, rp?);
+        for (key, a) in ra {
+            let diff = match rp.remove(&key) {
+                None => Some((&mut report.missing, a)),
+                Some(p) if p.hash != a.hash => Some((&mut report.differing, a)),
+                Some(_) => None,
+            };
+            if let Some((bucket, row)) = diff {
+                bucket.push(RowDiff { token: row.token, primary_key: row.primary_key });
+            }
+        }
+        for (_, p) in rp {
+            report.extra.push(RowDiff { token: p.token, primary_key: p.primary_key });
+        }
+        if report.diff_count() >= spec.max_diffs {
+            report.truncated = true;
+            break;
+        }
+    }
+    Ok(())
+}
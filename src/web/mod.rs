@@ -1,14 +1,82 @@
+use ntex::http::StatusCode;
 use ntex::web;
+use serde::{Deserialize, Serialize};
 
 use std::sync::Arc;
 
+use crate::config::{AppConfig, Reloader, SecretResolver, SharedConfig};
 use crate::db::DbClients;
-use crate::health::{service_health, db_health};
+use crate::errors::{AppError, AppResult};
+use crate::export::{run_export, ExportFormat, ExportSource, ExportSpec};
+use crate::health::{service_health, db_health, prepared_cache_health};
+use crate::health::probes::{self, ProbeReport, ProbeState};
+use crate::import::{run_import, ImportFormat, ImportMapping, ImportOptions, StreamReader};
+use crate::jobs::{JobInfo, JobRegistry};
 use crate::middleware::CorrelationId;
+use crate::repair::{parse_keys, run_repair, PrimaryKey, RepairSpec, RepairStrategy};
+use crate::replication::{Cluster, OutboxTarget, ReplicationManager};
+use crate::types::ApiResponse;
+use crate::verify::{run_verify, VerifySpec};
+
+const IMPORT_STREAM_CHUNKS: usize = 16;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db_clients: Arc<DbClients>,
+    pub replication: Arc<ReplicationManager>,
+    pub config: Arc<SharedConfig>,
+    pub jobs: JobRegistry,
+    pub probes: Arc<ProbeState>,
+    pub reloader: Arc<Reloader>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    format: Option<String>,
+    target: Option<String>,
+    concurrency: Option<usize>,
+    mapping: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExportRequest {
+    keyspace: Option<String>,
+    table: Option<String>,
+    query: Option<String>,
+    format: Option<String>,
+    chunk_rows: Option<usize>,
+    compress: Option<bool>,
+    resume: Option<bool>,
+    cluster: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VerifyRequest {
+    keyspace: Option<String>,
+    table: String,
+    ranges: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepairRequest {
+    keyspace: Option<String>,
+    table: String,
+    strategy: Option<String>,
+    dry_run: Option<bool>,
+    keys: Option<Vec<PrimaryKey>>,
+    verify_job: Option<String>,
+}
+
+impl web::WebResponseError for AppError {
+    fn status_code(&self) -> StatusCode { AppError::status_code(self) }
+
+    fn error_response(&self, _: &web::HttpRequest) -> web::HttpResponse {
+        web::HttpResponse::build(self.status_code()).json(&ApiResponse::<()>::from_error(self))
+    }
+}
+
+fn respond<T: Serialize>(response: &ApiResponse<T>) -> web::HttpResponse {
+    web::HttpResponse::build(response.status()).json(response)
 }
 
 #[web::get("/health-check/service")]
@@ -19,20 +87,224 @@ async fn health_service() -> impl web::Responder {
 
 #[web::get("/health-check/databases")]
 async fn health_databases(state: web::types::State<AppState>) -> impl web::Responder {
-    let response = db_health(&*state.db_clients).await;
-    web::HttpResponse::Ok().json(&response)
+    respond(&db_health(&state.db_clients).await)
 }
 
-pub fn configure_routes(cfg: &mut web::ServiceConfig) {
-    cfg.service(health_service)
-       .service(health_databases);
+fn probe_response(report: ProbeReport, probe: &str) -> web::HttpResponse {
+    respond(&report.into_response(probe))
+}
+
+#[web::get("/health-check/live")]
+async fn health_live() -> web::HttpResponse {
+    probe_response(probes::liveness(), "liveness")
+}
+
+#[web::get("/health-check/startup")]
+async fn health_startup(state: web::types::State<AppState>) -> web::HttpResponse {
+    probe_response(probes::startup(&state.probes), "startup")
+}
+
+#[web::get("/health-check/ready")]
+async fn health_ready(state: web::types::State<AppState>) -> web::HttpResponse {
+    let report = probes::readiness(&state.probes, &state.db_clients, &state.replication, &state.config.current().replication).await;
+    probe_response(report, "readiness")
+}
+
+#[web::get("/health-check/prepared-cache")]
+async fn health_prepared_cache(state: web::types::State<AppState>) -> impl web::Responder {
+    web::HttpResponse::Ok().json(&prepared_cache_health(&state.db_clients))
+}
+
+#[web::post("/import/{keyspace}/{table}")]
+async fn import_upload(
+    state: web::types::State<AppState>,
+    path: web::types::Path<(String, String)>,
+    query: web::types::Query<ImportQuery>,
+    payload: web::types::Payload,
+) -> Result<web::HttpResponse, AppError> {
+    let (keyspace, table) = path.into_inner();
+    let report = handle_import(&state, keyspace, table, query.into_inner(), payload).await?;
+    Ok(respond(&ApiResponse::success_with("import completed", report)))
+}
+
+async fn handle_import(
+    state: &AppState,
+    keyspace: String,
+    table: String,
+    query: ImportQuery,
+    mut payload: web::types::Payload,
+) -> AppResult<crate::import::ImportReport> {
+    let mapping = match query.mapping.as_deref() {
+        Some(pairs) => ImportMapping::parse_pairs(Some(keyspace), table, pairs)?,
+        None => ImportMapping::identity(Some(keyspace), table),
+    };
+    let defaults = ImportOptions::default();
+    let target = match query.target.as_deref() {
+        Some(t) => OutboxTarget::parse(t).ok_or_else(|| AppError::validation(format!("unknown import target '{}'", t)))?,
+        None => defaults.target,
+    };
+    let opts = ImportOptions {
+        format: ImportFormat::parse(query.format.as_deref().unwrap_or("csv"))?,
+        target,
+        concurrency: query.concurrency.unwrap_or(defaults.concurrency),
+        ..defaults
+    };
+
+    let (chunks, reader) = StreamReader::channel(IMPORT_STREAM_CHUNKS);
+    let forward = async move {
+        while let Some(chunk) = payload.recv().await {
+            let chunk = chunk.map(|c| c.to_vec()).map_err(|e| std::io::Error::other(format!("failed to read upload: {}", e)));
+            let failed = chunk.is_err();
+            if chunks.send(chunk).await.is_err() || failed { break; }
+        }
+    };
+
+    let keyspace = state.config.current().active.keyspace.clone();
+    let import = run_import(reader, &mapping, &keyspace, &opts, &state.replication, &state.db_clients);
+    let ((), report) = futures::join!(forward, import);
+    report
+}
+
+#[web::post("/jobs/export")]
+async fn jobs_export(state: web::types::State<AppState>, body: web::types::Json<ExportRequest>) -> Result<web::HttpResponse, AppError> {
+    let job = start_export_job(&state, body.into_inner())?;
+    Ok(respond(&ApiResponse::success_with("export job started", job)))
+}
+
+fn start_export_job(state: &AppState, req: ExportRequest) -> AppResult<JobInfo> {
+    let cfg = state.config.current();
+    let source = match (req.table, req.query) {
+        (Some(table), None) => ExportSource::table(req.keyspace.unwrap_or_else(|| cfg.active.keyspace.clone()), table),
+        (None, Some(name)) => ExportSource::named_query(&cfg.export, &name)?,
+        _ => return Err(AppError::validation("export requires exactly one of table or query")),
+    };
+    let cluster = match req.cluster.as_deref().map(str::to_ascii_lowercase).as_deref() {
+        None => None,
+        Some("active") => Some(Cluster::Active),
+        Some("passive") => Some(Cluster::Passive),
+        Some(other) => return Err(AppError::validation(format!("unknown cluster '{}'", other))),
+    };
+    let mut spec = ExportSpec::new(source, ExportFormat::parse(req.format.as_deref().unwrap_or("csv"))?, &cfg.export.out_dir)
+        .with_compress(req.compress.unwrap_or(true))
+        .with_resume(req.resume.unwrap_or(false))
+        .with_cluster(cluster);
+    if let Some(n) = req.chunk_rows { spec = spec.with_chunk_rows(n); }
+    if let Some(n) = cfg.driver.default_page_size { spec = spec.with_page_size(n); }
+
+    let clients = state.db_clients.clone();
+    Ok(state.jobs.spawn("export", async move { run_export(&spec, &clients).await }))
+}
+
+#[web::post("/jobs/verify")]
+async fn jobs_verify(state: web::types::State<AppState>, body: web::types::Json<VerifyRequest>) -> impl web::Responder {
+    let req = body.into_inner();
+    let cfg = state.config.current();
+    let mut spec = VerifySpec::new(req.keyspace.unwrap_or_else(|| cfg.active.keyspace.clone()), req.table);
+    if let Some(n) = req.ranges { spec = spec.with_ranges(n); }
+    if let Some(n) = cfg.driver.default_page_size { spec = spec.with_page_size(n); }
+
+    let clients = state.db_clients.clone();
+    let job = state.jobs.spawn_with_artifact("verify", "verify-report.json", async move { run_verify(&spec, &clients).await });
+    respond(&ApiResponse::success_with("verify job started", job))
+}
+
+#[web::post("/jobs/repair")]
+async fn jobs_repair(state: web::types::State<AppState>, body: web::types::Json<RepairRequest>) -> Result<web::HttpResponse, AppError> {
+    let job = start_repair_job(&state, body.into_inner())?;
+    Ok(respond(&ApiResponse::success_with("repair job started", job)))
+}
+
+fn start_repair_job(state: &AppState, req: RepairRequest) -> AppResult<JobInfo> {
+    let keys = match (req.keys, req.verify_job) {
+        (Some(keys), None) => keys,
+        (None, Some(id)) => {
+            let path = state.jobs.artifact_path(&id).ok_or_else(|| AppError::not_found(format!("verify job {} has no report", id)))?;
+            let body = std::fs::read_to_string(&path)
+                .map_err(|e| AppError::io(format!("failed to read {}", path.display()), e))?;
+            parse_keys(&body)?
+        }
+        _ => return Err(AppError::validation("repair requires exactly one of keys or verify_job")),
+    };
+    let strategy = RepairStrategy::parse(req.strategy.as_deref().unwrap_or("writetime"))?;
+    let keyspace = req.keyspace.unwrap_or_else(|| state.config.current().active.keyspace.clone());
+    let spec = RepairSpec::new(keyspace, req.table, strategy).with_dry_run(req.dry_run.unwrap_or(false));
+
+    let clients = state.db_clients.clone();
+    let replication = state.replication.clone();
+    Ok(state.jobs.spawn_with_artifact("repair", "repair-report.json", async move {
+        run_repair(&spec, &keys, &replication, &clients).await
+    }))
 }
 
-pub async fn start_server(db_clients: Arc<DbClients>, bind_addr: &str) -> std::io::Result<()> {
-    let app_state = AppState {
-        db_clients,
+async fn authorize_admin(req: &web::HttpRequest, cfg: &AppConfig) -> AppResult<()> {
+    let Some(token) = cfg.server.admin_token.clone() else {
+        return match req.peer_addr() {
+            Some(addr) if addr.ip().is_loopback() => Ok(()),
+            _ => Err(AppError::forbidden("admin endpoints only accept loopback clients unless server.admin_token is set")),
+        };
     };
+    let secrets = SecretResolver::from_config(&cfg.secrets);
+    let expected = tokio::task::spawn_blocking(move || secrets.interpolate(&token))
+        .await
+        .map_err(|e| AppError::other(format!("admin token resolution task failed: {}", e)))?
+        .map_err(|e| e.with_context("server.admin_token"))?;
+    let given = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    if given.len() == expected.len() && openssl::memcmp::eq(given.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(AppError::forbidden("missing or invalid admin token"))
+    }
+}
+
+#[web::post("/admin/config/reload")]
+async fn admin_config_reload(state: web::types::State<AppState>, req: web::HttpRequest) -> Result<web::HttpResponse, AppError> {
+    authorize_admin(&req, &state.config.current()).await?;
+    let report = state.reloader.reload("http").await?;
+    let message = if report.failed.is_empty() { "configuration reloaded" } else { "configuration reloaded with failures" };
+    Ok(respond(&ApiResponse::success_with(message, report)))
+}
+
+#[web::get("/jobs")]
+async fn jobs_list(state: web::types::State<AppState>) -> impl web::Responder {
+    respond(&ApiResponse::success_with("jobs", state.jobs.list()))
+}
+
+#[web::get("/jobs/{id}")]
+async fn jobs_get(state: web::types::State<AppState>, path: web::types::Path<String>) -> impl web::Responder {
+    respond(&ApiResponse::from_option(state.jobs.get(&path), "job", "job not found"))
+}
+
+#[web::get("/jobs/{id}/artifact")]
+async fn jobs_artifact(state: web::types::State<AppState>, path: web::types::Path<String>) -> Result<web::HttpResponse, AppError> {
+    let bytes = state.jobs.artifact_path(&path)
+        .and_then(|p| std::fs::read(p).ok())
+        .ok_or_else(|| AppError::not_found(format!("job {} has no artifact", path.as_str())))?;
+    Ok(web::HttpResponse::Ok().content_type("application/json").body(bytes))
+}
+
+pub fn configure_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(health_service)
+       .service(health_databases)
+       .service(health_prepared_cache)
+       .service(health_live)
+       .service(health_startup)
+       .service(health_ready)
+       .service(import_upload)
+       .service(jobs_export)
+       .service(jobs_verify)
+       .service(jobs_repair)
+       .service(jobs_list)
+       .service(jobs_artifact)
+       .service(jobs_get)
+       .service(admin_config_reload);
+}
 
+pub async fn start_server(app_state: AppState, bind_addr: &str) -> std::io::Result<()> {
     web::HttpServer::new(move || {
         web::App::new()
             .wrap(web::middleware::Logger::new("%{X-Correlation-Id}o %a %t \"%r\" %s %b %T"))
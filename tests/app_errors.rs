@@ -0,0 +1,81 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+use nayud_batch::config::AppConfig;
+use nayud_batch::errors::AppError;
+use nayud_batch::types::response::{ApiMessage, ApiResponse, CODE_CONFIG, CODE_DB_ERROR, CODE_DB_UNAVAILABLE};
+use ntex::http::StatusCode;
+use scylla::errors::{DbError, ExecutionError, RequestAttemptError};
+
+#[test]
+fn io_errors_keep_their_source() {
+    let err = AppError::io("outbox append", io::Error::new(io::ErrorKind::TimedOut, "disk stalled"));
+    assert_eq!(err.error_code(), "IO_ERROR");
+    assert!(err.is_retryable());
+    assert_eq!(err.source().unwrap().to_string(), "disk stalled");
+    assert_eq!(err.to_string(), "Io: outbox append");
+    assert_eq!(err.to_message(), "Io: outbox append: disk stalled");
+    assert_eq!(err.source_chain(), ["disk stalled"]);
+    match ApiResponse::<()>::from_error(&err).message {
+        ApiMessage::Detail { what, .. } => assert_eq!(what, "File system error: outbox append: disk stalled"),
+        _ => panic!("expected detail message"),
+    }
+
+    let err = AppError::io("cursor open", io::Error::from(io::ErrorKind::PermissionDenied)).with_context("Active");
+    assert!(matches!(&err, AppError::Io { context, .. } if context == "Active: cursor open"));
+    assert!(!err.is_retryable());
+    assert_eq!(err.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[test]
+fn driver_errors_are_classified() {
+    let timeout = AppError::query("range scan failed", ExecutionError::RequestTimeout(Duration::from_secs(2)));
+    assert_eq!(timeout.error_code(), "DB_QUERY_FAILED");
+    assert!(timeout.is_retryable());
+    assert_eq!(timeout.code(), CODE_DB_UNAVAILABLE);
+    assert_eq!(timeout.status_code(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let syntax = ExecutionError::LastAttemptError(RequestAttemptError::DbError(DbError::SyntaxError, "line 1:0 no viable alternative".into()));
+    let syntax = AppError::query("export query failed", syntax);
+    assert!(!syntax.is_retryable());
+    assert_eq!(syntax.code(), CODE_DB_ERROR);
+    assert_eq!(syntax.status_code(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let resp = ApiResponse::<()>::from_error(&syntax);
+    let info = resp.error.as_ref().unwrap();
+    assert_eq!((info.code, info.retryable), ("DB_QUERY_FAILED", false));
+    match &resp.message {
+        ApiMessage::Detail { what, .. } => assert!(what.contains("no viable alternative"), "{}", what),
+        _ => panic!("expected detail message"),
+    }
+}
+
+#[test]
+fn config_file_parse_errors_are_typed() {
+    let mut path = std::env::temp_dir();
+    path.push(format!("nayud_batch_test_bad_config_{}.toml", std::process::id()));
+    std::fs::write(&path, "[server]\nbind_addr = 8080\n").unwrap();
+
+    let err = AppConfig::from_file(&path).unwrap_err();
+    assert_eq!(err.error_code(), "CONFIG_PARSE");
+    assert_eq!(err.code(), CODE_CONFIG);
+    assert!(err.source().is_some());
+
+    let _ = std::fs::remove_file(&path);
+    let err = AppConfig::from_file(&path).unwrap_err();
+    assert!(matches!(&err, AppError::Io { source, .. } if source.kind() == io::ErrorKind::NotFound));
+}
+
+#[test]
+fn bad_requests_are_described_as_client_errors() {
+    let err = AppError::web("missing body");
+    assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    match ApiResponse::<()>::from_error(&err).message {
+        ApiMessage::Detail { what, why, .. } => {
+            assert_eq!(what, "Request error: missing body");
+            assert!(!why.contains("server-side"), "{}", why);
+        }
+        _ => panic!("expected detail message"),
+    }
+}
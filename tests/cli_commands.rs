@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+use nayud_batch::cli::{
+    parse_invocation, render, run_config_command, run_outbox_command, Command, FailoverCommand, JobCommand, OutboxCommand,
+    OutputFormat,
+};
+use nayud_batch::config::AppConfig;
+use nayud_batch::replication::{Cluster, Outbox, OutboxRecord, OutboxTarget};
+
+fn args(list: &[&str]) -> Vec<String> { list.iter().map(|s| s.to_string()).collect() }
+
+fn temp_outbox_dir() -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_test_cli_{}", ts));
+    dir
+}
+
+#[test]
+fn parses_subcommands_and_output_format() {
+    let inv = parse_invocation(args(&["--json", "outbox", "dump", "--from", "20", "-n", "5"])).unwrap();
+    assert_eq!(inv.output, OutputFormat::Json);
+    assert!(matches!(inv.command, Command::Outbox(OutboxCommand::Dump { from: 20, limit: 5, .. })));
+
+    let inv = parse_invocation(args(&["failover", "promote", "passive", "--force"])).unwrap();
+    assert!(matches!(inv.command, Command::Failover(FailoverCommand::Promote { to: Cluster::Passive, force: true })));
+
+    let inv = parse_invocation(args(&["verify", "batch.customers", "--ranges", "8"])).unwrap();
+    match inv.command {
+        Command::Verify(v) => {
+            assert_eq!(v.table, "batch.customers");
+            assert_eq!(v.ranges, Some(8));
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let inv = parse_invocation(args(&["--output", "human", "jobs", "run", "daily_orders", "--format", "ndjson"])).unwrap();
+    match inv.command {
+        Command::Jobs(JobCommand::Export(e)) => assert_eq!(e.query.as_deref(), Some("daily_orders")),
+        other => panic!("unexpected {:?}", other),
+    }
+
+    assert!(matches!(parse_invocation(args(&["check-config"])).unwrap().command, Command::CheckConfig));
+    assert!(parse_invocation(args(&["outbox", "truncate", "--all"])).is_err());
+    assert!(parse_invocation(args(&["outbox", "truncate", "--all", "--yes"])).is_ok());
+    assert!(parse_invocation(args(&["failover", "promote"])).is_err());
+    assert!(parse_invocation(args(&["ensure-keyspaces", "extra"])).is_err());
+    assert!(parse_invocation(args(&["--output", "yaml", "serve"])).is_err());
+}
+
+#[test]
+fn outbox_commands_work_offline() {
+    let dir = temp_outbox_dir();
+    let mut cfg = AppConfig::default();
+    cfg.replication.outbox_dir = dir.display().to_string();
+    assert!(run_outbox_command(&cfg, &OutboxCommand::Stats).is_err());
+
+    let outbox = Outbox::open(&dir).unwrap().with_fsync(false);
+    let mut ends = Vec::new();
+    for i in 0..4 {
+        ends.push(outbox.append(OutboxRecord::new_simple(format!("k{}", i), format!("INSERT {}", i), OutboxTarget::Both)).unwrap());
+    }
+    outbox.store_cursor(ends[1]).unwrap();
+
+    let stats = run_outbox_command(&cfg, &OutboxCommand::Stats).unwrap();
+    assert_eq!(stats["records"], 4);
+    assert_eq!(stats["pending_records"], 2);
+
+    let tail = run_outbox_command(&cfg, &OutboxCommand::Tail { limit: 1 }).unwrap();
+    assert_eq!(tail[0]["key"], "k3");
+    assert_eq!(tail[0]["target"], "both");
+
+    let dump = run_outbox_command(&cfg, &OutboxCommand::Dump { from: ends[0], limit: 10, filter: Default::default() }).unwrap();
+    assert_eq!(dump.as_array().unwrap().len(), 3);
+
+    assert!(run_outbox_command(&cfg, &OutboxCommand::Requeue { offset: ends[0] }).is_err());
+    drop(outbox);
+
+    assert!(run_outbox_command(&cfg, &OutboxCommand::Requeue { offset: ends[0] + 1 }).is_err());
+    let requeued = run_outbox_command(&cfg, &OutboxCommand::Requeue { offset: ends[0] }).unwrap();
+    assert_eq!(requeued["pending_records"], 3);
+
+    let truncated = run_outbox_command(&cfg, &OutboxCommand::Truncate { all: false }).unwrap();
+    assert_eq!(truncated["removed_bytes"], ends[0]);
+    assert_eq!(truncated["stats"]["records"], 3);
+    assert_eq!(truncated["stats"]["cursor"], 0);
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn outbox_export_refuses_to_skip_records_silently() {
+    let dir = temp_outbox_dir().with_extension("export");
+    let mut cfg = AppConfig::default();
+    cfg.replication.outbox_dir = dir.display().to_string();
+    let outbox = Outbox::open(&dir).unwrap().with_fsync(false);
+    let ends: Vec<u64> = (0..3)
+        .map(|i| outbox.append(OutboxRecord::new_simple(format!("k{}", i), "INSERT", OutboxTarget::Both)).unwrap())
+        .collect();
+    drop(outbox);
+    let mut log = std::fs::read(dir.join("outbox.log")).unwrap();
+    log[ends[0] as usize..ends[0] as usize + 4].copy_from_slice(&[0xff; 4]);
+    std::fs::write(dir.join("outbox.log"), log).unwrap();
+
+    let out = dir.join("export.jsonl").display().to_string();
+    let export = |allow_skips| run_outbox_command(&cfg, &OutboxCommand::Export { out: out.clone(), all: true, allow_skips });
+    let err = export(false).unwrap_err().to_message();
+    assert!(err.contains("1 corrupt region(s)") && err.contains("--allow-skips"), "{}", err);
+    assert!(!std::path::Path::new(&out).exists());
+
+    let exported = export(true).unwrap();
+    assert_eq!(exported["exported"]["records"], 2);
+    assert_eq!(exported["exported"]["skipped_corrupt_regions"], 1);
+    assert!(matches!(
+        parse_invocation(args(&["outbox", "export", "--out", "x", "--allow-skips"])).unwrap().command,
+        Command::Outbox(OutboxCommand::Export { allow_skips: true, .. })
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn check_config_fails_on_errors_and_renders_both_formats() {
+    let mut cfg = AppConfig::default();
+    cfg.server.bind_addr = "nope".into();
+    let (text, outcome) = run_config_command(&cfg, &Command::CheckConfig, OutputFormat::Human);
+    assert!(outcome.is_err());
+    assert!(text.contains("error: server.bind_addr"), "{}", text);
+
+    let (json, _) = run_config_command(&cfg, &Command::CheckConfig, OutputFormat::Json);
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["valid"], false);
+
+    let human = render(&serde_json::json!({ "outbox": { "cursor": 10, "dir": "data/outbox" }, "forced": false }), OutputFormat::Human);
+    assert!(human.contains("outbox.cursor  10\n"), "{}", human);
+    assert!(human.contains("forced         false\n"), "{}", human);
+}
@@ -75,4 +75,52 @@ fn config_defaults_and_overrides() {
             assert_eq!(cfg.active.username, "");
         });
     });
-}
\ No newline at end of file
+}
+
+#[test]
+fn contact_points_from_env_list() {
+    with_env_lock(|| {
+        let keys = ["ACTIVE_DB_CONTACT_POINTS", "PASSIVE_DB_CONTACT_POINTS", "DB_CONTACT_POINTS", "DB_HOST", "DB_PORT", "ACTIVE_DB_PORT"];
+
+        with_env_vars(&keys, &[], || {
+            let cfg = AppConfig::from_env();
+            assert!(cfg.active.contact_points.is_empty());
+            assert_eq!(cfg.active.contact_points(), vec!["127.0.0.1:9042".to_string()]);
+        });
+
+        with_env_vars(&keys, &[("ACTIVE_DB_CONTACT_POINTS", " a.internal, 10.0.0.2:9100 ,,::1,[fe80::1]:9200 ")], || {
+            let cfg = AppConfig::from_env();
+            assert_eq!(
+                cfg.active.contact_points(),
+                vec!["a.internal:9042", "10.0.0.2:9100", "[::1]:9042", "[fe80::1]:9200"]
+            );
+            assert!(cfg.passive.contact_points.is_empty());
+        });
+    });
+}
+
+#[ntex::test]
+async fn contact_points_resolve_literals_and_localhost() {
+    let points = vec!["127.0.0.1:9042".to_string(), "localhost:9043".to_string()];
+    let (resolved, unresolved) = nayud_batch::db::resolve_contact_points(&points).await;
+    assert!(unresolved.is_empty());
+    assert!(resolved.iter().any(|a| a.port() == 9042));
+    assert!(resolved.iter().any(|a| a.port() == 9043));
+}
+
+#[test]
+fn example_file_driver_profiles() {
+    with_env_lock(|| {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config/nayud-batch.example.toml");
+        with_env_vars(&["NAYUD_CONFIG_FILE"], &[("NAYUD_CONFIG_FILE", path)], || {
+            let cfg = AppConfig::from_file_or_env();
+            assert_eq!(cfg.driver.execution.request_timeout_ms, Some(5000));
+            assert_eq!(cfg.driver.execution.retry_policy.as_deref(), Some("default"));
+            assert_eq!(cfg.driver.execution_for(true).consistency.as_deref(), Some("local_quorum"));
+            assert_eq!(cfg.driver.execution_for(false).consistency.as_deref(), Some("one"));
+            assert_eq!(cfg.server.shutdown_grace_ms, 5000);
+            assert_eq!(cfg.replication.drift_max_records, 100);
+            assert_eq!(cfg.replication.drift_max_bytes, 1_000_000);
+        });
+    });
+}
@@ -0,0 +1,86 @@
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+use nayud_batch::cli::{parse_invocation, Command};
+use nayud_batch::config::{AppConfig, CliOverrides, ConfigSource};
+
+static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+const EXAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/config/nayud-batch.example.toml");
+
+fn with_env<T>(set: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+    let _g = ENV_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap_or_else(|e| e.into_inner());
+    let backup: Vec<(&str, Option<String>)> = set.iter().map(|(k, _)| (*k, env::var(k).ok())).collect();
+    for (k, v) in set {
+        unsafe { env::set_var(k, v) };
+    }
+    let out = f();
+    for (k, v) in backup {
+        match v {
+            Some(v) => unsafe { env::set_var(k, v) },
+            None => unsafe { env::remove_var(k) },
+        }
+    }
+    out
+}
+
+fn args(list: &[&str]) -> Vec<String> { list.iter().map(|s| s.to_string()).collect() }
+
+#[test]
+fn env_overrides_file_and_cli_overrides_env() {
+    let inv = parse_invocation(args(&["--config", EXAMPLE, "--set", "active.keyspace=cli_ks", "--bind", "0.0.0.0:9090", "serve"])).unwrap();
+    assert!(matches!(inv.command, Command::Serve));
+
+    let cfg = with_env(
+        &[("ACTIVE_DB_KEYSPACE", "env_ks"), ("ACTIVE_DB_HOST", "env-host"), ("DB_REPLICATION_FACTOR", "1"), ("PASSIVE_DB_DURABLE_WRITES", "false")],
+        || AppConfig::load(&inv.overrides),
+    );
+
+    assert!(cfg.load_problems.is_empty(), "{:?}", cfg.load_problems);
+    assert_eq!(cfg.active.keyspace, "cli_ks");
+    assert_eq!(cfg.active.host, "env-host");
+    assert_eq!(cfg.server.bind_addr, "0.0.0.0:9090");
+    assert_eq!(cfg.active.replication_factor, Some(1));
+    assert_eq!(cfg.passive.replication_factor, Some(1));
+    assert_eq!(cfg.passive.durable_writes, Some(false));
+    assert_eq!(cfg.driver.execution.request_timeout_ms, Some(5000));
+
+    assert_eq!(cfg.source_of("active.keyspace"), ConfigSource::Cli("--set".into()));
+    assert_eq!(cfg.source_of("server.bind_addr"), ConfigSource::Cli("--bind".into()));
+    assert_eq!(cfg.source_of("active.host"), ConfigSource::Env("ACTIVE_DB_HOST".into()));
+    assert_eq!(cfg.source_of("passive.replication_factor"), ConfigSource::Env("DB_REPLICATION_FACTOR".into()));
+    assert_eq!(cfg.source_of("driver.request_timeout_ms"), ConfigSource::File(EXAMPLE.into()));
+    assert_eq!(cfg.source_of("active.tls_ca_file"), ConfigSource::Default);
+}
+
+#[test]
+fn print_config_masks_secrets_and_names_layers() {
+    let mut overrides = CliOverrides { config_file: Some(EXAMPLE.into()), ..CliOverrides::default() };
+    overrides.push("--set", "passive.password", "super-secret-pw");
+    let cfg = with_env(&[], || AppConfig::load(&overrides));
+
+    let out = cfg.render_effective();
+    assert!(!out.contains("super-secret-pw"), "{}", out);
+    let line = out.lines().find(|l| l.starts_with("passive.password")).unwrap();
+    assert!(line.contains("su****pw") && line.ends_with("# cli --set"), "{}", line);
+    let line = out.lines().find(|l| l.starts_with("server.bind_addr")).unwrap();
+    assert!(line.contains(&format!("# file {}", EXAMPLE)), "{}", line);
+
+    let inv = parse_invocation(args(&["--print-config"])).unwrap();
+    assert!(matches!(inv.command, Command::PrintConfig));
+    assert!(parse_invocation(args(&["--print-config", "serve"])).is_err());
+}
+
+#[test]
+fn bad_overrides_are_reported() {
+    let mut overrides = CliOverrides { config_file: Some("/nonexistent/nayud.toml".into()), ..CliOverrides::default() };
+    overrides.push("--set", "active.no_such_field", "1");
+    overrides.push("--set", "server.shutdown_grace_ms", "soon");
+    let cfg = with_env(&[], || AppConfig::load(&overrides));
+
+    assert_eq!(cfg.load_problems.len(), 3, "{:?}", cfg.load_problems);
+    assert!(cfg.load_problems.iter().any(|p| p.contains("/nonexistent/nayud.toml")));
+    assert!(cfg.load_problems.iter().any(|p| p.contains("active.no_such_field")));
+    assert!(cfg.load_problems.iter().any(|p| p.contains("server.shutdown_grace_ms='soon'")));
+    assert!(parse_invocation(args(&["--set", "novalue"])).is_err());
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ntex::http::StatusCode;
+use ntex::web::{self, test};
+
+use nayud_batch::config::{AppConfig, CliOverrides, Reloader, SharedConfig};
+use nayud_batch::db::DbClients;
+use nayud_batch::health::probes::ProbeState;
+use nayud_batch::jobs::JobRegistry;
+use nayud_batch::replication::{FailoverManager, ReplicationManager, SyncWorker};
+use nayud_batch::web::{configure_routes, AppState};
+
+fn temp_config(tag: &str, body: &str) -> PathBuf {
+    let mut path = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    path.push(format!("nayud_batch_test_reload_{}_{}.toml", tag, ts));
+    fs::write(&path, body).unwrap();
+    path
+}
+
+fn reloader_for(path: &Path) -> (Arc<SharedConfig>, Reloader) {
+    let overrides = CliOverrides { config_file: Some(path.display().to_string()), values: Vec::new() };
+    let cfg = AppConfig::load(&overrides);
+    assert!(cfg.load_problems.is_empty(), "{:?}", cfg.load_problems);
+    let shared = Arc::new(SharedConfig::new(cfg));
+    let reloader = Reloader::new(overrides, shared.clone(), Arc::new(DbClients::default()));
+    (shared, reloader)
+}
+
+fn app_state(shared: &Arc<SharedConfig>, reloader: Reloader) -> AppState {
+    AppState {
+        db_clients: Arc::new(DbClients::default()),
+        replication: Arc::new(ReplicationManager::new()),
+        config: shared.clone(),
+        jobs: JobRegistry::new(),
+        probes: Arc::new(ProbeState::new()),
+        reloader: Arc::new(reloader),
+    }
+}
+
+const BASE: &str = r#"
+[server]
+bind_addr = "127.0.0.1:8080"
+
+[replication]
+drift_max_records = 100
+"#;
+
+const WITH_TOKEN: &str = "[server]\nadmin_token = \"s3cret\"\n";
+
+#[ntex::test]
+async fn reload_applies_live_settings_and_keeps_restart_only_ones() {
+    let path = temp_config("apply", BASE);
+    let (shared, reloader) = reloader_for(&path);
+
+    fs::write(&path, r#"
+[server]
+bind_addr = "0.0.0.0:9999"
+log_level = "debug"
+
+[driver]
+request_timeout_ms = 1500
+
+[replication]
+drift_max_records = 5
+failover_fail_threshold = 7
+"#).unwrap();
+
+    let report = reloader.reload("test").await.unwrap();
+    for key in ["replication.drift_max_records", "replication.failover_fail_threshold", "driver.request_timeout_ms", "server.log_level"] {
+        assert!(report.applied.iter().any(|k| k == key), "missing {} in {:?}", key, report.applied);
+    }
+    assert_eq!(report.restart_required, vec!["server.bind_addr".to_string()]);
+
+    let cfg = shared.current();
+    assert_eq!(cfg.replication.drift_max_records, 5);
+    assert_eq!(cfg.replication.failover_fail_threshold, 7);
+    assert_eq!(cfg.driver.execution.request_timeout_ms, Some(1500));
+    assert_eq!(cfg.server.bind_addr, "127.0.0.1:8080");
+    assert_eq!(log::max_level(), log::LevelFilter::Debug);
+
+    let again = reloader.reload("test").await.unwrap();
+    assert!(again.applied.is_empty(), "{:?}", again.applied);
+    let _ = fs::remove_file(&path);
+}
+
+#[ntex::test]
+async fn invalid_config_is_rejected_and_nothing_changes() {
+    let path = temp_config("invalid", BASE);
+    let (shared, reloader) = reloader_for(&path);
+
+    fs::write(&path, "[replication]\ndrift_max_records = 1\nfailover_recover_threshold = 0\n\n[driver]\ncompression = \"zstd\"\n").unwrap();
+    let err = reloader.reload("test").await.unwrap_err().to_message();
+    assert!(err.contains("driver.compression"), "{}", err);
+    assert!(err.contains("replication.failover_recover_threshold"), "{}", err);
+    assert_eq!(shared.current().replication.drift_max_records, 100);
+    let _ = fs::remove_file(&path);
+}
+
+fn admin_reload(token: Option<&str>) -> ntex::http::Request {
+    let req = test::TestRequest::post().uri("/admin/config/reload");
+    match token {
+        Some(t) => req.header("authorization", format!("Bearer {}", t)).to_request(),
+        None => req.to_request(),
+    }
+}
+
+#[ntex::test]
+async fn admin_endpoint_triggers_reload() {
+    let path = temp_config("http", WITH_TOKEN);
+    let (shared, reloader) = reloader_for(&path);
+    let app = test::init_service(web::App::new().state(app_state(&shared, reloader)).configure(configure_routes)).await;
+
+    fs::write(&path, format!("{}\n[replication]\ndrift_max_records = 42\n", WITH_TOKEN)).unwrap();
+    let resp = test::call_service(&app, admin_reload(Some("s3cret"))).await;
+    assert!(resp.status().is_success());
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(body["data"]["trigger"], "http");
+    assert_eq!(shared.current().replication.drift_max_records, 42);
+
+    fs::write(&path, format!("{}\n[driver]\ncompression = \"zstd\"\n", WITH_TOKEN)).unwrap();
+    let resp = test::call_service(&app, admin_reload(Some("s3cret"))).await;
+    assert!(resp.status().is_client_error() || resp.status().is_server_error());
+    assert_eq!(shared.current().replication.drift_max_records, 42);
+    let _ = fs::remove_file(&path);
+}
+
+#[ntex::test]
+async fn admin_endpoint_rejects_remote_clients_and_wrong_tokens() {
+    let path = temp_config("auth", BASE);
+    let (shared, reloader) = reloader_for(&path);
+    let app = test::init_service(web::App::new().state(app_state(&shared, reloader)).configure(configure_routes)).await;
+    fs::write(&path, "[replication]\ndrift_max_records = 7\n").unwrap();
+    assert_eq!(test::call_service(&app, admin_reload(None)).await.status(), StatusCode::FORBIDDEN);
+    assert_eq!(shared.current().replication.drift_max_records, 100);
+    let _ = fs::remove_file(&path);
+
+    let path = temp_config("auth-token", WITH_TOKEN);
+    let (shared, reloader) = reloader_for(&path);
+    let app = test::init_service(web::App::new().state(app_state(&shared, reloader)).configure(configure_routes)).await;
+    for token in [None, Some("wrong"), Some("s3cret-but-longer")] {
+        assert_eq!(test::call_service(&app, admin_reload(token)).await.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(test::call_service(&app, admin_reload(Some("s3cret"))).await.status().is_success());
+    let _ = fs::remove_file(&path);
+}
+
+#[ntex::test]
+async fn reloaded_thresholds_reach_the_sync_worker() {
+    let path = temp_config("worker", BASE);
+    let (shared, reloader) = reloader_for(&path);
+    let clients = DbClients::default();
+    let mut worker = SyncWorker::new().with_failover(FailoverManager::new_with_config(&shared.current())).with_shared_config(shared.clone());
+    assert_eq!(worker.drift_thresholds().0, 100);
+
+    fs::write(&path, "[replication]\ndrift_max_records = 9\nfailover_fail_threshold = 4\nfailover_recover_threshold = 6\n").unwrap();
+    reloader.reload("test").await.unwrap();
+    worker.run_once(&clients).await.unwrap();
+    assert_eq!(worker.drift_thresholds().0, 9);
+    assert_eq!(worker.failover().thresholds(), (4, 6));
+    let _ = fs::remove_file(&path);
+}
+
+#[ntex::test]
+async fn added_execution_profiles_need_a_restart() {
+    let path = temp_config("profiles", BASE);
+    let (shared, reloader) = reloader_for(&path);
+
+    fs::write(&path, "[driver]\nrequest_timeout_ms = 900\n\n[driver.profiles.bulk]\nrequest_timeout_ms = 60000\n").unwrap();
+    let report = reloader.reload("test").await.unwrap();
+    assert_eq!(report.restart_required, vec!["driver.profiles.bulk".to_string()]);
+    assert!(report.applied.iter().any(|k| k == "driver.request_timeout_ms"), "{:?}", report.applied);
+    assert!(shared.current().driver.profiles.is_empty());
+    let _ = fs::remove_file(&path);
+}
@@ -0,0 +1,106 @@
+use std::env;
+use std::sync::{Mutex, OnceLock};
+
+use nayud_batch::config::{AppConfig, Severity};
+use nayud_batch::errors::AppError;
+
+static ENV_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+
+fn distinct_config() -> AppConfig {
+    let mut cfg = AppConfig::default();
+    cfg.active.contact_points = vec!["10.0.0.1".into(), "10.0.0.2".into(), "10.0.0.3".into()];
+    cfg.passive.contact_points = vec!["10.1.0.1".into(), "10.1.0.2".into(), "10.1.0.3".into()];
+    cfg.active.replication_factor = Some(3);
+    cfg.passive.replication_factor = Some(3);
+    cfg
+}
+
+fn fields(cfg: &AppConfig, severity: Severity) -> Vec<String> {
+    cfg.validate().issues.into_iter().filter(|i| i.severity == severity).map(|i| i.field).collect()
+}
+
+#[test]
+fn valid_config_has_no_errors() {
+    let cfg = distinct_config();
+    let report = cfg.validate();
+    assert!(!report.has_errors(), "{:?}", report.issues);
+    assert!(report.into_result(false).is_ok());
+}
+
+#[test]
+fn collects_every_problem_into_one_error() {
+    let mut cfg = distinct_config();
+    cfg.active.host = String::new();
+    cfg.active.contact_points.clear();
+    cfg.passive.replication_factor = Some(0);
+    cfg.passive.use_tls = true;
+    cfg.passive.tls_ca_file = Some("/nonexistent/ca.pem".into());
+    cfg.server.bind_addr = "not-an-address".into();
+    cfg.driver.compression = Some("zstd".into());
+
+    let errors = fields(&cfg, Severity::Error);
+    for f in ["active.host", "passive.replication_factor", "passive.tls_ca_file", "server.bind_addr", "driver.compression"] {
+        assert!(errors.iter().any(|e| e == f), "missing {} in {:?}", f, errors);
+    }
+
+    match cfg.validate().into_result(false) {
+        Err(AppError::Config(msg)) => {
+            assert!(msg.contains(&format!("{} problem(s)", errors.len())), "{}", msg);
+            assert!(msg.contains("server.bind_addr"));
+            assert!(msg.contains("/nonexistent/ca.pem"));
+        }
+        other => panic!("expected aggregated config error, got {:?}", other),
+    }
+}
+
+#[test]
+fn identical_active_and_passive_is_an_error() {
+    let mut cfg = distinct_config();
+    cfg.passive.contact_points = cfg.active.contact_points.iter().rev().cloned().collect();
+    cfg.passive.port = cfg.active.port;
+    cfg.passive.keyspace = cfg.active.keyspace.clone();
+    assert!(fields(&cfg, Severity::Error).iter().any(|f| f == "passive"));
+
+    cfg.passive.keyspace = format!("{}_dr", cfg.active.keyspace);
+    assert!(!fields(&cfg, Severity::Error).iter().any(|f| f == "passive"));
+    assert!(fields(&cfg, Severity::Warning).iter().any(|f| f == "passive.contact_points"));
+}
+
+#[test]
+fn strict_mode_refuses_warnings() {
+    let mut cfg = distinct_config();
+    cfg.active.contact_points = vec!["10.0.0.1".into()];
+    assert!(cfg.validate().into_result(true).is_ok(), "a single contact point says nothing about cluster size");
+
+    cfg.active.datacenter = String::new();
+    assert!(fields(&cfg, Severity::Warning).iter().any(|f| f == "active.datacenter"));
+
+    let warnings = cfg.validate().into_result(false).expect("warnings alone are not fatal");
+    assert!(!warnings.is_empty());
+
+    match cfg.validate().into_result(true) {
+        Err(AppError::Config(msg)) => assert!(msg.contains("strict"), "{}", msg),
+        other => panic!("expected strict failure, got {:?}", other),
+    }
+}
+
+#[test]
+fn invalid_env_values_are_reported() {
+    let _g = ENV_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+    let vars = [("ACTIVE_DB_PORT", "90x2"), ("NAYUD_CONFIG_STRICT", "yes please")];
+    let backup: Vec<(&str, Option<String>)> = vars.iter().map(|(k, _)| (*k, env::var(k).ok())).collect();
+    for (k, v) in vars {
+        unsafe { env::set_var(k, v) };
+    }
+    let cfg = AppConfig::from_env();
+    for (k, v) in backup {
+        match v {
+            Some(v) => unsafe { env::set_var(k, v) },
+            None => unsafe { env::remove_var(k) },
+        }
+    }
+
+    assert!(cfg.load_problems.iter().any(|p| p.contains("ACTIVE_DB_PORT")), "{:?}", cfg.load_problems);
+    assert!(cfg.load_problems.iter().any(|p| p.contains("NAYUD_CONFIG_STRICT")), "{:?}", cfg.load_problems);
+    assert!(cfg.validate().has_errors());
+}
@@ -0,0 +1,183 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use nayud_batch::config::{DbEndpoint, DriverConfig, ExecutionSettings};
+use nayud_batch::db::policies::{parse_consistency, parse_serial_consistency, retry_policy, speculative_policy, LatencyWindow};
+use nayud_batch::db::{configure_session, unsupported_driver_settings, ClusterExecution, LbPreference, LoadBalancingSettings};
+use scylla::client::session_builder::SessionBuilder;
+use scylla::client::PoolSize;
+use scylla::frame::Compression;
+use scylla::statement::{Consistency, SerialConsistency};
+
+#[test]
+fn load_balancing_defaults_to_dc_and_rack() {
+    let ep = DbEndpoint::default();
+    let lb = LoadBalancingSettings::from_config(&ep, &DriverConfig::default()).unwrap();
+    assert_eq!(lb.preference, LbPreference::DcRack);
+    assert!(lb.token_aware);
+    assert!(!lb.permit_dc_failover);
+    assert_eq!(lb.describe(), "dc_rack_aware dc=asia-southeast2 rack=asia-southeast2-a token_aware=true permit_dc_failover=false");
+}
+
+#[test]
+fn load_balancing_options_are_applied() {
+    let ep = DbEndpoint { rack: String::new(), ..DbEndpoint::default() };
+    let drv = DriverConfig {
+        load_balancing: Some("DC_RACK_AWARE".into()),
+        token_aware: Some(false),
+        permit_dc_failover: Some(true),
+        ..DriverConfig::default()
+    };
+    let lb = LoadBalancingSettings::from_config(&ep, &drv).unwrap();
+    assert_eq!(lb.preference, LbPreference::Dc);
+    assert!(!lb.token_aware);
+    assert!(lb.permit_dc_failover);
+
+    let drv = DriverConfig { load_balancing: Some("round_robin".into()), ..DriverConfig::default() };
+    let lb = LoadBalancingSettings::from_config(&DbEndpoint::default(), &drv).unwrap();
+    assert_eq!(lb.preference, LbPreference::None);
+    assert!(lb.describe().starts_with("round_robin"));
+
+    let drv = DriverConfig { load_balancing: Some("closest".into()), ..DriverConfig::default() };
+    assert!(LoadBalancingSettings::from_config(&DbEndpoint::default(), &drv).is_err());
+}
+
+#[test]
+fn consistency_and_retry_names_parse() {
+    assert_eq!(parse_consistency("LOCAL_QUORUM").unwrap(), Consistency::LocalQuorum);
+    assert_eq!(parse_consistency("local-one").unwrap(), Consistency::LocalOne);
+    assert!(parse_consistency("most").is_err());
+    assert_eq!(parse_serial_consistency("local_serial").unwrap(), Some(SerialConsistency::LocalSerial));
+    assert_eq!(parse_serial_consistency("none").unwrap(), None);
+    assert!(parse_serial_consistency("quorum").is_err());
+    for name in ["default", "fallthrough", "downgrading"] {
+        assert!(retry_policy(name).is_ok(), "{}", name);
+    }
+    assert!(retry_policy("forever").is_err());
+}
+
+#[test]
+fn speculative_policy_validation() {
+    let window = Arc::new(LatencyWindow::new());
+    let none = ExecutionSettings::default();
+    assert!(speculative_policy(&none, &window).unwrap().is_none());
+
+    let simple = ExecutionSettings { speculative_execution: Some("simple".into()), ..ExecutionSettings::default() };
+    assert!(speculative_policy(&simple, &window).unwrap().is_some());
+
+    let bad_pct = ExecutionSettings {
+        speculative_execution: Some("percentile".into()),
+        speculative_percentile: Some(150.0),
+        ..ExecutionSettings::default()
+    };
+    assert!(speculative_policy(&bad_pct, &window).is_err());
+
+    let unknown = ExecutionSettings { speculative_execution: Some("eager".into()), ..ExecutionSettings::default() };
+    assert!(speculative_policy(&unknown, &window).is_err());
+}
+
+#[test]
+fn latency_window_percentile() {
+    let window = LatencyWindow::new();
+    for ms in 1..=10 {
+        window.record(Duration::from_millis(ms));
+    }
+    assert_eq!(window.percentile(99.0), None);
+    for ms in 11..=100 {
+        window.record(Duration::from_millis(ms));
+    }
+    assert_eq!(window.len(), 100);
+    assert_eq!(window.percentile(50.0), Some(Duration::from_millis(51)));
+    assert_eq!(window.percentile(99.0), Some(Duration::from_millis(99)));
+    assert_eq!(window.percentile(100.0), Some(Duration::from_millis(100)));
+}
+
+#[test]
+fn execution_settings_layer_cluster_and_profile() {
+    let mut drv = DriverConfig {
+        execution: ExecutionSettings {
+            request_timeout_ms: Some(5000),
+            consistency: Some("local_quorum".into()),
+            retry_policy: Some("default".into()),
+            ..ExecutionSettings::default()
+        },
+        passive: ExecutionSettings { consistency: Some("one".into()), ..ExecutionSettings::default() },
+        ..DriverConfig::default()
+    };
+    drv.profiles.insert(
+        "bulk".into(),
+        ExecutionSettings { request_timeout_ms: Some(30000), retry_policy: Some("fallthrough".into()), ..ExecutionSettings::default() },
+    );
+
+    let active = drv.execution_for(true);
+    assert_eq!(active.consistency.as_deref(), Some("local_quorum"));
+    let passive = drv.execution_for(false);
+    assert_eq!(passive.consistency.as_deref(), Some("one"));
+    assert_eq!(passive.request_timeout_ms, Some(5000));
+
+    let bulk = drv.profile_for("bulk", false).unwrap();
+    assert_eq!(bulk.consistency.as_deref(), Some("one"));
+    assert_eq!(bulk.request_timeout_ms, Some(30000));
+    assert_eq!(bulk.retry_policy.as_deref(), Some("fallthrough"));
+    assert!(drv.profile_for("missing", true).is_none());
+}
+
+#[test]
+fn session_options_reach_the_builder() {
+    let drv = DriverConfig {
+        connection_timeout_ms: Some(2500),
+        tcp_keepalive_secs: Some(45),
+        tcp_nodelay: Some(false),
+        connections_per_shard: Some(3),
+        compression: Some("LZ4".into()),
+        ..DriverConfig::default()
+    };
+    let cfg = configure_session(SessionBuilder::new(), &DbEndpoint::default(), &drv).config;
+    assert_eq!(cfg.connect_timeout, Duration::from_millis(2500));
+    assert_eq!(cfg.tcp_keepalive_interval, Some(Duration::from_secs(45)));
+    assert!(!cfg.tcp_nodelay);
+    assert!(matches!(cfg.connection_pool_size, PoolSize::PerShard(n) if n.get() == 3));
+    assert_eq!(cfg.compression, Some(Compression::Lz4));
+
+    let drv = DriverConfig { connections_per_host: Some(2), compression: Some("none".into()), ..DriverConfig::default() };
+    let cfg = configure_session(SessionBuilder::new(), &DbEndpoint::default(), &drv).config;
+    assert!(cfg.tcp_nodelay);
+    assert_eq!(cfg.tcp_keepalive_interval, None);
+    assert!(matches!(cfg.connection_pool_size, PoolSize::PerHost(n) if n.get() == 2));
+    assert_eq!(cfg.compression, None);
+}
+
+#[test]
+fn default_page_size_reaches_cluster_execution() {
+    let ep = DbEndpoint::default();
+    let drv = DriverConfig { default_page_size: Some(250), ..DriverConfig::default() };
+    let (handle, exec) = ClusterExecution::from_config(&ep, &drv, true).unwrap();
+    assert_eq!(exec.page_size, Some(250));
+
+    let drv = DriverConfig { default_page_size: Some(0), ..DriverConfig::default() };
+    let remapped = exec.remap(Some(&handle), &ep, &drv, true).unwrap();
+    assert_eq!(remapped.page_size, None);
+}
+
+#[test]
+fn unsupported_driver_settings_are_reported() {
+    assert!(unsupported_driver_settings(&DriverConfig::default()).is_empty());
+
+    let drv = DriverConfig {
+        compression: Some("zstd".into()),
+        connections_per_shard: Some(2),
+        connections_per_host: Some(4),
+        default_page_size: Some(0),
+        execution: ExecutionSettings {
+            speculative_execution: Some("simple".into()),
+            speculative_percentile: Some(95.0),
+            ..ExecutionSettings::default()
+        },
+        ..DriverConfig::default()
+    };
+    let warnings = unsupported_driver_settings(&drv);
+    assert!(warnings.iter().any(|w| w.contains("compression 'zstd'")));
+    assert!(warnings.iter().any(|w| w.contains("connections_per_host is ignored")));
+    assert!(warnings.iter().any(|w| w.contains("default_page_size 0")));
+    assert!(warnings.iter().any(|w| w.starts_with("driver.active: speculative_percentile")));
+}
@@ -0,0 +1,54 @@
+use nayud_batch::config::ExportConfig;
+use nayud_batch::db::values::{date_to_string, decimal_to_string, time_to_string, timestamp_to_string, to_json, to_text, varint_to_string};
+use nayud_batch::export::{ExportFormat, ExportSource, ExportSpec};
+use scylla::value::{CqlDecimal, CqlValue, CqlVarint};
+
+#[test]
+fn varint_and_decimal_render_as_base10() {
+    assert_eq!(varint_to_string(&CqlVarint::from_signed_bytes_be(vec![0x01, 0x00])), "256");
+    assert_eq!(varint_to_string(&CqlVarint::from_signed_bytes_be(vec![0xff])), "-1");
+    assert_eq!(varint_to_string(&CqlVarint::from_signed_bytes_be(vec![0xff, 0x00])), "-256");
+    assert_eq!(varint_to_string(&CqlVarint::from_signed_bytes_be(vec![0x00])), "0");
+
+    assert_eq!(decimal_to_string(&CqlDecimal::from_signed_be_bytes_and_exponent(vec![0x04, 0xd2], 2)), "12.34");
+    assert_eq!(decimal_to_string(&CqlDecimal::from_signed_be_bytes_and_exponent(vec![0xfb, 0x2e], 3)), "-1.234");
+    assert_eq!(decimal_to_string(&CqlDecimal::from_signed_be_bytes_and_exponent(vec![0x05], 3)), "0.005");
+    assert_eq!(decimal_to_string(&CqlDecimal::from_signed_be_bytes_and_exponent(vec![0x05], -2)), "500");
+}
+
+#[test]
+fn temporal_values_render_as_iso() {
+    assert_eq!(date_to_string(1 << 31), "1970-01-01");
+    assert_eq!(date_to_string((1 << 31) + 19_782), "2024-02-29");
+    assert_eq!(timestamp_to_string(0), "1970-01-01T00:00:00.000Z");
+    assert_eq!(timestamp_to_string(1_700_000_000_123), "2023-11-14T22:13:20.123Z");
+    assert_eq!(timestamp_to_string(-1), "1969-12-31T23:59:59.999Z");
+    assert_eq!(time_to_string(3_723_000_000_004), "01:02:03.000000004");
+}
+
+#[test]
+fn json_and_text_forms() {
+    assert_eq!(to_text(&CqlValue::Blob(vec![0xde, 0xad])), "0xdead");
+    assert_eq!(to_json(&CqlValue::Int(5)), serde_json::json!(5));
+    let m = CqlValue::Map(vec![(CqlValue::Text("a".into()), CqlValue::List(vec![CqlValue::Boolean(true)]))]);
+    assert_eq!(to_json(&m), serde_json::json!({"a": [true]}));
+}
+
+#[test]
+fn export_sources_are_whitelisted() {
+    let mut cfg = ExportConfig::default();
+    cfg.queries.insert("recent".into(), "SELECT id FROM batch.orders".into());
+    cfg.queries.insert("bad".into(), "DELETE FROM batch.orders WHERE id = 1".into());
+
+    let q = ExportSource::named_query(&cfg, "recent").unwrap();
+    assert_eq!(q.cql(), "SELECT id FROM batch.orders");
+    assert!(ExportSource::named_query(&cfg, "missing").is_err());
+    assert!(ExportSource::named_query(&cfg, "bad").is_err());
+
+    let t = ExportSource::table("batch", "orders");
+    assert_eq!(t.cql(), "SELECT * FROM \"batch\".\"orders\"");
+
+    let spec = ExportSpec::new(q, ExportFormat::parse("parquet").unwrap(), "/tmp/exports");
+    assert!(spec.out_dir.ends_with("query-recent"));
+    assert!(ExportFormat::parse("xlsx").is_err());
+}
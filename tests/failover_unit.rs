@@ -1,5 +1,6 @@
-use nayud_batch::replication::{FailoverManager, Cluster};
+use nayud_batch::config::AppConfig;
 use nayud_batch::db::DbClients;
+use nayud_batch::replication::{store_primary, Cluster, FailoverManager};
 
 #[ntex::test]
 async fn last_status_updates_and_no_switch_when_both_down() {
@@ -13,4 +14,29 @@ async fn last_status_updates_and_no_switch_when_both_down() {
     let _ = fm.tick_with_status(&clients, true, false).await;
     assert_eq!(fm.last_status(), (true, false));
     assert_eq!(fm.current_primary(), Cluster::Active);
-}
\ No newline at end of file
+}
+
+#[ntex::test]
+async fn promotions_written_by_the_cli_reach_a_running_manager() {
+    let dir = std::env::temp_dir().join(format!("nayud-failover-{}", uuid::Uuid::new_v4()));
+    let mut cfg = AppConfig::default();
+    cfg.replication.outbox_dir = dir.display().to_string();
+    let clients = DbClients::default();
+    let mut fm = FailoverManager::new_with_config(&cfg);
+
+    let _ = fm.tick_with_status(&clients, true, true).await;
+    assert_eq!(fm.current_primary(), Cluster::Active);
+
+    store_primary(&dir, Cluster::Passive).unwrap();
+    for _ in 0..10 {
+        let _ = fm.tick_with_status(&clients, true, true).await;
+    }
+    assert_eq!(fm.current_primary(), Cluster::Passive);
+    assert_eq!(clients.primary(), Cluster::Passive);
+
+    store_primary(&dir, Cluster::Active).unwrap();
+    let _ = fm.tick_with_status(&clients, true, true).await;
+    assert_eq!(fm.current_primary(), Cluster::Active);
+    assert_eq!(clients.primary(), Cluster::Active);
+    let _ = std::fs::remove_dir_all(&dir);
+}
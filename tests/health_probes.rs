@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nayud_batch::config::ReplicationConfig;
+use nayud_batch::db::DbClients;
+use nayud_batch::health::probes::{liveness, outbox_checks, readiness, startup, ProbeState};
+use nayud_batch::health::HealthState;
+use nayud_batch::replication::{Cluster, FailoverManager, OutboxRecord, OutboxTarget, ReplicationManager};
+use nayud_batch::types::response::CODE_SERVICE_UNAVAILABLE;
+
+fn temp_outbox_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    dir.push(format!("nayud_batch_test_probes_{}_{}", tag, ts));
+    dir
+}
+
+fn failed(checks: &[nayud_batch::health::probes::ProbeCheck]) -> Vec<&'static str> {
+    checks.iter().filter(|c| !c.ok).map(|c| c.name).collect()
+}
+
+#[test]
+fn startup_requires_keyspaces_migrations_and_start() {
+    assert!(liveness().ok);
+
+    let probes = ProbeState::new();
+    let report = startup(&probes);
+    assert_eq!(failed(&report.checks), vec!["keyspaces", "migrations", "started"]);
+    assert_eq!(report.into_response("startup").code, CODE_SERVICE_UNAVAILABLE);
+
+    probes.set_keyspaces_ok(true);
+    probes.set_migrations_ok(true);
+    probes.mark_started();
+    assert!(startup(&probes).ok);
+}
+
+#[ntex::test]
+async fn outbox_checks_enforce_drift_threshold() {
+    let dir = temp_outbox_dir("drift");
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let cfg = ReplicationConfig { drift_max_records: 1, ..ReplicationConfig::default() };
+    assert!(outbox_checks(&repl, &cfg).iter().all(|c| c.ok));
+
+    for i in 0..2 {
+        repl.enqueue(OutboxRecord::new_simple(format!("k{}", i), "SELECT now() FROM system.local", OutboxTarget::Passive)).await.unwrap();
+    }
+    assert_eq!(failed(&outbox_checks(&repl, &cfg)), vec!["outbox_drift"]);
+
+    let missing = ReplicationManager::new();
+    assert_eq!(failed(&outbox_checks(&missing, &cfg)), vec!["outbox_writable", "outbox_drift"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[ntex::test]
+async fn readiness_flips_during_shutdown() {
+    let dir = temp_outbox_dir("ready");
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let clients = DbClients::default();
+    let probes = ProbeState::new();
+    probes.set_keyspaces_ok(true);
+    probes.set_migrations_ok(true);
+    probes.mark_started();
+
+    let report = readiness(&probes, &clients, &repl, &ReplicationConfig::default()).await;
+    assert_eq!(failed(&report.checks), vec!["primary_reachable"]);
+
+    probes.begin_shutdown();
+    let report = readiness(&probes, &clients, &repl, &ReplicationConfig::default()).await;
+    assert_eq!(failed(&report.checks), vec!["accepting_traffic", "primary_reachable"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[ntex::test]
+async fn readiness_probes_the_cluster_failover_switched_to() {
+    let dir = temp_outbox_dir("failover");
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let clients = DbClients::default();
+    let probes = ProbeState::new();
+    let cfg = ReplicationConfig::default();
+    let primary_detail = |report: nayud_batch::health::probes::ProbeReport| {
+        report.checks.into_iter().find(|c| c.name == "primary_reachable").and_then(|c| c.detail).unwrap_or_default()
+    };
+
+    assert!(primary_detail(readiness(&probes, &clients, &repl, &cfg).await).starts_with("Active cluster"));
+
+    let mut fm = FailoverManager::new().with_force_ready(true);
+    for _ in 0..3 { let _ = fm.tick_with_health(&clients, HealthState::Down, HealthState::Up).await; }
+    assert_eq!(fm.current_primary(), Cluster::Passive);
+    assert_eq!(clients.primary(), Cluster::Passive);
+    assert!(primary_detail(readiness(&probes, &clients, &repl, &cfg).await).starts_with("Passive cluster"));
+
+    for _ in 0..5 { let _ = fm.tick_with_health(&clients, HealthState::Up, HealthState::Up).await; }
+    assert!(primary_detail(readiness(&probes, &clients, &repl, &cfg).await).starts_with("Active cluster"));
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use nayud_batch::db::DbClients;
+use nayud_batch::health::{db_health, ClusterHealth, DcHealth, HealthState};
+use nayud_batch::replication::{Cluster, FailoverManager};
+
+fn reachable() -> ClusterHealth {
+    let mut datacenters = BTreeMap::new();
+    datacenters.insert("dc1".to_string(), DcHealth { up: 3, down: 0 });
+    ClusterHealth {
+        latency_ms: Some(2),
+        release_version: Some("3.0.8".into()),
+        known_nodes: 3,
+        connected_nodes: 3,
+        datacenters,
+        schema_agreement: Some(true),
+        keyspace_exists: Some(true),
+        ..ClusterHealth::default()
+    }
+}
+
+#[test]
+fn cluster_health_classification() {
+    let mut h = reachable();
+    h.classify();
+    assert_eq!(h.state, HealthState::Up);
+    assert!(h.problems.is_empty());
+
+    let mut h = reachable();
+    h.datacenters.insert("dc2".into(), DcHealth { up: 1, down: 2 });
+    h.classify();
+    assert_eq!(h.state, HealthState::Degraded);
+
+    let mut h = reachable();
+    h.schema_agreement = Some(false);
+    h.keyspace_exists = Some(false);
+    h.classify();
+    assert_eq!(h.state, HealthState::Degraded);
+    assert_eq!(h.problems.len(), 2);
+
+    let h = ClusterHealth::unreachable("health query failed");
+    assert_eq!(h.state, HealthState::Down);
+}
+
+#[ntex::test]
+async fn health_without_sessions_is_down() {
+    let resp = db_health(&DbClients::default()).await;
+    let data = resp.data.unwrap();
+    assert!(!data.active_ok && !data.passive_ok);
+    assert_eq!(data.active.state, HealthState::Down);
+    assert!(!data.passive.problems.is_empty());
+}
+
+#[ntex::test]
+async fn degraded_active_does_not_fail_over_and_counts_toward_recovery() {
+    let clients = DbClients::default();
+    let mut fm = FailoverManager::new().with_force_ready(true);
+
+    for _ in 0..5 { let _ = fm.tick_with_health(&clients, HealthState::Degraded, HealthState::Up).await; }
+    assert_eq!(fm.current_primary(), Cluster::Active);
+    assert_eq!(fm.last_health(), (HealthState::Degraded, HealthState::Up));
+
+    for _ in 0..3 { let _ = fm.tick_with_health(&clients, HealthState::Down, HealthState::Degraded).await; }
+    assert_eq!(fm.current_primary(), Cluster::Passive);
+
+    for _ in 0..4 { let _ = fm.tick_with_health(&clients, HealthState::Degraded, HealthState::Up).await; }
+    assert_eq!(fm.current_primary(), Cluster::Passive);
+
+    let _ = fm.tick_with_health(&clients, HealthState::Up, HealthState::Up).await;
+    assert_eq!(fm.current_primary(), Cluster::Active);
+}
@@ -0,0 +1,42 @@
+use nayud_batch::errors::AppError;
+use nayud_batch::types::response::{status_for_code, ApiResponse, CODE_FAILURE, CODE_SUCCESS, CODE_VALIDATION};
+use ntex::http::StatusCode;
+use ntex::web::{self, test};
+
+#[test]
+fn app_errors_map_to_http_statuses() {
+    let cases = [
+        (AppError::validation("x"), StatusCode::BAD_REQUEST),
+        (AppError::web("x"), StatusCode::BAD_REQUEST),
+        (AppError::not_found("x"), StatusCode::NOT_FOUND),
+        (AppError::forbidden("x"), StatusCode::FORBIDDEN),
+        (AppError::db("x"), StatusCode::SERVICE_UNAVAILABLE),
+        (AppError::unavailable("x"), StatusCode::SERVICE_UNAVAILABLE),
+        (AppError::config("x"), StatusCode::INTERNAL_SERVER_ERROR),
+        (AppError::other("x"), StatusCode::INTERNAL_SERVER_ERROR),
+    ];
+    for (err, status) in cases {
+        assert_eq!(err.status_code(), status, "{}", err);
+        assert_eq!(status_for_code(err.code()), status, "{}", err);
+        assert_eq!(ApiResponse::<()>::from_error(&err).status(), status, "{}", err);
+    }
+    assert_eq!(status_for_code(CODE_SUCCESS), StatusCode::OK);
+    assert_eq!(status_for_code(CODE_FAILURE), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+async fn rejects() -> Result<web::HttpResponse, AppError> {
+    Err(AppError::validation("limit must be positive"))
+}
+
+#[ntex::test]
+async fn handler_errors_render_detail_body_with_status() {
+    let app = test::init_service(web::App::new().route("/x", web::get().to(rejects))).await;
+    let resp = test::call_service(&app, test::TestRequest::get().uri("/x").to_request()).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+    assert_eq!(body["code"], CODE_VALIDATION);
+    assert!(body["message"]["what"].as_str().unwrap().contains("limit must be positive"));
+    assert!(body["message"]["why"].is_string());
+    assert!(body["message"]["how"].is_string());
+}
@@ -0,0 +1,161 @@
+use nayud_batch::db::DbClients;
+use nayud_batch::import::{
+    run_import_with_schema, CqlType, ImportFormat, ImportMapping, ImportOptions, SchemaColumn, StreamReader, TableSchema,
+};
+use nayud_batch::replication::{OutboxTarget, ReplicationManager};
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+fn temp_outbox_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    dir.push(format!("nayud_batch_test_import_{}_{}", tag, ts));
+    dir
+}
+
+fn customers_schema() -> TableSchema {
+    let col = |name: &str, kind: &str, ty: &str| SchemaColumn { name: name.into(), kind: kind.into(), position: 0, ty: CqlType::parse(ty) };
+    TableSchema {
+        keyspace: "batch".into(),
+        table: "customers".into(),
+        columns: vec![
+            col("id", "partition_key", "uuid"),
+            col("name", "regular", "text"),
+            col("age", "regular", "int"),
+            col("active", "regular", "boolean"),
+        ],
+    }
+}
+
+#[test]
+fn literal_rendering_validates_types() {
+    assert_eq!(CqlType::Text.render_literal("O'Brien").unwrap(), "'O''Brien'");
+    assert_eq!(CqlType::Int.render_literal(" 42 ").unwrap(), "42");
+    assert!(CqlType::Int.render_literal("forty").is_err());
+    assert!(CqlType::TinyInt.render_literal("300").is_err());
+    assert_eq!(CqlType::Boolean.render_literal("Yes").unwrap(), "true");
+    assert_eq!(CqlType::Blob.render_literal("0xDEADbeef").unwrap(), "0xdeadbeef");
+    assert!(CqlType::Blob.render_literal("0xabc").is_err());
+    assert_eq!(CqlType::Date.render_literal("2024-02-29").unwrap(), "'2024-02-29'");
+    assert!(CqlType::Date.render_literal("2024-13-01").is_err());
+    assert_eq!(CqlType::Timestamp.render_literal("1700000000000").unwrap(), "1700000000000");
+    assert!(CqlType::Decimal.render_literal("1.5e-3").is_ok());
+    assert!(CqlType::Decimal.render_literal("1..5").is_err());
+    assert!(CqlType::parse("list<int>").render_literal("[1]").is_err());
+}
+
+#[test]
+fn mapping_pairs_and_toml() {
+    let m = ImportMapping::parse_pairs(None, "customers", "Customer ID:id, Full Name:name").unwrap();
+    assert_eq!(m.columns.len(), 2);
+    assert_eq!(m.columns[0].source, "Customer ID");
+    assert_eq!(m.columns[1].column, "name");
+    assert!(ImportMapping::parse_pairs(None, "customers", "broken").is_err());
+
+    let t = ImportMapping::from_toml_str(
+        "table = \"customers\"\n[[columns]]\nsource = \"cid\"\ncolumn = \"id\"\n",
+    )
+    .unwrap();
+    assert_eq!(t.table, "customers");
+    assert_eq!(t.columns.len(), 1);
+}
+
+#[ntex::test]
+async fn csv_import_queues_failed_writes_and_rejects_bad_lines() {
+    let dir = temp_outbox_dir("csv");
+    let _ = fs::remove_dir_all(&dir);
+
+    let clients = DbClients::default();
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let schema = customers_schema();
+    let mapping = ImportMapping::parse_pairs(None, "customers", "cid:id,full_name:name,age:age").unwrap();
+    let opts = ImportOptions { target: OutboxTarget::Active, concurrency: 2, ..ImportOptions::default() };
+
+    let csv = "cid,full_name,age\n\
+               6f1c7a2e-8c1b-4a57-9d0e-1f2a3b4c5d6e,Alice,30\n\
+               not-a-uuid,Bob,31\n\
+               0b9d7c1e-2f3a-4b5c-8d9e-0f1a2b3c4d5e,Carol,old\n\
+               1c2d3e4f-5a6b-4c7d-8e9f-0a1b2c3d4e5f,Dan,\n";
+
+    let report = run_import_with_schema(Cursor::new(csv), &mapping, &schema, &opts, &repl, &clients)
+        .await
+        .expect("import runs");
+
+    assert_eq!(report.rows_read, 4);
+    assert_eq!(report.rows_written, 0);
+    assert_eq!(report.rows_queued, 2);
+    assert_eq!(report.rows_rejected, 2);
+    assert_eq!(report.rejections[0].line, 3);
+    assert!(report.rejections[1].reason.contains("age"));
+    assert_eq!(repl.queue_len(), 2);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[ntex::test]
+async fn ndjson_import_requires_primary_key() {
+    let dir = temp_outbox_dir("ndjson");
+    let _ = fs::remove_dir_all(&dir);
+
+    let clients = DbClients::default();
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let schema = customers_schema();
+    let mapping = ImportMapping::identity(None, "customers");
+    let opts = ImportOptions { format: ImportFormat::Ndjson, target: OutboxTarget::Passive, ..ImportOptions::default() };
+
+    let input = "{\"id\":\"6f1c7a2e-8c1b-4a57-9d0e-1f2a3b4c5d6e\",\"name\":\"Alice\",\"active\":true}\n\
+                 \n\
+                 {\"name\":\"NoKey\"}\n\
+                 [1,2,3]\n";
+
+    let report = run_import_with_schema(Cursor::new(input), &mapping, &schema, &opts, &repl, &clients)
+        .await
+        .expect("import runs");
+
+    assert_eq!(report.rows_read, 3);
+    assert_eq!(report.rows_queued, 1);
+    assert_eq!(report.rows_rejected, 2);
+    assert!(report.rejections[0].reason.contains("primary key"));
+
+    let unmapped = ImportMapping::parse_pairs(None, "customers", "name:name").unwrap();
+    let err = run_import_with_schema(Cursor::new(""), &unmapped, &schema, &opts, &repl, &clients).await;
+    assert!(err.is_err());
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[ntex::test]
+async fn streamed_upload_is_parsed_across_chunk_boundaries() {
+    let dir = temp_outbox_dir("stream");
+    let _ = fs::remove_dir_all(&dir);
+
+    let clients = DbClients::default();
+    let repl = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let schema = customers_schema();
+    let mapping = ImportMapping::identity(None, "customers");
+    let opts = ImportOptions { format: ImportFormat::Ndjson, target: OutboxTarget::Active, concurrency: 4, ..ImportOptions::default() };
+
+    let body: String = (0..50)
+        .map(|i| format!("{{\"id\":\"6f1c7a2e-8c1b-4a57-9d0e-{:012}\",\"name\":\"n{}\"}}\n", i, i))
+        .collect();
+    let (chunks, reader) = StreamReader::channel(2);
+    let feed = async move {
+        for piece in body.as_bytes().chunks(37) {
+            chunks.send(Ok(piece.to_vec())).await.unwrap();
+        }
+    };
+    let import = run_import_with_schema(reader, &mapping, &schema, &opts, &repl, &clients);
+    let ((), report) = futures::join!(feed, import);
+    let report = report.expect("import runs");
+
+    assert_eq!(report.rows_read, 50);
+    assert_eq!(report.rows_rejected, 0);
+    assert_eq!(report.rows_queued, 50);
+    assert_eq!(repl.queue_len(), 50);
+
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,53 @@
+use nayud_batch::errors::AppError;
+use nayud_batch::jobs::{JobRegistry, JobStatus};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn temp_artifact_dir() -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_test_jobs_{}", ts));
+    dir
+}
+
+#[ntex::test]
+async fn spawned_jobs_record_result_and_failure() {
+    let jobs = JobRegistry::new();
+    let ok = jobs.spawn("demo", async { Ok::<_, AppError>(vec![1, 2, 3]) });
+    let bad = jobs.spawn("demo", async { Err::<(), _>(AppError::db("down")) });
+    assert_eq!(ok.status, JobStatus::Pending);
+
+    for _ in 0..50 {
+        let done = jobs.list().iter().all(|j| matches!(j.status, JobStatus::Succeeded | JobStatus::Failed));
+        if done { break; }
+        ntex::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let ok = jobs.get(&ok.id).unwrap();
+    assert_eq!(ok.status, JobStatus::Succeeded);
+    assert_eq!(ok.result, Some(serde_json::json!([1, 2, 3])));
+
+    let bad = jobs.get(&bad.id).unwrap();
+    assert_eq!(bad.status, JobStatus::Failed);
+    assert!(bad.error.unwrap().contains("down"));
+    assert_eq!(jobs.list().len(), 2);
+}
+
+#[ntex::test]
+async fn finished_jobs_beyond_the_limit_are_evicted_with_their_artifacts() {
+    let dir = temp_artifact_dir();
+    let jobs = JobRegistry::new().with_artifact_dir(&dir).with_max_finished(2);
+    let mut ids = Vec::new();
+    for i in 0..4 {
+        let job = jobs.run("demo", Some("out.json"), async move { Ok::<_, AppError>(i) }).await;
+        ids.push(job.id);
+        ntex::time::sleep(Duration::from_millis(2)).await;
+    }
+
+    let kept: Vec<String> = jobs.list().into_iter().map(|j| j.id).collect();
+    assert_eq!(kept, ids[2..].to_vec());
+    assert!(jobs.get(&ids[0]).is_none());
+    assert!(!dir.join(&ids[0]).exists());
+    assert!(dir.join(&ids[3]).join("out.json").exists());
+    let _ = std::fs::remove_dir_all(&dir);
+}
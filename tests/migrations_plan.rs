@@ -0,0 +1,75 @@
+use nayud_batch::migrations::{
+    load_migrations, parse_file_name, split_statements, AppliedMigration, ClusterMigrationStatus, Migration, MigrationStatus,
+};
+use std::fs;
+
+fn applied(m: &Migration) -> AppliedMigration {
+    AppliedMigration { version: m.version, name: m.name.clone(), checksum: m.checksum.clone(), applied_ms: 0 }
+}
+
+#[test]
+fn file_names_and_statement_splitting() {
+    assert_eq!(parse_file_name("0007_add_orders.cql"), Some((7, "add_orders".to_string())));
+    assert_eq!(parse_file_name("12.cql"), Some((12, String::new())));
+    assert_eq!(parse_file_name("v1_init.cql"), None);
+    assert_eq!(parse_file_name("0001_init.sql"), None);
+
+    let body = "-- orders\nCREATE TABLE {{KEYSPACE}}.t (id int PRIMARY KEY, note text);\n\
+                INSERT INTO {{KEYSPACE}}.t (id, note) VALUES (1, 'a;b'); // seed\n\n";
+    let m = Migration::new(2, "orders", body);
+    let stmts = m.statements("batch");
+    assert_eq!(stmts.len(), 2);
+    assert!(stmts[0].starts_with("CREATE TABLE \"batch\".t"));
+    assert!(stmts[1].ends_with("'a;b')"));
+    assert!(split_statements("  ;\n-- only a comment\n").is_empty());
+}
+
+#[test]
+fn evaluate_detects_pending_mismatch_and_unknown() {
+    let m1 = Migration::new(1, "init", "CREATE TABLE a (id int PRIMARY KEY)");
+    let m2 = Migration::new(2, "more", "CREATE TABLE b (id int PRIMARY KEY)");
+    let mut edited = applied(&m1);
+    edited.checksum = "deadbeef".into();
+    let ghost = AppliedMigration { version: 9, name: "ghost".into(), checksum: String::new(), applied_ms: 0 };
+
+    let st = ClusterMigrationStatus::evaluate("Active", "batch", &[m1.clone(), m2.clone()], vec![edited, ghost]);
+    assert_eq!(st.current_version, Some(9));
+    assert_eq!(st.checksum_mismatches, vec![1]);
+    assert_eq!(st.unknown, vec![9]);
+    assert_eq!(st.pending.len(), 1);
+    assert_eq!(st.pending[0].version, 2);
+}
+
+#[test]
+fn agreement_requires_equal_versions() {
+    let m1 = Migration::new(1, "init", "CREATE TABLE a (id int PRIMARY KEY)");
+    let m2 = Migration::new(2, "more", "CREATE TABLE b (id int PRIMARY KEY)");
+    let all = [m1.clone(), m2.clone()];
+
+    let a = ClusterMigrationStatus::evaluate("Active", "batch", &all, vec![applied(&m1), applied(&m2)]);
+    let p = ClusterMigrationStatus::evaluate("Passive", "batch", &all, vec![applied(&m1)]);
+    let st = MigrationStatus::new(a.clone(), p);
+    assert!(!st.in_agreement);
+    assert!(st.ensure_in_agreement().unwrap_err().to_message().contains("versions differ"));
+
+    let p = ClusterMigrationStatus::evaluate("Passive", "batch", &all, vec![applied(&m1), applied(&m2)]);
+    assert!(MigrationStatus::new(a, p).ensure_in_agreement().is_ok());
+}
+
+#[test]
+fn load_rejects_duplicates_and_reads_repo_migrations() {
+    let repo = load_migrations(concat!(env!("CARGO_MANIFEST_DIR"), "/cql/migrations")).unwrap();
+    assert!(repo.first().is_some_and(|m| m.version == 1));
+
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("nayud_batch_test_migrations_{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("0001_a.cql"), "SELECT 1").unwrap();
+    fs::write(dir.join("001_b.cql"), "SELECT 2").unwrap();
+    assert!(load_migrations(&dir).is_err());
+    let _ = fs::remove_dir_all(&dir);
+
+    let missing = load_migrations(dir.join("missing")).unwrap_err();
+    assert!(missing.to_message().contains("does not exist"));
+}
@@ -0,0 +1,124 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use nayud_batch::config::{AppConfig, SecretResolver};
+use nayud_batch::replication::{Outbox, OutboxCodec, OutboxCompression, OutboxFilter, OutboxRecord, OutboxTarget};
+
+const KEY_ONE: [u8; 32] = [7u8; 32];
+const KEY_TWO: [u8; 32] = [9u8; 32];
+
+fn temp_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_test_codec_{}_{}", tag, ts));
+    dir
+}
+
+fn big_record(key: &str) -> OutboxRecord {
+    let values = (0..64).map(|i| format!("('{}', {}, 'customer-{}')", key, i, i)).collect::<Vec<_>>().join(", ");
+    let mut rec = OutboxRecord::new_simple(key, format!("INSERT INTO batch.customers (k, n, name) VALUES {}", values), OutboxTarget::Both);
+    rec.params = vec![b"secret-card-number".to_vec()];
+    rec
+}
+
+fn keys(outbox: &Outbox) -> Vec<String> {
+    outbox.find(&OutboxFilter::default(), 0, 100).unwrap().into_iter().map(|e| e.key).collect()
+}
+
+#[test]
+fn compressed_frames_are_smaller_and_mix_with_plain_ones() {
+    let dir = temp_dir("compress");
+    let plain = Outbox::open(&dir).unwrap().with_fsync(false);
+    let first = plain.append(big_record("plain")).unwrap();
+    drop(plain);
+
+    for compression in [OutboxCompression::Lz4, OutboxCompression::Zstd] {
+        let outbox = Outbox::open(&dir).unwrap().with_fsync(false).with_codec(OutboxCodec::plain().with_compression(compression, 64));
+        let before = outbox.end_offset().unwrap();
+        let end = outbox.append(big_record(&format!("{:?}", compression))).unwrap();
+        assert!(end - before < first / 2, "{:?} frame is {} bytes, plain is {}", compression, end - before, first);
+    }
+
+    let outbox = Outbox::open(&dir).unwrap();
+    assert_eq!(keys(&outbox), ["plain", "Lz4", "Zstd"]);
+    assert_eq!(outbox.pending_count().unwrap(), 3);
+    let batch = outbox.read_from(0, 10).unwrap();
+    assert_eq!(batch[1].2.statement, big_record("Lz4").statement);
+    assert_eq!(batch[2].2.statement, big_record("Zstd").statement);
+    assert_eq!(batch[2].2.params, vec![b"secret-card-number".to_vec()]);
+    assert!(outbox.scan().unwrap().is_healthy());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn encrypted_frames_support_key_rotation_and_detect_tampering() {
+    let dir = temp_dir("encrypt");
+    let v1 = OutboxCodec::plain().with_key(1, KEY_ONE).with_write_key(Some(1)).unwrap();
+    let outbox = Outbox::open(&dir).unwrap().with_fsync(false).with_codec(v1);
+    let first = outbox.append(big_record("old")).unwrap();
+
+    let v2 = OutboxCodec::plain()
+        .with_compression(OutboxCompression::Lz4, 0)
+        .with_key(1, KEY_ONE)
+        .with_key(2, KEY_TWO)
+        .with_write_key(Some(2))
+        .unwrap();
+    let outbox = outbox.with_codec(v2.clone());
+    outbox.append(big_record("new")).unwrap();
+    assert_eq!(keys(&outbox), ["old", "new"]);
+
+    let raw = fs::read(dir.join("outbox.log")).unwrap();
+    assert!(!raw.windows(9).any(|w| w == b"customers"));
+    assert!(!raw.windows(6).any(|w| w == b"secret"));
+
+    let locked = Outbox::open_read_only(&dir).unwrap().with_codec(OutboxCodec::plain().with_key(2, KEY_TWO));
+    assert_eq!(locked.pending_count().unwrap(), 2);
+    let err = locked.read_from(0, 10).unwrap_err().to_message();
+    assert!(err.contains("key 1"), "{}", err);
+    let scan = locked.scan().unwrap();
+    assert!(scan.corrupt.is_empty(), "{:?}", scan.corrupt);
+    assert_eq!((scan.records, scan.unknown_key.len(), scan.unknown_key[0].key_id), (1, 1, 1));
+    assert!(scan.describe_problems().contains("1 record(s) encrypted with unknown key 1"), "{}", scan.describe_problems());
+    assert_eq!(locked.export_to(Vec::new(), true).unwrap().skipped_unknown_key, 1);
+
+    let mut file = OpenOptions::new().write(true).open(dir.join("outbox.log")).unwrap();
+    file.seek(SeekFrom::Start(first - 20)).unwrap();
+    file.write_all(&[0xff; 4]).unwrap();
+    drop(file);
+    let scan = Outbox::open_read_only(&dir).unwrap().with_codec(v2).scan().unwrap();
+    assert_eq!(scan.records, 1);
+    assert_eq!(scan.corrupt[0].reason, "decryption failed");
+
+    assert!(OutboxCodec::plain().with_write_key(Some(3)).is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn codec_is_built_and_validated_from_config() {
+    let dir = temp_dir("config");
+    fs::create_dir_all(&dir).unwrap();
+    let key_file = dir.join("outbox-2.key");
+    fs::write(&key_file, KEY_TWO).unwrap();
+
+    let mut cfg = AppConfig::default();
+    cfg.replication.outbox_compression = "lz4".into();
+    cfg.replication.outbox_keys.insert("1".into(), "07".repeat(32));
+    cfg.replication.outbox_key_files.insert("2".into(), key_file.display().to_string());
+    cfg.replication.outbox_encryption_key_id = Some(2);
+    let report = cfg.validate();
+    assert_eq!(report.errors().count(), 0, "{:?}", report);
+    let codec = OutboxCodec::from_config(&cfg.replication, &SecretResolver::local()).unwrap();
+    assert_eq!(codec.compression(), OutboxCompression::Lz4);
+    assert_eq!(codec.write_key(), Some(2));
+
+    cfg.replication.outbox_compression = "brotli".into();
+    cfg.replication.outbox_keys.insert("0".into(), "not-a-key".into());
+    cfg.replication.outbox_encryption_key_id = Some(5);
+    let fields: Vec<String> = cfg.validate().issues.iter().map(|i| i.field.clone()).collect();
+    for field in ["replication.outbox_compression", "replication.outbox_keys.0", "replication.outbox_encryption_key_id"] {
+        assert!(fields.iter().any(|f| f == field), "missing {} in {:?}", field, fields);
+    }
+    assert!(!cfg.render_effective().contains(&"07".repeat(32)));
+    let _ = fs::remove_dir_all(&dir);
+}
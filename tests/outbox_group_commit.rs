@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use nayud_batch::replication::{Outbox, OutboxRecord, OutboxTarget};
+
+fn temp_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_test_group_{}_{}", tag, ts));
+    dir
+}
+
+#[test]
+fn concurrent_appends_share_fsyncs_and_get_distinct_durable_offsets() {
+    let dir = temp_dir("concurrent");
+    let outbox = Arc::new(Outbox::open(&dir).unwrap().with_group_commit_window(Duration::from_millis(2)));
+    let threads: Vec<_> = (0..8)
+        .map(|t| {
+            let outbox = outbox.clone();
+            std::thread::spawn(move || {
+                (0..25)
+                    .map(|i| {
+                        let end = outbox.append(OutboxRecord::new_simple(format!("t{}-{}", t, i), "INSERT", OutboxTarget::Both)).unwrap();
+                        assert!(outbox.durable_offset() >= end);
+                        end
+                    })
+                    .collect::<Vec<u64>>()
+            })
+        })
+        .collect();
+    let ends: BTreeSet<u64> = threads.into_iter().flat_map(|h| h.join().unwrap()).collect();
+
+    assert_eq!(ends.len(), 200);
+    assert!(outbox.commit_count() < 200, "{} fsyncs for 200 records", outbox.commit_count());
+    let end = outbox.end_offset().unwrap();
+    assert_eq!(outbox.durable_offset(), end);
+    assert_eq!(fs::metadata(dir.join("outbox.log")).unwrap().len(), end);
+
+    let records = outbox.read_from(0, 1000).unwrap();
+    assert_eq!(records.iter().map(|(_, e, _)| *e).collect::<BTreeSet<_>>(), ends);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn batches_commit_once_and_reopen_continues_at_the_end() {
+    let dir = temp_dir("batch");
+    let outbox = Outbox::open(&dir).unwrap();
+    let first = outbox.append(OutboxRecord::new_simple("a", "INSERT", OutboxTarget::Active)).unwrap();
+    let ends = outbox
+        .append_batch((0..10).map(|i| OutboxRecord::new_simple(format!("b{}", i), "INSERT", OutboxTarget::Passive)).collect())
+        .unwrap();
+    assert_eq!(outbox.commit_count(), 2);
+    assert_eq!(ends.len(), 10);
+    assert!(ends.windows(2).all(|w| w[0] < w[1]) && ends[0] > first);
+    drop(outbox);
+
+    let reopened = Outbox::open(&dir).unwrap();
+    assert_eq!(reopened.end_offset().unwrap(), ends[9]);
+    assert_eq!(reopened.durable_offset(), ends[9]);
+    let next = reopened.append(OutboxRecord::new_simple("c", "INSERT", OutboxTarget::Both)).unwrap();
+    assert_eq!(reopened.read_from(ends[9], 10).unwrap()[0].1, next);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn failed_flushes_report_the_cause_to_every_writer() {
+    let dir = temp_dir("full");
+    fs::create_dir_all(&dir).unwrap();
+    std::os::unix::fs::symlink("/dev/full", dir.join("outbox.log")).unwrap();
+    let outbox = Arc::new(Outbox::open(&dir).unwrap().with_fsync(false));
+    let threads: Vec<_> = (0..4)
+        .map(|t| {
+            let outbox = outbox.clone();
+            std::thread::spawn(move || {
+                (0..5)
+                    .map(|i| outbox.append(OutboxRecord::new_simple(format!("t{}-{}", t, i), "INSERT", OutboxTarget::Both)).unwrap_err().to_message())
+                    .collect::<Vec<String>>()
+            })
+        })
+        .collect();
+    for err in threads.into_iter().flat_map(|h| h.join().unwrap()) {
+        assert!(err.contains("outbox flush") || err.contains("discarded after an I/O error"), "{}", err);
+    }
+    assert_eq!(outbox.end_offset().unwrap(), 0);
+    assert_eq!(outbox.durable_offset(), 0);
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,140 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use nayud_batch::replication::inspect::bind_markers;
+use nayud_batch::replication::{Outbox, OutboxFilter, OutboxRecord, OutboxTarget};
+
+fn temp_dir(tag: &str) -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos();
+    dir.push(format!("nayud_batch_test_inspect_{}_{}", tag, ts));
+    dir
+}
+
+fn record(key: &str, target: OutboxTarget, created_ms: u64) -> OutboxRecord {
+    let mut rec = OutboxRecord::new_simple(key, format!("INSERT INTO t (k) VALUES ('{}')", key), target);
+    rec.created_ms = created_ms;
+    rec.params = vec![key.as_bytes().to_vec(), vec![0, 1, 2]];
+    rec
+}
+
+fn seeded(tag: &str) -> (PathBuf, Outbox, Vec<u64>) {
+    let dir = temp_dir(tag);
+    let outbox = Outbox::open(&dir).unwrap().with_fsync(false);
+    let ends = vec![
+        outbox.append(record("a", OutboxTarget::Active, 100)).unwrap(),
+        outbox.append(record("b", OutboxTarget::Passive, 200)).unwrap(),
+        outbox.append(record("c", OutboxTarget::Both, 300)).unwrap(),
+        outbox.append(record("d", OutboxTarget::Passive, 400)).unwrap(),
+    ];
+    (dir, outbox, ends)
+}
+
+#[test]
+fn validate_reports_corrupt_regions_and_skips_them() {
+    let (dir, outbox, ends) = seeded("corrupt");
+    assert!(outbox.scan().unwrap().is_healthy());
+
+    let mut file = OpenOptions::new().write(true).open(dir.join("outbox.log")).unwrap();
+    file.seek(SeekFrom::Start(ends[0])).unwrap();
+    file.write_all(b"XXXX").unwrap();
+    drop(file);
+
+    let scan = outbox.scan().unwrap();
+    assert_eq!(scan.records, 3);
+    assert_eq!(scan.corrupt.len(), 1);
+    assert_eq!((scan.corrupt[0].start, scan.corrupt[0].end), (ends[0], ends[1]));
+    assert_eq!(scan.corrupt[0].reason, "bad magic");
+    assert!(scan.describe_problems().contains("bad magic"));
+
+    let keys: Vec<String> = outbox.find(&OutboxFilter::default(), 0, 10).unwrap().into_iter().map(|e| e.key).collect();
+    assert_eq!(keys, ["a", "c", "d"]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn replay_stops_at_a_frame_longer_than_the_log() {
+    let (dir, outbox, ends) = seeded("oversized");
+    let mut file = OpenOptions::new().write(true).open(dir.join("outbox.log")).unwrap();
+    file.seek(SeekFrom::Start(ends[1] + 6)).unwrap();
+    file.write_all(&u32::MAX.to_le_bytes()).unwrap();
+    drop(file);
+
+    let records = outbox.read_from(0, 10).unwrap();
+    assert_eq!(records.iter().map(|(_, end, _)| *end).collect::<Vec<_>>(), &ends[..2]);
+    assert!(outbox.read_from(ends[1], 10).unwrap().is_empty());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn find_filters_and_seek_by_key() {
+    let (dir, outbox, ends) = seeded("filter");
+
+    let passive = OutboxFilter { target: Some(OutboxTarget::Passive), ..OutboxFilter::default() };
+    let found = outbox.find(&passive, 0, 10).unwrap();
+    assert_eq!(found.iter().map(|e| e.key.as_str()).collect::<Vec<_>>(), ["b", "d"]);
+    assert_eq!(found[0].params[0].text.as_deref(), Some("b"));
+    assert_eq!(found[0].params[1].text, None);
+    assert_eq!(found[0].params[1].hex, "000102");
+
+    let window = OutboxFilter { since_ms: Some(200), until_ms: Some(400), ..OutboxFilter::default() };
+    assert_eq!(outbox.find(&window, 0, 10).unwrap().len(), 2);
+    let by_key = OutboxFilter { key: Some("c".into()), ..OutboxFilter::default() };
+    assert_eq!(outbox.find(&by_key, 0, 10).unwrap()[0].offset, ends[1]);
+
+    assert_eq!(outbox.seek_to_key("c").unwrap(), ends[1]);
+    assert_eq!(outbox.load_cursor().unwrap(), ends[1]);
+    assert_eq!(outbox.pending_count().unwrap(), 2);
+    assert!(outbox.seek_to_key("missing").is_err());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn export_and_import_round_trip() {
+    let (dir, outbox, ends) = seeded("export");
+    outbox.store_cursor(ends[1]).unwrap();
+
+    let mut pending_only = Vec::new();
+    assert_eq!(outbox.export_to(&mut pending_only, false).unwrap().records, 2);
+    let mut everything = Vec::new();
+    let summary = outbox.export_to(&mut everything, true).unwrap();
+    assert_eq!((summary.records, summary.replayed), (4, 2));
+
+    let target_dir = temp_dir("import");
+    let imported = Outbox::open(&target_dir).unwrap().with_fsync(false);
+    let summary = imported.import_from(everything.as_slice()).unwrap();
+    assert_eq!(summary.cursor, ends[1]);
+    assert_eq!(imported.pending_count().unwrap(), 2);
+    let keys: Vec<String> = imported.find(&OutboxFilter::default(), 0, 10).unwrap().into_iter().map(|e| e.key).collect();
+    assert_eq!(keys, ["a", "b", "c", "d"]);
+    assert_eq!(fs::read(target_dir.join("outbox.log")).unwrap(), fs::read(dir.join("outbox.log")).unwrap());
+
+    assert!(imported.import_from(pending_only.as_slice()).is_err());
+    let _ = fs::remove_dir_all(&dir);
+    let _ = fs::remove_dir_all(&target_dir);
+}
+
+#[test]
+fn params_are_labelled_with_their_bind_markers() {
+    assert_eq!(
+        bind_markers("INSERT INTO ks.t (k, \"Val\", ts) VALUES (?, ?, 'x?') USING TTL ?"),
+        [Some("k".to_string()), Some("Val".to_string()), Some("[ttl]".to_string())]
+    );
+    assert_eq!(
+        bind_markers("UPDATE t SET n = n + ?, v = :value WHERE k = ? AND c IN (?, ?) AND tags CONTAINS KEY ?"),
+        ["n", "value", "k", "c", "c", "tags"].map(|n| Some(n.to_string()))
+    );
+    assert_eq!(bind_markers("SELECT * FROM t WHERE token(k) > ? LIMIT ?"), [None, Some("[limit]".to_string())]);
+
+    let dir = temp_dir("markers");
+    let outbox = Outbox::open(&dir).unwrap().with_fsync(false);
+    let mut rec = OutboxRecord::new_simple("m", "INSERT INTO t (k, v) VALUES (?, ?)", OutboxTarget::Both);
+    rec.params = vec![b"key".to_vec(), vec![0, 0, 0, 7]];
+    outbox.append(rec).unwrap();
+    let entry = &outbox.find(&OutboxFilter::default(), 0, 1).unwrap()[0];
+    assert_eq!(entry.params[0].marker.as_deref(), Some("k"));
+    assert_eq!(entry.params[1].marker.as_deref(), Some("v"));
+    assert_eq!(entry.params[1].hex, "00000007");
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,67 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use futures::future::join_all;
+use nayud_batch::db::prepared::{is_schema_change, StatementCache};
+
+async fn prepare_slow(calls: &AtomicUsize, value: &str) -> Result<String, String> {
+    calls.fetch_add(1, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    Ok(value.to_string())
+}
+
+#[ntex::test]
+async fn concurrent_misses_share_one_prepare() {
+    let cache: StatementCache<String> = StatementCache::new(8);
+    let calls = AtomicUsize::new(0);
+    let results = join_all((0..10).map(|_| cache.get_or_prepare("SELECT 1", || prepare_slow(&calls, "ps1")))).await;
+    assert!(results.iter().all(|r| r.as_deref().map(String::as_str) == Ok("ps1")));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    let _ = cache.get_or_prepare("SELECT 1", || prepare_slow(&calls, "ps1")).await.unwrap();
+    let stats = cache.stats();
+    assert_eq!(stats.prepares, 1);
+    assert_eq!(stats.misses, 10);
+    assert_eq!(stats.hits, 1);
+}
+
+#[ntex::test]
+async fn failed_prepare_is_retried() {
+    let cache: StatementCache<String> = StatementCache::new(8);
+    let r: Result<_, String> = cache.get_or_prepare("BAD", || async { Err("syntax error".to_string()) }).await;
+    assert!(r.is_err());
+    assert!(!cache.contains("BAD"));
+    let r: Result<_, String> = cache.get_or_prepare("BAD", || async { Ok("fixed".to_string()) }).await;
+    assert_eq!(r.unwrap().as_str(), "fixed");
+}
+
+#[ntex::test]
+async fn least_recently_used_entry_is_evicted() {
+    let cache: StatementCache<String> = StatementCache::new(2);
+    let ok = |v: &'static str| async move { Ok::<_, String>(v.to_string()) };
+    cache.get_or_prepare("a", || ok("a")).await.unwrap();
+    cache.get_or_prepare("b", || ok("b")).await.unwrap();
+    cache.get_or_prepare("a", || ok("a")).await.unwrap();
+    cache.get_or_prepare("c", || ok("c")).await.unwrap();
+
+    assert!(cache.contains("a"));
+    assert!(!cache.contains("b"));
+    assert!(cache.contains("c"));
+    assert_eq!(cache.stats().evictions, 1);
+
+    assert!(cache.invalidate("a"));
+    assert!(!cache.invalidate("a"));
+    assert_eq!(cache.clear(), 1);
+    let stats = cache.stats();
+    assert_eq!(stats.invalidations, 2);
+    assert_eq!(stats.size, 0);
+}
+
+#[test]
+fn schema_statements_are_detected() {
+    assert!(is_schema_change("  alter TABLE ks.t ADD c int"));
+    assert!(is_schema_change("CREATE INDEX ON ks.t (c)"));
+    assert!(is_schema_change("DROP TABLE ks.t"));
+    assert!(!is_schema_change("INSERT INTO ks.t (id) VALUES (1)"));
+    assert!(!is_schema_change("SELECT * FROM ks.created"));
+}
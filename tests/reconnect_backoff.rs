@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use nayud_batch::config::DriverConfig;
+use nayud_batch::db::reconnect::Backoff;
+use nayud_batch::db::DbClients;
+use nayud_batch::replication::Cluster;
+
+#[test]
+fn backoff_doubles_up_to_max_and_resets() {
+    let mut b = Backoff::new(Duration::from_millis(100), Duration::from_millis(350));
+    let delays: Vec<u64> = (0..5).map(|_| b.next_delay().as_millis() as u64).collect();
+    assert_eq!(delays, vec![100, 200, 350, 350, 350]);
+    b.reset();
+    assert_eq!(b.next_delay(), Duration::from_millis(100));
+
+    let drv = DriverConfig { reconnect_initial_ms: Some(50), reconnect_max_ms: Some(10), ..DriverConfig::default() };
+    let mut b = Backoff::from_config(&drv);
+    assert_eq!(b.next_delay(), Duration::from_millis(50));
+    assert_eq!(b.next_delay(), Duration::from_millis(50));
+}
+
+#[ntex::test]
+async fn reconnect_without_connector_is_an_error() {
+    let clients = DbClients::default();
+    assert!(!clients.is_connected(Cluster::Active));
+    assert!(clients.session(Cluster::Passive).is_none());
+    assert!(clients.try_reconnect(Cluster::Active).await.is_err());
+
+    clients.replace_session(Cluster::Active, None);
+    assert!(clients.is_empty());
+}
@@ -0,0 +1,115 @@
+use nayud_batch::db::schema::{CqlType, SchemaColumn, TableSchema};
+use nayud_batch::db::values::to_literal;
+use nayud_batch::db::DbClients;
+use nayud_batch::repair::{parse_keys, plan_repair, run_repair_with_schema, FetchedRow, RepairAction, RepairSpec, RepairStrategy};
+use nayud_batch::replication::{Cluster, ReplicationManager};
+use scylla::value::CqlValue;
+
+#[test]
+fn keys_parse_from_report_array_and_ndjson() {
+    let report = r#"{"keyspace":"batch","table":"t","ranges_total":1,"ranges_mismatched":1,"leaf_ranges_compared":1,
+        "rows_active":1,"rows_passive":0,"missing":[{"token":5,"primary_key":{"id":1}}],"extra":[],
+        "differing":[{"token":9,"primary_key":{"id":2}}],"truncated":false,"started_ms":0,"finished_ms":0}"#;
+    let keys = parse_keys(report).unwrap();
+    assert_eq!(keys.len(), 2);
+    assert_eq!(keys[1]["id"], 2);
+
+    assert_eq!(parse_keys(r#"[{"id":1},{"id":2},{"id":3}]"#).unwrap().len(), 3);
+    assert_eq!(parse_keys("{\"id\":1}\n\n{\"id\":2}\n").unwrap().len(), 2);
+    assert!(parse_keys("[1,2]").is_err());
+    assert!(parse_keys("{\"id\":1}\nnot json\n").is_err());
+}
+
+#[test]
+fn strategy_and_literals() {
+    assert_eq!(RepairStrategy::parse("WriteTime").unwrap(), RepairStrategy::WriteTime);
+    assert_eq!(RepairStrategy::parse("passive").unwrap(), RepairStrategy::SourceOfTruth(Cluster::Passive));
+    assert!(RepairStrategy::parse("newest").is_err());
+
+    assert_eq!(to_literal(&CqlValue::Text("it's".into())), "'it''s'");
+    assert_eq!(to_literal(&CqlValue::Double(f64::NAN)), "NaN");
+    let map = CqlValue::Map(vec![(CqlValue::Text("a".into()), CqlValue::Int(1))]);
+    assert_eq!(to_literal(&map), "{'a': 1}");
+    let set = CqlValue::Set(vec![CqlValue::Int(1), CqlValue::Int(2)]);
+    assert_eq!(to_literal(&CqlValue::List(vec![set])), "[{1, 2}]");
+    assert_eq!(to_literal(&CqlValue::Tuple(vec![Some(CqlValue::Int(1)), None])), "(1, null)");
+}
+
+#[ntex::test]
+async fn repair_reports_unreachable_clusters_per_key() {
+    let col = |name: &str, kind: &str, ty: &str| SchemaColumn { name: name.into(), kind: kind.into(), position: 0, ty: CqlType::parse(ty) };
+    let schema = TableSchema {
+        keyspace: "batch".into(),
+        table: "customers".into(),
+        columns: vec![col("id", "partition_key", "int"), col("name", "regular", "text")],
+    };
+    let spec = RepairSpec::new("batch", "customers", RepairStrategy::WriteTime).with_dry_run(true);
+    let keys = parse_keys("{\"id\":1}\n{\"name\":\"no key\"}\n").unwrap();
+    let repl = ReplicationManager::new();
+
+    let report = run_repair_with_schema(&spec, &schema, &keys, &repl, &DbClients::default()).await.unwrap();
+    assert_eq!(report.keys_examined, 2);
+    assert_eq!(report.failures.len(), 2);
+    assert!(report.failures[1].reason.contains("primary key"));
+    assert!(report.entries.is_empty());
+}
+
+fn customers() -> TableSchema {
+    let col = |name: &str, kind: &str, ty: &str| SchemaColumn { name: name.into(), kind: kind.into(), position: 0, ty: CqlType::parse(ty) };
+    TableSchema {
+        keyspace: "batch".into(),
+        table: "customers".into(),
+        columns: vec![col("id", "partition_key", "int"), col("name", "regular", "text"), col("tier", "regular", "int")],
+    }
+}
+
+fn row(name: Option<&str>, tier: Option<i32>, ts: i64) -> Option<FetchedRow> {
+    Some(FetchedRow {
+        values: vec![name.map(|n| CqlValue::Text(n.into())), tier.map(CqlValue::Int)],
+        writetimes: vec![name.map(|_| ts), tier.map(|_| ts)],
+    })
+}
+
+#[test]
+fn planning_picks_winner_and_timestamps() {
+    let schema = customers();
+    let pk = parse_keys("{\"id\":7}").unwrap().remove(0);
+
+    let same = plan_repair(&schema, RepairStrategy::WriteTime, &pk, row(Some("a"), Some(1), 10), row(Some("a"), Some(1), 20), 1_000).unwrap();
+    assert_eq!(same.action, RepairAction::InSync);
+    assert!(same.statements.is_empty());
+
+    let newest = plan_repair(&schema, RepairStrategy::WriteTime, &pk, row(Some("a"), Some(1), 10), row(Some("b"), None, 20), 1_000).unwrap();
+    assert_eq!(newest.winner, Some("passive"));
+    assert_eq!(newest.timestamp, Some(20));
+    assert_eq!(newest.statements, vec![
+        "INSERT INTO \"batch\".\"customers\" (\"id\", \"name\") VALUES (7, 'b') USING TIMESTAMP 20".to_string(),
+        "DELETE \"tier\" FROM \"batch\".\"customers\" USING TIMESTAMP 20 WHERE \"id\" = 7".to_string(),
+    ]);
+}
+
+#[test]
+fn source_of_truth_outstamps_newer_cells_on_the_losing_side() {
+    let schema = customers();
+    let pk = parse_keys("{\"id\":7}").unwrap().remove(0);
+
+    let upsert = plan_repair(&schema, RepairStrategy::SourceOfTruth(Cluster::Active), &pk, row(Some("a"), Some(1), 10), row(Some("b"), Some(2), 5_000), 1_000).unwrap();
+    assert_eq!(upsert.winner, Some("active"));
+    assert_eq!(upsert.timestamp, Some(5_001));
+    assert_eq!(upsert.statements, vec!["INSERT INTO \"batch\".\"customers\" (\"id\", \"name\", \"tier\") VALUES (7, 'a', 1) USING TIMESTAMP 5001".to_string()]);
+
+    let delete = plan_repair(&schema, RepairStrategy::SourceOfTruth(Cluster::Active), &pk, None, row(Some("b"), None, 50), 1_000).unwrap();
+    assert_eq!(delete.action, RepairAction::Delete);
+    assert_eq!(delete.statements, vec!["DELETE FROM \"batch\".\"customers\" USING TIMESTAMP 1000 WHERE \"id\" = 7".to_string()]);
+
+    assert!(plan_repair(&schema, RepairStrategy::WriteTime, &parse_keys("{\"name\":\"x\"}").unwrap().remove(0), None, None, 0).is_err());
+}
+
+#[ntex::test]
+async fn tables_that_cannot_be_repaired_are_client_errors() {
+    let col = |name: &str, kind: &str, ty: &str| SchemaColumn { name: name.into(), kind: kind.into(), position: 0, ty: CqlType::parse(ty) };
+    let schema = TableSchema { keyspace: "batch".into(), table: "hits".into(), columns: vec![col("id", "partition_key", "int"), col("n", "regular", "counter")] };
+    let spec = RepairSpec::new("batch", "hits", RepairStrategy::WriteTime);
+    let err = run_repair_with_schema(&spec, &schema, &[], &ReplicationManager::new(), &DbClients::default()).await.unwrap_err();
+    assert_eq!(err.status_code(), ntex::http::StatusCode::BAD_REQUEST);
+}
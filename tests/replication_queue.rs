@@ -1,4 +1,4 @@
-use nayud_batch::replication::{ReplicationManager, OutboxRecord, OutboxTarget};
+use nayud_batch::replication::{Outbox, ReplicationManager, OutboxRecord, OutboxTarget, SyncWorker};
 use std::path::PathBuf;
 use std::fs;
 
@@ -17,11 +17,11 @@ async fn outbox_enqueue_and_replay_marks_cursor() {
     let dir = temp_outbox_dir();
     let _ = fs::remove_dir_all(&dir);
 
-    let mut rm = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let rm = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
     assert_eq!(rm.queue_len(), 0);
 
-    let _ = rm.enqueue(OutboxRecord::new_simple("k1", "INSERT INTO t ...", OutboxTarget::Active)).unwrap();
-    let _ = rm.enqueue(OutboxRecord::new_simple("k2", "INSERT INTO t ...", OutboxTarget::Passive)).unwrap();
+    let _ = rm.enqueue(OutboxRecord::new_simple("k1", "INSERT INTO t ...", OutboxTarget::Active)).await.unwrap();
+    let _ = rm.enqueue(OutboxRecord::new_simple("k2", "INSERT INTO t ...", OutboxTarget::Passive)).await.unwrap();
 
     assert_eq!(rm.queue_len(), 2);
 
@@ -34,4 +34,116 @@ async fn outbox_enqueue_and_replay_marks_cursor() {
     assert_eq!(rm.queue_len(), 0);
 
     let _ = fs::remove_dir_all(&dir);
-}
\ No newline at end of file
+}
+
+#[ntex::test]
+async fn cloned_managers_enqueue_while_replaying() {
+    let dir = temp_outbox_dir().with_extension("shared");
+    let rm = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+
+    let producers: Vec<_> = (0..4)
+        .map(|t| {
+            let rm = rm.clone();
+            tokio::spawn(async move {
+                for i in 0..50 {
+                    rm.enqueue(OutboxRecord::new_simple(format!("t{}-{}", t, i), "INSERT", OutboxTarget::Both)).await.unwrap();
+                }
+            })
+        })
+        .collect();
+    let mut replayed = 0;
+    while producers.iter().any(|p| !p.is_finished()) {
+        replayed += rm.replay_with(16, |_rec| async move { true }).await.unwrap();
+        tokio::task::yield_now().await;
+    }
+    for p in producers {
+        p.await.unwrap();
+    }
+    replayed += rm.replay_with(1000, |_rec| async move { true }).await.unwrap();
+
+    assert_eq!(replayed, 200);
+    assert_eq!(rm.queue_len(), 0);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn outbox_dir_is_locked_against_a_second_writer() {
+    let dir = temp_outbox_dir().with_extension("locked");
+    let outbox = Outbox::open(&dir).expect("open outbox").with_fsync(false);
+    let end = outbox.append(OutboxRecord::new_simple("k1", "INSERT", OutboxTarget::Active)).unwrap();
+
+    let err = Outbox::open(&dir).unwrap_err().to_message();
+    assert!(err.contains("in use by another process"), "{}", err);
+
+    let reader = Outbox::open_read_only(&dir).unwrap();
+    assert_eq!(reader.end_offset().unwrap(), end);
+    assert!(reader.append(OutboxRecord::new_simple("k2", "INSERT", OutboxTarget::Active)).is_err());
+    assert!(reader.store_cursor(end).is_err());
+
+    let handle = outbox.clone();
+    assert_eq!(handle.truncate(true).unwrap(), end);
+    assert_eq!(outbox.end_offset().unwrap(), 0);
+    assert!(outbox.append(OutboxRecord::new_simple("k3", "INSERT", OutboxTarget::Active)).unwrap() > 0);
+
+    drop((outbox, handle));
+    Outbox::open(&dir).expect("lock is released once every handle is dropped");
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[ntex::test]
+async fn sync_worker_shares_the_served_manager() {
+    let dir = temp_outbox_dir().with_extension("worker");
+    let rm = ReplicationManager::with_outbox_dir(&dir).expect("open outbox");
+    let worker = SyncWorker::new().with_replication(rm.clone());
+    assert!(worker.has_outbox());
+    assert_eq!(worker.queue_len(), 0);
+
+    rm.enqueue(OutboxRecord::new_simple("k1", "INSERT", OutboxTarget::Passive)).await.unwrap();
+    assert_eq!(worker.queue_len(), 1);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn truncate_keeps_pending_records_and_rebases_the_cursor() {
+    let dir = temp_outbox_dir().with_extension("truncate");
+    let outbox = Outbox::open(&dir).expect("open outbox").with_fsync(false);
+    let ends: Vec<u64> = (0..3)
+        .map(|i| outbox.append(OutboxRecord::new_simple(format!("k{}", i), "INSERT", OutboxTarget::Both)).unwrap())
+        .collect();
+    outbox.store_cursor(ends[0]).unwrap();
+
+    assert_eq!(outbox.truncate(false).unwrap(), ends[0]);
+    assert_eq!(outbox.current_cursor().unwrap(), 0);
+    assert_eq!(outbox.pending_count().unwrap(), 2);
+    assert_eq!(outbox.end_offset().unwrap(), ends[2] - ends[0]);
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn interrupted_truncate_is_finished_on_open() {
+    for renamed in [false, true] {
+        let dir = temp_outbox_dir().with_extension(format!("interrupted{}", renamed));
+        let ends: Vec<u64> = {
+            let outbox = Outbox::open(&dir).expect("open outbox").with_fsync(false);
+            (0..3)
+                .map(|i| outbox.append(OutboxRecord::new_simple(format!("k{}", i), "INSERT", OutboxTarget::Both)).unwrap())
+                .collect()
+        };
+        let log = fs::read(dir.join("outbox.log")).unwrap();
+        let compacted = &log[ends[0] as usize..];
+        if renamed {
+            fs::write(dir.join("outbox.log"), compacted).unwrap();
+        } else {
+            fs::write(dir.join("outbox.log.tmp"), compacted).unwrap();
+        }
+        fs::write(dir.join("outbox.cursor"), [ends[0].to_le_bytes(), ends[0].to_le_bytes()].concat()).unwrap();
+
+        assert_eq!(Outbox::open_read_only(&dir).unwrap().current_cursor().unwrap(), if renamed { 0 } else { ends[0] });
+        let outbox = Outbox::open(&dir).expect("reopen outbox");
+        assert!(!dir.join("outbox.log.tmp").exists());
+        assert_eq!(outbox.current_cursor().unwrap(), 0);
+        assert_eq!(outbox.pending_count().unwrap(), 2);
+        assert_eq!(fs::read(dir.join("outbox.cursor")).unwrap().len(), 8);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
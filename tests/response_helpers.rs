@@ -1,4 +1,4 @@
-use nayud_batch::types::response::{ApiResponse, ApiMessage, CODE_SUCCESS, CODE_FAILURE};
+use nayud_batch::types::response::{ApiResponse, ApiMessage, CODE_DB_UNAVAILABLE, CODE_FAILURE, CODE_NOT_FOUND, CODE_SUCCESS};
 use nayud_batch::errors::AppError;
 
 #[test]
@@ -42,7 +42,7 @@ fn response_from_result_and_option_and_error() {
     }
 
     let r_err: ApiResponse<i32> = ApiResponse::from_result(Err(AppError::db("boom")), "ignored");
-    assert_eq!(r_err.code, CODE_FAILURE);
+    assert_eq!(r_err.code, CODE_DB_UNAVAILABLE);
     assert!(r_err.data.is_none());
     match &r_err.message {
         ApiMessage::Detail { what, why, how } => {
@@ -58,7 +58,7 @@ fn response_from_result_and_option_and_error() {
     assert_eq!(r_some.data, Some("v"));
 
     let r_none: ApiResponse<&str> = ApiResponse::from_option(None, "has", "none");
-    assert_eq!(r_none.code, CODE_FAILURE);
+    assert_eq!(r_none.code, CODE_NOT_FOUND);
     assert!(r_none.data.is_none());
 
     let r_empty: ApiResponse<()> = ApiResponse::success("done");
@@ -0,0 +1,158 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::Arc;
+use std::thread;
+
+use nayud_batch::config::{self, AppConfig, DbEndpoint, SecretProvider, SecretResolver, SecretsConfig};
+use nayud_batch::errors::AppResult;
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("nayud-secrets-{}-{}", name, uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+struct Fixed;
+
+impl SecretProvider for Fixed {
+    fn scheme(&self) -> &str { "fixed" }
+    fn fetch(&self, reference: &str) -> AppResult<String> { Ok(format!("fixed-{}", reference)) }
+}
+
+#[test]
+fn password_files_are_reread_after_rotation() {
+    let dir = temp_dir("rotate");
+    let pw = dir.join("password");
+    fs::write(&pw, "first\n").unwrap();
+
+    let ep = DbEndpoint { password_file: Some(pw.display().to_string()), username: "${fixed:user}".into(), ..DbEndpoint::default() };
+    let resolver = SecretResolver::local().with_provider(Arc::new(Fixed));
+
+    let resolved = resolver.resolve_endpoint(&ep).unwrap();
+    assert_eq!(resolved.password, "first");
+    assert_eq!(resolved.username, "fixed-user");
+
+    fs::write(&pw, "second").unwrap();
+    assert_eq!(resolver.resolve_endpoint(&ep).unwrap().password, "second");
+
+    let text = format!("a-${{file:{}}}-$${{literal}}", pw.display());
+    assert_eq!(resolver.interpolate(&text).unwrap(), "a-second-${literal}");
+    assert!(resolver.interpolate("${nope:x}").is_err());
+    assert!(resolver.interpolate("${file:/nonexistent/secret}").is_err());
+}
+
+#[test]
+fn toml_interpolates_values_but_defers_secrets() {
+    let dir = temp_dir("toml");
+    let host_file = dir.join("host");
+    fs::write(&host_file, "10.9.8.7\n").unwrap();
+    let cfg_path = dir.join("nayud.toml");
+    fs::write(
+        &cfg_path,
+        format!(
+            "[active]\nhost = \"${{file:{}}}\"\npassword = \"${{file:{}}}\"\n",
+            host_file.display(),
+            dir.join("missing").display()
+        ),
+    )
+    .unwrap();
+
+    let cfg = AppConfig::from_file(&cfg_path).unwrap();
+    assert_eq!(cfg.active.host, "10.9.8.7");
+    assert!(cfg.active.password.starts_with("${file:"));
+
+    let report = cfg.validate();
+    assert!(report.errors().any(|i| i.field == "active.password"), "{:?}", report.issues);
+    let rendered = cfg.render_effective();
+    assert!(rendered.contains("active.password = \"${file:"), "{}", rendered);
+}
+
+#[test]
+fn credentials_are_masked_in_debug_output() {
+    let ep = DbEndpoint { username: "admin-user".into(), password: "hunter2-secret".into(), ..DbEndpoint::default() };
+    let dbg = format!("{:?}", ep);
+    assert!(!dbg.contains("hunter2-secret") && !dbg.contains("admin-user"), "{}", dbg);
+
+    let secrets = SecretsConfig { vault_token: Some("s.abcdefgh".into()), ..SecretsConfig::default() };
+    assert!(!format!("{:?}", secrets).contains("s.abcdefgh"));
+    assert_eq!(ep.masked_credentials().1, "hu****et");
+}
+
+#[test]
+fn vault_provider_reads_kv_v2_from_local_stub() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for _ in 0..2 {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap();
+            let req = String::from_utf8_lossy(&buf[..n]).to_string();
+            let response = if req.starts_with("GET /v1/secret/data/nayud ") {
+                let body = r#"{"data":{"data":{"password":"from-vault"},"metadata":{"version":3}}}"#;
+                format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n{}\r\n0\r\n\r\n", body.len(), body)
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+            };
+            stream.write_all(response.as_bytes()).unwrap();
+            requests.push(req);
+        }
+        requests
+    });
+
+    let resolver = SecretResolver::from_config(&SecretsConfig {
+        vault_addr: Some(format!("http://{}", addr)),
+        vault_token: Some("test-token".into()),
+        ..SecretsConfig::default()
+    });
+    let ep = DbEndpoint { password: "${vault:secret/data/nayud#password}".into(), ..DbEndpoint::default() };
+    assert_eq!(resolver.resolve_endpoint(&ep).unwrap().password, "from-vault");
+    assert!(resolver.fetch("vault", "secret/data/other#password").is_err());
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].contains("X-Vault-Token: test-token"), "{}", requests[0]);
+}
+
+#[test]
+fn chunked_vault_bodies_are_split_on_raw_bytes() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        let first: &[u8] = b"{\"data\":{\"data\":{\"password\":\"p\xffw\"},";
+        let second: &[u8] = b"\"metadata\":{\"version\":1}}}";
+        let mut response = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in [first, second] {
+            response.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            response.extend_from_slice(chunk);
+            response.extend_from_slice(b"\r\n");
+        }
+        response.extend_from_slice(b"0\r\n\r\n");
+        stream.write_all(&response).unwrap();
+    });
+
+    let resolver = SecretResolver::from_config(&SecretsConfig {
+        vault_addr: Some(format!("http://{}", addr)),
+        vault_token: Some("test-token".into()),
+        ..SecretsConfig::default()
+    });
+    assert_eq!(resolver.fetch("vault", "secret/data/nayud#password").unwrap(), "p\u{fffd}w");
+    server.join().unwrap();
+}
+
+#[test]
+fn secret_files_are_watched_alongside_the_config_file() {
+    let mut cfg = AppConfig { config_file: Some("/etc/nayud.toml".into()), ..AppConfig::default() };
+    cfg.active.password_file = Some("/run/secrets/active-password".into());
+    cfg.passive.username = "${file:/run/secrets/passive-user}".into();
+    cfg.secrets.vault_token_file = Some("/run/secrets/vault-token".into());
+
+    assert_eq!(
+        config::watched_files(&cfg),
+        ["/etc/nayud.toml", "/run/secrets/active-password", "/run/secrets/passive-user", "/run/secrets/vault-token"]
+    );
+}
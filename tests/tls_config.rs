@@ -0,0 +1,163 @@
+use std::fs;
+use std::net::{IpAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nayud_batch::config::DbEndpoint;
+use nayud_batch::db::tls::{build_tls_context, name_matches, node_server_names, verify_node, PinnedCertificates};
+use nayud_batch::errors::AppError;
+use openssl::asn1::Asn1Time;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::symm::Cipher;
+use openssl::x509::{X509Builder, X509NameBuilder};
+
+fn temp_tls_dir() -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    let ts = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    dir.push(format!("nayud_batch_test_tls_{}", ts));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_self_signed(dir: &Path, key_password: Option<&str>) -> (String, String) {
+    let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", "client.example.com").unwrap();
+    let name = name.build();
+    let mut b = X509Builder::new().unwrap();
+    b.set_version(2).unwrap();
+    b.set_subject_name(&name).unwrap();
+    b.set_issuer_name(&name).unwrap();
+    b.set_pubkey(&pkey).unwrap();
+    b.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+    b.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+    b.sign(&pkey, MessageDigest::sha256()).unwrap();
+    let cert = b.build();
+
+    let cert_path = dir.join("client.pem");
+    let key_path = dir.join("client.key");
+    fs::write(&cert_path, cert.to_pem().unwrap()).unwrap();
+    let key_pem = match key_password {
+        Some(pw) => pkey.private_key_to_pem_pkcs8_passphrase(Cipher::aes_256_cbc(), pw.as_bytes()).unwrap(),
+        None => pkey.private_key_to_pem_pkcs8().unwrap(),
+    };
+    fs::write(&key_path, key_pem).unwrap();
+    (cert_path.display().to_string(), key_path.display().to_string())
+}
+
+fn pins() -> Arc<PinnedCertificates> { Arc::new(PinnedCertificates::default()) }
+
+fn tls_endpoint() -> DbEndpoint {
+    DbEndpoint { use_tls: true, ..DbEndpoint::default() }
+}
+
+#[test]
+fn tls_load_failures_are_config_errors() {
+    assert!(build_tls_context(&DbEndpoint::default(), pins()).unwrap().is_none());
+
+    let ep = DbEndpoint { tls_ca_file: Some("/nonexistent/ca.pem".into()), ..tls_endpoint() };
+    assert!(matches!(build_tls_context(&ep, pins()), Err(AppError::Config(_))));
+
+    let ep = DbEndpoint { tls_cert_file: Some("/nonexistent/client.pem".into()), ..tls_endpoint() };
+    assert!(matches!(build_tls_context(&ep, pins()), Err(AppError::Config(_))));
+
+    let ep = DbEndpoint { tls_min_version: Some("1.4".into()), ..tls_endpoint() };
+    assert!(matches!(build_tls_context(&ep, pins()), Err(AppError::Config(_))));
+
+    let ep = DbEndpoint { tls_ciphers: Some("NOT-A-CIPHER".into()), ..tls_endpoint() };
+    assert!(matches!(build_tls_context(&ep, pins()), Err(AppError::Config(_))));
+
+    let ep = DbEndpoint { tls_verify_hostname: true, tls_insecure_skip_verify: true, ..tls_endpoint() };
+    assert!(matches!(build_tls_context(&ep, pins()), Err(AppError::Config(_))));
+}
+
+#[test]
+fn client_certificate_with_encrypted_key() {
+    let dir = temp_tls_dir();
+    let (cert, key) = write_self_signed(&dir, Some("s3cret"));
+
+    let ep = DbEndpoint {
+        tls_ca_file: Some(cert.clone()),
+        tls_cert_file: Some(cert.clone()),
+        tls_key_file: Some(key.clone()),
+        tls_key_password: Some("s3cret".into()),
+        tls_min_version: Some("TLSv1.2".into()),
+        tls_ciphers: Some("HIGH:!aNULL:TLS_AES_256_GCM_SHA384".into()),
+        tls_verify_hostname: true,
+        ..tls_endpoint()
+    };
+    assert!(build_tls_context(&ep, pins()).unwrap().is_some());
+
+    let wrong = DbEndpoint { tls_key_password: Some("wrong".into()), ..ep.clone() };
+    assert!(matches!(build_tls_context(&wrong, pins()), Err(AppError::Config(_))));
+
+    let no_key = DbEndpoint { tls_key_file: None, tls_key_password: None, ..ep };
+    assert!(matches!(build_tls_context(&no_key, pins()), Err(AppError::Config(_))));
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn server_name_matching() {
+    assert!(name_matches("db1.example.com", "DB1.example.com."));
+    assert!(name_matches("*.example.com", "db1.example.com"));
+    assert!(!name_matches("*.example.com", "example.com"));
+    assert!(!name_matches("*.example.com", "a.db1.example.com"));
+
+    let ep = DbEndpoint { tls_server_names: vec!["Scylla.Internal".into()], ..DbEndpoint::default() };
+    let ip: IpAddr = "10.0.0.5".parse().unwrap();
+    assert_eq!(node_server_names(&ep, ip, &["DB1.example.com".into()]), vec!["10.0.0.5", "db1.example.com", "scylla.internal"]);
+    assert_eq!(node_server_names(&DbEndpoint::default(), ip, &[]), vec!["10.0.0.5"]);
+
+    let pinned = PinnedCertificates::default();
+    let node = "10.0.0.5:9142".parse().unwrap();
+    let other = "10.0.0.6:9142".parse().unwrap();
+    assert!(!pinned.pin(node, vec![1, 2, 3]));
+    assert!(pinned.knows(&node) && pinned.is_pinned(&[1, 2, 3]));
+    assert!(pinned.is_pinned_for(&node, &[1, 2, 3]) && !pinned.is_pinned_for(&other, &[1, 2, 3]));
+    assert!(!pinned.is_pinned(&[4]));
+
+    assert!(pinned.pin(node, vec![4]));
+    assert_eq!(pinned.pinned_for(&node), Some(vec![4]));
+    assert!(!pinned.is_pinned(&[1, 2, 3]));
+
+    assert!(!pinned.take_stale());
+    assert!(pinned.check(&[4]) && !pinned.take_stale());
+    assert!(!pinned.check(&[5]));
+    assert!(pinned.take_stale() && !pinned.take_stale());
+}
+
+#[test]
+fn node_check_binds_the_certificate_to_the_dialled_node() {
+    let dir = temp_tls_dir();
+    let (cert, key) = write_self_signed(&dir, None);
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate_chain_file(&cert).unwrap();
+    acceptor.set_private_key_file(&key, openssl::ssl::SslFiletype::PEM).unwrap();
+    let acceptor = acceptor.build();
+    let server = std::thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let _ = acceptor.accept(stream.unwrap());
+        }
+    });
+
+    let ep = DbEndpoint { tls_ca_file: Some(cert.clone()), tls_verify_hostname: true, ..tls_endpoint() };
+    let timeout = Duration::from_secs(5);
+    let own = node_server_names(&ep, addr.ip(), &["client.example.com".into()]);
+    assert!(verify_node(&ep, addr, own, timeout).is_ok());
+    let other = node_server_names(&ep, addr.ip(), &["db-b.example.com".into()]);
+    assert!(matches!(verify_node(&ep, addr, other, timeout), Err(AppError::Config(_))));
+
+    server.join().unwrap();
+    let _ = fs::remove_dir_all(&dir);
+}
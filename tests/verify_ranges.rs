@@ -0,0 +1,96 @@
+use std::collections::BTreeMap;
+
+use nayud_batch::errors::AppResult;
+use nayud_batch::verify::{compare_sources, row_hash, RangeDigest, RangeSource, RowDiff, ScannedRow, TokenRange, VerifyReport, VerifySpec};
+use scylla::value::CqlValue;
+
+struct MemoryTable(Vec<ScannedRow>);
+
+impl MemoryTable {
+    fn new(rows: impl IntoIterator<Item = (i64, &'static str)>) -> Self {
+        let names = ["id".to_string()];
+        MemoryTable(
+            rows.into_iter()
+                .map(|(id, value)| {
+                    let token = (i64::MIN as i128 + (id as i128) * (1i128 << 58)) as i64;
+                    ScannedRow::new(token, &[Some(CqlValue::BigInt(id)), Some(CqlValue::Text(value.into()))], &names)
+                })
+                .collect(),
+        )
+    }
+
+    fn within(&self, range: TokenRange) -> impl Iterator<Item = &ScannedRow> {
+        self.0.iter().filter(move |r| r.token >= range.start && r.token <= range.end)
+    }
+}
+
+impl RangeSource for MemoryTable {
+    async fn digest(&self, range: TokenRange) -> AppResult<RangeDigest> {
+        let mut digest = RangeDigest::new();
+        self.within(range).for_each(|r| digest.absorb(r.hash));
+        Ok(digest)
+    }
+
+    async fn rows(&self, range: TokenRange) -> AppResult<BTreeMap<String, ScannedRow>> {
+        Ok(self.within(range).map(|r| (r.key.clone(), r.clone())).collect())
+    }
+}
+
+#[test]
+fn full_ring_split_covers_every_token_once() {
+    let ranges = TokenRange::full_ring().split(256);
+    assert_eq!(ranges.len(), 256);
+    assert_eq!(ranges[0].start, i64::MIN);
+    assert_eq!(ranges[255].end, i64::MAX);
+    for pair in ranges.windows(2) {
+        assert!(pair[0].start <= pair[0].end);
+        assert_eq!(pair[0].end.checked_add(1), Some(pair[1].start));
+    }
+}
+
+#[test]
+fn sub_split_is_bounded_by_range_width() {
+    let r = TokenRange { start: -2, end: 2 };
+    let parts = r.split(16);
+    assert_eq!(parts.len(), 5);
+    assert!(parts.iter().all(|p| p.start == p.end));
+
+    let one = TokenRange { start: 7, end: 7 }.split(0);
+    assert_eq!(one, vec![TokenRange { start: 7, end: 7 }]);
+}
+
+#[test]
+fn empty_report_is_consistent() {
+    let report = VerifyReport::default();
+    assert!(report.is_consistent());
+    assert_eq!(report.diff_count(), 0);
+}
+
+#[test]
+fn row_hash_separates_column_boundaries() {
+    let text = |s: &str| Some(CqlValue::Text(s.into()));
+    assert_ne!(row_hash(&[text("ab"), text("c")]), row_hash(&[text("a"), text("bc")]));
+    assert_ne!(row_hash(&[text(""), None]), row_hash(&[None, text("")]));
+    assert_eq!(row_hash(&[text("a"), text("bc")]), row_hash(&[text("a"), text("bc")]));
+}
+
+#[ntex::test]
+async fn drill_down_finds_missing_extra_and_differing_rows() {
+    let active = MemoryTable::new((0..60).filter(|i| *i != 41).map(|i| (i, if i == 17 { "changed" } else { "same" })));
+    let passive = MemoryTable::new((0..60).filter(|i| *i != 5).map(|i| (i, "same")));
+    let spec = VerifySpec { split_factor: 4, leaf_rows: 2, ..VerifySpec::new("ks", "t").with_ranges(4) };
+
+    let mut report = VerifyReport::default();
+    compare_sources(&spec, &active, &passive, &mut report).await.unwrap();
+
+    let ids = |diffs: &[RowDiff]| diffs.iter().map(|d| d.primary_key["id"].as_i64().unwrap()).collect::<Vec<_>>();
+    assert_eq!((ids(&report.missing), ids(&report.extra), ids(&report.differing)), (vec![5], vec![41], vec![17]));
+    assert_eq!((report.rows_active, report.rows_passive), (59, 59));
+    assert_eq!(report.ranges_mismatched, 3);
+    assert!(report.leaf_ranges_compared >= 3 && report.leaf_ranges_compared < 16, "{}", report.leaf_ranges_compared);
+    assert!(!report.is_consistent());
+
+    let mut same = VerifyReport::default();
+    compare_sources(&spec, &active, &active, &mut same).await.unwrap();
+    assert!(same.is_consistent() && same.leaf_ranges_compared == 0);
+}